@@ -76,6 +76,9 @@ fn main() {
     config
         .plugin_definitions
         .push(pepper_plugin_lsp::DEFINITION);
+    config
+        .plugin_definitions
+        .push(pepper_plugin_remote::DEFINITION);
     config.plugin_definitions.push(ALTERNATE_FILE_PLUGIN);
 
     config
@@ -1,15 +1,22 @@
 use std::{
-    env, fs, io, panic,
+    env, fs, io, iter, panic,
     path::{Path, PathBuf},
+    process::Child,
+    thread,
     time::Duration,
 };
 
 use crate::{
-    client::ClientManager,
+    buffer::BufferProperties,
+    client::{ClientHandle, ClientManager},
     editor::{Editor, EditorContext, EditorFlow},
     editor_utils::{load_config, MessageKind},
-    events::{ClientEvent, ClientEventReceiver, ServerEvent, TargetClient},
-    platform::{drop_event, Key, Platform, PlatformEvent, PlatformRequest, ProcessTag},
+    events::{ClientEvent, ClientEventReceiver, EditorEvent, ServerEvent, TargetClient},
+    mode::ModeKind,
+    platform::{
+        drop_event, drop_request, Key, Platform, PlatformEvent, PlatformRequest, ProcessTag,
+        SyncProcess, SyncProcessCollection,
+    },
     plugin::{PluginCollection, PluginDefinition},
     serialization::{DeserializeError, Serialize},
     ui, Args, ResourceFile,
@@ -77,10 +84,17 @@ impl ServerApplication {
                 continue;
             }
             match fs::read_to_string(path) {
-                Ok(source) => match load_config(&mut ctx, &config.path, &source) {
-                    EditorFlow::Continue => (),
-                    _ => return None,
-                },
+                Ok(source) => {
+                    let directory = path.parent().unwrap_or(path);
+                    let directory = ctx.editor.current_directory.join(directory);
+                    ctx.editor.commands.source_directories.push(directory);
+                    let flow = load_config(&mut ctx, &config.path, &source);
+                    ctx.editor.commands.source_directories.pop();
+                    match flow {
+                        EditorFlow::Continue => (),
+                        _ => return None,
+                    }
+                }
                 Err(_) => ctx
                     .editor
                     .status_bar
@@ -106,11 +120,20 @@ impl ServerApplication {
                     self.ctx.trigger_event_handlers();
                 }
                 PlatformEvent::ConnectionOpen { handle } => {
-                    self.ctx.clients.on_client_joined(handle)
+                    self.ctx.clients.on_client_joined(handle);
+                    self.ctx
+                        .editor
+                        .events
+                        .enqueue(EditorEvent::ClientJoin { handle });
                 }
                 PlatformEvent::ConnectionClose { handle } => {
                     self.ctx.clients.on_client_left(handle);
-                    if self.ctx.clients.iter().next().is_none() {
+                    if self
+                        .ctx
+                        .clients
+                        .iter()
+                        .all(|c| c.eval_wait_buffer_handle.is_some())
+                    {
                         self.ctx.platform.requests.enqueue(PlatformRequest::Quit);
 
                         for event in events {
@@ -241,6 +264,222 @@ impl ServerApplication {
     }
 }
 
+pub fn run_batch(mut config: ApplicationConfig) -> bool {
+    let script_path = match config.args.batch.take() {
+        Some(path) => path,
+        None => return false,
+    };
+    let script = match fs::read_to_string(&script_path) {
+        Ok(script) => script,
+        Err(_) => {
+            eprintln!("could not read batch script '{}'", script_path);
+            return false;
+        }
+    };
+    let files = std::mem::take(&mut config.args.files);
+
+    let mut application = match ServerApplication::new(config) {
+        Some(application) => application,
+        None => return false,
+    };
+    if let (MessageKind::Error, message) = application.ctx.editor.status_bar.message() {
+        if !message.is_empty() {
+            eprintln!("{}", message);
+            return false;
+        }
+    }
+
+    let mut platform = BatchPlatform::default();
+    let client_handle = platform.connect(&mut application);
+
+    let mut success = true;
+    for path in &files {
+        let flow = match run_batch_script(
+            &mut application,
+            &mut platform,
+            client_handle,
+            &script_path,
+            &script,
+            path,
+        ) {
+            Ok(flow) => flow,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                success = false;
+                EditorFlow::Continue
+            }
+        };
+
+        let ctx = &mut application.ctx;
+        for buffer in ctx.editor.buffers.iter() {
+            ctx.editor
+                .buffers
+                .defer_remove(buffer.handle(), &mut ctx.editor.events);
+        }
+        ctx.trigger_event_handlers();
+
+        if !platform.update(&mut application) {
+            break;
+        }
+        if let EditorFlow::Quit | EditorFlow::QuitAll = flow {
+            break;
+        }
+    }
+
+    success
+}
+
+pub(crate) fn run_batch_script(
+    application: &mut ServerApplication,
+    platform: &mut BatchPlatform,
+    client_handle: ClientHandle,
+    script_path: &str,
+    script: &str,
+    path: &str,
+) -> Result<EditorFlow, String> {
+    let ctx = &mut application.ctx;
+    let buffer_view_handle = ctx
+        .editor
+        .buffer_view_handle_from_path(
+            client_handle,
+            Path::new(path),
+            BufferProperties::text(),
+            false,
+        )
+        .map_err(|error| error.to_string())?;
+    ctx.clients
+        .get_mut(client_handle)
+        .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+    ctx.editor.enter_mode(ModeKind::default());
+    ctx.trigger_event_handlers();
+    platform.update(application);
+
+    let mut flow = EditorFlow::Continue;
+    for (line_index, line) in script.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let ctx = &mut application.ctx;
+        ctx.editor.status_bar.clear();
+        let keys = match ctx.editor.buffered_keys.parse(line) {
+            Ok(keys) => keys,
+            Err(error) => return Err(format!("{}:{}: {}", script_path, line_index + 1, error)),
+        };
+        flow = Editor::execute_keys(ctx, client_handle, keys);
+        if !platform.update(application) {
+            flow = EditorFlow::QuitAll;
+        }
+
+        if let (MessageKind::Error, message) = application.ctx.editor.status_bar.message() {
+            if !message.is_empty() {
+                return Err(format!("{}:{}: {}", script_path, line_index + 1, message));
+            }
+        }
+        if !matches!(flow, EditorFlow::Continue) {
+            break;
+        }
+    }
+
+    let ctx = &mut application.ctx;
+    ctx.editor.enter_mode(ModeKind::default());
+
+    if let EditorFlow::Continue = flow {
+        for buffer in ctx.editor.buffers.iter_mut() {
+            if buffer.needs_save() {
+                buffer
+                    .write_to_file(None, &mut ctx.editor.word_database, &mut ctx.editor.events)
+                    .map_err(|error| format!("could not save {:?}: {}", &buffer.path, error))?;
+            }
+        }
+        ctx.trigger_event_handlers();
+        platform.update(application);
+    }
+
+    Ok(flow)
+}
+
+impl SyncProcess for Child {
+    fn kill(mut self) {
+        let _ = Child::kill(&mut self);
+        let _ = self.wait();
+    }
+
+    fn run(mut self, stdin: Vec<u8>) -> Vec<u8> {
+        let child_stdin = self.stdin.take();
+        let writer = thread::spawn(move || {
+            if let Some(mut child_stdin) = child_stdin {
+                use io::Write;
+                let _ = child_stdin.write_all(&stdin);
+            }
+        });
+        let output = self.wait_with_output();
+        let _ = writer.join();
+        match output {
+            Ok(output) => output.stdout,
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct BatchPlatform {
+    client_handle: Option<ClientHandle>,
+    processes: SyncProcessCollection<Child>,
+}
+impl BatchPlatform {
+    pub fn connect(&mut self, application: &mut ServerApplication) -> ClientHandle {
+        let handle = ClientHandle(0);
+        self.client_handle = Some(handle);
+        application.update(iter::once(PlatformEvent::ConnectionOpen { handle }));
+        self.update(application);
+        handle
+    }
+
+    // services platform requests synchronously until the editor settles
+    // returns false if the editor asked to quit
+    pub fn update(&mut self, application: &mut ServerApplication) -> bool {
+        let mut running = true;
+        let mut events = Vec::new();
+        loop {
+            let platform = &mut application.ctx.platform;
+            for request in platform.requests.drain() {
+                match request {
+                    PlatformRequest::Quit => running = false,
+                    PlatformRequest::Redraw => (),
+                    PlatformRequest::WriteToClient { buf, .. } => platform.buf_pool.release(buf),
+                    PlatformRequest::CloseClient { handle } => {
+                        if self.client_handle == Some(handle) {
+                            running = false;
+                        }
+                    }
+                    request => {
+                        let request = self.processes.on_request(
+                            &mut platform.buf_pool,
+                            request,
+                            &mut events,
+                            |mut command| command.spawn().ok(),
+                        );
+                        if let Some(request) = request {
+                            drop_request(&mut platform.buf_pool, request);
+                        }
+                    }
+                }
+            }
+
+            if events.is_empty() {
+                if self.processes.is_empty() {
+                    break;
+                }
+                self.processes
+                    .finish_all(&mut platform.buf_pool, &mut events);
+            }
+            application.update(events.drain(..));
+        }
+        running
+    }
+}
+
 pub const CLIENT_STDIN_BUFFER_LEN: usize = 4 * 1024;
 pub const CLIENT_CONNECTION_BUFFER_LEN: usize = 4 * 1024;
 
@@ -253,6 +492,8 @@ where
     server_write_buf: Vec<u8>,
     pub output: Option<O>,
     stdout_buf: Vec<u8>,
+    eval_buf: Vec<u8>,
+    eval_failed: Option<bool>,
 }
 impl<O> ClientApplication<O>
 where
@@ -265,18 +506,20 @@ where
             server_write_buf: Vec::new(),
             output: None,
             stdout_buf: Vec::new(),
+            eval_buf: Vec::new(),
+            eval_failed: None,
         }
     }
 
     pub fn init(&mut self, args: Args) -> &[u8] {
-        if args.as_focused_client {
+        if args.as_focused_client || args.eval.is_some() {
             self.target_client = TargetClient::Focused;
         }
 
         self.server_write_buf.clear();
 
         self.reinit_screen();
-        if !args.quit && !args.as_focused_client {
+        if !args.quit && !args.as_focused_client && args.eval.is_none() {
             ClientEvent::Key(self.target_client, Key::default())
                 .serialize(&mut self.server_write_buf);
         }
@@ -291,7 +534,10 @@ where
                 .serialize(&mut self.server_write_buf);
         }
 
-        if args.quit {
+        if let Some(commands) = &args.eval {
+            ClientEvent::Eval(self.target_client, args.wait, commands)
+                .serialize(&mut self.server_write_buf);
+        } else if args.quit {
             ClientEvent::Command(TargetClient::Sender, "quit")
                 .serialize(&mut self.server_write_buf);
         }
@@ -357,6 +603,16 @@ where
                         self.stdout_buf.clear();
                         self.stdout_buf.extend_from_slice(bytes);
                     }
+                    Ok(ServerEvent::EvalOutput(bytes)) => {
+                        self.eval_buf.clear();
+                        self.eval_buf.extend_from_slice(bytes);
+                        self.eval_failed = Some(false);
+                    }
+                    Ok(ServerEvent::EvalError(bytes)) => {
+                        self.eval_buf.clear();
+                        self.eval_buf.extend_from_slice(bytes);
+                        self.eval_failed = Some(true);
+                    }
                     Err(DeserializeError::InsufficientData) => {
                         let read_len = self.server_read_buf.len() - previous_slice.len();
                         self.server_read_buf.drain(..read_len);
@@ -379,6 +635,14 @@ where
     pub fn get_stdout_bytes(&self) -> &[u8] {
         &self.stdout_buf
     }
+
+    pub fn get_eval_result(&self) -> Option<Result<&[u8], &[u8]>> {
+        match self.eval_failed {
+            Some(false) => Some(Ok(&self.eval_buf)),
+            Some(true) => Some(Err(&self.eval_buf)),
+            None => None,
+        }
+    }
 }
 impl<O> Drop for ClientApplication<O>
 where
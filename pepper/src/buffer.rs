@@ -1,6 +1,6 @@
 use std::{
     fmt,
-    fs::File,
+    fs::{self, File},
     io,
     ops::{Add, RangeBounds, Sub},
     path::{Component, Path, PathBuf},
@@ -11,13 +11,17 @@ use std::{
 use crate::{
     buffer_history::{BufferHistory, Edit, EditKind},
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    config::Config,
     editor_utils::ResidualStrBytes,
+    editorconfig::EditorConfigProperties,
     events::{EditorEvent, EditorEventQueue},
     help,
     pattern::Pattern,
     platform::{Platform, PlatformProcessHandle, PlatformRequest, PooledBuf, ProcessTag},
     plugin::PluginHandle,
-    syntax::{HighlightResult, HighlightedBuffer, SyntaxCollection, SyntaxHandle},
+    syntax::{
+        HighlightResult, HighlightedBuffer, Syntax, SyntaxCollection, SyntaxHandle, TokenKind,
+    },
     word_database::{WordDatabase, WordIter, WordKind},
 };
 
@@ -214,10 +218,20 @@ impl<'a> WordRefWithPosition<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BufferLintSeverity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
 pub struct BufferLint {
     pub message: String,
     pub range: BufferRange,
+    pub severity: BufferLintSeverity,
     pub plugin_handle: PluginHandle,
+    pub source: u32,
 }
 
 #[derive(Default)]
@@ -248,6 +262,19 @@ impl BufferLintCollection {
         BufferLintCollectionMutGuard {
             inner: self,
             plugin_handle,
+            source: None,
+        }
+    }
+
+    pub fn source_mut_guard(
+        &mut self,
+        plugin_handle: PluginHandle,
+        source: u32,
+    ) -> BufferLintCollectionMutGuard<'_> {
+        BufferLintCollectionMutGuard {
+            inner: self,
+            plugin_handle,
+            source: Some(source),
         }
     }
 }
@@ -255,30 +282,40 @@ impl BufferLintCollection {
 pub struct BufferLintCollectionMutGuard<'a> {
     inner: &'a mut BufferLintCollection,
     plugin_handle: PluginHandle,
+    source: Option<u32>,
 }
 impl<'a> BufferLintCollectionMutGuard<'a> {
     pub fn clear(&mut self) {
         for i in (0..self.inner.len as usize).rev() {
-            if self.inner.lints[i].plugin_handle == self.plugin_handle {
+            let lint = &self.inner.lints[i];
+            let same_source = match self.source {
+                Some(source) => lint.source == source,
+                None => true,
+            };
+            if lint.plugin_handle == self.plugin_handle && same_source {
                 self.inner.len -= 1;
                 self.inner.lints.swap(self.inner.len as usize, i);
             }
         }
     }
 
-    pub fn add(&mut self, message: &str, range: BufferRange) {
+    pub fn add(&mut self, message: &str, range: BufferRange, severity: BufferLintSeverity) {
         match self.inner.lints.get_mut(self.inner.len as usize) {
             Some(lint) => {
                 lint.message.clear();
                 lint.message.push_str(message);
                 lint.range = range;
+                lint.severity = severity;
                 lint.plugin_handle = self.plugin_handle;
+                lint.source = self.source.unwrap_or(0);
             }
             None => {
                 self.inner.lints.push(BufferLint {
                     message: message.into(),
                     range,
+                    severity,
                     plugin_handle: self.plugin_handle,
+                    source: self.source.unwrap_or(0),
                 });
             }
         }
@@ -291,6 +328,397 @@ impl<'a> Drop for BufferLintCollectionMutGuard<'a> {
     }
 }
 
+pub struct BufferAnnotation {
+    pub text: String,
+    pub position: BufferPosition,
+    pub plugin_handle: PluginHandle,
+    pub source: u32,
+}
+
+#[derive(Default)]
+pub struct BufferAnnotationCollection {
+    annotations: Vec<BufferAnnotation>,
+    len: u32,
+}
+impl BufferAnnotationCollection {
+    pub fn all(&self) -> &[BufferAnnotation] {
+        &self.annotations[..self.len as usize]
+    }
+
+    /// Display length of all annotations drawn on `position`'s line up to (and including) `position`.
+    pub fn display_len_until(&self, position: BufferPosition) -> usize {
+        let annotations = self.all();
+        let start = annotations.partition_point(|a| a.position.line_index < position.line_index);
+        annotations[start..]
+            .iter()
+            .take_while(|a| a.position <= position)
+            .map(|a| {
+                a.text
+                    .chars()
+                    .map(|c| char_display_len(c) as usize)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+
+    pub fn line_display_len(&self, line_index: BufferPositionIndex) -> usize {
+        self.display_len_until(BufferPosition::line_col(
+            line_index,
+            BufferPositionIndex::MAX,
+        ))
+    }
+
+    fn insert_range(&mut self, range: BufferRange) {
+        for annotation in &mut self.annotations[..self.len as usize] {
+            annotation.position = annotation.position.insert(range);
+        }
+    }
+
+    fn delete_range(&mut self, range: BufferRange) {
+        for annotation in &mut self.annotations[..self.len as usize] {
+            annotation.position = annotation.position.delete(range);
+        }
+    }
+
+    pub fn mut_guard(
+        &mut self,
+        plugin_handle: PluginHandle,
+        source: u32,
+    ) -> BufferAnnotationCollectionMutGuard<'_> {
+        BufferAnnotationCollectionMutGuard {
+            inner: self,
+            plugin_handle,
+            source,
+        }
+    }
+}
+
+pub struct BufferAnnotationCollectionMutGuard<'a> {
+    inner: &'a mut BufferAnnotationCollection,
+    plugin_handle: PluginHandle,
+    source: u32,
+}
+impl<'a> BufferAnnotationCollectionMutGuard<'a> {
+    pub fn clear(&mut self) {
+        for i in (0..self.inner.len as usize).rev() {
+            let annotation = &self.inner.annotations[i];
+            if annotation.plugin_handle == self.plugin_handle && annotation.source == self.source {
+                self.inner.len -= 1;
+                self.inner.annotations.swap(self.inner.len as usize, i);
+            }
+        }
+    }
+
+    pub fn add(&mut self, text: &str, position: BufferPosition) {
+        match self.inner.annotations.get_mut(self.inner.len as usize) {
+            Some(annotation) => {
+                annotation.text.clear();
+                annotation.text.push_str(text);
+                annotation.position = position;
+                annotation.plugin_handle = self.plugin_handle;
+                annotation.source = self.source;
+            }
+            None => {
+                self.inner.annotations.push(BufferAnnotation {
+                    text: text.into(),
+                    position,
+                    plugin_handle: self.plugin_handle,
+                    source: self.source,
+                });
+            }
+        }
+        self.inner.len += 1;
+    }
+}
+impl<'a> Drop for BufferAnnotationCollectionMutGuard<'a> {
+    fn drop(&mut self) {
+        self.inner.annotations[..self.inner.len as usize].sort_by_key(|a| a.position);
+    }
+}
+
+pub struct BufferSemanticToken {
+    pub range: BufferRange,
+    pub kind: TokenKind,
+    pub plugin_handle: PluginHandle,
+    pub source: u32,
+}
+
+#[derive(Default)]
+pub struct BufferSemanticTokenCollection {
+    tokens: Vec<BufferSemanticToken>,
+    len: u32,
+}
+impl BufferSemanticTokenCollection {
+    pub fn all(&self) -> &[BufferSemanticToken] {
+        &self.tokens[..self.len as usize]
+    }
+
+    fn insert_range(&mut self, range: BufferRange) {
+        for token in &mut self.tokens[..self.len as usize] {
+            token.range.from = token.range.from.insert(range);
+            token.range.to = token.range.to.insert(range);
+        }
+    }
+
+    fn delete_range(&mut self, range: BufferRange) {
+        for token in &mut self.tokens[..self.len as usize] {
+            token.range.from = token.range.from.delete(range);
+            token.range.to = token.range.to.delete(range);
+        }
+    }
+
+    pub fn mut_guard(
+        &mut self,
+        plugin_handle: PluginHandle,
+        source: u32,
+    ) -> BufferSemanticTokenCollectionMutGuard<'_> {
+        BufferSemanticTokenCollectionMutGuard {
+            inner: self,
+            plugin_handle,
+            source,
+        }
+    }
+}
+
+pub struct BufferSemanticTokenCollectionMutGuard<'a> {
+    inner: &'a mut BufferSemanticTokenCollection,
+    plugin_handle: PluginHandle,
+    source: u32,
+}
+impl<'a> BufferSemanticTokenCollectionMutGuard<'a> {
+    pub fn clear(&mut self) {
+        for i in (0..self.inner.len as usize).rev() {
+            let token = &self.inner.tokens[i];
+            if token.plugin_handle == self.plugin_handle && token.source == self.source {
+                self.inner.len -= 1;
+                self.inner.tokens.swap(self.inner.len as usize, i);
+            }
+        }
+    }
+
+    pub fn add(&mut self, range: BufferRange, kind: TokenKind) {
+        match self.inner.tokens.get_mut(self.inner.len as usize) {
+            Some(token) => {
+                token.range = range;
+                token.kind = kind;
+                token.plugin_handle = self.plugin_handle;
+                token.source = self.source;
+            }
+            None => {
+                self.inner.tokens.push(BufferSemanticToken {
+                    range,
+                    kind,
+                    plugin_handle: self.plugin_handle,
+                    source: self.source,
+                });
+            }
+        }
+        self.inner.len += 1;
+    }
+}
+impl<'a> Drop for BufferSemanticTokenCollectionMutGuard<'a> {
+    fn drop(&mut self) {
+        let tokens = &mut self.inner.tokens[..self.inner.len as usize];
+        tokens.sort_unstable_by_key(|t| (t.range.from, t.range.to));
+
+        let mut len = 0;
+        for i in 0..tokens.len() {
+            if len > 0 && tokens[i].range.from < tokens[len - 1].range.to {
+                continue;
+            }
+            tokens.swap(len, i);
+            len += 1;
+        }
+        self.inner.len = len as _;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BufferSymbolHighlightKind {
+    Read,
+    Write,
+}
+
+pub struct BufferSymbolHighlight {
+    pub range: BufferRange,
+    pub kind: BufferSymbolHighlightKind,
+    pub plugin_handle: PluginHandle,
+    pub source: u32,
+}
+
+#[derive(Default)]
+pub struct BufferSymbolHighlightCollection {
+    highlights: Vec<BufferSymbolHighlight>,
+    len: u32,
+}
+impl BufferSymbolHighlightCollection {
+    pub fn all(&self) -> &[BufferSymbolHighlight] {
+        &self.highlights[..self.len as usize]
+    }
+
+    fn insert_range(&mut self, range: BufferRange) {
+        for highlight in &mut self.highlights[..self.len as usize] {
+            highlight.range.from = highlight.range.from.insert(range);
+            highlight.range.to = highlight.range.to.insert(range);
+        }
+    }
+
+    fn delete_range(&mut self, range: BufferRange) {
+        for highlight in &mut self.highlights[..self.len as usize] {
+            highlight.range.from = highlight.range.from.delete(range);
+            highlight.range.to = highlight.range.to.delete(range);
+        }
+    }
+
+    pub fn mut_guard(
+        &mut self,
+        plugin_handle: PluginHandle,
+        source: u32,
+    ) -> BufferSymbolHighlightCollectionMutGuard<'_> {
+        BufferSymbolHighlightCollectionMutGuard {
+            inner: self,
+            plugin_handle,
+            source,
+        }
+    }
+}
+
+pub struct BufferSymbolHighlightCollectionMutGuard<'a> {
+    inner: &'a mut BufferSymbolHighlightCollection,
+    plugin_handle: PluginHandle,
+    source: u32,
+}
+impl<'a> BufferSymbolHighlightCollectionMutGuard<'a> {
+    pub fn clear(&mut self) {
+        for i in (0..self.inner.len as usize).rev() {
+            let highlight = &self.inner.highlights[i];
+            if highlight.plugin_handle == self.plugin_handle && highlight.source == self.source {
+                self.inner.len -= 1;
+                self.inner.highlights.swap(self.inner.len as usize, i);
+            }
+        }
+    }
+
+    pub fn add(&mut self, range: BufferRange, kind: BufferSymbolHighlightKind) {
+        match self.inner.highlights.get_mut(self.inner.len as usize) {
+            Some(highlight) => {
+                highlight.range = range;
+                highlight.kind = kind;
+                highlight.plugin_handle = self.plugin_handle;
+                highlight.source = self.source;
+            }
+            None => {
+                self.inner.highlights.push(BufferSymbolHighlight {
+                    range,
+                    kind,
+                    plugin_handle: self.plugin_handle,
+                    source: self.source,
+                });
+            }
+        }
+        self.inner.len += 1;
+    }
+}
+impl<'a> Drop for BufferSymbolHighlightCollectionMutGuard<'a> {
+    fn drop(&mut self) {
+        self.inner.highlights[..self.inner.len as usize].sort_unstable_by_key(|h| h.range.from);
+    }
+}
+
+pub struct BufferVirtualLine {
+    pub text: String,
+    pub line_index: BufferPositionIndex,
+    pub plugin_handle: PluginHandle,
+    pub source: u32,
+}
+
+#[derive(Default)]
+pub struct BufferVirtualLineCollection {
+    lines: Vec<BufferVirtualLine>,
+    len: u32,
+}
+impl BufferVirtualLineCollection {
+    pub fn all(&self) -> &[BufferVirtualLine] {
+        &self.lines[..self.len as usize]
+    }
+
+    pub fn at(&self, line_index: BufferPositionIndex) -> &[BufferVirtualLine] {
+        let lines = self.all();
+        let from = lines.partition_point(|l| l.line_index < line_index);
+        let to = from + lines[from..].partition_point(|l| l.line_index == line_index);
+        &lines[from..to]
+    }
+
+    fn insert_range(&mut self, range: BufferRange) {
+        for line in &mut self.lines[..self.len as usize] {
+            let position = BufferPosition::line_col(line.line_index, 0).insert(range);
+            line.line_index = position.line_index;
+        }
+    }
+
+    fn delete_range(&mut self, range: BufferRange) {
+        for line in &mut self.lines[..self.len as usize] {
+            let position = BufferPosition::line_col(line.line_index, 0).delete(range);
+            line.line_index = position.line_index;
+        }
+    }
+
+    pub fn mut_guard(
+        &mut self,
+        plugin_handle: PluginHandle,
+        source: u32,
+    ) -> BufferVirtualLineCollectionMutGuard<'_> {
+        BufferVirtualLineCollectionMutGuard {
+            inner: self,
+            plugin_handle,
+            source,
+        }
+    }
+}
+
+pub struct BufferVirtualLineCollectionMutGuard<'a> {
+    inner: &'a mut BufferVirtualLineCollection,
+    plugin_handle: PluginHandle,
+    source: u32,
+}
+impl<'a> BufferVirtualLineCollectionMutGuard<'a> {
+    pub fn clear(&mut self) {
+        for i in (0..self.inner.len as usize).rev() {
+            let line = &self.inner.lines[i];
+            if line.plugin_handle == self.plugin_handle && line.source == self.source {
+                self.inner.len -= 1;
+                self.inner.lines.swap(self.inner.len as usize, i);
+            }
+        }
+    }
+
+    pub fn add(&mut self, text: &str, line_index: BufferPositionIndex) {
+        match self.inner.lines.get_mut(self.inner.len as usize) {
+            Some(line) => {
+                line.text.clear();
+                line.text.push_str(text);
+                line.line_index = line_index;
+                line.plugin_handle = self.plugin_handle;
+                line.source = self.source;
+            }
+            None => {
+                self.inner.lines.push(BufferVirtualLine {
+                    text: text.into(),
+                    line_index,
+                    plugin_handle: self.plugin_handle,
+                    source: self.source,
+                });
+            }
+        }
+        self.inner.len += 1;
+    }
+}
+impl<'a> Drop for BufferVirtualLineCollectionMutGuard<'a> {
+    fn drop(&mut self) {
+        self.inner.lines[..self.inner.len as usize].sort_by_key(|l| l.line_index);
+    }
+}
+
 struct BufferLinePool {
     pool: Vec<BufferLine>,
 }
@@ -573,12 +1001,26 @@ impl BufferContent {
         Ok(())
     }
 
-    pub fn write<W>(&self, write: &mut W) -> io::Result<()>
+    pub fn write<W>(
+        &self,
+        write: &mut W,
+        line_ending: LineEnding,
+        final_newline: bool,
+        charset: Charset,
+    ) -> io::Result<()>
     where
         W: io::Write,
     {
-        for line in &self.lines {
-            write!(write, "{}\n", line.as_str())?;
+        if let Charset::Utf8Bom = charset {
+            write.write_all(b"\xef\xbb\xbf")?;
+        }
+
+        let line_count = self.lines.len();
+        for (i, line) in self.lines.iter().enumerate() {
+            charset.encode(write, line.as_str())?;
+            if final_newline || i + 1 < line_count {
+                charset.encode(write, line_ending.as_str())?;
+            }
         }
         Ok(())
     }
@@ -925,15 +1367,107 @@ impl From<io::Error> for BufferReadError {
     }
 }
 
-pub struct BufferWriteError;
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Be,
+    Utf16Le,
+}
+impl Charset {
+    pub fn decode(self, bytes: &[u8]) -> Result<String, BufferReadError> {
+        fn decode_utf16<F>(bytes: &[u8], f: F) -> Result<String, BufferReadError>
+        where
+            F: Fn([u8; 2]) -> u16,
+        {
+            let units = bytes.chunks(2).map(|c| f([c[0], *c.get(1).unwrap_or(&0)]));
+            let mut text = String::with_capacity(bytes.len() / 2);
+            for c in std::char::decode_utf16(units) {
+                match c {
+                    Ok(c) => text.push(c),
+                    Err(_) => return Err(BufferReadError::InvalidData),
+                }
+            }
+            Ok(text)
+        }
+
+        match self {
+            Self::Utf8 | Self::Utf8Bom => match String::from_utf8(bytes.into()) {
+                Ok(text) => Ok(text),
+                Err(_) => Err(BufferReadError::InvalidData),
+            },
+            Self::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Self::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Self::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+        }
+    }
+
+    pub fn encode<W>(self, write: &mut W, text: &str) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        match self {
+            Self::Utf8 | Self::Utf8Bom => write.write_all(text.as_bytes()),
+            Self::Latin1 => {
+                for c in text.chars() {
+                    if (c as u32) > 0xff {
+                        return Err(io::Error::from(io::ErrorKind::InvalidData));
+                    }
+                    write.write_all(&[c as u8])?;
+                }
+                Ok(())
+            }
+            Self::Utf16Be => {
+                for c in text.encode_utf16() {
+                    write.write_all(&c.to_be_bytes())?;
+                }
+                Ok(())
+            }
+            Self::Utf16Le => {
+                for c in text.encode_utf16() {
+                    write.write_all(&c.to_le_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub enum BufferWriteError {
+    InvalidData,
+    Other,
+}
 impl fmt::Display for BufferWriteError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str("could not write to file")
+        match self {
+            Self::InvalidData => f.write_str("text can not be represented in the file charset"),
+            Self::Other => f.write_str("could not write to file"),
+        }
     }
 }
 impl From<io::Error> for BufferWriteError {
-    fn from(_: io::Error) -> Self {
-        Self
+    fn from(other: io::Error) -> Self {
+        match other.kind() {
+            io::ErrorKind::InvalidData => Self::InvalidData,
+            _ => Self::Other,
+        }
     }
 }
 
@@ -973,9 +1507,14 @@ pub struct Buffer {
     highlighted: HighlightedBuffer,
     history: BufferHistory,
     pub lints: BufferLintCollection,
+    pub annotations: BufferAnnotationCollection,
+    pub semantic_tokens: BufferSemanticTokenCollection,
+    pub symbol_highlights: BufferSymbolHighlightCollection,
+    pub virtual_lines: BufferVirtualLineCollection,
     search_ranges: Vec<BufferRange>,
     needs_save: bool,
     pub properties: BufferProperties,
+    pub editorconfig: EditorConfigProperties,
 }
 
 impl Buffer {
@@ -989,9 +1528,14 @@ impl Buffer {
             highlighted: HighlightedBuffer::new(),
             history: BufferHistory::new(),
             lints: BufferLintCollection::default(),
+            annotations: BufferAnnotationCollection::default(),
+            semantic_tokens: BufferSemanticTokenCollection::default(),
+            symbol_highlights: BufferSymbolHighlightCollection::default(),
+            virtual_lines: BufferVirtualLineCollection::default(),
             search_ranges: Vec::new(),
             needs_save: false,
             properties: BufferProperties::default(),
+            editorconfig: EditorConfigProperties::default(),
         }
     }
 
@@ -1004,9 +1548,14 @@ impl Buffer {
         self.syntax_handle = SyntaxHandle::default();
         self.highlighted.clear();
         self.history.clear();
+        self.annotations.len = 0;
+        self.semantic_tokens.len = 0;
+        self.symbol_highlights.len = 0;
+        self.virtual_lines.len = 0;
         self.search_ranges.clear();
         self.needs_save = false;
         self.properties = BufferProperties::default();
+        self.editorconfig = EditorConfigProperties::default();
     }
 
     fn remove_all_words_from_database(&mut self, word_database: &mut WordDatabase) {
@@ -1042,6 +1591,97 @@ impl Buffer {
             .highlight_dirty_lines(syntaxes.get(self.syntax_handle), &self.content)
     }
 
+    pub fn syntax_handle(&self) -> SyntaxHandle {
+        self.syntax_handle
+    }
+
+    pub fn compute_indentation(
+        &self,
+        syntax: &Syntax,
+        config: &Config,
+        previous_line: &str,
+        line: &str,
+        buf: &mut String,
+    ) {
+        let tab_size = self.editorconfig.tab_size(config).get() as usize;
+        let indent_with_tabs = self.editorconfig.indent_with_tabs(config);
+        let level_width = if indent_with_tabs {
+            tab_size
+        } else {
+            self.editorconfig.indent_size(config).get() as usize
+        };
+
+        let mut width = 0;
+        for c in previous_line.chars() {
+            match c {
+                ' ' => width += 1,
+                '\t' => width += tab_size,
+                _ => break,
+            }
+        }
+        if syntax.increases_indent(previous_line) {
+            width += level_width;
+        }
+        if syntax.decreases_indent(line) {
+            width = width.saturating_sub(level_width);
+        }
+
+        if indent_with_tabs {
+            for _ in 0..width / tab_size {
+                buf.push('\t');
+            }
+            width %= tab_size;
+        }
+        for _ in 0..width {
+            buf.push(' ');
+        }
+    }
+
+    pub fn reindent_line(
+        &mut self,
+        word_database: &mut WordDatabase,
+        syntax: &Syntax,
+        config: &Config,
+        line_index: BufferPositionIndex,
+        buf: &mut String,
+        events: &mut EditorEventQueue,
+    ) {
+        let lines = self.content.lines();
+        let line = lines[line_index as usize].as_str();
+        if line.trim().is_empty() {
+            return;
+        }
+
+        let previous_line = lines[..line_index as usize]
+            .iter()
+            .rev()
+            .map(BufferLine::as_str)
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("");
+
+        buf.clear();
+        self.compute_indentation(syntax, config, previous_line, line, buf);
+        let indentation_len = line.len() - line.trim_start().len();
+        if line[..indentation_len] == buf[..] {
+            return;
+        }
+
+        self.delete_range(
+            word_database,
+            BufferRange::between(
+                BufferPosition::line_col(line_index, 0),
+                BufferPosition::line_col(line_index, indentation_len as _),
+            ),
+            events,
+        );
+        self.insert_text(
+            word_database,
+            BufferPosition::line_col(line_index, 0),
+            buf,
+            events,
+        );
+    }
+
     pub fn refresh_syntax(&mut self, syntaxes: &SyntaxCollection) {
         let path = self.path.to_str().unwrap_or("");
         if path.is_empty() {
@@ -1092,6 +1732,10 @@ impl Buffer {
             position,
             text,
         );
+        self.annotations.insert_range(range);
+        self.semantic_tokens.insert_range(range);
+        self.symbol_highlights.insert_range(range);
+        self.virtual_lines.insert_range(range);
 
         events.enqueue_buffer_insert(self.handle, range, text);
 
@@ -1213,6 +1857,10 @@ impl Buffer {
             word_database,
             range,
         );
+        self.annotations.delete_range(range);
+        self.semantic_tokens.delete_range(range);
+        self.symbol_highlights.delete_range(range);
+        self.virtual_lines.delete_range(range);
     }
 
     fn delete_range_no_history(
@@ -1283,13 +1931,17 @@ impl Buffer {
         let content = &mut self.content;
         let highlighted = &mut self.highlighted;
         let lints = &mut self.lints;
+        let annotations = &mut self.annotations;
+        let semantic_tokens = &mut self.semantic_tokens;
+        let symbol_highlights = &mut self.symbol_highlights;
+        let virtual_lines = &mut self.virtual_lines;
         let uses_word_database = self.properties.word_database_enabled;
 
         let edits = selector(&mut self.history);
         for edit in edits.clone() {
             match edit.kind {
                 EditKind::Insert => {
-                    Self::insert_text_no_history(
+                    let range = Self::insert_text_no_history(
                         content,
                         highlighted,
                         lints,
@@ -1298,6 +1950,10 @@ impl Buffer {
                         edit.range.from,
                         edit.text,
                     );
+                    annotations.insert_range(range);
+                    semantic_tokens.insert_range(range);
+                    symbol_highlights.insert_range(range);
+                    virtual_lines.insert_range(range);
                     events.enqueue_buffer_insert(self.handle, edit.range, edit.text);
                 }
                 EditKind::Delete => {
@@ -1309,6 +1965,10 @@ impl Buffer {
                         word_database,
                         edit.range,
                     );
+                    annotations.delete_range(edit.range);
+                    semantic_tokens.delete_range(edit.range);
+                    symbol_highlights.delete_range(edit.range);
+                    virtual_lines.delete_range(edit.range);
                     events.enqueue(EditorEvent::BufferDeleteText {
                         handle: self.handle,
                         range: edit.range,
@@ -1358,9 +2018,18 @@ impl Buffer {
         } else if let Some(mut reader) = help::open(&self.path) {
             self.content.read(&mut reader)?;
         } else {
-            let file = File::open(&self.path)?;
-            let mut reader = io::BufReader::new(file);
-            self.content.read(&mut reader)?;
+            match self.editorconfig.charset {
+                None | Some(Charset::Utf8) | Some(Charset::Utf8Bom) => {
+                    let file = File::open(&self.path)?;
+                    let mut reader = io::BufReader::new(file);
+                    self.content.read(&mut reader)?;
+                }
+                Some(charset) => {
+                    let bytes = fs::read(&self.path)?;
+                    let text = charset.decode(&bytes)?;
+                    self.content.read(&mut text.as_bytes())?;
+                }
+            }
         }
 
         self.highlighted.insert_range(BufferRange::between(
@@ -1379,9 +2048,29 @@ impl Buffer {
         Ok(())
     }
 
+    fn trim_trailing_whitespace(
+        &mut self,
+        word_database: &mut WordDatabase,
+        events: &mut EditorEventQueue,
+    ) {
+        for line_index in (0..self.content.lines.len()).rev() {
+            let line = self.content.lines[line_index].as_str();
+            let trimmed_len = line.trim_end().len();
+            if trimmed_len < line.len() {
+                let range = BufferRange::between(
+                    BufferPosition::line_col(line_index as _, trimmed_len as _),
+                    BufferPosition::line_col(line_index as _, line.len() as _),
+                );
+                self.delete_range(word_database, range, events);
+            }
+        }
+        self.commit_edits();
+    }
+
     pub fn write_to_file(
         &mut self,
         new_path: Option<&Path>,
+        word_database: &mut WordDatabase,
         events: &mut EditorEventQueue,
     ) -> Result<(), BufferWriteError> {
         let new_path = match new_path {
@@ -1399,8 +2088,19 @@ impl Buffer {
         }
 
         if self.properties.is_file {
-            let file = File::create(&self.path)?;
-            self.content.write(&mut io::BufWriter::new(file))?;
+            if let Some(true) = self.editorconfig.trim_trailing_whitespace {
+                self.trim_trailing_whitespace(word_database, events);
+            }
+
+            // encode everything first so a failed save does not truncate the file
+            let mut bytes = Vec::new();
+            self.content.write(
+                &mut bytes,
+                self.editorconfig.line_ending.unwrap_or(LineEnding::Lf),
+                self.editorconfig.insert_final_newline.unwrap_or(true),
+                self.editorconfig.charset.unwrap_or(Charset::Utf8),
+            )?;
+            fs::write(&self.path, bytes)?;
         }
 
         self.needs_save = false;
@@ -1644,6 +2344,13 @@ mod tests {
     use super::*;
     use crate::buffer_position::BufferPosition;
 
+    fn range(from: (usize, usize), to: (usize, usize)) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from.0 as _, from.1 as _),
+            BufferPosition::line_col(to.0 as _, to.1 as _),
+        )
+    }
+
     #[test]
     fn test_find_delimiter_pair_at() {
         let text = "|a|bcd|efg|";
@@ -2246,4 +2953,265 @@ mod tests {
         assert_eq!(6, len(&buffer, 1));
         assert_eq!(3, len(&buffer, 2));
     }
+
+    #[test]
+    fn buffer_write_to_file() {
+        let mut word_database = WordDatabase::new();
+        let mut events = EditorEventQueue::default();
+        let path = std::env::temp_dir().join("pepper-buffer-write-to-file-test.txt");
+
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.properties = BufferProperties::text();
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "a  \nb\t\n\nc",
+            &mut events,
+        );
+
+        buffer.editorconfig.line_ending = Some(LineEnding::CrLf);
+        buffer.editorconfig.insert_final_newline = Some(false);
+        assert!(buffer
+            .write_to_file(Some(&path), &mut word_database, &mut events)
+            .is_ok());
+        assert_eq!(b"a  \r\nb\t\r\n\r\nc", &fs::read(&path).unwrap()[..]);
+
+        buffer.editorconfig.line_ending = Some(LineEnding::Lf);
+        buffer.editorconfig.insert_final_newline = Some(true);
+        buffer.editorconfig.trim_trailing_whitespace = Some(true);
+        assert!(buffer
+            .write_to_file(None, &mut word_database, &mut events)
+            .is_ok());
+        assert_eq!(b"a\nb\n\nc\n", &fs::read(&path).unwrap()[..]);
+        assert_eq!("a\nb\n\nc", buffer.content.to_string());
+
+        buffer.editorconfig.charset = Some(Charset::Latin1);
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "\u{e9}",
+            &mut events,
+        );
+        assert!(buffer
+            .write_to_file(None, &mut word_database, &mut events)
+            .is_ok());
+        assert_eq!(b"\xe9a\nb\n\nc\n", &fs::read(&path).unwrap()[..]);
+
+        buffer.insert_text(
+            &mut word_database,
+            BufferPosition::zero(),
+            "\u{20ac}",
+            &mut events,
+        );
+        assert!(matches!(
+            buffer.write_to_file(None, &mut word_database, &mut events),
+            Err(BufferWriteError::InvalidData)
+        ));
+        assert!(buffer.needs_save());
+        assert_eq!(b"\xe9a\nb\n\nc\n", &fs::read(&path).unwrap()[..]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn buffer_lint_source_clear() {
+        fn lints(lints: &BufferLintCollection) -> Vec<(&str, u32)> {
+            lints
+                .all()
+                .iter()
+                .map(|l| (&l.message[..], l.plugin_handle.0))
+                .collect()
+        }
+
+        let plugin_a = PluginHandle(0);
+        let plugin_b = PluginHandle(1);
+        let range = |line| {
+            BufferRange::between(
+                BufferPosition::line_col(line, 0),
+                BufferPosition::line_col(line, 1),
+            )
+        };
+
+        let mut collection = BufferLintCollection::default();
+        let severity = BufferLintSeverity::Error;
+        collection
+            .source_mut_guard(plugin_a, 0)
+            .add("a0", range(0), severity);
+        collection
+            .source_mut_guard(plugin_a, 1)
+            .add("a1", range(1), severity);
+        collection.mut_guard(plugin_b).add("b", range(2), severity);
+        assert_eq!(vec![("a0", 0), ("a1", 0), ("b", 1)], lints(&collection));
+
+        collection.source_mut_guard(plugin_a, 1).clear();
+        assert_eq!(vec![("a0", 0), ("b", 1)], lints(&collection));
+
+        {
+            let mut lints = collection.source_mut_guard(plugin_a, 0);
+            lints.clear();
+            lints.add("a0 new", range(3), severity);
+        }
+        assert_eq!(vec![("b", 1), ("a0 new", 0)], lints(&collection));
+
+        collection.source_mut_guard(plugin_b, 1).clear();
+        assert_eq!(vec![("b", 1), ("a0 new", 0)], lints(&collection));
+
+        collection.mut_guard(plugin_a).clear();
+        assert_eq!(vec![("b", 1)], lints(&collection));
+    }
+
+    #[test]
+    fn buffer_annotation_shifting() {
+        fn positions(annotations: &BufferAnnotationCollection) -> Vec<BufferPosition> {
+            annotations.all().iter().map(|a| a.position).collect()
+        }
+
+        let mut annotations = BufferAnnotationCollection::default();
+        {
+            let mut annotations = annotations.mut_guard(PluginHandle(0), 0);
+            annotations.add(": u32", BufferPosition::line_col(0, 5));
+            annotations.add("x: ", BufferPosition::line_col(1, 0));
+            annotations.add("a", BufferPosition::line_col(0, 2));
+        }
+        assert_eq!(
+            vec![
+                BufferPosition::line_col(0, 2),
+                BufferPosition::line_col(0, 5),
+                BufferPosition::line_col(1, 0),
+            ],
+            positions(&annotations)
+        );
+        assert_eq!(
+            0,
+            annotations.display_len_until(BufferPosition::line_col(0, 1))
+        );
+        assert_eq!(
+            1,
+            annotations.display_len_until(BufferPosition::line_col(0, 2))
+        );
+        assert_eq!(6, annotations.line_display_len(0));
+        assert_eq!(3, annotations.line_display_len(1));
+        assert_eq!(0, annotations.line_display_len(2));
+
+        annotations.insert_range(BufferRange::between(
+            BufferPosition::line_col(0, 1),
+            BufferPosition::line_col(0, 3),
+        ));
+        assert_eq!(
+            vec![
+                BufferPosition::line_col(0, 4),
+                BufferPosition::line_col(0, 7),
+                BufferPosition::line_col(1, 0),
+            ],
+            positions(&annotations)
+        );
+
+        annotations.insert_range(BufferRange::between(
+            BufferPosition::line_col(0, 0),
+            BufferPosition::line_col(1, 0),
+        ));
+        assert_eq!(
+            vec![
+                BufferPosition::line_col(1, 4),
+                BufferPosition::line_col(1, 7),
+                BufferPosition::line_col(2, 0),
+            ],
+            positions(&annotations)
+        );
+
+        annotations.delete_range(BufferRange::between(
+            BufferPosition::line_col(1, 2),
+            BufferPosition::line_col(1, 6),
+        ));
+        assert_eq!(
+            vec![
+                BufferPosition::line_col(1, 2),
+                BufferPosition::line_col(1, 3),
+                BufferPosition::line_col(2, 0),
+            ],
+            positions(&annotations)
+        );
+
+        annotations.delete_range(BufferRange::between(
+            BufferPosition::line_col(0, 0),
+            BufferPosition::line_col(1, 0),
+        ));
+        assert_eq!(
+            vec![
+                BufferPosition::line_col(0, 2),
+                BufferPosition::line_col(0, 3),
+                BufferPosition::line_col(1, 0),
+            ],
+            positions(&annotations)
+        );
+
+        annotations.mut_guard(PluginHandle(0), 0).clear();
+        assert!(annotations.all().is_empty());
+    }
+
+    #[test]
+    fn semantic_tokens_move_with_edits() {
+        fn ranges(tokens: &BufferSemanticTokenCollection) -> Vec<BufferRange> {
+            tokens.all().iter().map(|t| t.range).collect()
+        }
+
+        let mut tokens = BufferSemanticTokenCollection::default();
+        let mut guard = tokens.mut_guard(PluginHandle(0), 0);
+        guard.add(range((1, 2), (1, 5)), TokenKind::Variable);
+        guard.add(range((0, 4), (0, 7)), TokenKind::Function);
+        drop(guard);
+        assert_eq!(
+            vec![range((0, 4), (0, 7)), range((1, 2), (1, 5))],
+            ranges(&tokens)
+        );
+
+        tokens.insert_range(range((0, 0), (0, 2)));
+        assert_eq!(
+            vec![range((0, 6), (0, 9)), range((1, 2), (1, 5))],
+            ranges(&tokens)
+        );
+
+        tokens.insert_range(range((0, 0), (1, 0)));
+        assert_eq!(
+            vec![range((1, 6), (1, 9)), range((2, 2), (2, 5))],
+            ranges(&tokens)
+        );
+
+        tokens.delete_range(range((1, 0), (1, 3)));
+        assert_eq!(
+            vec![range((1, 3), (1, 6)), range((2, 2), (2, 5))],
+            ranges(&tokens)
+        );
+
+        tokens.delete_range(range((1, 5), (2, 3)));
+        assert_eq!(
+            vec![range((1, 3), (1, 5)), range((1, 5), (1, 7))],
+            ranges(&tokens)
+        );
+    }
+
+    #[test]
+    fn semantic_tokens_do_not_overlap() {
+        let mut tokens = BufferSemanticTokenCollection::default();
+        let mut guard = tokens.mut_guard(PluginHandle(0), 0);
+        guard.add(range((0, 8), (0, 10)), TokenKind::Variable);
+        guard.add(range((0, 0), (0, 5)), TokenKind::Function);
+        drop(guard);
+
+        let mut guard = tokens.mut_guard(PluginHandle(1), 0);
+        guard.add(range((0, 6), (0, 9)), TokenKind::Type);
+        guard.add(range((0, 2), (0, 4)), TokenKind::Type);
+        guard.add(range((0, 10), (0, 12)), TokenKind::Type);
+        drop(guard);
+
+        let tokens: Vec<_> = tokens.all().iter().map(|t| (t.range, t.kind)).collect();
+        assert_eq!(
+            vec![
+                (range((0, 0), (0, 5)), TokenKind::Function),
+                (range((0, 6), (0, 9)), TokenKind::Type),
+                (range((0, 10), (0, 12)), TokenKind::Type),
+            ],
+            tokens
+        );
+    }
 }
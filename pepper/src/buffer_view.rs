@@ -434,6 +434,114 @@ impl BufferView {
         }
     }
 
+    pub fn toggle_comment_in_cursor_lines(
+        &self,
+        buffers: &mut BufferCollection,
+        word_database: &mut WordDatabase,
+        line_comment: &str,
+        block_comment: (&str, &str),
+        events: &mut EditorEventQueue,
+    ) {
+        let buffer = buffers.get_mut(self.buffer_handle);
+
+        let mut line_indices = Vec::new();
+        for cursor in &self.cursors[..] {
+            let range = cursor.to_range();
+            for line_index in range.from.line_index..=range.to.line_index {
+                if line_indices.last() < Some(&line_index) {
+                    line_indices.push(line_index);
+                }
+            }
+        }
+
+        let (block_open, block_close) = block_comment;
+        let use_line_comment = !line_comment.is_empty();
+
+        let lines = buffer.content().lines();
+        let mut all_commented = true;
+        let mut indentation_len = usize::MAX;
+        for &line_index in &line_indices {
+            let line = lines[line_index as usize].as_str();
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            all_commented &= if use_line_comment {
+                match trimmed.strip_prefix(line_comment) {
+                    Some(rest) => rest.is_empty() || rest.starts_with(char::is_whitespace),
+                    None => false,
+                }
+            } else {
+                trimmed.len() >= block_open.len() + block_close.len()
+                    && trimmed.starts_with(block_open)
+                    && trimmed.ends_with(block_close)
+                    && !trimmed[block_open.len()..trimmed.len() - block_close.len()]
+                        .contains(block_close)
+            };
+            indentation_len = indentation_len.min(line.len() - line.trim_start().len());
+        }
+        if indentation_len == usize::MAX {
+            return;
+        }
+
+        let mut buf = String::new();
+        for &line_index in line_indices.iter().rev() {
+            let line = buffer.content().lines()[line_index as usize].as_str();
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let line_start = line.len() - line.trim_start().len();
+            let line_end = line.trim_end().len();
+            let range = |from: usize, to: usize| {
+                BufferRange::between(
+                    BufferPosition::line_col(line_index, from as _),
+                    BufferPosition::line_col(line_index, to as _),
+                )
+            };
+
+            if all_commented {
+                if use_line_comment {
+                    let mut comment_end = line_start + line_comment.len();
+                    if line[comment_end..].starts_with(' ') {
+                        comment_end += 1;
+                    }
+                    buffer.delete_range(word_database, range(line_start, comment_end), events);
+                } else {
+                    let mut open_end = line_start + block_open.len();
+                    let mut close_start = line_end - block_close.len();
+                    if open_end < close_start && line[open_end..].starts_with(' ') {
+                        open_end += 1;
+                    }
+                    if open_end < close_start && line[..close_start].ends_with(' ') {
+                        close_start -= 1;
+                    }
+                    buffer.delete_range(word_database, range(close_start, line_end), events);
+                    buffer.delete_range(word_database, range(line_start, open_end), events);
+                }
+            } else if use_line_comment {
+                buf.clear();
+                buf.push_str(line_comment);
+                buf.push(' ');
+                let position = BufferPosition::line_col(line_index, indentation_len as _);
+                buffer.insert_text(word_database, position, &buf, events);
+            } else {
+                buf.clear();
+                buf.push(' ');
+                buf.push_str(block_close);
+                let position = BufferPosition::line_col(line_index, line_end as _);
+                buffer.insert_text(word_database, position, &buf, events);
+
+                buf.clear();
+                buf.push_str(block_open);
+                buf.push(' ');
+                let position = BufferPosition::line_col(line_index, indentation_len as _);
+                buffer.insert_text(word_database, position, &buf, events);
+            }
+        }
+    }
+
     pub fn find_completion_positions(
         &self,
         buffers: &mut BufferCollection,
@@ -794,4 +902,61 @@ mod tests {
         assert_movement(&mut ctx, 1..2, 1..0, CursorMovement::WordsBackward(1));
         assert_movement(&mut ctx, 2..0, 1..9, CursorMovement::WordsBackward(1));
     }
+
+    #[test]
+    fn toggle_comment() {
+        use crate::testing::TestEditor;
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "fn f() {\n\ta();\n\n\t\tb();\n}");
+
+        editor.keys("jvjj");
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!(
+            "fn f() {\n\t// a();\n\n\t// \tb();\n}",
+            editor.buffer_text()
+        );
+
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("fn f() {\n\ta();\n\n\t\tb();\n}", editor.buffer_text());
+
+        editor.keys("<esc>gkcj");
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!(
+            "// fn f() {\n// \ta();\n\n\t\tb();\n}",
+            editor.buffer_text()
+        );
+        editor.keys("u");
+        assert_eq!("fn f() {\n\ta();\n\n\t\tb();\n}", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.html", "<p>\n</p>");
+        editor.keys("vj");
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("<!-- <p> -->\n<!-- </p> -->", editor.buffer_text());
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("<p>\n</p>", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "/// doc\n// a");
+        editor.keys("vj");
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("// /// doc\n// // a", editor.buffer_text());
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("/// doc\n// a", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.html", "<!-- a --> b <!-- c -->");
+        assert!(editor.command("toggle-comment").is_ok());
+        assert_eq!("<!-- <!-- a --> b <!-- c --> -->", editor.buffer_text());
+        editor.keys("u");
+        assert_eq!("<!-- a --> b <!-- c -->", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+        assert!(matches!(
+            editor.command("toggle-comment"),
+            Err(crate::command::CommandError::NoCommentTokens)
+        ));
+    }
 }
@@ -1,4 +1,4 @@
-use std::{fmt, path::Path};
+use std::{fmt, ops::Range, path::Path};
 
 use crate::{
     buffer::{BufferHandle, BufferProperties, CharDisplayDistances},
@@ -47,6 +47,7 @@ pub struct Client {
     buffer_view_handle: Option<BufferViewHandle>,
     stdin_buffer_handle: Option<BufferHandle>,
     stdin_residual_bytes: ResidualStrBytes,
+    pub(crate) eval_wait_buffer_handle: Option<BufferHandle>,
 }
 
 impl Client {
@@ -63,6 +64,7 @@ impl Client {
             buffer_view_handle: None,
             stdin_buffer_handle: None,
             stdin_residual_bytes: ResidualStrBytes::default(),
+            eval_wait_buffer_handle: None,
         }
     }
 
@@ -77,6 +79,7 @@ impl Client {
         self.buffer_view_handle = None;
         self.stdin_buffer_handle = None;
         self.stdin_residual_bytes = ResidualStrBytes::default();
+        self.eval_wait_buffer_handle = None;
     }
 
     pub fn handle(&self) -> ClientHandle {
@@ -108,6 +111,43 @@ impl Client {
         self.viewport_size.0 != 0 && self.viewport_size.1 != 0
     }
 
+    pub fn visible_line_range(
+        &self,
+        editor: &Editor,
+    ) -> Option<(BufferHandle, Range<BufferPositionIndex>)> {
+        if !self.has_ui() {
+            return None;
+        }
+
+        let buffer_view_handle = self.buffer_view_handle()?;
+        let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+        let buffer = editor.buffers.get(buffer_handle);
+        let tab_size = buffer.editorconfig.tab_size(&editor.config).get();
+
+        let width = self.viewport_size.0 as usize;
+        let scroll = self.scroll as usize;
+        let height = self.viewport_size.1.saturating_sub(1) as usize;
+
+        let mut from = None;
+        let mut to = 0;
+        let mut padding_top = 0;
+        for (line_index, display_len) in buffer.content().line_display_lens().iter().enumerate() {
+            let annotations_len = buffer.annotations.line_display_len(line_index as _);
+            padding_top += buffer.virtual_lines.at(line_index as _).len();
+            padding_top += 1 + (display_len.total_len(tab_size) + annotations_len) / width;
+            if from.is_none() && padding_top > scroll {
+                from = Some(line_index);
+            }
+            to = line_index + 1;
+            if padding_top >= scroll + height {
+                break;
+            }
+        }
+
+        let from = from.unwrap_or(to);
+        Some((buffer_handle, from as _..to as _))
+    }
+
     pub fn set_view_anchor(&mut self, editor: &Editor, anchor: ViewAnchor) {
         if !self.has_ui() {
             return;
@@ -211,23 +251,33 @@ impl Client {
             None => return 0,
         };
 
-        let tab_size = editor.config.tab_size.get();
         let width = self.viewport_size.0 as usize;
 
         let buffer_view = editor.buffer_views.get(buffer_view_handle);
-        let buffer = editor.buffers.get(buffer_view.buffer_handle).content();
+        let buffer = editor.buffers.get(buffer_view.buffer_handle);
+        let tab_size = buffer.editorconfig.tab_size(&editor.config).get();
         let position = buffer_view.cursors.main_cursor().position;
-
-        let mut height = position.line_index as usize;
-        for display_len in &buffer.line_display_lens()[..position.line_index as usize] {
-            height += display_len.total_len(tab_size) / width;
+        let virtual_lines_count = buffer
+            .virtual_lines
+            .all()
+            .partition_point(|l| l.line_index <= position.line_index);
+        let annotations = &buffer.annotations;
+        let buffer = buffer.content();
+
+        let mut height = position.line_index as usize + virtual_lines_count;
+        let line_display_lens = &buffer.line_display_lens()[..position.line_index as usize];
+        for (line_index, display_len) in line_display_lens.iter().enumerate() {
+            let annotations_len = annotations.line_display_len(line_index as _);
+            height += (display_len.total_len(tab_size) + annotations_len) / width;
         }
 
         let cursor_line = buffer.lines()[position.line_index as usize].as_str();
         let cursor_line = &cursor_line[..position.column_byte_index as usize];
-        if let Some(d) = CharDisplayDistances::new(cursor_line, tab_size).last() {
-            height += d.distance as usize / width;
-        }
+        let cursor_distance = match CharDisplayDistances::new(cursor_line, tab_size).next_back() {
+            Some(d) => d.distance as usize,
+            None => 0,
+        };
+        height += (cursor_distance + annotations.display_len_until(position)) / width;
 
         height
     }
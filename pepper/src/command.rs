@@ -1,21 +1,24 @@
-use std::{collections::VecDeque, fmt};
+use std::{collections::VecDeque, fmt, fmt::Write, ops::Range, path::PathBuf};
 
 use crate::{
     buffer::{Buffer, BufferHandle, BufferReadError, BufferWriteError},
-    buffer_view::BufferViewHandle,
+    buffer_view::{BufferView, BufferViewHandle},
     client::ClientHandle,
     config::ParseConfigError,
     editor::{EditorContext, EditorFlow},
-    editor_utils::{MessageKind, ParseKeyMapError},
-    events::KeyParseAllError,
-    glob::InvalidGlobError,
+    editor_utils::{MessageKind, ParseKeyMapError, RegisterKey},
+    events::{EditorEvent, EditorEventIter, KeyParseAllError},
+    glob::{Glob, InvalidGlobError},
     pattern::PatternError,
     plugin::PluginHandle,
+    script::ScriptError,
 };
 
 mod builtin;
 
 const HISTORY_CAPACITY: usize = 10;
+const MAX_USER_COMMAND_DEPTH: u8 = 32;
+const MAX_SOURCE_DEPTH: usize = 32;
 
 pub enum CommandError {
     NoSuchCommand,
@@ -34,6 +37,16 @@ pub enum CommandError {
     KeyParseError(KeyParseAllError),
     PatternError(PatternError),
     InvalidGlob(InvalidGlobError),
+    InvalidAutoPairs,
+    NoCommentTokens,
+    NoSuchHookEvent,
+    NoSuchVariable,
+    InvalidVariableName,
+    InvalidCondition,
+    CommandAlreadyExists,
+    UserCommandTooDeep,
+    SourceTooDeep,
+    ScriptError(ScriptError),
     OtherStatic(&'static str),
     OtherOwned(String),
 }
@@ -56,6 +69,16 @@ impl fmt::Display for CommandError {
             Self::KeyParseError(error) => error.fmt(f),
             Self::PatternError(error) => error.fmt(f),
             Self::InvalidGlob(error) => error.fmt(f),
+            Self::InvalidAutoPairs => f.write_str("auto pairs must be a sequence of char pairs"),
+            Self::NoCommentTokens => f.write_str("current syntax has no comment tokens"),
+            Self::NoSuchHookEvent => f.write_str("no such hook event"),
+            Self::NoSuchVariable => f.write_str("no such variable"),
+            Self::InvalidVariableName => f.write_str("invalid variable name"),
+            Self::InvalidCondition => f.write_str("invalid condition"),
+            Self::CommandAlreadyExists => f.write_str("command already exists"),
+            Self::UserCommandTooDeep => f.write_str("too many nested user command calls"),
+            Self::SourceTooDeep => f.write_str("too many nested config sources"),
+            Self::ScriptError(error) => error.fmt(f),
             Self::OtherStatic(error) => f.write_str(error),
             Self::OtherOwned(error) => f.write_str(&error),
         }
@@ -95,6 +118,7 @@ pub struct CommandIO<'a> {
     client_handle: Option<ClientHandle>,
     plugin_handle: Option<PluginHandle>,
 
+    pub name: &'a str,
     pub args: CommandArgs<'a>,
     pub bang: bool,
     pub flow: EditorFlow,
@@ -116,6 +140,15 @@ impl<'a> CommandIO<'a> {
         ctx: &EditorContext,
     ) -> Result<BufferViewHandle, CommandError> {
         let client_handle = self.client_handle()?;
+        if let Some(buffer_handle) = ctx.editor.commands.context_buffer_handle {
+            return ctx
+                .editor
+                .buffer_views
+                .iter()
+                .find(|v| v.client_handle == client_handle && v.buffer_handle == buffer_handle)
+                .map(BufferView::handle)
+                .ok_or(CommandError::NoBufferOpened);
+        }
         match ctx.clients.get(client_handle).buffer_view_handle() {
             Some(handle) => Ok(handle),
             None => Err(CommandError::NoBufferOpened),
@@ -123,6 +156,9 @@ impl<'a> CommandIO<'a> {
     }
 
     pub fn current_buffer_handle(&self, ctx: &EditorContext) -> Result<BufferHandle, CommandError> {
+        if let Some(buffer_handle) = ctx.editor.commands.context_buffer_handle {
+            return Ok(buffer_handle);
+        }
         let buffer_view_handle = self.current_buffer_view_handle(ctx)?;
         let buffer_handle = ctx
             .editor
@@ -239,6 +275,69 @@ impl<'a> Iterator for CommandTokenizer<'a> {
     }
 }
 
+fn has_unclosed_raw_token(command: &str) -> bool {
+    let base = command.as_ptr() as usize;
+    for token in CommandTokenizer(command) {
+        let token_start = token.as_ptr() as usize - base;
+        let is_literal = command[..token_start].ends_with(&[' ', '\t'][..]) || token_start == 0;
+        if is_literal {
+            if let Some(rest) = token.strip_prefix('[') {
+                if rest.trim_start_matches('=').starts_with('[') {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+pub struct CommandIter<'a> {
+    source: &'a str,
+    line_index: usize,
+}
+impl<'a> CommandIter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            line_index: 0,
+        }
+    }
+}
+impl<'a> Iterator for CommandIter<'a> {
+    type Item = (usize, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.source.is_empty() {
+                return None;
+            }
+
+            let line_index = self.line_index;
+            let mut end = 0;
+            loop {
+                end += match self.source[end..].find('\n') {
+                    Some(len) => len,
+                    None => self.source.len() - end,
+                };
+                self.line_index += 1;
+                if end == self.source.len() || !has_unclosed_raw_token(&self.source[..end]) {
+                    break;
+                }
+                end += 1;
+            }
+
+            let command = &self.source[..end];
+            self.source = self.source.get(end + 1..).unwrap_or("");
+
+            let command = command.strip_suffix('\r').unwrap_or(command);
+            let command = command.trim_start_matches(&[' ', '\t'][..]);
+            if command.is_empty() || command.starts_with('#') {
+                continue;
+            }
+            return Some((line_index, command));
+        }
+    }
+}
+
 pub type CommandFn = fn(ctx: &mut EditorContext, io: &mut CommandIO) -> Result<(), CommandError>;
 
 pub struct Command {
@@ -319,10 +418,374 @@ impl AliasCollection {
     }
 }
 
+#[derive(Default)]
+pub struct VariableCollection {
+    texts: String,
+    variables: Vec<Alias>,
+}
+impl VariableCollection {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), CommandError> {
+        if name.is_empty() || name.contains(&['{', '}', ':', ' ', '\t'][..]) {
+            return Err(CommandError::InvalidVariableName);
+        }
+        if name.len() > u16::MAX as _ || value.len() > u16::MAX as _ {
+            return Err(CommandError::OtherStatic("variable value too long"));
+        }
+
+        self.remove(name);
+
+        let start = self.texts.len() as _;
+        self.texts.push_str(name);
+        self.texts.push_str(value);
+
+        self.variables.push(Alias {
+            start,
+            from_len: name.len() as _,
+            to_len: value.len() as _,
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        for (i, variable) in self.variables.iter().enumerate() {
+            if name == variable.from(&self.texts) {
+                let variable_start = variable.start as usize;
+                let variable_len = variable.from_len as u32 + variable.to_len as u32;
+                self.variables.remove(i);
+                for variable in &mut self.variables[i..] {
+                    variable.start -= variable_len;
+                }
+                self.texts
+                    .drain(variable_start..variable_start + variable_len as usize);
+                break;
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        for variable in &self.variables {
+            if name == variable.from(&self.texts) {
+                return Some(variable.to(&self.texts));
+            }
+        }
+
+        None
+    }
+}
+
+struct UserCommand {
+    name: String,
+    params: Vec<String>,
+    body: String,
+}
+
+#[derive(Default)]
+pub struct UserCommandCollection {
+    commands: Vec<UserCommand>,
+    depth: u8,
+}
+impl UserCommandCollection {
+    pub fn add(&mut self, name: &str, params: &[&str], body: &str) {
+        let params = params.iter().map(|&p| p.into()).collect();
+        match self.commands.iter_mut().find(|c| c.name == name) {
+            Some(command) => {
+                command.params = params;
+                command.body.clear();
+                command.body.push_str(body);
+            }
+            None => self.commands.push(UserCommand {
+                name: name.into(),
+                params,
+                body: body.into(),
+            }),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.iter().map(|c| &c.name[..])
+    }
+
+    fn eval(
+        ctx: &mut EditorContext,
+        client_handle: Option<ClientHandle>,
+        name: &str,
+        mut args: CommandArgs,
+    ) -> Result<EditorFlow, CommandError> {
+        let user_commands = &ctx.editor.commands.user_commands;
+        let command = match user_commands.commands.iter().find(|c| c.name == name) {
+            Some(command) => command,
+            None => return Err(CommandError::NoSuchCommand),
+        };
+        if user_commands.depth >= MAX_USER_COMMAND_DEPTH {
+            return Err(CommandError::UserCommandTooDeep);
+        }
+
+        let mut bindings = Vec::with_capacity(command.params.len());
+        for param in &command.params {
+            bindings.push((param.clone(), args.next()?));
+        }
+        args.assert_empty()?;
+        let body = ctx.editor.string_pool.acquire_with(&command.body);
+
+        let mut previous_values = Vec::with_capacity(bindings.len());
+        for (name, value) in &bindings {
+            let previous_value = ctx.editor.commands.variables.get(name).map(String::from);
+            previous_values.push(previous_value);
+            ctx.editor.commands.variables.set(name, value)?;
+        }
+
+        ctx.editor.commands.user_commands.depth += 1;
+        let result = CommandManager::eval_commands(ctx, client_handle, &body);
+        ctx.editor.commands.user_commands.depth -= 1;
+        ctx.editor.string_pool.release(body);
+
+        for ((name, _), previous_value) in bindings.iter().zip(previous_values) {
+            match previous_value {
+                Some(value) => {
+                    let _ = ctx.editor.commands.variables.set(name, &value);
+                }
+                None => ctx.editor.commands.variables.remove(name),
+            }
+        }
+
+        result
+    }
+}
+
+pub static HOOK_EVENT_NAMES: &[&str] = &[
+    "buffer-read",
+    "buffer-write",
+    "buffer-close",
+    "mode-change",
+    "client-join",
+    "idle",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    BufferRead,
+    BufferWrite,
+    BufferClose,
+    ModeChange,
+    ClientJoin,
+    Idle,
+}
+impl HookEvent {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "buffer-read" => Some(Self::BufferRead),
+            "buffer-write" => Some(Self::BufferWrite),
+            "buffer-close" => Some(Self::BufferClose),
+            "mode-change" => Some(Self::ModeChange),
+            "client-join" => Some(Self::ClientJoin),
+            "idle" => Some(Self::Idle),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_editor_event(
+        ctx: &EditorContext,
+        event: &EditorEvent,
+        subject: &mut String,
+    ) -> Option<(Self, Option<ClientHandle>, Option<BufferHandle>)> {
+        let focused_client = ctx.clients.focused_client();
+        let hook_event = match *event {
+            EditorEvent::BufferRead { handle } => {
+                Self::buffer_subject(ctx, handle, subject);
+                (Self::BufferRead, focused_client, Some(handle))
+            }
+            EditorEvent::BufferWrite { handle, .. } => {
+                Self::buffer_subject(ctx, handle, subject);
+                (Self::BufferWrite, focused_client, Some(handle))
+            }
+            EditorEvent::BufferClose { handle } => {
+                Self::buffer_subject(ctx, handle, subject);
+                (Self::BufferClose, focused_client, Some(handle))
+            }
+            EditorEvent::ModeChange { kind } => {
+                subject.push_str(kind.name());
+                (Self::ModeChange, focused_client, None)
+            }
+            EditorEvent::ClientJoin { handle } => {
+                Self::client_subject(ctx, handle, subject);
+                (Self::ClientJoin, Some(handle), None)
+            }
+            EditorEvent::Idle => {
+                if let Some(handle) = focused_client {
+                    Self::client_subject(ctx, handle, subject);
+                }
+                (Self::Idle, focused_client, None)
+            }
+            _ => return None,
+        };
+        Some(hook_event)
+    }
+
+    fn buffer_subject(ctx: &EditorContext, buffer_handle: BufferHandle, subject: &mut String) {
+        if let Some(path) = ctx.editor.buffers.get(buffer_handle).path.to_str() {
+            subject.push_str(path);
+        }
+    }
+
+    fn client_subject(ctx: &EditorContext, client_handle: ClientHandle, subject: &mut String) {
+        if let Some(buffer_view_handle) = ctx.clients.get(client_handle).buffer_view_handle() {
+            let buffer_handle = ctx
+                .editor
+                .buffer_views
+                .get(buffer_view_handle)
+                .buffer_handle;
+            if let Some(path) = ctx.editor.buffers.get(buffer_handle).path.to_str() {
+                subject.push_str(path);
+            }
+        }
+    }
+}
+
+struct Hook {
+    event: HookEvent,
+    glob_text: String,
+    glob: Glob,
+    command: String,
+    running: bool,
+    // events (as ranges into the event queue) caused by this hook, directly or through other hooks
+    triggered_events: Vec<Range<usize>>,
+    handling_events: Vec<Range<usize>>,
+}
+
+#[derive(Default)]
+pub struct HookCollection {
+    hooks: Vec<Hook>,
+}
+impl HookCollection {
+    pub fn add(
+        &mut self,
+        event: HookEvent,
+        glob: &str,
+        command: &str,
+    ) -> Result<(), InvalidGlobError> {
+        let mut hook_glob = Glob::default();
+        hook_glob.compile(glob)?;
+        self.hooks.push(Hook {
+            event,
+            glob_text: glob.into(),
+            glob: hook_glob,
+            command: command.into(),
+            running: false,
+            triggered_events: Vec::new(),
+            handling_events: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, event: HookEvent, glob: Option<&str>) {
+        self.hooks.retain(|h| match glob {
+            Some(glob) => h.event != event || h.glob_text != glob,
+            None => h.event != event,
+        });
+    }
+
+    fn is_running(&self) -> bool {
+        self.hooks.iter().any(|h| h.running)
+    }
+
+    pub(crate) fn on_events_handled(&mut self) {
+        if self.is_running() {
+            return;
+        }
+        for hook in &mut self.hooks {
+            hook.triggered_events.clear();
+            hook.handling_events.clear();
+        }
+    }
+
+    pub(crate) fn on_editor_events(ctx: &mut EditorContext) {
+        let hooks = &mut ctx.editor.commands.hooks;
+        if hooks.hooks.is_empty() {
+            return;
+        }
+
+        // when nested inside a hook evaluation, only the running hooks are skipped
+        let track_events = !hooks.is_running();
+        if track_events {
+            for hook in &mut hooks.hooks {
+                std::mem::swap(&mut hook.handling_events, &mut hook.triggered_events);
+                hook.triggered_events.clear();
+            }
+        }
+
+        let mut subject = ctx.editor.string_pool.acquire();
+        let mut event_index = 0;
+        let mut events = EditorEventIter::new();
+        while let Some(event) = events.next(&ctx.editor.events) {
+            event_index += 1;
+            let event_index = event_index - 1;
+
+            subject.clear();
+            let (hook_event, client_handle, buffer_handle) =
+                match HookEvent::from_editor_event(ctx, event, &mut subject) {
+                    Some(event) => event,
+                    None => continue,
+                };
+
+            let mut i = 0;
+            while i < ctx.editor.commands.hooks.hooks.len() {
+                let hook = &mut ctx.editor.commands.hooks.hooks[i];
+                i += 1;
+                if hook.event != hook_event
+                    || hook.running
+                    || hook
+                        .handling_events
+                        .iter()
+                        .any(|r| r.contains(&event_index))
+                    || !hook.glob.matches(&subject)
+                {
+                    continue;
+                }
+                hook.running = true;
+
+                let mut command = ctx.editor.string_pool.acquire_with(&hook.command);
+                let previous_buffer_handle = std::mem::replace(
+                    &mut ctx.editor.commands.context_buffer_handle,
+                    buffer_handle,
+                );
+                let pending_len = ctx.editor.events.pending_len();
+                CommandManager::eval_and_write_error(ctx, client_handle, &mut command);
+                let triggered_events = pending_len..ctx.editor.events.pending_len();
+                ctx.editor.commands.context_buffer_handle = previous_buffer_handle;
+                ctx.editor.string_pool.release(command);
+
+                let hooks = &mut ctx.editor.commands.hooks.hooks;
+                if let Some(hook) = hooks.get_mut(i - 1) {
+                    hook.running = false;
+                }
+                if !track_events || triggered_events.is_empty() {
+                    continue;
+                }
+                for (j, hook) in hooks.iter_mut().enumerate() {
+                    if j == i - 1
+                        || hook
+                            .handling_events
+                            .iter()
+                            .any(|r| r.contains(&event_index))
+                    {
+                        hook.triggered_events.push(triggered_events.clone());
+                    }
+                }
+            }
+        }
+        ctx.editor.string_pool.release(subject);
+    }
+}
+
 pub struct CommandManager {
     commands: Vec<Command>,
     history: VecDeque<String>,
     pub aliases: AliasCollection,
+    pub hooks: HookCollection,
+    pub variables: VariableCollection,
+    pub user_commands: UserCommandCollection,
+    pub(crate) source_directories: Vec<PathBuf>,
+    context_buffer_handle: Option<BufferHandle>,
 }
 
 impl CommandManager {
@@ -331,6 +794,11 @@ impl CommandManager {
             commands: Vec::new(),
             history: VecDeque::with_capacity(HISTORY_CAPACITY),
             aliases: AliasCollection::default(),
+            hooks: HookCollection::default(),
+            variables: VariableCollection::default(),
+            user_commands: UserCommandCollection::default(),
+            source_directories: Vec::new(),
+            context_buffer_handle: None,
         };
         builtin::register_commands(&mut this);
         this
@@ -422,9 +890,133 @@ impl CommandManager {
             }
         }
 
+        Self::expand_variables(ctx, client_handle, command)?;
         Self::eval(ctx, client_handle, command)
     }
 
+    pub fn eval_commands(
+        ctx: &mut EditorContext,
+        client_handle: Option<ClientHandle>,
+        source: &str,
+    ) -> Result<EditorFlow, CommandError> {
+        for (_, command) in CommandIter::new(source) {
+            let mut command = ctx.editor.string_pool.acquire_with(command);
+            let result = Self::try_eval(ctx, client_handle, &mut command);
+            ctx.editor.string_pool.release(command);
+
+            match result? {
+                EditorFlow::Continue => (),
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(EditorFlow::Continue)
+    }
+
+    fn expand_variables(
+        ctx: &mut EditorContext,
+        client_handle: Option<ClientHandle>,
+        command: &mut String,
+    ) -> Result<(), CommandError> {
+        if !command.contains("%{") {
+            return Ok(());
+        }
+
+        let mut expanded = ctx.editor.string_pool.acquire();
+        let result = expand_variables(command, &mut expanded, |name, output| {
+            Self::expand_variable(ctx, client_handle, name, output)
+        });
+        if result.is_ok() {
+            std::mem::swap(command, &mut expanded);
+        }
+        ctx.editor.string_pool.release(expanded);
+        result
+    }
+
+    fn expand_variable(
+        ctx: &EditorContext,
+        client_handle: Option<ClientHandle>,
+        name: &str,
+        output: &mut String,
+    ) -> Result<(), CommandError> {
+        fn buffer_view(
+            ctx: &EditorContext,
+            client_handle: Option<ClientHandle>,
+        ) -> Result<&BufferView, CommandError> {
+            let client_handle = match client_handle {
+                Some(handle) => handle,
+                None => return Err(CommandError::NoTargetClient),
+            };
+            match ctx.clients.get(client_handle).buffer_view_handle() {
+                Some(handle) => Ok(ctx.editor.buffer_views.get(handle)),
+                None => Err(CommandError::NoBufferOpened),
+            }
+        }
+
+        match name {
+            "buffer_path" => {
+                let buffer_handle = match ctx.editor.commands.context_buffer_handle {
+                    Some(handle) => handle,
+                    None => buffer_view(ctx, client_handle)?.buffer_handle,
+                };
+                let buffer = ctx.editor.buffers.get(buffer_handle);
+                if let Some(path) = buffer.path.to_str() {
+                    output.push_str(path);
+                }
+            }
+            "selection" => {
+                let buffer_view = buffer_view(ctx, client_handle)?;
+                let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+                let range = buffer_view.cursors.main_cursor().to_range();
+                for text in buffer.content().text_range(range) {
+                    output.push_str(text);
+                }
+            }
+            "line" => {
+                let buffer_view = buffer_view(ctx, client_handle)?;
+                let position = buffer_view.cursors.main_cursor().position;
+                let _ = write!(output, "{}", position.line_index + 1);
+            }
+            "column" => {
+                let buffer_view = buffer_view(ctx, client_handle)?;
+                let position = buffer_view.cursors.main_cursor().position;
+                let _ = write!(output, "{}", position.column_byte_index + 1);
+            }
+            "platform" => output.push_str(std::env::consts::OS),
+            _ => {
+                if let Some(key) = name.strip_prefix("register:") {
+                    let mut chars = key.chars();
+                    let key = match (chars.next(), chars.next()) {
+                        (Some(c), None) => RegisterKey::from_char(c),
+                        _ => None,
+                    };
+                    match key {
+                        Some(key) => output.push_str(ctx.editor.registers.get(key)),
+                        None => return Err(CommandError::NoSuchVariable),
+                    }
+                } else if let Some(key) = name.strip_prefix("config:") {
+                    match ctx.editor.config.display_config(key) {
+                        Some(display) => {
+                            let _ = write!(output, "{}", display);
+                        }
+                        None => return Err(CommandError::NoSuchVariable),
+                    }
+                } else if let Some(key) = name.strip_prefix("env:") {
+                    if let Ok(value) = std::env::var(key) {
+                        output.push_str(&value);
+                    }
+                } else {
+                    match ctx.editor.commands.variables.get(name) {
+                        Some(value) => output.push_str(value),
+                        None => return Err(CommandError::NoSuchVariable),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn eval(
         ctx: &mut EditorContext,
         client_handle: Option<ClientHandle>,
@@ -441,12 +1033,16 @@ impl CommandManager {
         };
         let (plugin_handle, command_fn) = match ctx.editor.commands.find_command(command) {
             Some(command) => (command.plugin_handle, command.command_fn),
-            None => return Err(CommandError::NoSuchCommand),
+            None => {
+                let args = CommandArgs(tokenizer);
+                return UserCommandCollection::eval(ctx, client_handle, command, args);
+            }
         };
 
         let mut io = CommandIO {
             client_handle,
             plugin_handle,
+            name: command,
             args: CommandArgs(tokenizer),
             bang,
             flow: EditorFlow::Continue,
@@ -456,10 +1052,100 @@ impl CommandManager {
     }
 }
 
+fn expand_variables<F>(
+    command: &str,
+    output: &mut String,
+    mut expand: F,
+) -> Result<(), CommandError>
+where
+    F: FnMut(&str, &mut String) -> Result<(), CommandError>,
+{
+    fn push_raw_token(token: &str, output: &mut String) {
+        let start = output.len();
+        for depth in 0.. {
+            output.push('[');
+            for _ in 0..depth {
+                output.push('=');
+            }
+            output.push('[');
+            output.push_str(token);
+            output.push(']');
+            for _ in 0..depth {
+                output.push('=');
+            }
+            output.push(']');
+
+            let mut tokens = CommandTokenizer(&output[start..]);
+            if tokens.next() == Some(token) && tokens.next().is_none() {
+                break;
+            }
+            output.truncate(start);
+        }
+    }
+
+    let base = command.as_ptr() as usize;
+    let mut last_end = 0;
+    let mut expanded_token = String::new();
+    for token in CommandTokenizer(command) {
+        let token_start = token.as_ptr() as usize - base;
+        let token_end = token_start + token.len();
+        let is_raw_token = command[..token_start].ends_with('[');
+        if is_raw_token {
+            continue;
+        }
+
+        expanded_token.clear();
+        let mut expanded = false;
+        let mut rest = token;
+        while let Some(i) = rest.find("%{") {
+            let name_start = i + 2;
+            let name_len = match rest[name_start..].find('}') {
+                Some(len) => len,
+                None => break,
+            };
+            let name = &rest[name_start..name_start + name_len];
+            let is_variable_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':'));
+            if !is_variable_name {
+                expanded_token.push_str(&rest[..name_start]);
+                rest = &rest[name_start..];
+                continue;
+            }
+
+            expanded_token.push_str(&rest[..i]);
+            expand(name, &mut expanded_token)?;
+            expanded = true;
+
+            rest = &rest[name_start + name_len + 1..];
+        }
+        if !expanded {
+            continue;
+        }
+        expanded_token.push_str(rest);
+
+        let previous_char = command[..token_start].chars().next_back();
+        let next_char = command[token_end..].chars().next();
+        let (token_start, token_end) = match (previous_char, next_char) {
+            (Some(delim @ ('"' | '\'')), Some(c)) if c == delim => (token_start - 1, token_end + 1),
+            _ => (token_start, token_end),
+        };
+
+        output.push_str(&command[last_end..token_start]);
+        push_raw_token(&expanded_token, output);
+        last_end = token_end;
+    }
+    output.push_str(&command[last_end..]);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::testing::TestEditor;
+
     #[test]
     fn command_tokens() {
         let mut tokens = CommandTokenizer("cmd arg");
@@ -504,4 +1190,331 @@ mod tests {
         assert_eq!(Some("arg]]=]"), tokens.next());
         assert_eq!(None, tokens.next());
     }
+
+    #[test]
+    fn variable_expansion() {
+        fn expand(command: &str) -> Vec<String> {
+            let mut output = String::new();
+            let result = expand_variables(command, &mut output, |name, output| match name {
+                "var" => {
+                    output.push_str("value");
+                    Ok(())
+                }
+                "spaced" => {
+                    output.push_str("a b");
+                    Ok(())
+                }
+                "quoted" => {
+                    output.push_str("'a\" ]]b]=]");
+                    Ok(())
+                }
+                _ => Err(CommandError::NoSuchVariable),
+            });
+            match result {
+                Ok(()) => CommandTokenizer(&output).map(String::from).collect(),
+                Err(_) => vec![String::from("<error>")],
+            }
+        }
+
+        assert_eq!(vec!["cmd", "arg"], expand("cmd arg"));
+        assert_eq!(vec!["cmd", "value"], expand("cmd %{var}"));
+        assert_eq!(
+            vec!["cmd", "xvaluey", "value"],
+            expand("cmd x%{var}y %{var}")
+        );
+        assert_eq!(vec!["cmd", "a b"], expand("cmd %{spaced}"));
+        assert_eq!(vec!["cmd", "xa by"], expand("cmd x%{spaced}y"));
+        assert_eq!(vec!["cmd", "value a b"], expand("cmd '%{var} %{spaced}'"));
+        assert_eq!(
+            vec!["cmd", "'a\" ]]b]=]", "arg"],
+            expand("cmd %{quoted} arg")
+        );
+        assert_eq!(vec!["cmd", "<'a\" ]]b]=]>"], expand("cmd \"<%{quoted}>\""));
+        assert_eq!(vec!["cmd", "%{var}"], expand("cmd [[%{var}]]"));
+        assert_eq!(vec!["cmd", "%{var"], expand("cmd %{var"));
+        assert_eq!(
+            vec!["cmd", "%[|%]|%{|%}|value"],
+            expand("cmd %[|%]|%{|%}|%{var}")
+        );
+        assert_eq!(vec!["<error>"], expand("cmd %{other}"));
+
+        let mut output = String::new();
+        let command = "cmd 'a' \"b\" [[%{var}]]";
+        assert!(expand_variables(command, &mut output, |_, _| Ok(())).is_ok());
+        assert_eq!(command, output);
+    }
+
+    #[test]
+    fn variables_with_spaces() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "a b\nc");
+
+        assert!(editor.command("set spaced 'x \"y\"'").is_ok());
+        assert!(editor.command("set copied %{spaced}").is_ok());
+        let variables = &editor.ctx().editor.commands.variables;
+        assert_eq!(Some("x \"y\""), variables.get("copied"));
+
+        editor.keys("vl");
+        assert!(editor.command("find-pattern %{selection}").is_ok());
+    }
+
+    #[test]
+    fn hooks() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        assert!(matches!(
+            editor.command("hook no-such-event * [[set v x]]"),
+            Err(CommandError::NoSuchHookEvent)
+        ));
+        assert!(matches!(
+            editor.command("hook mode-change {insert [[set v x]]"),
+            Err(CommandError::InvalidGlob(_))
+        ));
+
+        assert!(editor
+            .command("hook mode-change insert [[set entered insert]]")
+            .is_ok());
+        assert!(editor
+            .command("hook mode-change command [[set entered command]]")
+            .is_ok());
+        let variables = |editor: &TestEditor| {
+            editor
+                .ctx()
+                .editor
+                .commands
+                .variables
+                .get("entered")
+                .map(String::from)
+        };
+
+        editor.keys("<esc>");
+        assert_eq!(None, variables(&editor));
+        editor.keys("i");
+        assert_eq!(Some("insert".into()), variables(&editor));
+        editor.keys("<esc>:<esc>");
+        assert_eq!(Some("command".into()), variables(&editor));
+
+        assert!(editor.command("hook-remove mode-change insert").is_ok());
+        assert!(editor.command("set entered none").is_ok());
+        editor.keys("i<esc>");
+        assert_eq!(Some("none".into()), variables(&editor));
+        editor.keys(":<esc>");
+        assert_eq!(Some("command".into()), variables(&editor));
+
+        assert!(editor.command("hook-remove mode-change").is_ok());
+        assert!(editor.command("set entered none").is_ok());
+        editor.keys(":<esc>i<esc>");
+        assert_eq!(Some("none".into()), variables(&editor));
+    }
+
+    #[test]
+    fn buffer_hooks() {
+        let dir = std::env::temp_dir().join("pepper-buffer-hooks-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.txt");
+        let b = dir.join("b.txt");
+
+        let mut editor = TestEditor::default();
+        let a_handle = editor.open_buffer(a.to_str().unwrap(), "a");
+        editor.open_buffer(b.to_str().unwrap(), "b");
+        let variable = |editor: &TestEditor, name| {
+            editor
+                .ctx()
+                .editor
+                .commands
+                .variables
+                .get(name)
+                .map(String::from)
+        };
+
+        // runs against the triggering buffer even though the client is viewing another one
+        assert!(editor
+            .command("hook buffer-write **/a.txt [[set saved %{buffer_path}]]")
+            .is_ok());
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(a_handle);
+        buffer.properties.saving_enabled = true;
+        assert!(editor.command("save-all").is_ok());
+        assert_eq!(a.to_str().map(String::from), variable(&editor, "saved"));
+        assert!(editor.command("hook-remove buffer-write").is_ok());
+
+        // saving inside a buffer-write hook does not evaluate that same hook again
+        assert!(editor.command("hook buffer-write **/b.txt save").is_ok());
+        assert!(editor
+            .command("hook buffer-write **/b.txt [[set writes %{writes}x]]")
+            .is_ok());
+        assert!(editor.command("set writes").is_err());
+        assert!(editor.command("set writes \"\"").is_ok());
+        assert!(editor
+            .command(&format!("save {}", b.to_str().unwrap()))
+            .is_ok());
+        assert_eq!(Some("xx".into()), variable(&editor, "writes"));
+
+        // hooks are evaluated again for later events
+        assert!(editor.command("save").is_ok());
+        assert_eq!(Some("xxxx".into()), variable(&editor, "writes"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn user_commands() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        assert!(editor
+            .command("define-command greet who [[set greeting 'hello %{who}']]")
+            .is_ok());
+        assert!(matches!(
+            editor.command("define-command print [[set greeting x]]"),
+            Err(CommandError::CommandAlreadyExists)
+        ));
+        assert!(matches!(
+            editor.command("define-command bad {who} [[print]]"),
+            Err(CommandError::InvalidVariableName)
+        ));
+
+        assert!(editor.command("greet 'big world'").is_ok());
+        let variables = &editor.ctx().editor.commands.variables;
+        assert_eq!(Some("hello big world"), variables.get("greeting"));
+        assert_eq!(None, variables.get("who"));
+        assert!(matches!(
+            editor.command("greet"),
+            Err(CommandError::TooFewArguments)
+        ));
+
+        assert!(editor
+            .command("define-command greet [[set greeting again]]")
+            .is_ok());
+        assert!(editor.command("greet").is_ok());
+        let variables = &editor.ctx().editor.commands.variables;
+        assert_eq!(Some("again"), variables.get("greeting"));
+
+        assert!(editor.command("define-command loop [[loop]]").is_ok());
+        assert!(matches!(
+            editor.command("loop"),
+            Err(CommandError::UserCommandTooDeep)
+        ));
+    }
+
+    #[test]
+    fn if_command() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        assert!(editor.command("set v a").is_ok());
+        assert!(editor
+            .command("if %{v} == a [[set r then]] else [[set r else]]")
+            .is_ok());
+        assert_eq!(
+            Some("then"),
+            editor.ctx().editor.commands.variables.get("r")
+        );
+        assert!(editor
+            .command("if %{v} != a [[set r then]] else [[set r else]]")
+            .is_ok());
+        assert_eq!(
+            Some("else"),
+            editor.ctx().editor.commands.variables.get("r")
+        );
+        assert!(editor.command("if %{v} == b [[set r other]]").is_ok());
+        assert_eq!(
+            Some("else"),
+            editor.ctx().editor.commands.variables.get("r")
+        );
+
+        assert!(matches!(
+            editor.command("if a < b [[set r x]]"),
+            Err(CommandError::InvalidCondition)
+        ));
+        assert!(matches!(
+            editor.command("if a == b [[set r x]] otherwise [[set r y]]"),
+            Err(CommandError::TooManyArguments)
+        ));
+    }
+
+    #[test]
+    fn source_command() {
+        let dir = std::env::temp_dir().join(format!("pepper-source-test-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let config_path = dir.join("config.pp");
+        let recursive_path = dir.join("recursive.pp");
+        let _ = std::fs::write(&config_path, "set sourced yes\nset count x%{count}\n");
+        let _ = std::fs::write(
+            &recursive_path,
+            format!(
+                "set depth x%{{depth}}\nsource '{}'\n",
+                recursive_path.display()
+            ),
+        );
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        assert!(editor.command("set count ''").is_ok());
+        let command = format!("source '{}'", config_path.display());
+        assert!(editor.command(&command).is_ok());
+        assert!(editor.command(&command).is_ok());
+        let variables = &editor.ctx().editor.commands.variables;
+        assert_eq!(Some("yes"), variables.get("sourced"));
+        assert_eq!(Some("xx"), variables.get("count"));
+
+        assert!(editor.command("source no-such-file.pp").is_err());
+
+        assert!(editor.command("set depth ''").is_ok());
+        let command = format!("source '{}'", recursive_path.display());
+        assert!(editor.command(&command).is_ok());
+        let depth = editor.ctx().editor.commands.variables.get("depth");
+        assert_eq!(Some(MAX_SOURCE_DEPTH), depth.map(str::len));
+        assert!(editor.ctx().editor.commands.source_directories.is_empty());
+        assert!(matches!(editor.status_bar().0, MessageKind::Error));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn source_relative_paths() {
+        let dir = std::env::temp_dir().join("pepper-source-relative-paths-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(
+            dir.join("config.pp"),
+            "source nested/inner.pp\nset outer yes\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("nested/inner.pp"), "source leaf.pp\n").unwrap();
+        std::fs::write(dir.join("nested/leaf.pp"), "set leaf yes\n").unwrap();
+
+        let mut editor = TestEditor::default();
+        assert_ne!(dir, editor.ctx().editor.current_directory);
+        let command = format!("source '{}'", dir.join("config.pp").display());
+        assert!(editor.command(&command).is_ok());
+
+        let variables = &editor.ctx().editor.commands.variables;
+        assert_eq!(Some("yes"), variables.get("leaf"));
+        assert_eq!(Some("yes"), variables.get("outer"));
+        assert!(editor.ctx().editor.commands.source_directories.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn command_iter() {
+        let mut commands = CommandIter::new("cmd0\n \n  # comment\r\n\tcmd1 arg\r\n");
+        assert_eq!(Some((0, "cmd0")), commands.next());
+        assert_eq!(Some((3, "cmd1 arg")), commands.next());
+        assert_eq!(None, commands.next());
+
+        let mut commands = CommandIter::new("cmd0 [[a\nb]] c\ncmd1 [=[\n]]\n]=]\ncmd2 '[['\ncmd3");
+        assert_eq!(Some((0, "cmd0 [[a\nb]] c")), commands.next());
+        assert_eq!(Some((2, "cmd1 [=[\n]]\n]=]")), commands.next());
+        assert_eq!(Some((5, "cmd2 '[['")), commands.next());
+        assert_eq!(Some((6, "cmd3")), commands.next());
+        assert_eq!(None, commands.next());
+
+        let mut commands = CommandIter::new("cmd0 [[\nunclosed");
+        assert_eq!(Some((0, "cmd0 [[\nunclosed")), commands.next());
+        assert_eq!(None, commands.next());
+    }
 }
@@ -1,16 +1,21 @@
-use std::path::Path;
+use std::{fs, path::Path};
 
 use crate::{
     buffer::{parse_path_and_position, BufferProperties},
     buffer_position::BufferPosition,
     client::ViewAnchor,
-    command::{CommandError, CommandIO, CommandManager, CompletionSource},
+    command::{
+        CommandError, CommandIO, CommandManager, CompletionSource, HookEvent, HOOK_EVENT_NAMES,
+        MAX_SOURCE_DEPTH,
+    },
     config::{ParseConfigError, CONFIG_NAMES},
     cursor::Cursor,
     editor::{EditorContext, EditorFlow},
-    editor_utils::MessageKind,
+    editor_utils::{load_config, MessageKind},
+    editorconfig::EditorConfigProperties,
     help,
     mode::{picker, read_line, ModeKind},
+    script::ScriptEngine,
     syntax::TokenKind,
     theme::{Color, THEME_COLOR_NAMES},
 };
@@ -148,9 +153,12 @@ pub fn register_commands(commands: &mut CommandManager) {
 
         let buffer_handle = io.current_buffer_handle(ctx)?;
         let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        if let Some(path) = path {
+            buffer.editorconfig = EditorConfigProperties::find(&ctx.editor.current_directory, path);
+        }
 
         buffer
-            .write_to_file(path, &mut ctx.editor.events)
+            .write_to_file(path, &mut ctx.editor.word_database, &mut ctx.editor.events)
             .map_err(CommandError::BufferWriteError)?;
 
         ctx.editor
@@ -167,7 +175,7 @@ pub fn register_commands(commands: &mut CommandManager) {
         for buffer in ctx.editor.buffers.iter_mut() {
             if buffer.properties.saving_enabled {
                 buffer
-                    .write_to_file(None, &mut ctx.editor.events)
+                    .write_to_file(None, &mut ctx.editor.word_database, &mut ctx.editor.events)
                     .map_err(CommandError::BufferWriteError)?;
                 count += 1;
             }
@@ -277,6 +285,23 @@ pub fn register_commands(commands: &mut CommandManager) {
         }
     });
 
+    r("set", &[], |ctx, io| {
+        let name = io.args.next()?;
+        let value = io.args.try_next();
+        io.args.assert_empty()?;
+
+        match value {
+            Some(value) => ctx.editor.commands.variables.set(name, value),
+            None => match ctx.editor.commands.variables.get(name) {
+                Some(value) => {
+                    ctx.editor.status_bar.write(MessageKind::Info).str(value);
+                    Ok(())
+                }
+                None => Err(CommandError::NoSuchVariable),
+            },
+        }
+    });
+
     static COLOR_COMPLETIONS: &[CompletionSource] = &[CompletionSource::Custom(THEME_COLOR_NAMES)];
     r("color", COLOR_COMPLETIONS, |ctx, io| {
         let key = io.args.next()?;
@@ -325,6 +350,158 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    r("define-command", &[], |ctx, io| {
+        let name = io.args.next()?;
+        let mut params = Vec::new();
+        let mut body = io.args.next()?;
+        while let Some(arg) = io.args.try_next() {
+            params.push(body);
+            body = arg;
+        }
+
+        if ctx.editor.commands.find_command(name).is_some() {
+            return Err(CommandError::CommandAlreadyExists);
+        }
+        for param in &params {
+            if param.is_empty() || param.contains(&['{', '}', ':'][..]) {
+                return Err(CommandError::InvalidVariableName);
+            }
+        }
+
+        ctx.editor.commands.user_commands.add(name, &params, body);
+        Ok(())
+    });
+
+    r("if", &[], |ctx, io| {
+        let left = io.args.next()?;
+        let op = io.args.next()?;
+        let condition = match op {
+            "==" => left == io.args.next()?,
+            "!=" => left != io.args.next()?,
+            path if left == "exists" => ctx.editor.current_directory.join(path).exists(),
+            _ => return Err(CommandError::InvalidCondition),
+        };
+
+        let body = io.args.next()?;
+        let else_body = match io.args.try_next() {
+            Some("else") => Some(io.args.next()?),
+            Some(_) => return Err(CommandError::TooManyArguments),
+            None => None,
+        };
+        io.args.assert_empty()?;
+
+        let body = if condition { Some(body) } else { else_body };
+        if let Some(body) = body {
+            io.flow = CommandManager::eval_commands(ctx, io.client_handle().ok(), body)?;
+        }
+        Ok(())
+    });
+
+    r("source", &[CompletionSource::Files], |ctx, io| {
+        let path = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let source_directories = &ctx.editor.commands.source_directories;
+        let source_path = match source_directories.last() {
+            Some(directory) => directory.join(path),
+            None => ctx.editor.current_directory.join(path),
+        };
+        let content = match fs::read_to_string(&source_path) {
+            Ok(content) => content,
+            Err(_) => {
+                return Err(CommandError::OtherOwned(format!(
+                    "could not load config '{}'",
+                    path
+                )))
+            }
+        };
+        if source_directories.len() >= MAX_SOURCE_DEPTH {
+            return Err(CommandError::SourceTooDeep);
+        }
+
+        let source_directory = source_path.parent().unwrap_or(&source_path).into();
+        ctx.editor
+            .commands
+            .source_directories
+            .push(source_directory);
+        io.flow = load_config(ctx, path, &content);
+        ctx.editor.commands.source_directories.pop();
+        Ok(())
+    });
+
+    r("script", &[CompletionSource::Files], |ctx, io| {
+        let path = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let source = match fs::read_to_string(ctx.editor.current_directory.join(path)) {
+            Ok(source) => source,
+            Err(_) => {
+                return Err(CommandError::OtherOwned(format!(
+                    "could not load script '{}'",
+                    path
+                )))
+            }
+        };
+        let client_handle = io.client_handle().ok();
+        match ScriptEngine::run(ctx, client_handle, path, &source) {
+            Ok(flow) => {
+                io.flow = flow;
+                Ok(())
+            }
+            Err(error) => Err(CommandError::ScriptError(error)),
+        }
+    });
+
+    r("script-eval", &[], |ctx, io| {
+        let source = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle().ok();
+        match ScriptEngine::run(ctx, client_handle, "script-eval", source) {
+            Ok(flow) => {
+                io.flow = flow;
+                Ok(())
+            }
+            Err(error) => Err(CommandError::ScriptError(error)),
+        }
+    });
+
+    static HOOK_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Custom(HOOK_EVENT_NAMES),
+        CompletionSource::Custom(&[]),
+        CompletionSource::Commands,
+    ];
+    r("hook", HOOK_COMPLETIONS, |ctx, io| {
+        let event = io.args.next()?;
+        let glob = io.args.next()?;
+        let command = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let event = match HookEvent::from_name(event) {
+            Some(event) => event,
+            None => return Err(CommandError::NoSuchHookEvent),
+        };
+        match ctx.editor.commands.hooks.add(event, glob, command) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CommandError::InvalidGlob(error)),
+        }
+    });
+
+    static HOOK_REMOVE_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Custom(HOOK_EVENT_NAMES)];
+    r("hook-remove", HOOK_REMOVE_COMPLETIONS, |ctx, io| {
+        let event = io.args.next()?;
+        let glob = io.args.try_next();
+        io.args.assert_empty()?;
+
+        let event = match HookEvent::from_name(event) {
+            Some(event) => event,
+            None => return Err(CommandError::NoSuchHookEvent),
+        };
+        ctx.editor.commands.hooks.remove(event, glob);
+        Ok(())
+    });
+
     r("syntax", &[], |ctx, io| {
         let glob = io.args.next()?;
         io.args.assert_empty()?;
@@ -355,6 +532,93 @@ pub fn register_commands(commands: &mut CommandManager) {
     r("syntax-texts", &[], |ctx, io| {
         syntax_pattern(ctx, io, TokenKind::Text)
     });
+    r("syntax-auto-pairs", &[], |ctx, io| {
+        let pairs = io.args.next()?;
+        io.args.assert_empty()?;
+        if ctx.editor.syntaxes.get_current().set_auto_pairs(pairs) {
+            Ok(())
+        } else {
+            Err(CommandError::InvalidAutoPairs)
+        }
+    });
+    r("syntax-indent-increase", &[], |ctx, io| {
+        let pattern = io.args.next()?;
+        io.args.assert_empty()?;
+        match ctx
+            .editor
+            .syntaxes
+            .get_current()
+            .set_indent_increase_rule(pattern)
+        {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CommandError::PatternError(error)),
+        }
+    });
+    r("syntax-indent-decrease", &[], |ctx, io| {
+        let pattern = io.args.next()?;
+        io.args.assert_empty()?;
+        match ctx
+            .editor
+            .syntaxes
+            .get_current()
+            .set_indent_decrease_rule(pattern)
+        {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CommandError::PatternError(error)),
+        }
+    });
+
+    r("syntax-comment-tokens", &[], |ctx, io| {
+        let line = io.args.next()?;
+        let (block_open, block_close) = match io.args.try_next() {
+            Some(block_open) => (block_open, io.args.next()?),
+            None => ("", ""),
+        };
+        io.args.assert_empty()?;
+        ctx.editor
+            .syntaxes
+            .get_current()
+            .set_comment_tokens(line, block_open, block_close);
+        Ok(())
+    });
+
+    r("snippet", &[], |ctx, io| {
+        let glob = io.args.next()?;
+        let trigger = io.args.next()?;
+        let body = io.args.next()?;
+        io.args.assert_empty()?;
+        ctx.editor
+            .snippets
+            .add(glob, trigger, body)
+            .map_err(CommandError::InvalidGlob)
+    });
+
+    r("toggle-comment", &[], |ctx, io| {
+        io.args.assert_empty()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+        let syntax = ctx.editor.syntaxes.get(buffer.syntax_handle());
+
+        let line_comment = syntax.line_comment_token();
+        let block_comment = syntax.block_comment_tokens();
+        if line_comment.is_empty() && (block_comment.0.is_empty() || block_comment.1.is_empty()) {
+            return Err(CommandError::NoCommentTokens);
+        }
+
+        buffer_view.toggle_comment_in_cursor_lines(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            line_comment,
+            block_comment,
+            &mut ctx.editor.events,
+        );
+        ctx.editor
+            .buffers
+            .get_mut(buffer_view.buffer_handle)
+            .commit_edits();
+        Ok(())
+    });
 
     r("copy-command", &[], |ctx, io| {
         let command = io.args.next()?;
@@ -160,7 +160,7 @@ impl<'a> CursorCollectionMutGuard<'a> {
                 let line = &buffer.lines()[c.position.line_index as usize].as_str()
                     [..c.position.column_byte_index as usize];
                 let distance = CharDisplayDistances::new(line, tab_size)
-                    .last()
+                    .next_back()
                     .map(|d| d.distance)
                     .unwrap_or(0);
 
@@ -8,12 +8,13 @@ use crate::{
     buffer_position::{BufferPosition, BufferRange},
     buffer_view::{BufferViewCollection, BufferViewHandle},
     client::{ClientHandle, ClientManager},
-    command::CommandManager,
+    command::{CommandManager, HookCollection},
     config::Config,
     editor_utils::{
         KeyMapCollection, MatchResult, ReadLine, RegisterCollection, RegisterKey, StatusBar,
         StatusBarDisplay, StringPool,
     },
+    editorconfig::EditorConfigProperties,
     events::{
         ClientEvent, EditorEvent, EditorEventIter, EditorEventQueue, KeyParseAllError, KeyParser,
         ServerEvent, TargetClient,
@@ -23,6 +24,8 @@ use crate::{
     picker::Picker,
     platform::{Key, KeyCode, Platform, PlatformRequest},
     plugin::{PluginCollection, PluginHandle},
+    script::ScriptEngine,
+    snippet::SnippetCollection,
     syntax::{HighlightResult, SyntaxCollection},
     theme::Theme,
     ui,
@@ -167,10 +170,13 @@ impl EditorContext {
             self.editor.events.flip();
             let mut events = EditorEventIter::new();
             if events.next(&self.editor.events).is_none() {
+                self.editor.commands.hooks.on_events_handled();
                 return;
             }
 
             PluginCollection::on_editor_events(self);
+            HookCollection::on_editor_events(self);
+            ScriptEngine::on_editor_events(self);
 
             let mut events = EditorEventIter::new();
             while let Some(event) = events.next(&self.editor.events) {
@@ -235,6 +241,14 @@ impl EditorContext {
                         );
                         for client in self.clients.iter_mut() {
                             client.on_buffer_close(&mut self.editor, handle);
+                            if client.eval_wait_buffer_handle == Some(handle) {
+                                client.eval_wait_buffer_handle = None;
+                                self.platform
+                                    .requests
+                                    .enqueue(PlatformRequest::CloseClient {
+                                        handle: client.handle(),
+                                    });
+                            }
                         }
                         self.editor.buffer_views.remove_buffer_views(handle);
                         self.editor.mode.read_line_state.on_buffer_close(handle);
@@ -251,6 +265,7 @@ impl EditorContext {
                             view_cursors.add(cursor);
                         }
                     }
+                    EditorEvent::ModeChange { .. } | EditorEvent::ClientJoin { .. } => (),
                 }
             }
         }
@@ -262,6 +277,7 @@ pub struct Editor {
     pub config: Config,
     pub theme: Theme,
     pub syntaxes: SyntaxCollection,
+    pub snippets: SnippetCollection,
     pub keymaps: KeyMapCollection,
 
     pub mode: Mode,
@@ -280,6 +296,7 @@ pub struct Editor {
     pub aux_pattern: Pattern,
 
     pub commands: CommandManager,
+    pub scripts: ScriptEngine,
     pub events: EditorEventQueue,
 }
 impl Editor {
@@ -289,6 +306,7 @@ impl Editor {
             config: Config::default(),
             theme: Theme::default(),
             syntaxes: SyntaxCollection::new(),
+            snippets: SnippetCollection::default(),
             keymaps: KeyMapCollection::default(),
 
             mode: Mode::default(),
@@ -308,6 +326,7 @@ impl Editor {
             aux_pattern: Pattern::new(),
 
             commands: CommandManager::new(),
+            scripts: ScriptEngine::new(),
             events: EditorEventQueue::default(),
         }
     }
@@ -329,6 +348,7 @@ impl Editor {
             let buffer = self.buffers.add_new();
             buffer.set_path(path);
             buffer.properties = properties;
+            buffer.editorconfig = EditorConfigProperties::find(&self.current_directory, path);
 
             match buffer.read_from_file(&mut self.word_database, &mut self.events) {
                 Ok(()) => {
@@ -470,6 +490,62 @@ impl Editor {
                 ctx.trigger_event_handlers();
                 EditorFlow::Continue
             }
+            ClientEvent::Eval(target, wait, commands) => {
+                let target_handle = match target {
+                    TargetClient::Sender => Some(client_handle),
+                    TargetClient::Focused => ctx.clients.focused_client(),
+                };
+
+                ctx.editor.status_bar.begin_capture();
+                let result = CommandManager::eval_commands(ctx, target_handle, commands);
+                ctx.trigger_event_handlers();
+                let output = ctx.editor.status_bar.end_capture();
+
+                let mut buf = ctx.platform.buf_pool.acquire();
+                let write = buf.write_with_len(ServerEvent::bytes_variant_header_len());
+                let (flow, event) = match result {
+                    Ok(flow) => {
+                        write.extend_from_slice(output.as_bytes());
+                        (flow, ServerEvent::EvalOutput(&[]))
+                    }
+                    Err(error) => {
+                        use std::io::Write;
+                        let _ = write!(write, "{}", error);
+                        (EditorFlow::Continue, ServerEvent::EvalError(&[]))
+                    }
+                };
+                if write.len() > ServerEvent::bytes_variant_header_len() && !write.ends_with(b"\n")
+                {
+                    write.push(b'\n');
+                }
+                event.serialize_bytes_variant_header(write);
+                ctx.platform
+                    .requests
+                    .enqueue(PlatformRequest::WriteToClient {
+                        handle: client_handle,
+                        buf,
+                    });
+
+                let wait_buffer_handle = match (wait, flow, target_handle) {
+                    (true, EditorFlow::Continue, Some(handle)) => ctx
+                        .clients
+                        .get(handle)
+                        .buffer_view_handle()
+                        .map(|h| ctx.editor.buffer_views.get(h).buffer_handle),
+                    _ => None,
+                };
+                match wait_buffer_handle {
+                    Some(buffer_handle) => {
+                        let client = ctx.clients.get_mut(client_handle);
+                        client.eval_wait_buffer_handle = Some(buffer_handle);
+                        EditorFlow::Continue
+                    }
+                    None => match flow {
+                        EditorFlow::QuitAll => EditorFlow::QuitAll,
+                        _ => EditorFlow::Quit,
+                    },
+                }
+            }
         }
     }
 
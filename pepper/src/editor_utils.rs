@@ -2,11 +2,12 @@ use std::{fmt, process::Command};
 
 use crate::{
     buffer::char_display_len,
-    command::{CommandManager, CommandTokenizer},
+    command::{CommandIter, CommandManager, CommandTokenizer},
     editor::{BufferedKeys, EditorContext, EditorFlow, KeysIterator},
     events::{KeyParseAllError, KeyParser},
     mode::ModeKind,
     platform::{Key, KeyCode, Platform},
+    plugin::PluginHandle,
     word_database::{WordIter, WordKind},
 };
 
@@ -253,32 +254,100 @@ pub struct StatusBarDisplay<'status_bar, 'lines> {
     pub lines: &'lines [&'status_bar str],
 }
 
+struct StatusBarIndicator {
+    plugin_handle: PluginHandle,
+    source: u32,
+    text: String,
+}
+
 pub struct StatusBar {
     kind: MessageKind,
     message: String,
+    indicators: Vec<StatusBarIndicator>,
+    captured: Option<String>,
 }
 impl StatusBar {
     pub fn new() -> Self {
         Self {
             kind: MessageKind::Info,
             message: String::new(),
+            indicators: Vec::new(),
+            captured: None,
         }
     }
 
+    pub fn set_indicator(&mut self, plugin_handle: PluginHandle, source: u32, text: &str) {
+        let index = self
+            .indicators
+            .iter()
+            .position(|i| i.plugin_handle == plugin_handle && i.source == source);
+        match (index, text.is_empty()) {
+            (Some(index), true) => {
+                self.indicators.remove(index);
+            }
+            (Some(index), false) => {
+                let indicator = &mut self.indicators[index];
+                indicator.text.clear();
+                indicator.text.push_str(text);
+            }
+            (None, true) => (),
+            (None, false) => self.indicators.push(StatusBarIndicator {
+                plugin_handle,
+                source,
+                text: text.into(),
+            }),
+        }
+    }
+
+    pub fn indicators(&self) -> impl Iterator<Item = &str> {
+        self.indicators.iter().map(|i| i.text.as_str())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.message.is_empty()
     }
 
     pub fn clear(&mut self) {
+        self.flush_captured();
         self.message.clear();
     }
 
+    pub fn message(&self) -> (MessageKind, &str) {
+        (self.kind, &self.message)
+    }
+
     pub fn write(&mut self, kind: MessageKind) -> StatusBarWriter {
+        self.flush_captured();
         self.kind = kind;
         self.message.clear();
         StatusBarWriter(&mut self.message)
     }
 
+    pub(crate) fn begin_capture(&mut self) {
+        self.message.clear();
+        self.captured = Some(String::new());
+    }
+
+    pub(crate) fn end_capture(&mut self) -> String {
+        self.flush_captured();
+        self.captured.take().unwrap_or_default()
+    }
+
+    fn flush_captured(&mut self) {
+        let captured = match &mut self.captured {
+            Some(captured) => captured,
+            None => return,
+        };
+        if let MessageKind::Info = self.kind {
+            if !self.message.is_empty() {
+                captured.push_str(&self.message);
+                if !self.message.ends_with('\n') {
+                    captured.push('\n');
+                }
+            }
+        }
+    }
+
     pub(crate) fn on_before_render(&mut self) {
         let trimmed_len = self.message.trim_end().len();
         self.message.truncate(trimmed_len);
@@ -525,6 +594,39 @@ impl ResidualStrBytes {
     }
 }
 
+pub fn path_completion_parts(text: &str) -> Option<(&str, &str)> {
+    let token_start = text
+        .rfind(|c: char| {
+            c.is_ascii_whitespace()
+                || matches!(
+                    c,
+                    '"' | '\''
+                        | '`'
+                        | '('
+                        | ')'
+                        | '['
+                        | ']'
+                        | '{'
+                        | '}'
+                        | '<'
+                        | '>'
+                        | ','
+                        | ';'
+                        | '='
+                        | '|'
+                )
+        })
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let token = &text[token_start..];
+    if token.starts_with("//") || token.bytes().all(|b| b == b'/') {
+        return None;
+    }
+
+    let separator_index = token.rfind('/')?;
+    Some(token.split_at(separator_index + 1))
+}
+
 pub fn parse_process_command(command: &str) -> Option<Command> {
     let mut tokenizer = CommandTokenizer(command);
     let name = tokenizer.next()?;
@@ -536,11 +638,7 @@ pub fn parse_process_command(command: &str) -> Option<Command> {
 }
 
 pub fn load_config(ctx: &mut EditorContext, config_name: &str, config_content: &str) -> EditorFlow {
-    for (line_index, line) in config_content.lines().enumerate() {
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
+    for (line_index, line) in CommandIter::new(config_content) {
         let mut command = ctx.editor.string_pool.acquire_with(line);
         let result = CommandManager::try_eval(ctx, None, &mut command);
         ctx.editor.string_pool.release(command);
@@ -583,6 +681,41 @@ mod tests {
         assert!(!is_char_boundary(bytes[3]));
     }
 
+    #[test]
+    fn path_completion_parts_test() {
+        assert_eq!(None, path_completion_parts(""));
+        assert_eq!(None, path_completion_parts("word"));
+        assert_eq!(None, path_completion_parts("a / b"));
+        assert_eq!(None, path_completion_parts("x = 1 //"));
+        assert_eq!(None, path_completion_parts("//path/"));
+        assert_eq!(Some(("./", "")), path_completion_parts("./"));
+        assert_eq!(Some(("src/", "ma")), path_completion_parts("open src/ma"));
+        assert_eq!(
+            Some(("../src/mode/", "in")),
+            path_completion_parts("include(\"../src/mode/in")
+        );
+        assert_eq!(Some(("/", "us")), path_completion_parts("cd /us"));
+    }
+
+    #[test]
+    fn status_bar_capture() {
+        let mut status_bar = StatusBar::new();
+        status_bar.write(MessageKind::Info).str("before");
+
+        status_bar.begin_capture();
+        status_bar.write(MessageKind::Info).str("first");
+        status_bar.write(MessageKind::Error).str("error");
+        status_bar.write(MessageKind::Info).str("second\n");
+        status_bar.clear();
+        status_bar.write(MessageKind::Info).str("third");
+        assert_eq!("first\nsecond\nthird\n", status_bar.end_capture());
+        assert_eq!("third", status_bar.message().1);
+
+        status_bar.write(MessageKind::Info).str("after");
+        assert_eq!("", status_bar.end_capture());
+        assert_eq!("after", status_bar.message().1);
+    }
+
     #[test]
     fn residual_str_bytes() {
         let message = "abcdef".as_bytes();
@@ -0,0 +1,283 @@
+use std::{fs, num::NonZeroU8, path::Path};
+
+use crate::{
+    buffer::{Charset, LineEnding},
+    config::Config,
+    glob::Glob,
+};
+
+pub const EDITORCONFIG_FILE_NAME: &str = ".editorconfig";
+
+#[derive(Default, Clone, Copy)]
+pub struct EditorConfigProperties {
+    pub indent_with_tabs: Option<bool>,
+    pub indent_size: Option<NonZeroU8>,
+    pub tab_size: Option<NonZeroU8>,
+    pub line_ending: Option<LineEnding>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub charset: Option<Charset>,
+}
+
+impl EditorConfigProperties {
+    pub fn find(current_directory: &Path, path: &Path) -> Self {
+        let mut properties = Self::default();
+
+        let path = current_directory.join(path);
+        let mut directories = Vec::new();
+        let mut contents = Vec::new();
+        for directory in path.ancestors().skip(1) {
+            let content = match fs::read_to_string(directory.join(EDITORCONFIG_FILE_NAME)) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+            let is_root = is_root(&content);
+            directories.push(directory);
+            contents.push(content);
+            if is_root {
+                break;
+            }
+        }
+
+        let mut glob = Glob::default();
+        for (directory, content) in directories.iter().zip(contents.iter()).rev() {
+            let relative_path = match path.strip_prefix(directory).ok().and_then(Path::to_str) {
+                Some(path) => path,
+                None => continue,
+            };
+            properties.parse(content, relative_path, &mut glob);
+        }
+
+        properties
+    }
+
+    pub fn indent_with_tabs(&self, config: &Config) -> bool {
+        self.indent_with_tabs.unwrap_or(config.indent_with_tabs)
+    }
+
+    pub fn indent_size(&self, config: &Config) -> NonZeroU8 {
+        self.indent_size
+            .or(self.tab_size)
+            .unwrap_or(config.tab_size)
+    }
+
+    pub fn tab_size(&self, config: &Config) -> NonZeroU8 {
+        self.tab_size
+            .or(self.indent_size)
+            .unwrap_or(config.tab_size)
+    }
+
+    fn parse(&mut self, content: &str, relative_path: &str, glob: &mut Glob) {
+        let mut in_matching_section = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let section = match section.strip_suffix(']') {
+                    Some(section) => section,
+                    None => {
+                        in_matching_section = false;
+                        continue;
+                    }
+                };
+                in_matching_section = section_matches(section, relative_path, glob);
+                continue;
+            }
+
+            if !in_matching_section {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            self.set(key, value);
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let key = key.to_ascii_lowercase();
+        let value = value.to_ascii_lowercase();
+        let unset = value == "unset";
+
+        fn parse_bool(value: &str) -> Option<bool> {
+            match value {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            }
+        }
+
+        match &key[..] {
+            "indent_style" => {
+                self.indent_with_tabs = match &value[..] {
+                    "tab" => Some(true),
+                    "space" => Some(false),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "indent_size" => {
+                if unset || value == "tab" {
+                    self.indent_size = None;
+                } else if let Ok(size) = value.parse() {
+                    self.indent_size = Some(size);
+                }
+            }
+            "tab_width" => {
+                if unset {
+                    self.tab_size = None;
+                } else if let Ok(size) = value.parse() {
+                    self.tab_size = Some(size);
+                }
+            }
+            "end_of_line" => {
+                self.line_ending = match &value[..] {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::CrLf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            "insert_final_newline" => match parse_bool(&value) {
+                Some(value) => self.insert_final_newline = Some(value),
+                None if unset => self.insert_final_newline = None,
+                None => (),
+            },
+            "trim_trailing_whitespace" => match parse_bool(&value) {
+                Some(value) => self.trim_trailing_whitespace = Some(value),
+                None if unset => self.trim_trailing_whitespace = None,
+                None => (),
+            },
+            "charset" => {
+                self.charset = match &value[..] {
+                    "utf-8" => Some(Charset::Utf8),
+                    "utf-8-bom" => Some(Charset::Utf8Bom),
+                    "latin1" => Some(Charset::Latin1),
+                    "utf-16be" => Some(Charset::Utf16Be),
+                    "utf-16le" => Some(Charset::Utf16Le),
+                    _ if unset => None,
+                    _ => return,
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn is_root(content: &str) -> bool {
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            break;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            if key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true")
+            {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn section_matches(section: &str, relative_path: &str, glob: &mut Glob) -> bool {
+    let compiled = if section.contains('/') {
+        let section = section.strip_prefix('/').unwrap_or(section);
+        glob.compile(section)
+    } else {
+        let mut pattern = String::with_capacity(section.len() + 3);
+        pattern.push_str("**/");
+        pattern.push_str(section);
+        glob.compile(&pattern)
+    };
+
+    match compiled {
+        Ok(()) => glob.matches(relative_path),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str, relative_path: &str) -> EditorConfigProperties {
+        let mut properties = EditorConfigProperties::default();
+        let mut glob = Glob::default();
+        properties.parse(content, relative_path, &mut glob);
+        properties
+    }
+
+    #[test]
+    fn root_detection() {
+        assert!(is_root("root = true\n[*]\nindent_style = tab"));
+        assert!(is_root("# comment\nROOT=True"));
+        assert!(!is_root("[*]\nroot = true"));
+        assert!(!is_root("root = false"));
+    }
+
+    #[test]
+    fn section_matching() {
+        let content = concat!(
+            "root = true\n",
+            "[*]\n",
+            "indent_style = space\n",
+            "indent_size = 4\n",
+            "[*.{md,txt}]\n",
+            "trim_trailing_whitespace = false\n",
+            "[/docs/**/*.md]\n",
+            "indent_size = 2\n",
+            "[Makefile]\n",
+            "indent_style = tab\n",
+            "indent_size = tab\n",
+            "tab_width = 8\n",
+        );
+
+        let properties = parse(content, "src/main.rs");
+        assert_eq!(Some(false), properties.indent_with_tabs);
+        assert_eq!(NonZeroU8::new(4), properties.indent_size);
+        assert_eq!(None, properties.trim_trailing_whitespace);
+
+        let properties = parse(content, "README.md");
+        assert_eq!(Some(false), properties.trim_trailing_whitespace);
+        assert_eq!(NonZeroU8::new(4), properties.indent_size);
+
+        let properties = parse(content, "docs/guide/intro.md");
+        assert_eq!(NonZeroU8::new(2), properties.indent_size);
+
+        let properties = parse(content, "sub/Makefile");
+        assert_eq!(Some(true), properties.indent_with_tabs);
+        assert_eq!(None, properties.indent_size);
+        assert_eq!(NonZeroU8::new(8), properties.tab_size);
+    }
+
+    #[test]
+    fn property_values() {
+        let content = concat!(
+            "[*]\n",
+            "end_of_line = CRLF\n",
+            "insert_final_newline = true\n",
+            "charset = utf-8-bom\n",
+            "[*.txt]\n",
+            "end_of_line = unset\n",
+            "charset = latin1\n",
+            "insert_final_newline = maybe\n",
+        );
+
+        let properties = parse(content, "a.rs");
+        assert!(matches!(properties.line_ending, Some(LineEnding::CrLf)));
+        assert_eq!(Some(true), properties.insert_final_newline);
+        assert!(matches!(properties.charset, Some(Charset::Utf8Bom)));
+
+        let properties = parse(content, "a.txt");
+        assert!(properties.line_ending.is_none());
+        assert_eq!(Some(true), properties.insert_final_newline);
+        assert!(matches!(properties.charset, Some(Charset::Latin1)));
+    }
+}
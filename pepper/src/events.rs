@@ -6,6 +6,7 @@ use crate::{
     buffer_view::BufferViewHandle,
     client::ClientHandle,
     cursor::Cursor,
+    mode::ModeKind,
     platform::{Key, KeyCode},
     serialization::{DeserializeError, Deserializer, Serialize, Serializer},
 };
@@ -57,6 +58,12 @@ pub enum EditorEvent {
         handle: BufferViewHandle,
         cursors: EditorEventCursors,
     },
+    ModeChange {
+        kind: ModeKind,
+    },
+    ClientJoin {
+        handle: ClientHandle,
+    },
 }
 
 #[derive(Default)]
@@ -78,6 +85,10 @@ impl EditorEventQueue {
         std::mem::swap(&mut self.read, &mut self.write);
     }
 
+    pub(crate) fn pending_len(&self) -> usize {
+        self.write.events.len()
+    }
+
     pub(crate) fn enqueue(&mut self, event: EditorEvent) {
         self.write.events.push(event);
     }
@@ -527,6 +538,8 @@ pub enum ServerEvent<'a> {
     Display(&'a [u8]),
     Suspend,
     StdoutOutput(&'a [u8]),
+    EvalOutput(&'a [u8]),
+    EvalError(&'a [u8]),
 }
 impl<'a> ServerEvent<'a> {
     pub const fn bytes_variant_header_len() -> usize {
@@ -538,6 +551,8 @@ impl<'a> ServerEvent<'a> {
             Self::Display(_) => 0,
             Self::Suspend => unreachable!(),
             Self::StdoutOutput(_) => 2,
+            Self::EvalOutput(_) => 3,
+            Self::EvalError(_) => 4,
         };
         let len = buf.len() as u32 - Self::bytes_variant_header_len() as u32;
         let len_buf = len.to_le_bytes();
@@ -559,6 +574,14 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 2u8.serialize(serializer);
                 bytes.serialize(serializer);
             }
+            Self::EvalOutput(bytes) => {
+                3u8.serialize(serializer);
+                bytes.serialize(serializer);
+            }
+            Self::EvalError(bytes) => {
+                4u8.serialize(serializer);
+                bytes.serialize(serializer);
+            }
         }
     }
 
@@ -577,6 +600,14 @@ impl<'de> Serialize<'de> for ServerEvent<'de> {
                 let bytes = Serialize::deserialize(deserializer)?;
                 Ok(Self::StdoutOutput(bytes))
             }
+            3 => {
+                let bytes = Serialize::deserialize(deserializer)?;
+                Ok(Self::EvalOutput(bytes))
+            }
+            4 => {
+                let bytes = Serialize::deserialize(deserializer)?;
+                Ok(Self::EvalError(bytes))
+            }
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -616,6 +647,7 @@ pub enum ClientEvent<'a> {
     Resize(u16, u16),
     Command(TargetClient, &'a str),
     StdinInput(TargetClient, &'a [u8]),
+    Eval(TargetClient, bool, &'a str),
 }
 impl<'de> Serialize<'de> for ClientEvent<'de> {
     fn serialize<S>(&self, serializer: &mut S)
@@ -643,6 +675,12 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 target.serialize(serializer);
                 bytes.serialize(serializer);
             }
+            Self::Eval(target, wait, commands) => {
+                4u8.serialize(serializer);
+                target.serialize(serializer);
+                (*wait as u8).serialize(serializer);
+                commands.serialize(serializer);
+            }
         }
     }
 
@@ -672,6 +710,12 @@ impl<'de> Serialize<'de> for ClientEvent<'de> {
                 let bytes = Serialize::deserialize(deserializer)?;
                 Ok(Self::StdinInput(target, bytes))
             }
+            4 => {
+                let target = Serialize::deserialize(deserializer)?;
+                let wait = u8::deserialize(deserializer)? != 0;
+                let commands = Serialize::deserialize(deserializer)?;
+                Ok(Self::Eval(target, wait, commands))
+            }
             _ => Err(DeserializeError::InvalidData),
         }
     }
@@ -948,6 +992,28 @@ mod tests {
         assert_eq!(EVENT_COUNT, event_count);
     }
 
+    #[test]
+    fn eval_events_serialization() {
+        let mut buf = Vec::new();
+        ClientEvent::Eval(TargetClient::Focused, true, "config tab_size").serialize(&mut buf);
+        let mut slice = &buf[..];
+        match ClientEvent::deserialize(&mut slice) {
+            Ok(ClientEvent::Eval(TargetClient::Focused, true, "config tab_size")) => (),
+            _ => panic!("could not deserialize eval event"),
+        }
+        assert!(slice.is_empty());
+
+        let mut buf = vec![0; ServerEvent::bytes_variant_header_len()];
+        buf.extend_from_slice(b"output");
+        ServerEvent::EvalError(&[]).serialize_bytes_variant_header(&mut buf);
+        let mut slice = &buf[..];
+        match ServerEvent::deserialize(&mut slice) {
+            Ok(ServerEvent::EvalError(b"output")) => (),
+            _ => panic!("could not deserialize eval error event"),
+        }
+        assert!(slice.is_empty());
+    }
+
     #[test]
     fn key_parser() {
         fn assert_key(expect_code: KeyCode, expect_control: bool, key: Key) {
@@ -42,6 +42,10 @@ static MAIN_HELP_PAGES: HelpPages = HelpPages::new(&[
         name: "config_recipes.md",
         content: include_str!("../rc/config_recipes.md"),
     },
+    ResourceFile {
+        name: "scripting.md",
+        content: include_str!("../rc/scripting.md"),
+    },
     ResourceFile {
         name: "help.md",
         content: include_str!("../rc/help.md"),
@@ -9,6 +9,7 @@ pub mod config;
 pub mod cursor;
 pub mod editor;
 pub mod editor_utils;
+pub mod editorconfig;
 pub mod events;
 pub mod glob;
 pub mod help;
@@ -18,8 +19,11 @@ pub mod pattern;
 pub mod picker;
 pub mod platform;
 pub mod plugin;
+pub mod script;
 pub mod serialization;
+pub mod snippet;
 pub mod syntax;
+pub mod testing;
 pub mod theme;
 pub mod ui;
 pub mod word_database;
@@ -59,6 +63,9 @@ pub struct Args {
     pub print_session: bool,
     pub as_focused_client: bool,
     pub quit: bool,
+    pub eval: Option<String>,
+    pub wait: bool,
+    pub batch: Option<String>,
     pub server: bool,
     pub configs: Vec<ArgsConfig>,
     pub files: Vec<String>,
@@ -87,6 +94,11 @@ fn print_help() {
     println!("  --print-session          prints the computed session name and quits");
     println!("  --as-focused-client      sends events as if it was the currently focused client");
     println!("  --quit                   sends a `quit` event on start");
+    println!("  --eval <commands>        evaluates commands on the focused client, prints their output and quits");
+    println!("  --wait                   with `--eval`, only quits after the focused client's buffer is closed");
+    println!(
+        "  --batch <script>         runs the keys in script on each file, saves them and quits"
+    );
     println!("  --server                 only run as server");
     println!("  -c, --config[!]          sources config file at path (repeatable) (server only)");
     println!("                           with `!` it will suppress the 'file not found' error");
@@ -136,6 +148,21 @@ impl Args {
                 "--print-session" => parsed.print_session = true,
                 "--as-focused-client" => parsed.as_focused_client = true,
                 "--quit" => parsed.quit = true,
+                "--eval" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.eval = Some(arg.into());
+                    }
+                    None => error(format_args!("expected commands after {}", arg)),
+                },
+                "--wait" => parsed.wait = true,
+                "--batch" => match args.next() {
+                    Some(arg) => {
+                        let arg = arg_to_str(&arg);
+                        parsed.batch = Some(arg.into());
+                    }
+                    None => error(format_args!("expected script path after {}", arg)),
+                },
                 "--server" => parsed.server = true,
                 "-c" | "-c!" | "--config" | "--config!" => {
                     let suppress_file_not_found = arg.ends_with('!');
@@ -257,5 +284,11 @@ pub fn init(config: &application::ApplicationConfig) {
 
 pub fn run(config: application::ApplicationConfig) {
     init(&config);
-    platform_impl::sys::main(config);
+    if config.args.batch.is_some() {
+        if !application::run_batch(config) {
+            std::process::exit(1);
+        }
+    } else {
+        platform_impl::sys::main(config);
+    }
 }
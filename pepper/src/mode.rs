@@ -1,6 +1,7 @@
 use crate::{
     client::ClientHandle,
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
+    events::EditorEvent,
     plugin::PluginHandle,
 };
 
@@ -30,6 +31,19 @@ pub enum ModeKind {
     Plugin,
 }
 
+impl ModeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Insert => "insert",
+            Self::Command => "command",
+            Self::ReadLine => "readline",
+            Self::Picker => "picker",
+            Self::Plugin => "plugin",
+        }
+    }
+}
+
 impl Default for ModeKind {
     fn default() -> Self {
         Self::Normal
@@ -68,6 +82,9 @@ impl Mode {
         }
 
         editor.mode.kind = next;
+        editor
+            .events
+            .enqueue(EditorEvent::ModeChange { kind: next });
 
         match editor.mode.kind {
             ModeKind::Normal => normal::State::on_enter(editor),
@@ -1,12 +1,9 @@
-use std::fs;
-
 use crate::{
     client::ClientHandle,
     command::{CommandManager, CommandTokenizer, CompletionSource},
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
-    editor_utils::{hash_bytes, ReadLinePoll},
+    editor_utils::{hash_bytes, path_completion_parts, ReadLinePoll},
     mode::{ModeKind, ModeState},
-    picker::Picker,
     platform::{Key, KeyCode},
     word_database::WordIndicesIter,
 };
@@ -216,6 +213,12 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
         completion_source = CompletionSource::Commands;
     }
 
+    if let CompletionSource::Custom(&[]) = completion_source {
+        if path_completion_parts(pattern).is_some() {
+            completion_source = CompletionSource::Files;
+        }
+    }
+
     state.completion_index = pattern.as_ptr() as usize - input.as_ptr() as usize;
 
     if state.completion_source != completion_source {
@@ -230,6 +233,9 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
                 for (from, _) in ctx.editor.commands.aliases.iter() {
                     ctx.editor.picker.add_custom_entry(from);
                 }
+                for name in ctx.editor.commands.user_commands.names() {
+                    ctx.editor.picker.add_custom_entry(name);
+                }
             }
             CompletionSource::Buffers => {
                 for buffer in ctx.editor.buffers.iter() {
@@ -248,24 +254,6 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
     }
 
     if let CompletionSource::Files = completion_source {
-        fn set_files_in_path_as_entries(picker: &mut Picker, path: &str) {
-            picker.clear();
-            let path = if path.is_empty() { "." } else { path };
-            let read_dir = match fs::read_dir(path) {
-                Ok(iter) => iter,
-                Err(_) => return,
-            };
-            for entry in read_dir {
-                let entry = match entry {
-                    Ok(entry) => entry.file_name(),
-                    Err(_) => return,
-                };
-                if let Some(entry) = entry.to_str() {
-                    picker.add_custom_entry(entry);
-                }
-            }
-        }
-
         let (parent, file) = match pattern.rfind('/') {
             Some(i) => pattern.split_at(i + 1),
             None => ("", pattern),
@@ -273,7 +261,9 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
 
         let parent_hash = hash_bytes(parent.as_bytes());
         if state.completion_path_hash != Some(parent_hash) {
-            set_files_in_path_as_entries(&mut ctx.editor.picker, parent);
+            ctx.editor.picker.clear();
+            let parent = ctx.editor.current_directory.join(parent);
+            ctx.editor.picker.add_path_entries(&parent);
             state.completion_path_hash = Some(parent_hash);
         }
 
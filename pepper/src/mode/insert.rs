@@ -1,23 +1,44 @@
-use std::fmt::Write;
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
 
 use crate::{
     buffer::BufferHandle,
-    buffer_position::{BufferPosition, BufferRange},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
     client::ClientHandle,
+    cursor::Cursor,
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
-    editor_utils::AUTO_MACRO_REGISTER,
+    editor_utils::{hash_bytes, path_completion_parts, AUTO_MACRO_REGISTER},
     mode::{ModeKind, ModeState},
+    picker::{CompletionKind, CompletionSource},
     platform::{Key, KeyCode},
     plugin::{CompletionContext, PluginHandle},
-    word_database::WordKind,
+    snippet::{parse_snippet, snippet_text_position, SnippetSession},
+    word_database::{WordIndicesIter, WordIter, WordKind},
 };
 
+const COMPLETION_NEARBY_LINES: u32 = 64;
+
+#[derive(Clone, Copy)]
+enum AutoPairAction {
+    Insert,
+    InsertPair(char),
+    SkipClose,
+}
+
 #[derive(Default)]
 pub struct State {
     editing_buffer_handle: Option<BufferHandle>,
     completion_positions: Vec<BufferPosition>,
     completing_plugin_handle: Option<PluginHandle>,
+    applied_completion: String,
+    auto_pair_actions: Vec<AutoPairAction>,
+    split_pair_columns: Vec<Option<BufferPositionIndex>>,
+    snippet_session: SnippetSession,
+    completion_directory_hash: Option<u64>,
+    completion_directory: Option<PathBuf>,
 }
 
 impl State {
@@ -29,6 +50,7 @@ impl State {
                 }
             }
         }
+        self.snippet_session.on_buffer_insert_text(handle, range);
     }
 
     pub fn on_buffer_delete_text(&mut self, handle: BufferHandle, range: BufferRange) {
@@ -37,17 +59,21 @@ impl State {
                 *position = position.delete(range);
             }
         }
+        self.snippet_session.on_buffer_delete_text(handle, range);
     }
 }
 
 impl ModeState for State {
     fn on_enter(editor: &mut Editor) {
+        editor.mode.insert_state.completion_directory_hash = None;
         cancel_completion(editor);
+        editor.mode.insert_state.snippet_session.cancel();
     }
 
     fn on_exit(editor: &mut Editor) {
         editor.mode.insert_state.editing_buffer_handle = None;
         cancel_completion(editor);
+        editor.mode.insert_state.snippet_session.cancel();
     }
 
     fn on_keys(
@@ -63,13 +89,53 @@ impl ModeState for State {
             }
         };
 
-        ctx.editor.mode.insert_state.editing_buffer_handle =
-            Some(ctx.editor.buffer_views.get(handle).buffer_handle);
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        ctx.editor.mode.insert_state.editing_buffer_handle = Some(buffer_handle);
+        let editorconfig = ctx.editor.buffers.get(buffer_handle).editorconfig;
 
         let key = keys.next(&ctx.editor.buffered_keys);
         let register = ctx.editor.registers.get_mut(AUTO_MACRO_REGISTER);
         let _ = write!(register, "{}", key);
 
+        let placeholder_selected = std::mem::take(
+            &mut ctx
+                .editor
+                .mode
+                .insert_state
+                .snippet_session
+                .placeholder_selected,
+        );
+        if placeholder_selected {
+            #[rustfmt::skip]
+            let deletes_placeholder = match key {
+                Key { code: KeyCode::Char('\t'), .. } => false,
+                Key { code: KeyCode::Char(_), control: false, alt: false, .. } => true,
+                Key { code: KeyCode::Backspace | KeyCode::Delete, shift: false, control: false, alt: false }
+                | Key { code: KeyCode::Char('h'), shift: false, control: true, alt: false } => {
+                    ctx.editor.buffer_views.get(handle).delete_text_in_cursor_ranges(
+                        &mut ctx.editor.buffers,
+                        &mut ctx.editor.word_database,
+                        &mut ctx.editor.events,
+                    );
+                    ctx.trigger_event_handlers();
+                    update_completions(ctx, client_handle, handle);
+                    return Some(EditorFlow::Continue);
+                }
+                _ => false,
+            };
+            if deletes_placeholder {
+                ctx.editor
+                    .buffer_views
+                    .get(handle)
+                    .delete_text_in_cursor_ranges(
+                        &mut ctx.editor.buffers,
+                        &mut ctx.editor.word_database,
+                        &mut ctx.editor.events,
+                    );
+                ctx.trigger_event_handlers();
+            }
+        }
+
         #[rustfmt::skip]
         match key {
             Key { code: KeyCode::Esc, shift: false, control: false, alt: false }
@@ -96,7 +162,7 @@ impl ModeState for State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesForward {
                         count: 1,
-                        tab_size: ctx.editor.config.tab_size.get(),
+                        tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                     },
                     CursorMovementKind::PositionAndAnchor,
                 );
@@ -108,7 +174,7 @@ impl ModeState for State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesBackward {
                         count: 1,
-                        tab_size: ctx.editor.config.tab_size.get(),
+                        tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                     },
                     CursorMovementKind::PositionAndAnchor,
                 );
@@ -125,11 +191,27 @@ impl ModeState for State {
                 return Some(EditorFlow::Continue);
             }
             Key { code: KeyCode::Char('\t'), shift: false, control: false, alt: false } => {
+                let expanded = !placeholder_selected && expand_snippet(ctx, handle);
+                if expanded
+                    || ctx
+                        .editor
+                        .mode
+                        .insert_state
+                        .snippet_session
+                        .is_active_in(buffer_handle)
+                {
+                    if !expanded {
+                        jump_to_next_tabstop(ctx, handle);
+                    }
+                    cancel_completion(&mut ctx.editor);
+                    return Some(EditorFlow::Continue);
+                }
+
                 static SPACES_BUF: &[u8; u8::MAX as usize] = &[b' '; u8::MAX as usize];
-                let text = if ctx.editor.config.indent_with_tabs {
+                let text = if editorconfig.indent_with_tabs(&ctx.editor.config) {
                     "\t"
                 } else {
-                    let len = ctx.editor.config.tab_size.get() as usize;
+                    let len = editorconfig.indent_size(&ctx.editor.config).get() as usize;
                     unsafe { std::str::from_utf8_unchecked(&SPACES_BUF[..len]) }
                 };
 
@@ -148,20 +230,48 @@ impl ModeState for State {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
                 let cursor_count = buffer_view.cursors[..].len();
                 let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                let syntax = ctx.editor.syntaxes.get(buffer.syntax_handle());
+
+                let split_pair_columns = &mut ctx.editor.mode.insert_state.split_pair_columns;
+                split_pair_columns.clear();
+                split_pair_columns.resize(cursor_count, None);
 
                 let mut buf = ctx.editor.string_pool.acquire();
                 for i in (0..cursor_count).rev() {
                     let position = buffer_view.cursors[i].position;
 
                     buf.push('\n');
-                    let indentation_word = buffer
-                        .content()
-                        .word_at(BufferPosition::line_col(position.line_index, 0));
-                    if indentation_word.kind == WordKind::Whitespace {
-                        let indentation_len = position
-                            .column_byte_index
-                            .min(indentation_word.text.len() as _);
-                        buf.push_str(&indentation_word.text[..indentation_len as usize]);
+                    let line = buffer.content().lines()[position.line_index as usize].as_str();
+                    if syntax.has_indent_rules() {
+                        let (before, after) = line.split_at(position.column_byte_index as usize);
+                        if syntax.increases_indent(before) && syntax.decreases_indent(after) {
+                            buffer.compute_indentation(
+                                syntax,
+                                &ctx.editor.config,
+                                before,
+                                "",
+                                &mut buf,
+                            );
+                            split_pair_columns[i] = Some(buf.len() as BufferPositionIndex - 1);
+                            buf.push('\n');
+                        }
+                        buffer.compute_indentation(
+                            syntax,
+                            &ctx.editor.config,
+                            before,
+                            after,
+                            &mut buf,
+                        );
+                    } else {
+                        let indentation_word = buffer
+                            .content()
+                            .word_at(BufferPosition::line_col(position.line_index, 0));
+                        if indentation_word.kind == WordKind::Whitespace {
+                            let indentation_len = position
+                                .column_byte_index
+                                .min(indentation_word.text.len() as _);
+                            buf.push_str(&indentation_word.text[..indentation_len as usize]);
+                        }
                     }
 
                     buffer.insert_text(
@@ -173,17 +283,27 @@ impl ModeState for State {
                     buf.clear();
                 }
                 ctx.editor.string_pool.release(buf);
+
+                if split_pair_columns.iter().any(Option::is_some) {
+                    ctx.trigger_event_handlers();
+
+                    let split_pair_columns = &ctx.editor.mode.insert_state.split_pair_columns;
+                    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+                    for (cursor, &column) in buffer_view.cursors.mut_guard()[..]
+                        .iter_mut()
+                        .zip(split_pair_columns.iter())
+                    {
+                        if let Some(column) = column {
+                            cursor.position =
+                                BufferPosition::line_col(cursor.position.line_index - 1, column);
+                            cursor.anchor = cursor.position;
+                        }
+                    }
+                }
             }
             Key { code: KeyCode::Char(c), control: false, alt: false, .. } => {
-                let mut buf = [0; std::mem::size_of::<char>()];
-                let s = c.encode_utf8(&mut buf);
-                let buffer_view = ctx.editor.buffer_views.get(handle);
-                buffer_view.insert_text_at_cursor_positions(
-                    &mut ctx.editor.buffers,
-                    &mut ctx.editor.word_database,
-                    s,
-                    &mut ctx.editor.events,
-                );
+                insert_char_with_auto_pairs(ctx, handle, c);
+                decrease_indent_on_type(ctx, handle);
             }
             Key { code: KeyCode::Backspace, shift: false, control: false, alt: false }
             | Key { code: KeyCode::Char('h'), shift: false, control: true, alt: false } => {
@@ -193,6 +313,36 @@ impl ModeState for State {
                     CursorMovement::ColumnsBackward(1),
                     CursorMovementKind::PositionOnly,
                 );
+
+                let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+                let auto_pairs = ctx.editor.syntaxes.get(buffer.syntax_handle()).auto_pairs();
+                if !auto_pairs.is_empty() {
+                    let lines = buffer.content().lines();
+                    for cursor in &mut buffer_view.cursors.mut_guard()[..] {
+                        let position = cursor.position;
+                        let anchor = cursor.anchor;
+                        if position.line_index != anchor.line_index
+                            || position.column_byte_index >= anchor.column_byte_index
+                        {
+                            continue;
+                        }
+
+                        let line = lines[position.line_index as usize].as_str();
+                        let deleted = &line[position.column_byte_index as usize
+                            ..anchor.column_byte_index as usize];
+                        let after = &line[anchor.column_byte_index as usize..];
+                        for &(open, close) in auto_pairs {
+                            if deleted.len() == open.len_utf8()
+                                && deleted.starts_with(open)
+                                && after.starts_with(close)
+                            {
+                                cursor.anchor.column_byte_index += close.len_utf8() as u32;
+                                break;
+                            }
+                        }
+                    }
+                }
+
                 buffer_view.delete_text_in_cursor_ranges(
                     &mut ctx.editor.buffers,
                     &mut ctx.editor.word_database,
@@ -242,7 +392,269 @@ impl ModeState for State {
     }
 }
 
+fn insert_char_with_auto_pairs(ctx: &mut EditorContext, handle: BufferViewHandle, c: char) {
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    let auto_pairs = ctx.editor.syntaxes.get(buffer.syntax_handle()).auto_pairs();
+
+    let mut buf = [0; 2 * std::mem::size_of::<char>()];
+    if auto_pairs.is_empty() {
+        let s = c.encode_utf8(&mut buf);
+        buffer_view.insert_text_at_cursor_positions(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            s,
+            &mut ctx.editor.events,
+        );
+        return;
+    }
+
+    let actions = &mut ctx.editor.mode.insert_state.auto_pair_actions;
+    actions.clear();
+    let lines = buffer.content().lines();
+    for cursor in &buffer_view.cursors[..] {
+        let position = cursor.position;
+        let line = lines[position.line_index as usize].as_str();
+        let previous_char = line[..position.column_byte_index as usize]
+            .chars()
+            .next_back();
+        let next_char = line[position.column_byte_index as usize..].chars().next();
+
+        let is_close = auto_pairs.iter().any(|&(_, close)| close == c);
+        let action = if is_close && next_char == Some(c) {
+            AutoPairAction::SkipClose
+        } else {
+            match auto_pairs.iter().find(|&&(open, _)| open == c) {
+                Some(&(open, close)) => {
+                    let next_allows_pair = match next_char {
+                        Some(next) => {
+                            next.is_whitespace()
+                                || auto_pairs.iter().any(|&(_, close)| close == next)
+                        }
+                        None => true,
+                    };
+                    let previous_allows_pair = open != close
+                        || !matches!(previous_char, Some(previous) if previous.is_alphanumeric());
+
+                    if next_allows_pair && previous_allows_pair {
+                        AutoPairAction::InsertPair(close)
+                    } else {
+                        AutoPairAction::Insert
+                    }
+                }
+                None => AutoPairAction::Insert,
+            }
+        };
+        actions.push(action);
+    }
+
+    for (cursor, &action) in buffer_view.cursors[..].iter().zip(actions.iter()).rev() {
+        let len = c.encode_utf8(&mut buf).len();
+        let len = match action {
+            AutoPairAction::Insert => len,
+            AutoPairAction::InsertPair(close) => len + close.encode_utf8(&mut buf[len..]).len(),
+            AutoPairAction::SkipClose => continue,
+        };
+        let text = unsafe { std::str::from_utf8_unchecked(&buf[..len]) };
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            cursor.position,
+            text,
+            &mut ctx.editor.events,
+        );
+    }
+
+    ctx.trigger_event_handlers();
+
+    let actions = &ctx.editor.mode.insert_state.auto_pair_actions;
+    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+    for (cursor, &action) in buffer_view.cursors.mut_guard()[..]
+        .iter_mut()
+        .zip(actions.iter())
+    {
+        match action {
+            AutoPairAction::Insert => continue,
+            AutoPairAction::InsertPair(close) => {
+                cursor.position.column_byte_index -= close.len_utf8() as u32;
+            }
+            AutoPairAction::SkipClose => cursor.position.column_byte_index += c.len_utf8() as u32,
+        }
+        cursor.anchor = cursor.position;
+    }
+}
+
+fn decrease_indent_on_type(ctx: &mut EditorContext, handle: BufferViewHandle) {
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer_handle = buffer_view.buffer_handle;
+    let buffer = ctx.editor.buffers.get(buffer_handle);
+    let syntax_handle = buffer.syntax_handle();
+    let syntax = ctx.editor.syntaxes.get(syntax_handle);
+    if !syntax.has_indent_rules() {
+        return;
+    }
+
+    // typing a char does not move lines around so we can check them before cursors are updated
+    let lines = buffer.content().lines();
+    let may_decrease_indent = buffer_view.cursors[..].iter().any(|cursor| {
+        let line = lines[cursor.position.line_index as usize]
+            .as_str()
+            .trim_start();
+        let word = match line.find(char::is_whitespace) {
+            Some(len) => &line[..len],
+            None => line,
+        };
+        word.char_indices()
+            .any(|(i, c)| syntax.is_indent_decrease(&word[..i + c.len_utf8()]))
+    });
+    if !may_decrease_indent {
+        return;
+    }
+
+    ctx.trigger_event_handlers();
+
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+    let syntax = ctx.editor.syntaxes.get(syntax_handle);
+
+    let mut buf = ctx.editor.string_pool.acquire();
+    let mut last_line_index = None;
+    for cursor in buffer_view.cursors[..].iter().rev() {
+        let line_index = cursor.position.line_index;
+        if last_line_index == Some(line_index) {
+            continue;
+        }
+        last_line_index = Some(line_index);
+
+        let line = buffer.content().lines()[line_index as usize].as_str();
+        let typed = line[..cursor.position.column_byte_index as usize].trim_start();
+        let previously_typed = match typed.char_indices().next_back() {
+            Some((i, _)) => &typed[..i],
+            None => typed,
+        };
+        // also reindent when the typed text stops matching so `end` -> `endpoint` is restored
+        if syntax.is_indent_decrease(typed) || syntax.is_indent_decrease(previously_typed) {
+            buffer.reindent_line(
+                &mut ctx.editor.word_database,
+                syntax,
+                &ctx.editor.config,
+                line_index,
+                &mut buf,
+                &mut ctx.editor.events,
+            );
+        }
+    }
+    ctx.editor.string_pool.release(buf);
+}
+
+fn expand_snippet(ctx: &mut EditorContext, handle: BufferViewHandle) -> bool {
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+    let content = buffer.content();
+    let main_cursor_position = buffer_view.cursors.main_cursor().position;
+
+    let word = content.word_at(content.position_before(main_cursor_position));
+    if word.kind != WordKind::Identifier || word.end_position() != main_cursor_position {
+        return false;
+    }
+    let path = buffer.path.to_str().unwrap_or("");
+    let (trigger, snippet) = match ctx.editor.snippets.find(path, word.text) {
+        Some(body) => (
+            ctx.editor.string_pool.acquire_with(word.text),
+            ctx.editor.string_pool.acquire_with(body),
+        ),
+        None => return false,
+    };
+
+    insert_snippet(ctx, handle, &trigger, &snippet);
+    ctx.editor.string_pool.release(trigger);
+    ctx.editor.string_pool.release(snippet);
+    true
+}
+
+fn insert_snippet(ctx: &mut EditorContext, handle: BufferViewHandle, trigger: &str, snippet: &str) {
+    let buffer_view = ctx.editor.buffer_views.get(handle);
+    let buffer_handle = buffer_view.buffer_handle;
+    let cursor_count = buffer_view.cursors[..].len();
+    ctx.editor
+        .mode
+        .insert_state
+        .snippet_session
+        .begin(buffer_handle);
+
+    let mut text = ctx.editor.string_pool.acquire();
+    let mut tabstops = Vec::new();
+    for i in (0..cursor_count).rev() {
+        let position = ctx.editor.buffer_views.get(handle).cursors[i].position;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+        let line = buffer.content().lines()[position.line_index as usize].as_str();
+        let column = position.column_byte_index as usize;
+        let from = if line[..column].ends_with(trigger) {
+            column - trigger.len()
+        } else {
+            column
+        };
+        let indentation_len = line.len() - line.trim_start().len();
+        let indentation = &line[..indentation_len.min(from)];
+        parse_snippet(snippet, indentation, &mut text, &mut tabstops);
+
+        let from = BufferPosition::line_col(position.line_index, from as _);
+        buffer.delete_range(
+            &mut ctx.editor.word_database,
+            BufferRange::between(from, position),
+            &mut ctx.editor.events,
+        );
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            from,
+            &text,
+            &mut ctx.editor.events,
+        );
+        ctx.trigger_event_handlers();
+
+        for tabstop in &tabstops {
+            let range = BufferRange::between(
+                snippet_text_position(from, &text, tabstop.range.start),
+                snippet_text_position(from, &text, tabstop.range.end),
+            );
+            ctx.editor
+                .mode
+                .insert_state
+                .snippet_session
+                .add_tabstop(tabstop.index, range);
+        }
+    }
+    ctx.editor.string_pool.release(text);
+
+    jump_to_next_tabstop(ctx, handle);
+}
+
+fn jump_to_next_tabstop(ctx: &mut EditorContext, handle: BufferViewHandle) {
+    let mut ranges = Vec::new();
+    let session = &mut ctx.editor.mode.insert_state.snippet_session;
+    session.next_tabstop_ranges(&mut ranges);
+    if ranges.is_empty() {
+        return;
+    }
+    session.placeholder_selected = ranges.iter().any(|r| r.from != r.to);
+
+    let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+    cursors.clear();
+    for range in ranges {
+        cursors.add(Cursor {
+            anchor: range.from,
+            position: range.to,
+        });
+    }
+}
+
 fn cancel_completion(editor: &mut Editor) {
+    let applied_completion = &mut editor.mode.insert_state.applied_completion;
+    if !applied_completion.is_empty() {
+        editor.picker.add_recent_completion(applied_completion);
+        applied_completion.clear();
+    }
+    editor.snippets.clear_completion_snippets();
     editor.picker.clear();
     editor.mode.insert_state.completion_positions.clear();
     editor.mode.insert_state.completing_plugin_handle = None;
@@ -260,7 +672,32 @@ fn update_completions(
 
     let main_cursor_position = buffer_view.cursors.main_cursor().position;
     let word = content.word_at(content.position_before(main_cursor_position));
-    let word_range = BufferRange::between(word.position, word.end_position());
+    let mut word_range = BufferRange::between(word.position, word.end_position());
+
+    let path_directory = path_completion_parts(
+        &content.lines()[main_cursor_position.line_index as usize].as_str()
+            [..main_cursor_position.column_byte_index as usize],
+    )
+    .and_then(|(directory, file)| {
+        // only hit the file system when the directory part changes
+        let state = &mut ctx.editor.mode.insert_state;
+        let directory_hash = hash_bytes(directory.as_bytes());
+        if state.completion_directory_hash != Some(directory_hash) {
+            state.completion_directory_hash = Some(directory_hash);
+            state.completion_directory = find_path_completion_directory(
+                &ctx.editor.current_directory,
+                &buffer.path,
+                directory,
+            );
+        }
+        let directory = state.completion_directory.clone()?;
+        Some((directory, file.len()))
+    });
+    if let Some((_, file_len)) = path_directory {
+        let mut position = main_cursor_position;
+        position.column_byte_index -= file_len as BufferPositionIndex;
+        word_range = BufferRange::between(position, main_cursor_position);
+    }
 
     let main_cursor_index = buffer_view.cursors.main_cursor_index();
 
@@ -277,7 +714,7 @@ fn update_completions(
                     cancel_completion(&mut ctx.editor);
                     return;
                 }
-                if position == word.position {
+                if position == word_range.from {
                     break;
                 }
 
@@ -286,6 +723,45 @@ fn update_completions(
             None => {
                 ctx.editor.picker.clear();
 
+                if let Some((directory, _)) = &path_directory {
+                    ctx.editor.picker.add_path_entries(directory);
+                    ctx.editor.mode.insert_state.completing_plugin_handle = None;
+                    ctx.editor.mode.insert_state.completion_positions.clear();
+
+                    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+                    let lines = ctx.editor.buffers.get(buffer_handle).content().lines();
+                    for cursor in &buffer_view.cursors[..] {
+                        let mut position = cursor.position;
+                        let line = &lines[position.line_index as usize].as_str()
+                            [..position.column_byte_index as usize];
+                        if let Some((_, file)) = path_completion_parts(line) {
+                            position.column_byte_index -= file.len() as BufferPositionIndex;
+                        }
+                        ctx.editor
+                            .mode
+                            .insert_state
+                            .completion_positions
+                            .push(position);
+                    }
+
+                    break;
+                }
+
+                let lines = ctx.editor.buffers.get(buffer_handle).content().lines();
+                let cursor_line_index = main_cursor_position.line_index;
+                let first_line_index = cursor_line_index.saturating_sub(COMPLETION_NEARBY_LINES);
+                let last_line_index =
+                    (cursor_line_index + COMPLETION_NEARBY_LINES + 1).min(lines.len() as _);
+                ctx.editor
+                    .picker
+                    .set_nearby_words((first_line_index..last_line_index).flat_map(|line_index| {
+                        let distance =
+                            line_index.max(cursor_line_index) - line_index.min(cursor_line_index);
+                        WordIter(lines[line_index as usize].as_str())
+                            .of_kind(WordKind::Identifier)
+                            .map(move |word| (word, distance))
+                    }));
+
                 let completion_requested = word.kind == WordKind::Identifier
                     && word.text.len() >= ctx.editor.config.completion_min_len as _;
                 let completion_ctx = CompletionContext {
@@ -305,13 +781,22 @@ fn update_completions(
                     }
                 }
 
-                if !completion_requested
-                    && ctx
-                        .editor
-                        .mode
-                        .insert_state
-                        .completing_plugin_handle
-                        .is_none()
+                if completion_requested {
+                    let path = ctx.editor.buffers.get(buffer_handle).path.to_str();
+                    for (trigger, body) in ctx.editor.snippets.triggers(path.unwrap_or("")) {
+                        let entry = ctx.editor.picker.add_completion_entry(
+                            CompletionSource::Snippet,
+                            CompletionKind::Snippet,
+                            trigger,
+                        );
+                        entry.documentation.push_str(body);
+                    }
+                } else if ctx
+                    .editor
+                    .mode
+                    .insert_state
+                    .completing_plugin_handle
+                    .is_none()
                 {
                     cancel_completion(&mut ctx.editor);
                     return;
@@ -339,24 +824,44 @@ fn update_completions(
         }
     }
 
-    let completion_filter = match ctx
+    let completion_filter = ctx
         .editor
         .buffers
         .get(buffer_handle)
         .content()
         .text_range(word_range)
-        .next()
-    {
-        Some(filter) => filter,
-        None => {
+        .next();
+    let completion_filter = match (completion_filter, &path_directory) {
+        (Some(filter), _) => filter,
+        (None, Some(_)) => "",
+        (None, None) => {
             cancel_completion(&mut ctx.editor);
             return;
         }
     };
 
+    let word_indices = match path_directory {
+        Some(_) => WordIndicesIter::empty(),
+        None => ctx.editor.word_database.word_indices(),
+    };
     ctx.editor
         .picker
-        .filter_completion(ctx.editor.word_database.word_indices(), completion_filter);
+        .filter_completion(word_indices, completion_filter);
+}
+
+fn find_path_completion_directory(
+    current_directory: &Path,
+    buffer_path: &Path,
+    directory: &str,
+) -> Option<PathBuf> {
+    let buffer_directory = match buffer_path.parent() {
+        Some(parent) => current_directory.join(parent),
+        None => current_directory.to_path_buf(),
+    };
+    [&buffer_directory, current_directory]
+        .iter()
+        .map(|base| base.join(directory))
+        .find(|directory| directory.is_dir())
 }
 
 fn apply_completion(
@@ -413,6 +918,10 @@ fn apply_completion(
         }
     };
 
+    let applied_completion = &mut ctx.editor.mode.insert_state.applied_completion;
+    applied_completion.clear();
+    applied_completion.push_str(entry);
+
     let completion = ctx.editor.string_pool.acquire_with(entry);
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     buffer_view.apply_completion(
@@ -422,5 +931,189 @@ fn apply_completion(
         &ctx.editor.mode.insert_state.completion_positions,
         &mut ctx.editor.events,
     );
+
+    let snippet = match ctx.editor.snippets.find_completion_snippet(&completion) {
+        Some((replaced_prefix, body)) => {
+            let mut trigger = ctx.editor.string_pool.acquire_with(replaced_prefix);
+            trigger.push_str(&completion);
+            Some((trigger, ctx.editor.string_pool.acquire_with(body)))
+        }
+        None => None,
+    };
     ctx.editor.string_pool.release(completion);
+
+    if let Some((trigger, body)) = snippet {
+        ctx.trigger_event_handlers();
+        cancel_completion(&mut ctx.editor);
+        insert_snippet(ctx, buffer_view_handle, &trigger, &body);
+        ctx.editor.string_pool.release(trigger);
+        ctx.editor.string_pool.release(body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        buffer_position::BufferPosition,
+        picker::{CompletionKind, CompletionSource},
+        plugin::PluginHandle,
+        testing::TestEditor,
+        word_database::WordIndicesIter,
+    };
+
+    #[test]
+    fn auto_pairs() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.c", "a\nb");
+
+        editor.keys("cjgli(x");
+        assert_eq!("a(x)\nb(x)", editor.buffer_text());
+        assert_eq!(2, editor.cursors().len());
+        assert_eq!(BufferPosition::line_col(0, 3), editor.cursors()[0].position);
+        assert_eq!(BufferPosition::line_col(1, 3), editor.cursors()[1].position);
+
+        editor.keys(") [<backspace>\"\"");
+        assert_eq!("a(x) \"\"\nb(x) \"\"", editor.buffer_text());
+        assert_eq!(BufferPosition::line_col(0, 7), editor.cursors()[0].position);
+
+        editor.keys(" it's<esc>");
+        assert_eq!("a(x) \"\" it's\nb(x) \"\" it's", editor.buffer_text());
+
+        editor.keys("gii{<esc>");
+        assert_eq!("{a(x) \"\" it's\n{b(x) \"\" it's", editor.buffer_text());
+    }
+
+    #[test]
+    fn indent_rules() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "fn f() {");
+        editor.keys("gli<enter>a;<enter>}<esc>");
+        assert_eq!("fn f() {\n\ta;\n}", editor.buffer_text());
+
+        editor.keys("gji<enter>fn g() {}<left><enter>b;<esc>");
+        assert_eq!("fn f() {\n\ta;\n}\nfn g() {\n\tb;\n}", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "fn f() {\nif x {\n  y();\n      }\n}");
+        editor.keys("gkvgj=");
+        assert_eq!("fn f() {\n\tif x {\n\t\ty();\n\t}\n}", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.lua", "if x then");
+        editor.keys("gli<enter>y()<enter>else<enter>z()<enter>end<esc>");
+        assert_eq!("if x then\n\ty()\nelse\n\tz()\nend", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.lua", "if x then");
+        editor.keys("gli<enter>endpoint()<enter>elsewhere()<esc>");
+        assert_eq!(
+            "if x then\n\tendpoint()\n\telsewhere()",
+            editor.buffer_text()
+        );
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.py", "def f(x: int) -> int:");
+        editor.keys("gli<enter>d = {\"a\":<enter>1}<enter>s = \"if:\"<enter>return x<esc>");
+        assert_eq!(
+            "def f(x: int) -> int:\n\td = {\"a\":\n\t1}\n\ts = \"if:\"\n\treturn x",
+            editor.buffer_text()
+        );
+    }
+
+    #[test]
+    fn snippets() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "");
+        assert!(editor
+            .command("snippet **/*.rs fn [[fn ${1:name}($2) { $0 }]]")
+            .is_ok());
+        assert!(editor
+            .command("snippet **/*.rs let [[let ${1:a} = ${1:a};]]")
+            .is_ok());
+
+        editor.keys("ifn<tab>");
+        assert_eq!("fn name() {  }", editor.buffer_text());
+        editor.keys("main<tab>x<tab>y<esc>");
+        assert_eq!("fn main(x) { y }", editor.buffer_text());
+
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "let\nlet");
+        assert!(editor
+            .command("snippet **/*.rs let [[let ${1:a} = ${1:a};]]")
+            .is_ok());
+        editor.keys("cjgli<tab>b");
+        assert_eq!("let b = b;\nlet b = b;", editor.buffer_text());
+        assert_eq!(4, editor.cursors().len());
+        editor.keys("<tab>;");
+        assert_eq!("let b = b;;\nlet b = b;;", editor.buffer_text());
+
+        editor.keys("<tab>");
+        assert_eq!("let b = b;;\t\nlet b = b;;\t", editor.buffer_text());
+    }
+
+    #[test]
+    fn completion_snippets() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.rs", "x.");
+        editor.keys("gli");
+
+        let ctx = editor.ctx_mut();
+        let entry = ctx.editor.picker.add_completion_entry(
+            CompletionSource::Plugin(PluginHandle(0)),
+            CompletionKind::Snippet,
+            "len",
+        );
+        entry.detail.push_str("usize");
+        ctx.editor
+            .snippets
+            .add_completion_snippet("len", ".", "->len(${1:n})$0;");
+        ctx.editor
+            .picker
+            .filter_completion(WordIndicesIter::empty(), "");
+        ctx.editor
+            .mode
+            .insert_state
+            .completion_positions
+            .push(BufferPosition::line_col(0, 2));
+
+        editor.keys("<c-n>");
+        assert_eq!("x->len(n);", editor.buffer_text());
+        assert_eq!(0, editor.ctx().editor.picker.len());
+        editor.keys("m<tab>y");
+        assert_eq!("x->len(m)y;", editor.buffer_text());
+    }
+
+    #[test]
+    fn path_completion() {
+        let mut editor = TestEditor::default();
+        editor.ctx_mut().editor.current_directory = env!("CARGO_MANIFEST_DIR").into();
+        editor.open_buffer("main.rs", "");
+
+        editor.keys("i./sr<c-n>");
+        assert_eq!("./src/", editor.buffer_text());
+        editor.keys("edito<c-n>");
+        assert_eq!("./src/editor.rs", editor.buffer_text());
+        editor.keys("<c-n>");
+        assert_eq!("./src/editor_utils.rs", editor.buffer_text());
+
+        editor.keys("<esc>ssr");
+        assert_eq!("sr", editor.ctx().editor.read_line.input());
+        editor.keys("<c-n>");
+        assert_eq!("sr", editor.ctx().editor.read_line.input());
+        editor.keys("<backspace><backspace>src/mode/ins<c-n>");
+        assert_eq!("src/mode/insert.rs", editor.ctx().editor.read_line.input());
+
+        editor.keys("<esc>!cat src/mode/ins<c-n>");
+        assert_eq!(
+            "cat src/mode/insert.rs",
+            editor.ctx().editor.read_line.input()
+        );
+
+        editor.ctx_mut().editor.current_directory =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        editor.keys("<esc>:open mode/ins<c-n>");
+        assert_eq!("open mode/insert.rs", editor.ctx().editor.read_line.input());
+    }
 }
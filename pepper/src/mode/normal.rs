@@ -82,6 +82,8 @@ impl State {
         keys: &mut KeysIterator,
         handle: BufferViewHandle,
     ) -> Option<EditorFlow> {
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        let editorconfig = ctx.editor.buffers.get(buffer_handle).editorconfig;
         let state = &mut ctx.editor.mode.normal_state;
         let keys_from_index = keys.index;
         match keys.next(&ctx.editor.buffered_keys) {
@@ -104,7 +106,7 @@ impl State {
                 &ctx.editor.buffers,
                 CursorMovement::LinesForward {
                     count: state.count.max(1) as _,
-                    tab_size: ctx.editor.config.tab_size.get(),
+                    tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                 },
                 state.movement_kind,
             ),
@@ -117,7 +119,7 @@ impl State {
                 &ctx.editor.buffers,
                 CursorMovement::LinesBackward {
                     count: state.count.max(1) as _,
-                    tab_size: ctx.editor.config.tab_size.get(),
+                    tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                 },
                 state.movement_kind,
             ),
@@ -1092,7 +1094,7 @@ impl State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesForward {
                         count: half_height as usize * state.count.max(1) as usize,
-                        tab_size: ctx.editor.config.tab_size.get(),
+                        tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                     },
                     state.movement_kind,
                 );
@@ -1109,7 +1111,7 @@ impl State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesBackward {
                         count: half_height as usize * state.count.max(1) as usize,
-                        tab_size: ctx.editor.config.tab_size.get(),
+                        tab_size: editorconfig.tab_size(&ctx.editor.config).get(),
                     },
                     state.movement_kind,
                 );
@@ -1175,7 +1177,11 @@ impl State {
                                 Some((i, c @ '\t')) => i + c.len_utf8(),
                                 Some((i, c @ ' ')) => {
                                     match chars
-                                        .take(ctx.editor.config.tab_size.get() as usize - 1)
+                                        .take(
+                                            editorconfig.indent_size(&ctx.editor.config).get()
+                                                as usize
+                                                - 1,
+                                        )
                                         .take_while(|(_, c)| *c == ' ')
                                         .last()
                                     {
@@ -1210,12 +1216,12 @@ impl State {
             } => {
                 let cursor_count = ctx.editor.buffer_views.get(handle).cursors[..].len();
 
-                let extender = if ctx.editor.config.indent_with_tabs {
+                let extender = if editorconfig.indent_with_tabs(&ctx.editor.config) {
                     let count = state.count.max(1) as _;
                     std::iter::repeat('\t').take(count)
                 } else {
-                    let tab_size = ctx.editor.config.tab_size.get() as usize;
-                    let count = state.count.max(1) as usize * tab_size;
+                    let indent_size = editorconfig.indent_size(&ctx.editor.config).get() as usize;
+                    let count = state.count.max(1) as usize * indent_size;
                     std::iter::repeat(' ').take(count)
                 };
 
@@ -1241,6 +1247,40 @@ impl State {
                 Self::on_edit_keys(&mut ctx.editor, keys, keys_from_index);
                 return Some(EditorFlow::Continue);
             }
+            Key {
+                code: KeyCode::Char('='),
+                control: false,
+                alt: false,
+                ..
+            } => {
+                let buffer_view = ctx.editor.buffer_views.get(handle);
+                let cursor_count = buffer_view.cursors[..].len();
+                let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+                let syntax = ctx.editor.syntaxes.get(buffer.syntax_handle());
+                if !syntax.has_indent_rules() {
+                    return Some(EditorFlow::Continue);
+                }
+
+                let mut buf = ctx.editor.string_pool.acquire();
+                for i in 0..cursor_count {
+                    let range = ctx.editor.buffer_views.get(handle).cursors[i].to_range();
+                    for line_index in range.from.line_index..=range.to.line_index {
+                        buffer.reindent_line(
+                            &mut ctx.editor.word_database,
+                            syntax,
+                            &ctx.editor.config,
+                            line_index,
+                            &mut buf,
+                            &mut ctx.editor.events,
+                        );
+                    }
+                }
+                ctx.editor.string_pool.release(buf);
+
+                buffer.commit_edits();
+                Self::on_edit_keys(&mut ctx.editor, keys, keys_from_index);
+                return Some(EditorFlow::Continue);
+            }
             Key {
                 code: KeyCode::Char('c' | 'C'),
                 control: false,
@@ -7,13 +7,16 @@ use crate::{
     client::ClientHandle,
     cursor::{Cursor, CursorCollection},
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
-    editor_utils::{parse_process_command, MessageKind, ReadLinePoll, ResidualStrBytes},
+    editor_utils::{
+        hash_bytes, parse_process_command, path_completion_parts, MessageKind, ReadLinePoll,
+        ResidualStrBytes,
+    },
     events::EditorEventQueue,
     mode::{ModeKind, ModeState},
     navigation_history::NavigationHistory,
     pattern::Pattern,
-    platform::{PlatformRequest, PooledBuf, ProcessTag},
-    word_database::WordDatabase,
+    platform::{Key, KeyCode, PlatformRequest, PooledBuf, ProcessTag},
+    word_database::{WordDatabase, WordIndicesIter},
 };
 
 pub struct State {
@@ -23,6 +26,8 @@ pub struct State {
     find_pattern_command: String,
     find_pattern_buffer_handle: Option<BufferHandle>,
     find_pattern_residual_bytes: ResidualStrBytes,
+    completion_index: usize,
+    completion_path_hash: Option<u64>,
 }
 
 impl State {
@@ -75,6 +80,8 @@ impl Default for State {
             find_pattern_command: String::new(),
             find_pattern_buffer_handle: None,
             find_pattern_residual_bytes: ResidualStrBytes::default(),
+            completion_index: 0,
+            completion_path_hash: None,
         }
     }
 }
@@ -82,12 +89,22 @@ impl Default for State {
 impl ModeState for State {
     fn on_enter(editor: &mut Editor) {
         editor.read_line.input_mut().clear();
+        editor.mode.read_line_state.completion_path_hash = None;
     }
 
     fn on_exit(editor: &mut Editor) {
         editor.mode.plugin_handle = None;
         editor.mode.read_line_state.find_pattern_command.clear();
         editor.read_line.input_mut().clear();
+        if editor
+            .mode
+            .read_line_state
+            .completion_path_hash
+            .take()
+            .is_some()
+        {
+            editor.picker.clear();
+        }
     }
 
     fn on_keys(
@@ -95,17 +112,79 @@ impl ModeState for State {
         client_handle: ClientHandle,
         keys: &mut KeysIterator,
     ) -> Option<EditorFlow> {
+        let key = ctx
+            .editor
+            .buffered_keys
+            .as_slice()
+            .get(keys.index)
+            .copied()
+            .unwrap_or_default();
         let poll = ctx.editor.read_line.poll(
             &mut ctx.platform,
             &mut ctx.editor.string_pool,
             &ctx.editor.buffered_keys,
             keys,
         );
+        if let ReadLinePoll::Pending = poll {
+            match key {
+                Key {
+                    code: KeyCode::Char('n'),
+                    shift: false,
+                    control: true,
+                    alt: false,
+                } => apply_path_completion(&mut ctx.editor, 1),
+                Key {
+                    code: KeyCode::Char('p'),
+                    shift: false,
+                    control: true,
+                    alt: false,
+                } => apply_path_completion(&mut ctx.editor, -1),
+                _ => update_path_completion(&mut ctx.editor),
+            }
+        }
+
         let f = ctx.editor.mode.read_line_state.on_client_keys;
         f(ctx, client_handle, keys, poll)
     }
 }
 
+fn update_path_completion(editor: &mut Editor) {
+    let state = &mut editor.mode.read_line_state;
+    let input = editor.read_line.input();
+    match path_completion_parts(input) {
+        Some((directory, file)) => {
+            let directory_hash = hash_bytes(directory.as_bytes());
+            if state.completion_path_hash != Some(directory_hash) {
+                editor.picker.clear();
+                editor
+                    .picker
+                    .add_path_entries(&editor.current_directory.join(directory));
+                state.completion_path_hash = Some(directory_hash);
+            }
+            state.completion_index = input.len() - file.len();
+            editor.picker.filter(WordIndicesIter::empty(), file);
+        }
+        None => {
+            if state.completion_path_hash.take().is_some() {
+                editor.picker.clear();
+            }
+        }
+    }
+}
+
+fn apply_path_completion(editor: &mut Editor, cursor_movement: isize) {
+    if editor.mode.read_line_state.completion_path_hash.is_none() {
+        return;
+    }
+
+    editor.picker.move_cursor(cursor_movement);
+    if let Some((_, entry)) = editor.picker.current_entry(&editor.word_database) {
+        let input = editor.read_line.input_mut();
+        input.truncate(editor.mode.read_line_state.completion_index);
+        input.push_str(entry);
+    }
+}
+
 pub mod search {
     use super::*;
 
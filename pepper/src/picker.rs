@@ -1,6 +1,16 @@
-use std::fmt;
+use std::{collections::HashSet, fmt, fs, path::Path};
 
-use crate::word_database::{WordDatabase, WordIndicesIter};
+use crate::{
+    editor_utils::hash_bytes,
+    plugin::PluginHandle,
+    word_database::{WordDatabase, WordIndicesIter},
+};
+
+const RECENT_COMPLETIONS_MAX_LEN: usize = 16;
+const FUZZY_SCORE_WEIGHT: u32 = 16;
+const RECENT_COMPLETION_SCORE: u32 = 24;
+const NEARBY_WORD_SCORE: u32 = 16;
+const NEARBY_WORD_DISTANCE_STEP: u32 = 4;
 
 #[derive(Clone, Copy)]
 pub enum EntrySource {
@@ -8,16 +18,100 @@ pub enum EntrySource {
     WordDatabase(usize),
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompletionSource {
+    Custom,
+    Word,
+    Path,
+    Snippet,
+    Plugin(PluginHandle),
+}
+impl CompletionSource {
+    fn score(self) -> u32 {
+        match self {
+            Self::Custom | Self::Word => 0,
+            Self::Path | Self::Snippet => 1,
+            Self::Plugin(_) => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Text,
+    Word,
+    Path,
+    Snippet,
+    Keyword,
+    Function,
+    Method,
+    Field,
+    Variable,
+    Constant,
+    Type,
+    Module,
+    Value,
+}
+impl CompletionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "",
+            Self::Word => "word",
+            Self::Path => "path",
+            Self::Snippet => "snippet",
+            Self::Keyword => "keyword",
+            Self::Function => "fn",
+            Self::Method => "method",
+            Self::Field => "field",
+            Self::Variable => "var",
+            Self::Constant => "const",
+            Self::Type => "type",
+            Self::Module => "module",
+            Self::Value => "value",
+        }
+    }
+}
+
+pub struct CustomEntry {
+    pub name: String,
+    pub source: CompletionSource,
+    pub kind: CompletionKind,
+    pub detail: String,
+    pub documentation: String,
+}
+
 struct FilteredEntry {
     pub source: EntrySource,
     pub score: u32,
 }
 
+#[derive(Default)]
+struct CompletionRanking {
+    nearby_words: Vec<(u64, u32)>,
+    recent_completions: Vec<u64>,
+}
+impl CompletionRanking {
+    fn score(&self, fuzzy_score: u32, text: &str) -> u32 {
+        let hash = hash_bytes(text.as_bytes());
+        let mut score = fuzzy_score * FUZZY_SCORE_WEIGHT;
+        if let Some(i) = self.recent_completions.iter().rposition(|&h| h == hash) {
+            let age = (self.recent_completions.len() - 1 - i) as u32;
+            score += RECENT_COMPLETION_SCORE - age;
+        }
+        if let Ok(i) = self.nearby_words.binary_search_by_key(&hash, |&(h, _)| h) {
+            let distance = self.nearby_words[i].1;
+            score += NEARBY_WORD_SCORE.saturating_sub(distance / NEARBY_WORD_DISTANCE_STEP);
+        }
+        score
+    }
+}
+
 #[derive(Default)]
 pub struct Picker {
     fuzzy_matcher: FuzzyMatcher,
+    ranking: CompletionRanking,
     custom_entries_len: usize,
-    custom_entries_buffer: Vec<String>,
+    custom_entries_buffer: Vec<CustomEntry>,
     filtered_entries: Vec<FilteredEntry>,
 
     cursor: Option<usize>,
@@ -84,24 +178,108 @@ impl Picker {
         self.scroll = 0;
     }
 
-    fn new_custom_entry(&mut self) -> &mut String {
+    fn new_custom_entry(&mut self) -> &mut CustomEntry {
         if self.custom_entries_len == self.custom_entries_buffer.len() {
-            self.custom_entries_buffer.push(String::new());
+            self.custom_entries_buffer.push(CustomEntry {
+                name: String::new(),
+                source: CompletionSource::Custom,
+                kind: CompletionKind::Text,
+                detail: String::new(),
+                documentation: String::new(),
+            });
         }
         let entry = &mut self.custom_entries_buffer[self.custom_entries_len];
         self.custom_entries_len += 1;
-        entry.clear();
+        entry.name.clear();
+        entry.source = CompletionSource::Custom;
+        entry.kind = CompletionKind::Text;
+        entry.detail.clear();
+        entry.documentation.clear();
         entry
     }
 
     pub fn add_custom_entry(&mut self, name: &str) {
         let entry = self.new_custom_entry();
-        entry.push_str(name);
+        entry.name.push_str(name);
     }
 
     pub fn add_custom_entry_fmt(&mut self, args: fmt::Arguments) {
         let entry = self.new_custom_entry();
-        let _ = fmt::write(entry, args);
+        let _ = fmt::write(&mut entry.name, args);
+    }
+
+    pub fn add_completion_entry(
+        &mut self,
+        source: CompletionSource,
+        kind: CompletionKind,
+        name: &str,
+    ) -> &mut CustomEntry {
+        let entry = self.new_custom_entry();
+        entry.name.push_str(name);
+        entry.source = source;
+        entry.kind = kind;
+        entry
+    }
+
+    pub fn add_path_entries(&mut self, directory: &Path) {
+        let read_dir = match fs::read_dir(directory) {
+            Ok(read_dir) => read_dir,
+            Err(_) => return,
+        };
+
+        let start = self.custom_entries_len;
+        for entry in read_dir.flatten() {
+            let name = entry.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+
+            let entry =
+                self.add_completion_entry(CompletionSource::Path, CompletionKind::Path, name);
+            if is_dir {
+                entry.name.push('/');
+            }
+        }
+
+        self.custom_entries_buffer[start..self.custom_entries_len]
+            .sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn remove_custom_entries(&mut self, source: CompletionSource) {
+        let mut len = 0;
+        for i in 0..self.custom_entries_len {
+            if self.custom_entries_buffer[i].source != source {
+                self.custom_entries_buffer.swap(len, i);
+                len += 1;
+            }
+        }
+        self.custom_entries_len = len;
+        self.filtered_entries.clear();
+        self.cursor = None;
+        self.scroll = 0;
+    }
+
+    pub fn set_nearby_words<'a, I>(&mut self, words: I)
+    where
+        I: Iterator<Item = (&'a str, u32)>,
+    {
+        let nearby_words = &mut self.ranking.nearby_words;
+        nearby_words.clear();
+        nearby_words.extend(words.map(|(word, distance)| (hash_bytes(word.as_bytes()), distance)));
+        nearby_words.sort_unstable();
+        nearby_words.dedup_by_key(|&mut (hash, _)| hash);
+    }
+
+    pub fn add_recent_completion(&mut self, text: &str) {
+        let hash = hash_bytes(text.as_bytes());
+        let recent_completions = &mut self.ranking.recent_completions;
+        recent_completions.retain(|&h| h != hash);
+        if recent_completions.len() == RECENT_COMPLETIONS_MAX_LEN {
+            recent_completions.remove(0);
+        }
+        recent_completions.push(hash);
     }
 
     pub fn add_custom_filtered_entries<'picker, 'pattern>(
@@ -149,21 +327,59 @@ impl Picker {
     }
 
     pub fn filter_completion(&mut self, word_indices: WordIndicesIter, pattern: &str) {
-        if self.custom_entries_len == 0 {
-            self.filter(word_indices, pattern);
-            if self.cursor.is_none() {
-                self.cursor = Some(0);
+        self.filtered_entries.clear();
+
+        let custom_entries = &self.custom_entries_buffer[..self.custom_entries_len];
+        for (i, entry) in custom_entries.iter().enumerate() {
+            let score = self.fuzzy_matcher.score(&entry.name, pattern);
+            if score != 0 {
+                let score = self.ranking.score(score, &entry.name) + entry.source.score();
+                self.filtered_entries.push(FilteredEntry {
+                    source: EntrySource::Custom(i),
+                    score,
+                });
             }
-            if self.len() == 1 {
-                self.clear();
+        }
+
+        if !pattern.is_empty() {
+            let custom_names: HashSet<&str> = self
+                .filtered_entries
+                .iter()
+                .filter_map(|e| match e.source {
+                    EntrySource::Custom(i) => Some(&custom_entries[i].name[..]),
+                    EntrySource::WordDatabase(_) => None,
+                })
+                .collect();
+            for (i, word) in word_indices {
+                if word == pattern {
+                    continue;
+                }
+                let score = self.fuzzy_matcher.score(word, pattern);
+                if score == 0 {
+                    continue;
+                }
+                if !custom_names.contains(word) {
+                    self.filtered_entries.push(FilteredEntry {
+                        source: EntrySource::WordDatabase(i),
+                        score: self.ranking.score(score, word),
+                    });
+                }
             }
+        }
+
+        self.filtered_entries
+            .sort_by_key(|e| std::cmp::Reverse(e.score));
+
+        let len = self.filtered_entries.len();
+        if len > 0 {
+            self.cursor = self.cursor.map(|c| c.min(len - 1));
         } else {
-            self.filter(WordIndicesIter::empty(), pattern);
+            self.cursor = None;
         }
     }
 
     fn filter_custom_entry(&mut self, index: usize, pattern: &str) -> bool {
-        let entry = &self.custom_entries_buffer[index];
+        let entry = &self.custom_entries_buffer[index].name;
         let score = self.fuzzy_matcher.score(entry, pattern);
         if score == 0 {
             return false;
@@ -179,14 +395,21 @@ impl Picker {
     pub fn current_entry<'a>(&'a self, words: &'a WordDatabase) -> Option<(EntrySource, &'a str)> {
         let entry = &self.filtered_entries[self.cursor?];
         let source = entry.source;
-        let entry = filtered_to_picker_entry(entry, &self.custom_entries_buffer, words);
+        let (_, entry) = filtered_to_picker_entry(entry, &self.custom_entries_buffer, words);
         Some((source, entry))
     }
 
+    pub fn current_custom_entry(&self) -> Option<&CustomEntry> {
+        match self.filtered_entries[self.cursor?].source {
+            EntrySource::Custom(i) => Some(&self.custom_entries_buffer[i]),
+            EntrySource::WordDatabase(_) => None,
+        }
+    }
+
     pub fn entries<'a>(
         &'a self,
         words: &'a WordDatabase,
-    ) -> impl 'a + ExactSizeIterator<Item = &'a str> {
+    ) -> impl 'a + ExactSizeIterator<Item = (CompletionKind, &'a str)> {
         let custom_entries = &self.custom_entries_buffer[..];
         self.filtered_entries
             .iter()
@@ -196,12 +419,12 @@ impl Picker {
 
 fn filtered_to_picker_entry<'a>(
     entry: &FilteredEntry,
-    custom_entries: &'a [String],
+    custom_entries: &'a [CustomEntry],
     words: &'a WordDatabase,
-) -> &'a str {
+) -> (CompletionKind, &'a str) {
     match entry.source {
-        EntrySource::Custom(i) => &custom_entries[i],
-        EntrySource::WordDatabase(i) => words.word_at(i),
+        EntrySource::Custom(i) => (custom_entries[i].kind, &custom_entries[i].name),
+        EntrySource::WordDatabase(i) => (CompletionKind::Word, words.word_at(i)),
     }
 }
 
@@ -379,4 +602,87 @@ mod tests {
             fuzzy_matcher.score(&big_repetitive_text, &big_repetitive_text),
         );
     }
+
+    #[test]
+    fn completion_ranking() {
+        fn entries(picker: &Picker, words: &WordDatabase) -> Vec<String> {
+            picker
+                .entries(words)
+                .map(|(kind, name)| format!("{}:{}", kind.as_str(), name))
+                .collect()
+        }
+
+        let mut words = WordDatabase::new();
+        for word in ["al", "alpha", "alphabet", "alpine"] {
+            words.add(word);
+        }
+
+        let mut picker = Picker::default();
+        picker.filter_completion(words.word_indices(), "al");
+        assert_eq!(
+            vec!["word:alpha", "word:alphabet", "word:alpine"],
+            entries(&picker, &words)
+        );
+
+        picker.set_nearby_words([("alpine", 2), ("alphabet", 40), ("alpine", 8)].into_iter());
+        picker.filter_completion(words.word_indices(), "al");
+        assert_eq!(
+            vec!["word:alpine", "word:alphabet", "word:alpha"],
+            entries(&picker, &words)
+        );
+
+        picker.add_recent_completion("alpha");
+        picker.filter_completion(words.word_indices(), "al");
+        assert_eq!(
+            vec!["word:alpha", "word:alpine", "word:alphabet"],
+            entries(&picker, &words)
+        );
+
+        let entry = picker.add_completion_entry(
+            CompletionSource::Plugin(PluginHandle(0)),
+            CompletionKind::Function,
+            "alpine",
+        );
+        entry.detail.push_str("fn alpine()");
+        picker.add_completion_entry(CompletionSource::Snippet, CompletionKind::Snippet, "alp");
+        picker.add_completion_entry(
+            CompletionSource::Plugin(PluginHandle(1)),
+            CompletionKind::Text,
+            "alpaca",
+        );
+        picker.filter_completion(words.word_indices(), "al");
+        assert_eq!(
+            vec![
+                "word:alpha",
+                "fn:alpine",
+                "word:alphabet",
+                ":alpaca",
+                "snippet:alp"
+            ],
+            entries(&picker, &words)
+        );
+
+        picker.move_cursor(1);
+        picker.move_cursor(1);
+        assert_eq!(
+            "fn alpine()",
+            picker
+                .current_custom_entry()
+                .map(|e| &e.detail[..])
+                .unwrap_or("")
+        );
+
+        picker.remove_custom_entries(CompletionSource::Plugin(PluginHandle(0)));
+        picker.filter_completion(words.word_indices(), "al");
+        assert_eq!(
+            vec![
+                "word:alpha",
+                "word:alpine",
+                "word:alphabet",
+                ":alpaca",
+                "snippet:alp"
+            ],
+            entries(&picker, &words)
+        );
+    }
 }
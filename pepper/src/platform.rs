@@ -186,6 +186,104 @@ impl Platform {
     }
 }
 
+// process that only receives its stdin and runs once the editor has settled
+// used by platforms that service requests synchronously
+pub(crate) trait SyncProcess: Sized {
+    fn kill(self);
+    fn run(self, stdin: Vec<u8>) -> Vec<u8>;
+}
+
+struct SyncProcessEntry<P> {
+    tag: ProcessTag,
+    handle: PlatformProcessHandle,
+    process: P,
+    stdin: Vec<u8>,
+    buf_len: usize,
+}
+
+pub(crate) struct SyncProcessCollection<P> {
+    entries: Vec<SyncProcessEntry<P>>,
+}
+impl<P> Default for SyncProcessCollection<P> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+impl<P: SyncProcess> SyncProcessCollection<P> {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // services process requests and gives back any other request
+    pub fn on_request<F>(
+        &mut self,
+        buf_pool: &mut BufPool,
+        request: PlatformRequest,
+        events: &mut Vec<PlatformEvent>,
+        spawn: F,
+    ) -> Option<PlatformRequest>
+    where
+        F: FnOnce(Command) -> Option<P>,
+    {
+        match request {
+            PlatformRequest::SpawnProcess {
+                tag,
+                command,
+                buf_len,
+            } => {
+                let index = (0..=u8::MAX).find(|&i| self.entries.iter().all(|e| e.handle.0 != i));
+                match index.and_then(|index| Some((index, spawn(command)?))) {
+                    Some((index, process)) => {
+                        let handle = PlatformProcessHandle(index);
+                        self.entries.push(SyncProcessEntry {
+                            tag,
+                            handle,
+                            process,
+                            stdin: Vec::new(),
+                            buf_len,
+                        });
+                        events.push(PlatformEvent::ProcessSpawned { tag, handle });
+                    }
+                    None => events.push(PlatformEvent::ProcessExit { tag }),
+                }
+            }
+            PlatformRequest::WriteToProcess { handle, buf } => {
+                if let Some(entry) = self.entries.iter_mut().find(|e| e.handle.0 == handle.0) {
+                    entry.stdin.extend_from_slice(buf.as_bytes());
+                }
+                buf_pool.release(buf);
+            }
+            PlatformRequest::CloseProcessInput { .. } => (),
+            PlatformRequest::KillProcess { handle } => {
+                if let Some(i) = self.entries.iter().position(|e| e.handle.0 == handle.0) {
+                    let entry = self.entries.remove(i);
+                    entry.process.kill();
+                    events.push(PlatformEvent::ProcessExit { tag: entry.tag });
+                }
+            }
+            request => return Some(request),
+        }
+        None
+    }
+
+    pub fn finish_all(&mut self, buf_pool: &mut BufPool, events: &mut Vec<PlatformEvent>) {
+        for entry in self.entries.drain(..) {
+            let output = entry.process.run(entry.stdin);
+            for chunk in output.chunks(entry.buf_len.max(1)) {
+                let mut buf = buf_pool.acquire();
+                buf.write().extend_from_slice(chunk);
+                events.push(PlatformEvent::ProcessOutput {
+                    tag: entry.tag,
+                    buf,
+                });
+            }
+            events.push(PlatformEvent::ProcessExit { tag: entry.tag });
+        }
+    }
+}
+
 pub struct PooledBuf(Vec<u8>);
 impl PooledBuf {
     pub fn as_bytes(&self) -> &[u8] {
@@ -408,7 +408,8 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
 fn run_client(args: Args, mut connection: UnixStream) {
     use io::{Read, Write};
 
-    let terminal = if args.quit {
+    let is_eval = args.eval.is_some();
+    let terminal = if args.quit || is_eval {
         None
     } else {
         Some(Terminal::new())
@@ -424,7 +425,7 @@ fn run_client(args: Args, mut connection: UnixStream) {
 
     let epoll = Epoll::new();
     epoll.add(connection.as_raw_fd(), 1, 0);
-    if is_pipped(libc::STDIN_FILENO) {
+    if !is_eval && is_pipped(libc::STDIN_FILENO) {
         epoll.add(libc::STDIN_FILENO, 3, 0);
     }
 
@@ -448,7 +449,7 @@ fn run_client(args: Args, mut connection: UnixStream) {
         resize_signal = None;
     }
 
-    if is_pipped(libc::STDOUT_FILENO) {
+    if !is_eval && is_pipped(libc::STDOUT_FILENO) {
         let (_, bytes) = application.update(None, &[], Some(&[]), &[]);
         if connection.write_all(bytes).is_err() {
             return;
@@ -511,11 +512,27 @@ fn run_client(args: Args, mut connection: UnixStream) {
         }
     }
 
-    if is_pipped(libc::STDOUT_FILENO) {
+    let mut exit_code = 0;
+    if is_eval {
+        match application.get_eval_result() {
+            Some(Ok(output)) => {
+                write_all_bytes(libc::STDOUT_FILENO, output);
+            }
+            Some(Err(error)) => {
+                write_all_bytes(libc::STDERR_FILENO, error);
+                exit_code = 1;
+            }
+            None => exit_code = 1,
+        }
+    } else if is_pipped(libc::STDOUT_FILENO) {
         let bytes = application.get_stdout_bytes();
         write_all_bytes(libc::STDOUT_FILENO, bytes);
     }
 
     drop(terminal);
     drop(application);
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
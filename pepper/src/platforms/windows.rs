@@ -1438,7 +1438,8 @@ fn run_client(args: Args, pipe_path: &[u16]) {
     let console_input_handle;
     let console_output_handle;
 
-    if args.quit {
+    let is_eval = args.eval.is_some();
+    if args.quit || is_eval {
         console_input_handle = None;
         console_output_handle = None;
     } else {
@@ -1492,10 +1493,13 @@ fn run_client(args: Args, pipe_path: &[u16]) {
     let mut console_event_buf = [unsafe { std::mem::zeroed() }; CLIENT_EVENT_BUFFER_LEN];
     let mut keys = Vec::with_capacity(CLIENT_EVENT_BUFFER_LEN);
 
-    let mut stdin_pipe = get_std_handle(STD_INPUT_HANDLE).and_then(StdinPipe::new);
+    let mut stdin_pipe = match is_eval {
+        true => None,
+        false => get_std_handle(STD_INPUT_HANDLE).and_then(StdinPipe::new),
+    };
     let output_handle = get_std_handle(STD_OUTPUT_HANDLE);
     if let Some(handle) = &output_handle {
-        if is_pipped(&handle) {
+        if !is_eval && is_pipped(&handle) {
             let (_, bytes) = application.update(None, &[], Some(&[]), &[]);
             if !connection.write(bytes) {
                 return;
@@ -1572,10 +1576,26 @@ fn run_client(args: Args, pipe_path: &[u16]) {
         }
     }
 
-    if let Some(handle) = output_handle {
-        if is_pipped(&handle) {
+    let mut exit_code = 0;
+    if is_eval {
+        match application.get_eval_result() {
+            Some(Ok(output)) => {
+                if let Some(handle) = &output_handle {
+                    write_all_bytes(handle, output);
+                }
+            }
+            Some(Err(error)) => {
+                if let Some(handle) = get_std_handle(STD_ERROR_HANDLE) {
+                    write_all_bytes(&handle, error);
+                }
+                exit_code = 1;
+            }
+            None => exit_code = 1,
+        }
+    } else if let Some(handle) = &output_handle {
+        if is_pipped(handle) {
             let bytes = application.get_stdout_bytes();
-            write_all_bytes(&handle, bytes);
+            write_all_bytes(handle, bytes);
         }
     }
 
@@ -1585,6 +1605,10 @@ fn run_client(args: Args, pipe_path: &[u16]) {
     drop(application);
     drop(console_input_handle);
     drop(console_output_handle);
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
 }
 
 fn parse_console_events(
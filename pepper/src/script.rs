@@ -0,0 +1,959 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    client::ClientHandle,
+    command::{CommandError, CommandIO, HookEvent},
+    editor::{EditorContext, EditorFlow},
+    events::EditorEventIter,
+};
+
+mod api;
+mod parser;
+
+use parser::{BinaryOp, Block, Expression, FunctionDefinition, Statement, StatementKind, UnaryOp};
+
+const MAX_CALL_DEPTH: u32 = 128;
+const MAX_STEPS: u32 = 10_000_000;
+
+pub struct ScriptError {
+    pub source: Option<Rc<str>>,
+    pub line: u32,
+    pub message: String,
+}
+impl ScriptError {
+    pub fn new(message: String) -> Self {
+        Self {
+            source: None,
+            line: 0,
+            message,
+        }
+    }
+
+    fn at(line: u32, message: String) -> Self {
+        Self {
+            source: None,
+            line,
+            message,
+        }
+    }
+
+    fn locate(mut self, source: &Rc<str>, line: u32) -> Self {
+        if self.line == 0 {
+            self.line = line;
+        }
+        if self.source.is_none() {
+            self.source = Some(source.clone());
+        }
+        self
+    }
+}
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(source) = &self.source {
+            write!(f, "{}:", source)?;
+        }
+        if self.line > 0 {
+            write!(f, "{}: ", self.line)?;
+        } else if self.source.is_some() {
+            f.write_str(" ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+pub type NativeFunction = fn(&mut Interpreter, Vec<Value>) -> Result<Value, ScriptError>;
+
+type Local = (Rc<str>, Rc<RefCell<Value>>);
+
+pub struct Function {
+    definition: Rc<FunctionDefinition>,
+    captures: Vec<Local>,
+}
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Table(Rc<RefCell<Table>>),
+    Function(Rc<Function>),
+    NativeFunction(NativeFunction),
+}
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Nil => "nil",
+            Self::Boolean(_) => "boolean",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Table(_) => "table",
+            Self::Function(_) | Self::NativeFunction(_) => "function",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Self::Nil | Self::Boolean(false))
+    }
+
+    pub fn table(table: Table) -> Self {
+        Self::Table(Rc::new(RefCell::new(table)))
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Boolean(a), Self::Boolean(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Table(a), Self::Table(b)) => Rc::ptr_eq(a, b),
+            (Self::Function(a), Self::Function(b)) => Rc::ptr_eq(a, b),
+            (Self::NativeFunction(a), Self::NativeFunction(b)) => *a as usize == *b as usize,
+            _ => false,
+        }
+    }
+}
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Nil => f.write_str("nil"),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Number(n) => {
+                if n.fract() == 0.0 && n.abs() < 1e15 {
+                    write!(f, "{}", *n as i64)
+                } else {
+                    write!(f, "{}", n)
+                }
+            }
+            Self::String(s) => f.write_str(s),
+            Self::Table(t) => write!(f, "table: {:p}", Rc::as_ptr(t)),
+            Self::Function(function) => write!(f, "function: {:p}", Rc::as_ptr(function)),
+            Self::NativeFunction(function) => write!(f, "function: {:p}", *function as *const ()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Table {
+    array: Vec<Value>,
+    fields: Vec<(Rc<str>, Value)>,
+}
+impl Table {
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty()
+    }
+
+    pub fn push(&mut self, value: Value) {
+        self.array.push(value);
+    }
+
+    pub fn array(&self) -> &[Value] {
+        &self.array
+    }
+
+    pub fn get_field(&self, name: &str) -> Value {
+        match self.fields.iter().find(|(k, _)| &k[..] == name) {
+            Some((_, value)) => value.clone(),
+            None => Value::Nil,
+        }
+    }
+
+    pub fn set_field(&mut self, name: Rc<str>, value: Value) {
+        match self.fields.iter().position(|(k, _)| *k == name) {
+            Some(i) => match value {
+                Value::Nil => {
+                    self.fields.remove(i);
+                }
+                value => self.fields[i].1 = value,
+            },
+            None => {
+                if !matches!(value, Value::Nil) {
+                    self.fields.push((name, value));
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, key: &Value) -> Value {
+        match key {
+            Value::Number(n) => match array_index(*n) {
+                Some(i) if i < self.array.len() => self.array[i].clone(),
+                _ => Value::Nil,
+            },
+            Value::String(name) => self.get_field(name),
+            _ => Value::Nil,
+        }
+    }
+
+    pub fn set(&mut self, key: Value, value: Value) -> Result<(), ScriptError> {
+        match key {
+            Value::Number(n) => match array_index(n) {
+                Some(i) if i < self.array.len() => {
+                    if matches!(value, Value::Nil) && i + 1 == self.array.len() {
+                        self.array.pop();
+                    } else {
+                        self.array[i] = value;
+                    }
+                    Ok(())
+                }
+                Some(i) if i == self.array.len() => {
+                    if !matches!(value, Value::Nil) {
+                        self.array.push(value);
+                    }
+                    Ok(())
+                }
+                _ => Err(ScriptError::new(format!(
+                    "table index {} is out of bounds",
+                    Value::Number(n)
+                ))),
+            },
+            Value::String(name) => {
+                self.set_field(name, value);
+                Ok(())
+            }
+            key => Err(ScriptError::new(format!(
+                "can not index table with a {} key",
+                key.type_name()
+            ))),
+        }
+    }
+
+    fn entries(&self) -> Vec<(Value, Value)> {
+        let mut entries = Vec::with_capacity(self.array.len() + self.fields.len());
+        for (i, value) in self.array.iter().enumerate() {
+            entries.push((Value::Number((i + 1) as _), value.clone()));
+        }
+        for (key, value) in &self.fields {
+            entries.push((Value::String(key.clone()), value.clone()));
+        }
+        entries
+    }
+}
+
+fn array_index(n: f64) -> Option<usize> {
+    if n >= 1.0 && n.fract() == 0.0 {
+        Some(n as usize - 1)
+    } else {
+        None
+    }
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Return(Value),
+}
+
+pub struct Interpreter<'a> {
+    pub ctx: &'a mut EditorContext,
+    pub client_handle: Option<ClientHandle>,
+    pub flow: EditorFlow,
+    globals: Rc<RefCell<Table>>,
+    locals: Vec<Local>,
+    frame_base: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(ctx: &'a mut EditorContext, client_handle: Option<ClientHandle>) -> Self {
+        let globals = ctx.editor.scripts.globals.clone();
+        Self {
+            ctx,
+            client_handle,
+            flow: EditorFlow::Continue,
+            globals,
+            locals: Vec::new(),
+            frame_base: 0,
+        }
+    }
+
+    fn get_variable(&self, name: &str) -> Value {
+        for (local_name, value) in self.locals[self.frame_base..].iter().rev() {
+            if &local_name[..] == name {
+                return value.borrow().clone();
+            }
+        }
+        self.globals.borrow().get_field(name)
+    }
+
+    fn set_variable(&mut self, name: &Rc<str>, value: Value) {
+        for (local_name, local) in self.locals[self.frame_base..].iter().rev() {
+            if local_name == name {
+                *local.borrow_mut() = value;
+                return;
+            }
+        }
+        self.globals.borrow_mut().set_field(name.clone(), value);
+    }
+
+    fn push_local(&mut self, name: &Rc<str>, value: Value) {
+        self.locals
+            .push((name.clone(), Rc::new(RefCell::new(value))));
+    }
+
+    pub fn call(&mut self, function: &Value, args: Vec<Value>) -> Result<Value, ScriptError> {
+        match function {
+            Value::NativeFunction(function) => function(self, args),
+            Value::Function(function) => {
+                let scripts = &mut self.ctx.editor.scripts;
+                if scripts.depth >= MAX_CALL_DEPTH {
+                    return Err(ScriptError::new("stack overflow".into()));
+                }
+                scripts.depth += 1;
+
+                let previous_frame_base = self.frame_base;
+                self.frame_base = self.locals.len();
+                self.locals.extend(function.captures.iter().cloned());
+                let definition = &function.definition;
+                if let Some(name) = &definition.name {
+                    self.push_local(name, Value::Function(function.clone()));
+                }
+                let mut args = args.into_iter();
+                for param in &definition.params {
+                    let arg = args.next().unwrap_or(Value::Nil);
+                    self.push_local(param, arg);
+                }
+
+                let result = self.exec_block(&definition.source, &definition.body);
+
+                self.locals.truncate(self.frame_base);
+                self.frame_base = previous_frame_base;
+                self.ctx.editor.scripts.depth -= 1;
+
+                match result? {
+                    Flow::Return(value) => Ok(value),
+                    Flow::Normal | Flow::Break => Ok(Value::Nil),
+                }
+            }
+            value => Err(ScriptError::new(format!(
+                "attempt to call a {} value",
+                value.type_name()
+            ))),
+        }
+    }
+
+    fn exec_block(&mut self, source: &Rc<str>, block: &Block) -> Result<Flow, ScriptError> {
+        let scope_len = self.locals.len();
+        let mut result = Ok(Flow::Normal);
+        for statement in block {
+            result = self
+                .exec_statement(source, statement)
+                .map_err(|e| e.locate(source, statement.line));
+            if !matches!(result, Ok(Flow::Normal)) {
+                break;
+            }
+        }
+        self.locals.truncate(scope_len);
+        result
+    }
+
+    fn step(&mut self) -> Result<(), ScriptError> {
+        let scripts = &mut self.ctx.editor.scripts;
+        scripts.steps += 1;
+        if scripts.steps > MAX_STEPS {
+            return Err(ScriptError::new("script took too long to run".into()));
+        }
+        Ok(())
+    }
+
+    fn exec_statement(
+        &mut self,
+        source: &Rc<str>,
+        statement: &Statement,
+    ) -> Result<Flow, ScriptError> {
+        self.step()?;
+
+        match &statement.kind {
+            StatementKind::Local(name, value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Nil,
+                };
+                self.push_local(name, value);
+            }
+            StatementKind::Assign(target, value) => {
+                let value = self.eval(value)?;
+                match target {
+                    Expression::Variable(name) => self.set_variable(name, value),
+                    Expression::Index(table, key) => {
+                        let table = self.eval(table)?;
+                        let key = self.eval(key)?;
+                        match table {
+                            Value::Table(table) => table.borrow_mut().set(key, value)?,
+                            table => {
+                                return Err(ScriptError::new(format!(
+                                    "attempt to index a {} value",
+                                    table.type_name()
+                                )))
+                            }
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            StatementKind::Call(call) => {
+                self.eval(call)?;
+            }
+            StatementKind::If(branches, else_block) => {
+                for (condition, block) in branches {
+                    if self.eval(condition)?.is_truthy() {
+                        return self.exec_block(source, block);
+                    }
+                }
+                if let Some(block) = else_block {
+                    return self.exec_block(source, block);
+                }
+            }
+            StatementKind::While(condition, body) => {
+                while self.eval(condition)?.is_truthy() {
+                    self.step()?;
+                    match self.exec_block(source, body)? {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            StatementKind::NumericFor {
+                name,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                let start = self.eval_number(start)?;
+                let end = self.eval_number(end)?;
+                let step = match step {
+                    Some(step) => self.eval_number(step)?,
+                    None => 1.0,
+                };
+                if step == 0.0 {
+                    return Err(ScriptError::new("'for' step is zero".into()));
+                }
+
+                let mut i = start;
+                while (step > 0.0 && i <= end) || (step < 0.0 && i >= end) {
+                    self.step()?;
+                    self.push_local(name, Value::Number(i));
+                    let flow = self.exec_block(source, body);
+                    self.locals.pop();
+                    match flow? {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                    i += step;
+                }
+            }
+            StatementKind::GenericFor {
+                key_name,
+                value_name,
+                table,
+                body,
+            } => {
+                let entries = match self.eval(table)? {
+                    Value::Table(table) => table.borrow().entries(),
+                    value => {
+                        return Err(ScriptError::new(format!(
+                            "attempt to iterate over a {} value",
+                            value.type_name()
+                        )))
+                    }
+                };
+
+                for (key, value) in entries {
+                    let scope_len = self.locals.len();
+                    self.push_local(key_name, key);
+                    if let Some(value_name) = value_name {
+                        self.push_local(value_name, value);
+                    }
+                    let flow = self.exec_block(source, body);
+                    self.locals.truncate(scope_len);
+                    match flow? {
+                        Flow::Normal => (),
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            StatementKind::Do(body) => return self.exec_block(source, body),
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Nil,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StatementKind::Break => return Ok(Flow::Break),
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn eval_number(&mut self, expression: &Expression) -> Result<f64, ScriptError> {
+        match self.eval(expression)? {
+            Value::Number(n) => Ok(n),
+            value => Err(ScriptError::new(format!(
+                "expected number, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+
+    fn eval_args(&mut self, args: &[Expression]) -> Result<Vec<Value>, ScriptError> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        Ok(values)
+    }
+
+    fn eval(&mut self, expression: &Expression) -> Result<Value, ScriptError> {
+        match expression {
+            Expression::Nil => Ok(Value::Nil),
+            Expression::Boolean(b) => Ok(Value::Boolean(*b)),
+            Expression::Number(n) => Ok(Value::Number(*n)),
+            Expression::String(s) => Ok(Value::String(s.clone())),
+            Expression::Variable(name) => Ok(self.get_variable(name)),
+            Expression::Index(table, key) => {
+                let table = self.eval(table)?;
+                let key = self.eval(key)?;
+                match table {
+                    Value::Table(table) => Ok(table.borrow().get(&key)),
+                    table => Err(ScriptError::new(format!(
+                        "attempt to index a {} value",
+                        table.type_name()
+                    ))),
+                }
+            }
+            Expression::Call(function, args) => {
+                let function = self.eval(function)?;
+                let args = self.eval_args(args)?;
+                self.call(&function, args)
+            }
+            Expression::MethodCall(object, name, args) => {
+                let object = self.eval(object)?;
+                let function = match &object {
+                    Value::Table(table) => table.borrow().get_field(name),
+                    Value::String(_) => match self.globals.borrow().get_field("string") {
+                        Value::Table(string) => string.borrow().get_field(name),
+                        _ => Value::Nil,
+                    },
+                    value => {
+                        return Err(ScriptError::new(format!(
+                            "attempt to index a {} value",
+                            value.type_name()
+                        )))
+                    }
+                };
+                let mut values = Vec::with_capacity(args.len() + 1);
+                values.push(object);
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.call(&function, values)
+            }
+            Expression::Function(definition) => {
+                let captures = self.locals[self.frame_base..].to_vec();
+                Ok(Value::Function(Rc::new(Function {
+                    definition: definition.clone(),
+                    captures,
+                })))
+            }
+            Expression::Table(entries) => {
+                let mut table = Table::default();
+                for (key, value) in entries {
+                    let value = self.eval(value)?;
+                    match key {
+                        Some(key) => {
+                            let key = self.eval(key)?;
+                            table.set(key, value)?;
+                        }
+                        None => table.push(value),
+                    }
+                }
+                Ok(Value::table(table))
+            }
+            Expression::Unary(op, operand) => {
+                let operand = self.eval(operand)?;
+                match (op, operand) {
+                    (UnaryOp::Not, operand) => Ok(Value::Boolean(!operand.is_truthy())),
+                    (UnaryOp::Negate, Value::Number(n)) => Ok(Value::Number(-n)),
+                    (UnaryOp::Length, Value::String(s)) => Ok(Value::Number(s.len() as _)),
+                    (UnaryOp::Length, Value::Table(t)) => Ok(Value::Number(t.borrow().len() as _)),
+                    (UnaryOp::Negate, operand) => Err(ScriptError::new(format!(
+                        "attempt to perform arithmetic on a {} value",
+                        operand.type_name()
+                    ))),
+                    (UnaryOp::Length, operand) => Err(ScriptError::new(format!(
+                        "attempt to get length of a {} value",
+                        operand.type_name()
+                    ))),
+                }
+            }
+            Expression::Binary(BinaryOp::And, left, right) => {
+                let left = self.eval(left)?;
+                if left.is_truthy() {
+                    self.eval(right)
+                } else {
+                    Ok(left)
+                }
+            }
+            Expression::Binary(BinaryOp::Or, left, right) => {
+                let left = self.eval(left)?;
+                if left.is_truthy() {
+                    Ok(left)
+                } else {
+                    self.eval(right)
+                }
+            }
+            Expression::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                binary_op(*op, left, right)
+            }
+        }
+    }
+}
+
+fn binary_op(op: BinaryOp, left: Value, right: Value) -> Result<Value, ScriptError> {
+    use std::cmp::Ordering;
+
+    let compare = |left: &Value, right: &Value| match (left, right) {
+        (Value::Number(a), Value::Number(b)) => Ok(a.partial_cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(Some(a.cmp(b))),
+        _ => Err(ScriptError::new(format!(
+            "attempt to compare {} with {}",
+            left.type_name(),
+            right.type_name()
+        ))),
+    };
+
+    let value = match op {
+        BinaryOp::Equals => Value::Boolean(left.equals(&right)),
+        BinaryOp::NotEquals => Value::Boolean(!left.equals(&right)),
+        BinaryOp::Less => Value::Boolean(compare(&left, &right)? == Some(Ordering::Less)),
+        BinaryOp::LessEquals => Value::Boolean(matches!(
+            compare(&left, &right)?,
+            Some(Ordering::Less | Ordering::Equal)
+        )),
+        BinaryOp::Greater => Value::Boolean(compare(&left, &right)? == Some(Ordering::Greater)),
+        BinaryOp::GreaterEquals => Value::Boolean(matches!(
+            compare(&left, &right)?,
+            Some(Ordering::Greater | Ordering::Equal)
+        )),
+        BinaryOp::Concat => match (&left, &right) {
+            (Value::String(_) | Value::Number(_), Value::String(_) | Value::Number(_)) => {
+                Value::String(format!("{}{}", left, right).into())
+            }
+            _ => {
+                let value = if let Value::String(_) | Value::Number(_) = left {
+                    right
+                } else {
+                    left
+                };
+                return Err(ScriptError::new(format!(
+                    "attempt to concatenate a {} value",
+                    value.type_name()
+                )));
+            }
+        },
+        BinaryOp::Add
+        | BinaryOp::Subtract
+        | BinaryOp::Multiply
+        | BinaryOp::Divide
+        | BinaryOp::Modulo => {
+            let (a, b) = match (&left, &right) {
+                (Value::Number(a), Value::Number(b)) => (*a, *b),
+                _ => {
+                    let value = if let Value::Number(_) = left {
+                        right
+                    } else {
+                        left
+                    };
+                    return Err(ScriptError::new(format!(
+                        "attempt to perform arithmetic on a {} value",
+                        value.type_name()
+                    )));
+                }
+            };
+            Value::Number(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Subtract => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide => a / b,
+                BinaryOp::Modulo => a - (a / b).floor() * b,
+                _ => unreachable!(),
+            })
+        }
+        BinaryOp::And | BinaryOp::Or => unreachable!(),
+    };
+    Ok(value)
+}
+
+pub struct ScriptEngine {
+    globals: Rc<RefCell<Table>>,
+    commands: Vec<(&'static str, Value)>,
+    event_handlers: Vec<(HookEvent, Value)>,
+    depth: u32,
+    steps: u32,
+}
+
+impl ScriptEngine {
+    pub fn new() -> Self {
+        let mut globals = Table::default();
+        api::register(&mut globals);
+        Self {
+            globals: Rc::new(RefCell::new(globals)),
+            commands: Vec::new(),
+            event_handlers: Vec::new(),
+            depth: 0,
+            steps: 0,
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Value {
+        self.globals.borrow().get_field(name)
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().set_field(name.into(), value);
+    }
+
+    pub fn run(
+        ctx: &mut EditorContext,
+        client_handle: Option<ClientHandle>,
+        source_name: &str,
+        source: &str,
+    ) -> Result<EditorFlow, ScriptError> {
+        let definition = parser::parse(source_name, source).map_err(|e| {
+            let source_name = source_name.into();
+            e.locate(&source_name, 0)
+        })?;
+        let function = Value::Function(Rc::new(Function {
+            definition,
+            captures: Vec::new(),
+        }));
+        Self::call(ctx, client_handle, &function, Vec::new()).map(|(flow, _)| flow)
+    }
+
+    pub fn call(
+        ctx: &mut EditorContext,
+        client_handle: Option<ClientHandle>,
+        function: &Value,
+        args: Vec<Value>,
+    ) -> Result<(EditorFlow, Value), ScriptError> {
+        if ctx.editor.scripts.depth == 0 {
+            ctx.editor.scripts.steps = 0;
+        }
+        let mut interpreter = Interpreter::new(ctx, client_handle);
+        let value = interpreter.call(function, args)?;
+        Ok((interpreter.flow, value))
+    }
+
+    pub(crate) fn on_editor_events(ctx: &mut EditorContext) {
+        if ctx.editor.scripts.event_handlers.is_empty() {
+            return;
+        }
+
+        let mut subject = ctx.editor.string_pool.acquire();
+        let mut events = EditorEventIter::new();
+        while let Some(event) = events.next(&ctx.editor.events) {
+            subject.clear();
+            let (hook_event, client_handle, _) =
+                match HookEvent::from_editor_event(ctx, event, &mut subject) {
+                    Some(event) => event,
+                    None => continue,
+                };
+
+            let mut i = 0;
+            while i < ctx.editor.scripts.event_handlers.len() {
+                let (event, handler) = &ctx.editor.scripts.event_handlers[i];
+                i += 1;
+                if *event != hook_event {
+                    continue;
+                }
+
+                let handler = handler.clone();
+                let args = vec![Value::String(subject.as_str().into())];
+                if let Err(error) = Self::call(ctx, client_handle, &handler, args) {
+                    ctx.editor
+                        .status_bar
+                        .write(crate::editor_utils::MessageKind::Error)
+                        .fmt(format_args!("{}", error));
+                }
+            }
+        }
+        ctx.editor.string_pool.release(subject);
+    }
+}
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn execute_script_command(ctx: &mut EditorContext, io: &mut CommandIO) -> Result<(), CommandError> {
+    let function = match ctx
+        .editor
+        .scripts
+        .commands
+        .iter()
+        .find(|(name, _)| *name == io.name)
+    {
+        Some((_, function)) => function.clone(),
+        None => return Err(CommandError::NoSuchCommand),
+    };
+
+    let mut args = Table::default();
+    while let Some(arg) = io.args.try_next() {
+        args.push(arg.into());
+    }
+    let args = vec![Value::table(args), Value::Boolean(io.bang)];
+
+    let client_handle = io.client_handle().ok();
+    match ScriptEngine::call(ctx, client_handle, &function, args) {
+        Ok((flow, _)) => {
+            io.flow = flow;
+            Ok(())
+        }
+        Err(error) => Err(CommandError::ScriptError(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::{client::ClientManager, platform::Platform, plugin::PluginCollection};
+
+    fn run(source: &str) -> Result<String, String> {
+        let mut ctx = EditorContext {
+            editor: crate::editor::Editor::new(PathBuf::new()),
+            platform: Platform::default(),
+            clients: ClientManager::default(),
+            plugins: PluginCollection::default(),
+        };
+        match ScriptEngine::run(&mut ctx, None, "test", source) {
+            Ok(_) => Ok(ctx.editor.scripts.get_global("result").to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(Ok("7".into()), run("result = 1 + 2 * 3"));
+        assert_eq!(Ok("2.5".into()), run("result = 5 / 2"));
+        assert_eq!(Ok("1".into()), run("result = -5 % 3"));
+        assert_eq!(Ok("a1b".into()), run("result = 'a' .. 1 .. \"b\""));
+        assert_eq!(Ok("true".into()), run("result = 1 < 2 and 'a' < 'b'"));
+        assert_eq!(Ok("x".into()), run("result = nil or false or 'x'"));
+        assert_eq!(Ok("false".into()), run("result = not 0"));
+        assert_eq!(Ok("3".into()), run("result = #'abc'"));
+        assert_eq!(Ok("true".into()), run("result = {} ~= {}"));
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(
+            Ok("55".into()),
+            run("result = 0 for i = 1, 10 do result = result + i end")
+        );
+        assert_eq!(
+            Ok("4".into()),
+            run("local i = 0 while true do i = i + 1 if i > 3 then break end end result = i")
+        );
+        assert_eq!(
+            Ok("b".into()),
+            run("local x = 2 if x == 1 then result = 'a' elseif x == 2 then result = 'b' else result = 'c' end")
+        );
+        assert_eq!(
+            Ok("1a2b3c".into()),
+            run("result = '' local t = {'a', 'b', 'c'} for i, v in t do result = result .. i .. v end")
+        );
+        assert_eq!(
+            Ok("nil".into()),
+            run("do local hidden = 1 end result = hidden")
+        );
+    }
+
+    #[test]
+    fn functions_and_tables() {
+        assert_eq!(
+            Ok("120".into()),
+            run("local function fact(n) if n <= 1 then return 1 end return n * fact(n - 1) end result = fact(5)")
+        );
+        assert_eq!(
+            Ok("11".into()),
+            run("local base = 10 local function add(x) return base + x end result = add(1)")
+        );
+        assert_eq!(
+            Ok("3".into()),
+            run("local t = {x = 1, y = {z = 2}} t.w = t.x + t.y.z result = t['w']")
+        );
+        assert_eq!(
+            Ok("5".into()),
+            run("local obj = {n = 4} function obj:inc() self.n = self.n + 1 return self.n end result = obj:inc()")
+        );
+        assert_eq!(Ok("BC".into()), run("result = ('abc'):sub(2):upper()"));
+    }
+
+    #[test]
+    fn upvalues() {
+        assert_eq!(
+            Ok("2".into()),
+            run("local c = 0 local function inc() c = c + 1 end inc() inc() result = c")
+        );
+        assert_eq!(
+            Ok("3".into()),
+            run("local function counter() local n = 0 return function() n = n + 1 return n end end local next = counter() next() next() result = next()")
+        );
+        assert_eq!(
+            Ok("31".into()),
+            run("local function counter() local n = 0 return function() n = n + 1 return n end end local a = counter() local b = counter() a() a() result = a() .. b()")
+        );
+        assert_eq!(
+            Ok("123".into()),
+            run("local fs = {} for i = 1, 3 do fs[i] = function() return i end end result = fs[1]() .. fs[2]() .. fs[3]()")
+        );
+        assert_eq!(
+            Ok("10".into()),
+            run("local x = 1 local function get() return x end x = 10 result = get()")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err("test:2: attempt to perform arithmetic on a nil value".into()),
+            run("local a = 1\nlocal b = a + nothing")
+        );
+        assert_eq!(
+            Err("test:1: attempt to call a nil value".into()),
+            run("missing()")
+        );
+        assert_eq!(
+            Err("test:3: expected 'end'".into()),
+            run("if true then\nx = 1\n")
+        );
+        assert_eq!(
+            Err("test:1: stack overflow".into()),
+            run("local function f() f() end f()")
+        );
+        assert_eq!(
+            Err("test:1: script took too long to run".into()),
+            run("while true do end")
+        );
+    }
+}
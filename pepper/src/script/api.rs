@@ -0,0 +1,466 @@
+use std::rc::Rc;
+
+use crate::{
+    buffer::BufferHandle,
+    buffer_view::BufferViewHandle,
+    command::{CommandError, CommandManager, HookEvent},
+    editor::EditorFlow,
+    editor_utils::MessageKind,
+    mode::ModeKind,
+};
+
+use super::{execute_script_command, Interpreter, NativeFunction, ScriptError, Table, Value};
+
+pub fn register(globals: &mut Table) {
+    let r = |table: &mut Table, name: &str, function: NativeFunction| {
+        table.set_field(name.into(), Value::NativeFunction(function));
+    };
+
+    r(globals, "print", print);
+    r(globals, "tostring", |_, args| {
+        Ok(Value::String(arg(&args, 0).to_string().into()))
+    });
+    r(globals, "tonumber", |_, args| match arg(&args, 0) {
+        Value::Number(n) => Ok(Value::Number(n)),
+        Value::String(s) => match s.trim().parse() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => Ok(Value::Nil),
+        },
+        _ => Ok(Value::Nil),
+    });
+    r(globals, "type", |_, args| {
+        Ok(arg(&args, 0).type_name().into())
+    });
+    r(globals, "error", |_, args| {
+        Err(ScriptError::new(arg(&args, 0).to_string()))
+    });
+    r(globals, "pairs", |_, args| {
+        expect_table(&args, 0)?;
+        Ok(arg(&args, 0))
+    });
+    r(globals, "ipairs", |_, args| {
+        let table = expect_table(&args, 0)?;
+        let mut array = Table::default();
+        for value in table.borrow().array() {
+            array.push(value.clone());
+        }
+        Ok(Value::table(array))
+    });
+
+    let mut string = Table::default();
+    r(&mut string, "len", |_, args| {
+        Ok(Value::Number(expect_string(&args, 0)?.len() as _))
+    });
+    r(&mut string, "sub", |_, args| {
+        let s = expect_string(&args, 0)?;
+        let len = s.len() as i64;
+        let to_byte_index = |i: i64| if i < 0 { len + i + 1 } else { i };
+        let from = to_byte_index(expect_integer(&args, 1)?).max(1);
+        let to = match arg(&args, 2) {
+            Value::Nil => len,
+            _ => to_byte_index(expect_integer(&args, 2)?).min(len),
+        };
+        if from > to {
+            return Ok("".into());
+        }
+        match s.get(from as usize - 1..to as usize) {
+            Some(sub) => Ok(sub.into()),
+            None => Err(ScriptError::new(
+                "string index is not at a char boundary".into(),
+            )),
+        }
+    });
+    r(&mut string, "upper", |_, args| {
+        Ok(Value::String(
+            expect_string(&args, 0)?.to_uppercase().into(),
+        ))
+    });
+    r(&mut string, "lower", |_, args| {
+        Ok(Value::String(
+            expect_string(&args, 0)?.to_lowercase().into(),
+        ))
+    });
+    r(&mut string, "rep", |_, args| {
+        let s = expect_string(&args, 0)?;
+        let count = expect_integer(&args, 1)?.max(0);
+        Ok(Value::String(s.repeat(count as _).into()))
+    });
+    r(&mut string, "find", |_, args| {
+        let s = expect_string(&args, 0)?;
+        let pattern = expect_string(&args, 1)?;
+        let init = match arg(&args, 2) {
+            Value::Nil => 1,
+            _ => expect_integer(&args, 2)?.max(1),
+        };
+        let found = s
+            .get(init as usize - 1..)
+            .and_then(|rest| rest.find(&pattern[..]));
+        match found {
+            Some(i) => Ok(Value::Number((i + init as usize) as _)),
+            None => Ok(Value::Nil),
+        }
+    });
+    r(&mut string, "split", |_, args| {
+        let s = expect_string(&args, 0)?;
+        let separator = expect_string(&args, 1)?;
+        let mut parts = Table::default();
+        if separator.is_empty() {
+            parts.push(Value::String(s));
+        } else {
+            for part in s.split(&separator[..]) {
+                parts.push(part.into());
+            }
+        }
+        Ok(Value::table(parts))
+    });
+    r(&mut string, "trim", |_, args| {
+        Ok(expect_string(&args, 0)?.trim().into())
+    });
+    globals.set_field("string".into(), Value::table(string));
+
+    let mut table = Table::default();
+    r(&mut table, "insert", |_, args| {
+        let table = expect_table(&args, 0)?;
+        table.borrow_mut().push(arg(&args, 1));
+        Ok(Value::Nil)
+    });
+    r(&mut table, "remove", |_, args| {
+        let table = expect_table(&args, 0)?;
+        let mut table = table.borrow_mut();
+        Ok(table.array.pop().unwrap_or(Value::Nil))
+    });
+    r(&mut table, "concat", |_, args| {
+        let table = expect_table(&args, 0)?;
+        let separator = match arg(&args, 1) {
+            Value::Nil => "".into(),
+            _ => expect_string(&args, 1)?,
+        };
+        let mut result = String::new();
+        for (i, value) in table.borrow().array().iter().enumerate() {
+            if i > 0 {
+                result.push_str(&separator);
+            }
+            use std::fmt::Write;
+            let _ = write!(result, "{}", value);
+        }
+        Ok(Value::String(result.into()))
+    });
+    globals.set_field("table".into(), Value::table(table));
+
+    let mut editor = Table::default();
+    r(&mut editor, "command", command);
+    r(&mut editor, "register_command", register_command);
+    r(&mut editor, "map", map);
+    r(&mut editor, "on", on);
+    r(&mut editor, "mode", |interpreter, _| {
+        Ok(interpreter.ctx.editor.mode.kind().name().into())
+    });
+    r(
+        &mut editor,
+        "current_buffer",
+        |interpreter, _| match current_buffer_view_handle(interpreter) {
+            Ok(handle) => {
+                let buffer_view = interpreter.ctx.editor.buffer_views.get(handle);
+                Ok(Value::Number(buffer_view.buffer_handle.0 as _))
+            }
+            Err(_) => Ok(Value::Nil),
+        },
+    );
+    r(&mut editor, "buffers", |interpreter, _| {
+        let mut buffers = Table::default();
+        for buffer in interpreter.ctx.editor.buffers.iter() {
+            let mut entry = Table::default();
+            entry.set_field("handle".into(), Value::Number(buffer.handle().0 as _));
+            let path = buffer.path.to_str().unwrap_or("");
+            entry.set_field("path".into(), path.into());
+            buffers.push(Value::table(entry));
+        }
+        Ok(Value::table(buffers))
+    });
+    r(&mut editor, "buffer_path", |interpreter, args| {
+        let handle = buffer_handle_arg(interpreter, &args, 0)?;
+        let buffer = interpreter.ctx.editor.buffers.get(handle);
+        Ok(buffer.path.to_str().unwrap_or("").into())
+    });
+    r(&mut editor, "buffer_text", |interpreter, args| {
+        let handle = buffer_handle_arg(interpreter, &args, 0)?;
+        let content = interpreter.ctx.editor.buffers.get(handle).content();
+        let mut text = String::new();
+        for (i, line) in content.lines().iter().enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(line.as_str());
+        }
+        Ok(Value::String(text.into()))
+    });
+    r(&mut editor, "line_count", |interpreter, args| {
+        let handle = buffer_handle_arg(interpreter, &args, 0)?;
+        let content = interpreter.ctx.editor.buffers.get(handle).content();
+        Ok(Value::Number(content.lines().len() as _))
+    });
+    r(&mut editor, "buffer_line", |interpreter, args| {
+        let line = expect_integer(&args, 0)?;
+        let handle = buffer_handle_arg(interpreter, &args, 1)?;
+        let lines = interpreter.ctx.editor.buffers.get(handle).content().lines();
+        if line < 1 || line as usize > lines.len() {
+            return Ok(Value::Nil);
+        }
+        Ok(lines[line as usize - 1].as_str().into())
+    });
+    r(&mut editor, "cursors", |interpreter, _| {
+        let handle = current_buffer_view_handle(interpreter)?;
+        let buffer_view = interpreter.ctx.editor.buffer_views.get(handle);
+        let mut cursors = Table::default();
+        for cursor in &buffer_view.cursors[..] {
+            let mut entry = Table::default();
+            let mut set = |name: &str, value: u32| {
+                entry.set_field(name.into(), Value::Number((value + 1) as _));
+            };
+            set("anchor_line", cursor.anchor.line_index as _);
+            set("anchor_column", cursor.anchor.column_byte_index as _);
+            set("line", cursor.position.line_index as _);
+            set("column", cursor.position.column_byte_index as _);
+            cursors.push(Value::table(entry));
+        }
+        Ok(Value::table(cursors))
+    });
+    globals.set_field("editor".into(), Value::table(editor));
+}
+
+fn arg(args: &[Value], index: usize) -> Value {
+    args.get(index).cloned().unwrap_or(Value::Nil)
+}
+
+fn bad_argument(index: usize, expected: &str, value: &Value) -> ScriptError {
+    ScriptError::new(format!(
+        "bad argument #{} (expected {}, got {})",
+        index + 1,
+        expected,
+        value.type_name()
+    ))
+}
+
+fn expect_string(args: &[Value], index: usize) -> Result<Rc<str>, ScriptError> {
+    match arg(args, index) {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(Value::Number(n).to_string().into()),
+        value => Err(bad_argument(index, "string", &value)),
+    }
+}
+
+fn expect_integer(args: &[Value], index: usize) -> Result<i64, ScriptError> {
+    match arg(args, index) {
+        Value::Number(n) if n.fract() == 0.0 => Ok(n as _),
+        value => Err(bad_argument(index, "integer", &value)),
+    }
+}
+
+fn expect_table(
+    args: &[Value],
+    index: usize,
+) -> Result<Rc<std::cell::RefCell<Table>>, ScriptError> {
+    match arg(args, index) {
+        Value::Table(table) => Ok(table),
+        value => Err(bad_argument(index, "table", &value)),
+    }
+}
+
+fn expect_function(args: &[Value], index: usize) -> Result<Value, ScriptError> {
+    match arg(args, index) {
+        value @ (Value::Function(_) | Value::NativeFunction(_)) => Ok(value),
+        value => Err(bad_argument(index, "function", &value)),
+    }
+}
+
+fn command_error(error: CommandError) -> ScriptError {
+    ScriptError::new(error.to_string())
+}
+
+fn current_buffer_view_handle(interpreter: &Interpreter) -> Result<BufferViewHandle, ScriptError> {
+    let client_handle = interpreter
+        .client_handle
+        .ok_or_else(|| command_error(CommandError::NoTargetClient))?;
+    interpreter
+        .ctx
+        .clients
+        .get(client_handle)
+        .buffer_view_handle()
+        .ok_or_else(|| command_error(CommandError::NoBufferOpened))
+}
+
+fn buffer_handle_arg(
+    interpreter: &Interpreter,
+    args: &[Value],
+    index: usize,
+) -> Result<BufferHandle, ScriptError> {
+    match arg(args, index) {
+        Value::Nil => {
+            let handle = current_buffer_view_handle(interpreter)?;
+            Ok(interpreter
+                .ctx
+                .editor
+                .buffer_views
+                .get(handle)
+                .buffer_handle)
+        }
+        _ => {
+            let handle = BufferHandle(expect_integer(args, index)? as _);
+            if interpreter
+                .ctx
+                .editor
+                .buffers
+                .iter()
+                .any(|b| b.handle() == handle)
+            {
+                Ok(handle)
+            } else {
+                Err(ScriptError::new("no such buffer".into()))
+            }
+        }
+    }
+}
+
+fn print(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, ScriptError> {
+    let mut write = interpreter.ctx.editor.status_bar.write(MessageKind::Info);
+    for (i, value) in args.iter().enumerate() {
+        if i > 0 {
+            write.str(" ");
+        }
+        write.fmt(format_args!("{}", value));
+    }
+    Ok(Value::Nil)
+}
+
+fn command(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, ScriptError> {
+    let source = expect_string(&args, 0)?;
+    let mut command = interpreter.ctx.editor.string_pool.acquire_with(&source);
+    let result = CommandManager::try_eval(interpreter.ctx, interpreter.client_handle, &mut command);
+    interpreter.ctx.editor.string_pool.release(command);
+    match result {
+        Ok(EditorFlow::Continue) => Ok(Value::Nil),
+        Ok(flow) => {
+            interpreter.flow = flow;
+            Ok(Value::Nil)
+        }
+        Err(error) => Err(command_error(error)),
+    }
+}
+
+fn register_command(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, ScriptError> {
+    let name = expect_string(&args, 0)?;
+    let function = expect_function(&args, 1)?;
+
+    let editor = &mut interpreter.ctx.editor;
+    if let Some((_, f)) = editor
+        .scripts
+        .commands
+        .iter_mut()
+        .find(|(n, _)| **n == *name)
+    {
+        *f = function;
+        return Ok(Value::Nil);
+    }
+    if editor.commands.find_command(&name).is_some() {
+        return Err(command_error(CommandError::CommandAlreadyExists));
+    }
+
+    let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+    editor
+        .commands
+        .register(None, name, &[], execute_script_command);
+    editor.scripts.commands.push((name, function));
+    Ok(Value::Nil)
+}
+
+fn map(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, ScriptError> {
+    let mode = expect_string(&args, 0)?;
+    let from = expect_string(&args, 1)?;
+    let to = expect_string(&args, 2)?;
+
+    let mode = match &mode[..] {
+        "normal" => ModeKind::Normal,
+        "insert" => ModeKind::Insert,
+        "command" => ModeKind::Command,
+        "readline" => ModeKind::ReadLine,
+        "picker" => ModeKind::Picker,
+        _ => return Err(ScriptError::new(format!("no such mode '{}'", mode))),
+    };
+
+    match interpreter
+        .ctx
+        .editor
+        .keymaps
+        .parse_and_map(mode, &from, &to)
+    {
+        Ok(()) => Ok(Value::Nil),
+        Err(error) => Err(command_error(CommandError::KeyMapError(error))),
+    }
+}
+
+fn on(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, ScriptError> {
+    let event = expect_string(&args, 0)?;
+    let function = expect_function(&args, 1)?;
+    let event = match HookEvent::from_name(&event) {
+        Some(event) => event,
+        None => return Err(command_error(CommandError::NoSuchHookEvent)),
+    };
+    interpreter
+        .ctx
+        .editor
+        .scripts
+        .event_handlers
+        .push((event, function));
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{
+        client::ClientManager,
+        editor::{Editor, EditorContext},
+        platform::Platform,
+        plugin::PluginCollection,
+        script::ScriptEngine,
+    };
+
+    #[test]
+    fn editor_api() {
+        let mut ctx = EditorContext {
+            editor: Editor::new(PathBuf::new()),
+            platform: Platform::default(),
+            clients: ClientManager::default(),
+            plugins: PluginCollection::default(),
+        };
+
+        let source = r#"
+            editor.register_command("greet", function(args, bang)
+                local name = args[1] or "world"
+                if bang then name = name:upper() end
+                greeting = "hello " .. name
+            end)
+            editor.command("greet pepper")
+            first = greeting
+            editor.command("greet! you")
+            mode = editor.mode()
+            parts = table.concat(("a,b,,c"):split(","), "|")
+        "#;
+        if let Err(error) = ScriptEngine::run(&mut ctx, None, "test", source) {
+            panic!("{}", error);
+        }
+
+        let global = |name| ctx.editor.scripts.get_global(name).to_string();
+        assert_eq!("hello pepper", global("first"));
+        assert_eq!("hello YOU", global("greeting"));
+        assert_eq!("normal", global("mode"));
+        assert_eq!("a|b||c", global("parts"));
+
+        let result = ScriptEngine::run(&mut ctx, None, "test", "editor.command('no-such-command')");
+        match result {
+            Ok(_) => panic!("expected error"),
+            Err(error) => assert_eq!("test:1: no such command", error.to_string()),
+        }
+    }
+}
@@ -0,0 +1,908 @@
+use std::rc::Rc;
+
+use super::ScriptError;
+
+const MAX_NESTING_DEPTH: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Name(&'a str),
+    Number(f64),
+    String(&'a str),
+    LongString(&'a str),
+    Keyword(&'static str),
+    Symbol(&'static str),
+    Eof,
+}
+
+static KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "return", "then", "true", "while",
+];
+
+static SYMBOLS: &[&str] = &[
+    "..", "==", "~=", "<=", ">=", "+", "-", "*", "/", "%", "#", "<", ">", "=", "(", ")", "{", "}",
+    "[", "]", ";", ":", ",", ".",
+];
+
+struct Tokenizer<'a> {
+    source: &'a str,
+    index: usize,
+    line: u32,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn error(&self, message: String) -> ScriptError {
+        ScriptError::at(self.line, message)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), ScriptError> {
+        loop {
+            let rest = &self.source[self.index..];
+            let trimmed = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+            let skipped = &rest[..rest.len() - trimmed.len()];
+            self.line += skipped.matches('\n').count() as u32;
+            self.index += skipped.len();
+
+            let comment = match trimmed.strip_prefix("--") {
+                Some(comment) => comment,
+                None => return Ok(()),
+            };
+            self.index += 2;
+
+            if let Some(level) = long_bracket_level(comment) {
+                let content = &comment[level + 2..];
+                let content_len = self.long_bracket_content_len(content, level)?;
+                self.index += level + 2 + content_len + level + 2;
+            } else {
+                let len = comment.find('\n').unwrap_or(comment.len());
+                self.index += len;
+            }
+        }
+    }
+
+    fn long_bracket_content_len(
+        &mut self,
+        content: &str,
+        level: usize,
+    ) -> Result<usize, ScriptError> {
+        let mut close = String::with_capacity(level + 2);
+        close.push(']');
+        for _ in 0..level {
+            close.push('=');
+        }
+        close.push(']');
+
+        match content.find(&close) {
+            Some(len) => {
+                self.line += content[..len].matches('\n').count() as u32;
+                Ok(len)
+            }
+            None => Err(self.error("unfinished long string or comment".into())),
+        }
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, ScriptError> {
+        self.skip_whitespace_and_comments()?;
+        let rest = &self.source[self.index..];
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return Ok(Token::Eof),
+        };
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            self.index += len;
+            return match KEYWORDS.iter().find(|&&k| k == name) {
+                Some(keyword) => Ok(Token::Keyword(keyword)),
+                None => Ok(Token::Name(name)),
+            };
+        }
+
+        if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+                .unwrap_or(rest.len());
+            let text = &rest[..len];
+            self.index += len;
+            let number = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                Some(hex) => i64::from_str_radix(hex, 16).ok().map(|n| n as f64),
+                None => text.parse().ok(),
+            };
+            return match number {
+                Some(number) => Ok(Token::Number(number)),
+                None => Err(self.error(format!("malformed number '{}'", text))),
+            };
+        }
+
+        if c == '"' || c == '\'' {
+            let mut escaped = false;
+            for (i, d) in rest.char_indices().skip(1) {
+                if escaped {
+                    escaped = false;
+                } else if d == '\\' {
+                    escaped = true;
+                } else if d == c {
+                    self.index += i + 1;
+                    return Ok(Token::String(&rest[1..i]));
+                } else if d == '\n' {
+                    break;
+                }
+            }
+            return Err(self.error("unfinished string".into()));
+        }
+
+        if let Some(level) = long_bracket_level(rest) {
+            let content = &rest[level + 2..];
+            let content_len = self.long_bracket_content_len(content, level)?;
+            self.index += level + 2 + content_len + level + 2;
+            let content = &content[..content_len];
+            let content = content.strip_prefix('\n').unwrap_or(content);
+            return Ok(Token::LongString(content));
+        }
+
+        for symbol in SYMBOLS {
+            if rest.starts_with(symbol) {
+                self.index += symbol.len();
+                return Ok(Token::Symbol(symbol));
+            }
+        }
+
+        Err(self.error(format!("unexpected character '{}'", c)))
+    }
+}
+
+fn long_bracket_level(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix('[')?;
+    let level = rest.len() - rest.trim_start_matches('=').len();
+    if rest[level..].starts_with('[') {
+        Some(level)
+    } else {
+        None
+    }
+}
+
+fn unescape(text: &str, line: u32) -> Result<String, ScriptError> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some('"') => result.push('"'),
+            Some('\'') => result.push('\''),
+            Some(c) => {
+                return Err(ScriptError::at(
+                    line,
+                    format!("invalid escape sequence '\\{}'", c),
+                ))
+            }
+            None => return Err(ScriptError::at(line, "unfinished string".into())),
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Length,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equals,
+    NotEquals,
+    Less,
+    LessEquals,
+    Greater,
+    GreaterEquals,
+    Concat,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+}
+impl BinaryOp {
+    fn from_token(token: Token) -> Option<Self> {
+        let op = match token {
+            Token::Keyword("or") => Self::Or,
+            Token::Keyword("and") => Self::And,
+            Token::Symbol("==") => Self::Equals,
+            Token::Symbol("~=") => Self::NotEquals,
+            Token::Symbol("<") => Self::Less,
+            Token::Symbol("<=") => Self::LessEquals,
+            Token::Symbol(">") => Self::Greater,
+            Token::Symbol(">=") => Self::GreaterEquals,
+            Token::Symbol("..") => Self::Concat,
+            Token::Symbol("+") => Self::Add,
+            Token::Symbol("-") => Self::Subtract,
+            Token::Symbol("*") => Self::Multiply,
+            Token::Symbol("/") => Self::Divide,
+            Token::Symbol("%") => Self::Modulo,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn precedence(self) -> (u8, u8) {
+        match self {
+            Self::Or => (1, 1),
+            Self::And => (2, 2),
+            Self::Equals
+            | Self::NotEquals
+            | Self::Less
+            | Self::LessEquals
+            | Self::Greater
+            | Self::GreaterEquals => (3, 3),
+            Self::Concat => (5, 4),
+            Self::Add | Self::Subtract => (6, 6),
+            Self::Multiply | Self::Divide | Self::Modulo => (7, 7),
+        }
+    }
+}
+
+const UNARY_PRECEDENCE: u8 = 8;
+
+#[derive(Debug)]
+pub enum Expression {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Variable(Rc<str>),
+    Index(Box<Expression>, Box<Expression>),
+    Call(Box<Expression>, Vec<Expression>),
+    MethodCall(Box<Expression>, Rc<str>, Vec<Expression>),
+    Function(Rc<FunctionDefinition>),
+    Table(Vec<(Option<Expression>, Expression)>),
+    Unary(UnaryOp, Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug)]
+pub struct FunctionDefinition {
+    pub source: Rc<str>,
+    pub name: Option<Rc<str>>,
+    pub params: Vec<Rc<str>>,
+    pub body: Block,
+}
+
+#[derive(Debug)]
+pub enum StatementKind {
+    Local(Rc<str>, Option<Expression>),
+    Assign(Expression, Expression),
+    Call(Expression),
+    If(Vec<(Expression, Block)>, Option<Block>),
+    While(Expression, Block),
+    NumericFor {
+        name: Rc<str>,
+        start: Expression,
+        end: Expression,
+        step: Option<Expression>,
+        body: Block,
+    },
+    GenericFor {
+        key_name: Rc<str>,
+        value_name: Option<Rc<str>>,
+        table: Expression,
+        body: Block,
+    },
+    Do(Block),
+    Return(Option<Expression>),
+    Break,
+}
+
+#[derive(Debug)]
+pub struct Statement {
+    pub line: u32,
+    pub kind: StatementKind,
+}
+
+pub type Block = Vec<Statement>;
+
+pub fn parse(source_name: &str, source: &str) -> Result<Rc<FunctionDefinition>, ScriptError> {
+    let mut parser = Parser {
+        source_name: source_name.into(),
+        tokenizer: Tokenizer {
+            source,
+            index: 0,
+            line: 1,
+        },
+        current: Token::Eof,
+        line: 1,
+        depth: 0,
+    };
+    parser.advance()?;
+    let body = parser.parse_block()?;
+    if parser.current != Token::Eof {
+        return Err(parser.unexpected_token());
+    }
+
+    Ok(Rc::new(FunctionDefinition {
+        source: parser.source_name,
+        name: None,
+        params: Vec::new(),
+        body,
+    }))
+}
+
+struct Parser<'a> {
+    source_name: Rc<str>,
+    tokenizer: Tokenizer<'a>,
+    current: Token<'a>,
+    line: u32,
+    depth: u32,
+}
+
+impl<'a> Parser<'a> {
+    fn advance(&mut self) -> Result<Token<'a>, ScriptError> {
+        let previous = self.current;
+        self.current = self.tokenizer.next()?;
+        self.line = self.tokenizer.line;
+        Ok(previous)
+    }
+
+    fn error(&self, message: String) -> ScriptError {
+        ScriptError::at(self.line, message)
+    }
+
+    fn unexpected_token(&self) -> ScriptError {
+        match self.current {
+            Token::Name(name) => self.error(format!("unexpected name '{}'", name)),
+            Token::Number(_) => self.error("unexpected number".into()),
+            Token::String(_) | Token::LongString(_) => self.error("unexpected string".into()),
+            Token::Keyword(keyword) => self.error(format!("unexpected '{}'", keyword)),
+            Token::Symbol(symbol) => self.error(format!("unexpected '{}'", symbol)),
+            Token::Eof => self.error("unexpected end of script".into()),
+        }
+    }
+
+    fn accept(&mut self, token: Token) -> Result<bool, ScriptError> {
+        if self.current == token {
+            self.advance()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ScriptError> {
+        if self.accept(token)? {
+            return Ok(());
+        }
+        match token {
+            Token::Keyword(keyword) => Err(self.error(format!("expected '{}'", keyword))),
+            Token::Symbol(symbol) => Err(self.error(format!("expected '{}'", symbol))),
+            _ => Err(self.unexpected_token()),
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<Rc<str>, ScriptError> {
+        match self.current {
+            Token::Name(name) => {
+                self.advance()?;
+                Ok(name.into())
+            }
+            _ => Err(self.error("expected name".into())),
+        }
+    }
+
+    fn enter_nesting(&mut self) -> Result<(), ScriptError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(self.error("too many nested levels".into()));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn is_block_end(&self) -> bool {
+        matches!(
+            self.current,
+            Token::Eof | Token::Keyword("end") | Token::Keyword("else") | Token::Keyword("elseif")
+        )
+    }
+
+    fn parse_block(&mut self) -> Result<Block, ScriptError> {
+        self.enter_nesting()?;
+        let mut block = Vec::new();
+        while !self.is_block_end() {
+            if self.accept(Token::Symbol(";"))? {
+                continue;
+            }
+
+            let line = self.line;
+            let kind = self.parse_statement()?;
+            let is_last = matches!(kind, StatementKind::Return(_) | StatementKind::Break);
+            block.push(Statement { line, kind });
+
+            if is_last {
+                self.accept(Token::Symbol(";"))?;
+                if !self.is_block_end() {
+                    return Err(self.unexpected_token());
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(block)
+    }
+
+    fn parse_statement(&mut self) -> Result<StatementKind, ScriptError> {
+        match self.current {
+            Token::Keyword("local") => {
+                self.advance()?;
+                if self.accept(Token::Keyword("function"))? {
+                    let name = self.expect_name()?;
+                    let function = self.parse_function_body(Some(name.clone()))?;
+                    return Ok(StatementKind::Local(
+                        name,
+                        Some(Expression::Function(function)),
+                    ));
+                }
+
+                let name = self.expect_name()?;
+                let value = if self.accept(Token::Symbol("="))? {
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                Ok(StatementKind::Local(name, value))
+            }
+            Token::Keyword("function") => {
+                self.advance()?;
+                let mut name = self.expect_name()?;
+                let mut target = Expression::Variable(name.clone());
+                let mut is_method = false;
+                loop {
+                    if self.accept(Token::Symbol("."))? {
+                        name = self.expect_name()?;
+                        let key = Expression::String(name.clone());
+                        target = Expression::Index(Box::new(target), Box::new(key));
+                    } else if self.accept(Token::Symbol(":"))? {
+                        name = self.expect_name()?;
+                        let key = Expression::String(name.clone());
+                        target = Expression::Index(Box::new(target), Box::new(key));
+                        is_method = true;
+                        break;
+                    } else {
+                        break;
+                    }
+                }
+
+                let mut function = self.parse_function_body(Some(name))?;
+                if is_method {
+                    if let Some(function) = Rc::get_mut(&mut function) {
+                        function.params.insert(0, "self".into());
+                    }
+                }
+                Ok(StatementKind::Assign(
+                    target,
+                    Expression::Function(function),
+                ))
+            }
+            Token::Keyword("if") => {
+                self.advance()?;
+                let mut branches = Vec::new();
+                let condition = self.parse_expression()?;
+                self.expect(Token::Keyword("then"))?;
+                let block = self.parse_block()?;
+                branches.push((condition, block));
+
+                let mut else_block = None;
+                loop {
+                    if self.accept(Token::Keyword("elseif"))? {
+                        let condition = self.parse_expression()?;
+                        self.expect(Token::Keyword("then"))?;
+                        let block = self.parse_block()?;
+                        branches.push((condition, block));
+                    } else if self.accept(Token::Keyword("else"))? {
+                        else_block = Some(self.parse_block()?);
+                        self.expect(Token::Keyword("end"))?;
+                        break;
+                    } else {
+                        self.expect(Token::Keyword("end"))?;
+                        break;
+                    }
+                }
+                Ok(StatementKind::If(branches, else_block))
+            }
+            Token::Keyword("while") => {
+                self.advance()?;
+                let condition = self.parse_expression()?;
+                self.expect(Token::Keyword("do"))?;
+                let body = self.parse_block()?;
+                self.expect(Token::Keyword("end"))?;
+                Ok(StatementKind::While(condition, body))
+            }
+            Token::Keyword("for") => {
+                self.advance()?;
+                let name = self.expect_name()?;
+                if self.accept(Token::Symbol("="))? {
+                    let start = self.parse_expression()?;
+                    self.expect(Token::Symbol(","))?;
+                    let end = self.parse_expression()?;
+                    let step = if self.accept(Token::Symbol(","))? {
+                        Some(self.parse_expression()?)
+                    } else {
+                        None
+                    };
+                    self.expect(Token::Keyword("do"))?;
+                    let body = self.parse_block()?;
+                    self.expect(Token::Keyword("end"))?;
+                    Ok(StatementKind::NumericFor {
+                        name,
+                        start,
+                        end,
+                        step,
+                        body,
+                    })
+                } else {
+                    let value_name = if self.accept(Token::Symbol(","))? {
+                        Some(self.expect_name()?)
+                    } else {
+                        None
+                    };
+                    self.expect(Token::Keyword("in"))?;
+                    let table = self.parse_expression()?;
+                    self.expect(Token::Keyword("do"))?;
+                    let body = self.parse_block()?;
+                    self.expect(Token::Keyword("end"))?;
+                    Ok(StatementKind::GenericFor {
+                        key_name: name,
+                        value_name,
+                        table,
+                        body,
+                    })
+                }
+            }
+            Token::Keyword("do") => {
+                self.advance()?;
+                let body = self.parse_block()?;
+                self.expect(Token::Keyword("end"))?;
+                Ok(StatementKind::Do(body))
+            }
+            Token::Keyword("return") => {
+                self.advance()?;
+                if self.is_block_end() || self.current == Token::Symbol(";") {
+                    Ok(StatementKind::Return(None))
+                } else {
+                    Ok(StatementKind::Return(Some(self.parse_expression()?)))
+                }
+            }
+            Token::Keyword("break") => {
+                self.advance()?;
+                Ok(StatementKind::Break)
+            }
+            _ => {
+                let expression = self.parse_suffixed_expression()?;
+                if self.accept(Token::Symbol("="))? {
+                    match expression {
+                        Expression::Variable(_) | Expression::Index(..) => {
+                            let value = self.parse_expression()?;
+                            Ok(StatementKind::Assign(expression, value))
+                        }
+                        _ => Err(self.error("cannot assign to this expression".into())),
+                    }
+                } else {
+                    match expression {
+                        Expression::Call(..) | Expression::MethodCall(..) => {
+                            Ok(StatementKind::Call(expression))
+                        }
+                        _ => Err(self.error("syntax error: expected statement".into())),
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_function_body(
+        &mut self,
+        name: Option<Rc<str>>,
+    ) -> Result<Rc<FunctionDefinition>, ScriptError> {
+        self.expect(Token::Symbol("("))?;
+        let mut params = Vec::new();
+        if !self.accept(Token::Symbol(")"))? {
+            loop {
+                params.push(self.expect_name()?);
+                if self.accept(Token::Symbol(")"))? {
+                    break;
+                }
+                self.expect(Token::Symbol(","))?;
+            }
+        }
+
+        let body = self.parse_block()?;
+        self.expect(Token::Keyword("end"))?;
+
+        Ok(Rc::new(FunctionDefinition {
+            source: self.source_name.clone(),
+            name,
+            params,
+            body,
+        }))
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, ScriptError> {
+        self.parse_binary_expression(0)
+    }
+
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expression, ScriptError> {
+        self.enter_nesting()?;
+        let unary_op = match self.current {
+            Token::Keyword("not") => Some(UnaryOp::Not),
+            Token::Symbol("-") => Some(UnaryOp::Negate),
+            Token::Symbol("#") => Some(UnaryOp::Length),
+            _ => None,
+        };
+
+        let mut left = match unary_op {
+            Some(op) => {
+                self.advance()?;
+                let operand = self.parse_binary_expression(UNARY_PRECEDENCE)?;
+                Expression::Unary(op, Box::new(operand))
+            }
+            None => self.parse_simple_expression()?,
+        };
+
+        while let Some(op) = BinaryOp::from_token(self.current) {
+            let (left_precedence, right_precedence) = op.precedence();
+            if left_precedence <= min_precedence {
+                break;
+            }
+            self.advance()?;
+            let right = self.parse_binary_expression(right_precedence)?;
+            left = Expression::Binary(op, Box::new(left), Box::new(right));
+        }
+
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn parse_simple_expression(&mut self) -> Result<Expression, ScriptError> {
+        let expression = match self.current {
+            Token::Keyword("nil") => Expression::Nil,
+            Token::Keyword("true") => Expression::Boolean(true),
+            Token::Keyword("false") => Expression::Boolean(false),
+            Token::Number(number) => Expression::Number(number),
+            Token::String(text) => Expression::String(unescape(text, self.line)?.into()),
+            Token::LongString(text) => Expression::String(text.into()),
+            Token::Keyword("function") => {
+                self.advance()?;
+                return Ok(Expression::Function(self.parse_function_body(None)?));
+            }
+            Token::Symbol("{") => return self.parse_table(),
+            _ => return self.parse_suffixed_expression(),
+        };
+        self.advance()?;
+        Ok(expression)
+    }
+
+    fn parse_table(&mut self) -> Result<Expression, ScriptError> {
+        self.expect(Token::Symbol("{"))?;
+        let mut entries = Vec::new();
+        while !self.accept(Token::Symbol("}"))? {
+            let entry = if self.accept(Token::Symbol("["))? {
+                let key = self.parse_expression()?;
+                self.expect(Token::Symbol("]"))?;
+                self.expect(Token::Symbol("="))?;
+                (Some(key), self.parse_expression()?)
+            } else {
+                let value = self.parse_expression()?;
+                match value {
+                    Expression::Variable(name) if self.accept(Token::Symbol("="))? => {
+                        let key = Expression::String(name);
+                        (Some(key), self.parse_expression()?)
+                    }
+                    value => (None, value),
+                }
+            };
+            entries.push(entry);
+
+            if !self.accept(Token::Symbol(","))? && !self.accept(Token::Symbol(";"))? {
+                self.expect(Token::Symbol("}"))?;
+                break;
+            }
+        }
+        Ok(Expression::Table(entries))
+    }
+
+    fn parse_call_args(&mut self) -> Result<Vec<Expression>, ScriptError> {
+        match self.current {
+            Token::String(text) => {
+                self.advance()?;
+                Ok(vec![Expression::String(unescape(text, self.line)?.into())])
+            }
+            Token::LongString(text) => {
+                self.advance()?;
+                Ok(vec![Expression::String(text.into())])
+            }
+            Token::Symbol("{") => Ok(vec![self.parse_table()?]),
+            _ => {
+                self.expect(Token::Symbol("("))?;
+                let mut args = Vec::new();
+                if !self.accept(Token::Symbol(")"))? {
+                    loop {
+                        args.push(self.parse_expression()?);
+                        if self.accept(Token::Symbol(")"))? {
+                            break;
+                        }
+                        self.expect(Token::Symbol(","))?;
+                    }
+                }
+                Ok(args)
+            }
+        }
+    }
+
+    fn parse_suffixed_expression(&mut self) -> Result<Expression, ScriptError> {
+        let mut expression = match self.current {
+            Token::Name(name) => {
+                self.advance()?;
+                Expression::Variable(name.into())
+            }
+            Token::Symbol("(") => {
+                self.advance()?;
+                let expression = self.parse_expression()?;
+                self.expect(Token::Symbol(")"))?;
+                expression
+            }
+            _ => return Err(self.unexpected_token()),
+        };
+
+        loop {
+            match self.current {
+                Token::Symbol(".") => {
+                    self.advance()?;
+                    let key = Expression::String(self.expect_name()?);
+                    expression = Expression::Index(Box::new(expression), Box::new(key));
+                }
+                Token::Symbol("[") => {
+                    self.advance()?;
+                    let key = self.parse_expression()?;
+                    self.expect(Token::Symbol("]"))?;
+                    expression = Expression::Index(Box::new(expression), Box::new(key));
+                }
+                Token::Symbol(":") => {
+                    self.advance()?;
+                    let name = self.expect_name()?;
+                    let args = self.parse_call_args()?;
+                    expression = Expression::MethodCall(Box::new(expression), name, args);
+                }
+                Token::Symbol("(")
+                | Token::Symbol("{")
+                | Token::String(_)
+                | Token::LongString(_) => {
+                    let args = self.parse_call_args()?;
+                    expression = Expression::Call(Box::new(expression), args);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(source: &str) -> Vec<Token<'_>> {
+        let mut tokenizer = Tokenizer {
+            source,
+            index: 0,
+            line: 1,
+        };
+        let mut tokens = Vec::new();
+        loop {
+            match tokenizer.next() {
+                Ok(Token::Eof) => break,
+                Ok(token) => tokens.push(token),
+                Err(error) => panic!("{}", error),
+            }
+        }
+        tokens
+    }
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(
+            vec![
+                Token::Keyword("local"),
+                Token::Name("a_1"),
+                Token::Symbol("="),
+                Token::Number(1.5),
+                Token::Symbol(".."),
+                Token::String("x\\\"y"),
+                Token::Symbol("~="),
+                Token::LongString("raw ]] text"),
+            ],
+            tokens(
+                "local a_1 = 1.5 -- comment\n..\"x\\\"y\" --[[ block\n]] ~= [=[\nraw ]] text]=]"
+            )
+        );
+        assert_eq!(vec![Token::Number(255.0)], tokens("0xff"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        fn error_line(source: &str) -> u32 {
+            match parse("test", source) {
+                Ok(_) => panic!("expected error"),
+                Err(error) => error.line,
+            }
+        }
+
+        assert!(parse("test", "local a = 1 + 2 * 3").is_ok());
+        assert!(parse("test", "f{1, 2; x = 3, [4] = 5} f'text' f[[raw]]").is_ok());
+        assert_eq!(1, error_line("local = 1"));
+        assert_eq!(2, error_line("if true then\nx + 1\nend"));
+        assert_eq!(3, error_line("while true do\n\nreturn 1 x()\nend"));
+        assert_eq!(1, error_line("x = 'abc\n'"));
+    }
+
+    #[test]
+    fn nesting_depth() {
+        fn nested(depth: usize, open: &str, close: &str) -> String {
+            let mut source = String::from("x = ");
+            for _ in 0..depth {
+                source.push_str(open);
+            }
+            source.push('1');
+            for _ in 0..depth {
+                source.push_str(close);
+            }
+            source
+        }
+        fn error_message(source: &str) -> String {
+            match parse("test", source) {
+                Ok(_) => panic!("expected error"),
+                Err(error) => error.message,
+            }
+        }
+
+        assert!(parse("test", &nested(50, "(", ")")).is_ok());
+        assert!(parse("test", &nested(50, "{", "}")).is_ok());
+        assert_eq!(
+            "too many nested levels",
+            error_message(&nested(20000, "(", ")"))
+        );
+        assert_eq!(
+            "too many nested levels",
+            error_message(&nested(20000, "{", "}"))
+        );
+        assert_eq!(
+            "too many nested levels",
+            error_message(&nested(20000, "not ", ""))
+        );
+
+        let mut source = String::new();
+        for _ in 0..20000 {
+            source.push_str("do ");
+        }
+        assert_eq!("too many nested levels", error_message(&source));
+
+        let mut source = String::new();
+        for _ in 0..50 {
+            source.push_str("do ");
+        }
+        for _ in 0..50 {
+            source.push_str("end ");
+        }
+        assert!(parse("test", &source).is_ok());
+    }
+}
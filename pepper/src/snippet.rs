@@ -0,0 +1,416 @@
+use std::ops::Range;
+
+use crate::{
+    buffer::BufferHandle,
+    buffer_position::{BufferPosition, BufferRange},
+    glob::{Glob, InvalidGlobError},
+};
+
+pub struct SnippetTabstop {
+    pub index: u32,
+    pub range: Range<usize>,
+}
+
+pub fn parse_snippet(
+    snippet: &str,
+    indentation: &str,
+    text: &mut String,
+    tabstops: &mut Vec<SnippetTabstop>,
+) {
+    text.clear();
+    tabstops.clear();
+
+    let mut rest = snippet;
+    parse_text(&mut rest, false, indentation, text, tabstops);
+
+    if !tabstops.iter().any(|t| t.index == 0) {
+        tabstops.push(SnippetTabstop {
+            index: 0,
+            range: text.len()..text.len(),
+        });
+    }
+}
+
+fn parse_text(
+    rest: &mut &str,
+    nested: bool,
+    indentation: &str,
+    text: &mut String,
+    tabstops: &mut Vec<SnippetTabstop>,
+) {
+    loop {
+        let mut chars = rest.chars();
+        match chars.next() {
+            None => return,
+            Some('\\') => match chars.next() {
+                Some(c @ ('$' | '}' | '\\')) => {
+                    text.push(c);
+                    *rest = chars.as_str();
+                }
+                _ => {
+                    text.push('\\');
+                    *rest = &rest[1..];
+                }
+            },
+            Some('}') if nested => {
+                *rest = chars.as_str();
+                return;
+            }
+            Some('$') => {
+                *rest = chars.as_str();
+                parse_dollar(rest, indentation, text, tabstops);
+            }
+            Some('\n') => {
+                text.push('\n');
+                text.push_str(indentation);
+                *rest = chars.as_str();
+            }
+            Some(c) => {
+                text.push(c);
+                *rest = chars.as_str();
+            }
+        }
+    }
+}
+
+fn parse_dollar(
+    rest: &mut &str,
+    indentation: &str,
+    text: &mut String,
+    tabstops: &mut Vec<SnippetTabstop>,
+) {
+    fn digits_len(s: &str) -> usize {
+        s.bytes().take_while(u8::is_ascii_digit).count()
+    }
+
+    fn name_len(s: &str) -> usize {
+        s.bytes()
+            .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+            .count()
+    }
+
+    let start = text.len();
+
+    let len = digits_len(rest);
+    if len > 0 {
+        tabstops.push(SnippetTabstop {
+            index: rest[..len].parse().unwrap_or(0),
+            range: start..start,
+        });
+        *rest = &rest[len..];
+        return;
+    }
+
+    let len = name_len(rest);
+    if len > 0 {
+        *rest = &rest[len..];
+        return;
+    }
+
+    let inner = match rest.strip_prefix('{') {
+        Some(inner) => inner,
+        None => {
+            text.push('$');
+            return;
+        }
+    };
+
+    let len = digits_len(inner);
+    if len > 0 {
+        let index = inner[..len].parse().unwrap_or(0);
+        let after = &inner[len..];
+        match after.chars().next() {
+            Some('}') => {
+                tabstops.push(SnippetTabstop {
+                    index,
+                    range: start..start,
+                });
+                *rest = &after[1..];
+            }
+            Some(':') => {
+                let tabstop_index = tabstops.len();
+                tabstops.push(SnippetTabstop {
+                    index,
+                    range: start..start,
+                });
+                *rest = &after[1..];
+                parse_text(rest, true, indentation, text, tabstops);
+                tabstops[tabstop_index].range.end = text.len();
+            }
+            Some('|') => {
+                let choices = &after[1..];
+                let choices_len = choices.find("|}").unwrap_or(choices.len());
+                let choice = match choices[..choices_len].find(',') {
+                    Some(i) => &choices[..i],
+                    None => &choices[..choices_len],
+                };
+                text.push_str(choice);
+                tabstops.push(SnippetTabstop {
+                    index,
+                    range: start..text.len(),
+                });
+                *rest = choices.get(choices_len + 2..).unwrap_or("");
+            }
+            _ => text.push('$'),
+        }
+        return;
+    }
+
+    let len = name_len(inner);
+    if len > 0 {
+        let after = &inner[len..];
+        match after.chars().next() {
+            Some('}') => *rest = &after[1..],
+            Some(':') => {
+                *rest = &after[1..];
+                parse_text(rest, true, indentation, text, tabstops);
+            }
+            _ => text.push('$'),
+        }
+        return;
+    }
+
+    text.push('$');
+}
+
+pub fn snippet_text_position(base: BufferPosition, text: &str, offset: usize) -> BufferPosition {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(i) => BufferPosition::line_col(
+            base.line_index + before.matches('\n').count() as u32,
+            (offset - i - 1) as _,
+        ),
+        None => BufferPosition::line_col(base.line_index, base.column_byte_index + offset as u32),
+    }
+}
+
+struct SnippetDefinition {
+    glob: Glob,
+    glob_text: String,
+    trigger: String,
+    body: String,
+}
+
+struct CompletionSnippet {
+    label: String,
+    replaced_prefix: String,
+    body: String,
+}
+
+#[derive(Default)]
+pub struct SnippetCollection {
+    definitions: Vec<SnippetDefinition>,
+    completion_snippets: Vec<CompletionSnippet>,
+}
+
+impl SnippetCollection {
+    pub fn add(&mut self, glob: &str, trigger: &str, body: &str) -> Result<(), InvalidGlobError> {
+        let index = match self
+            .definitions
+            .iter()
+            .position(|d| d.glob_text == glob && d.trigger == trigger)
+        {
+            Some(index) => index,
+            None => {
+                let mut compiled_glob = Glob::default();
+                compiled_glob.compile(glob)?;
+                self.definitions.push(SnippetDefinition {
+                    glob: compiled_glob,
+                    glob_text: glob.into(),
+                    trigger: trigger.into(),
+                    body: String::new(),
+                });
+                self.definitions.len() - 1
+            }
+        };
+
+        let definition = &mut self.definitions[index];
+        definition.body.clear();
+        definition.body.push_str(body);
+        Ok(())
+    }
+
+    pub fn find(&self, path: &str, trigger: &str) -> Option<&str> {
+        self.definitions
+            .iter()
+            .rev()
+            .find(|d| d.trigger == trigger && d.glob.matches(path))
+            .map(|d| &d.body[..])
+    }
+
+    pub fn triggers<'a>(&'a self, path: &'a str) -> impl 'a + Iterator<Item = (&'a str, &'a str)> {
+        self.definitions
+            .iter()
+            .filter(move |d| d.glob.matches(path))
+            .map(|d| (&d.trigger[..], &d.body[..]))
+    }
+
+    pub fn clear_completion_snippets(&mut self) {
+        self.completion_snippets.clear();
+    }
+
+    // `replaced_prefix` is the text right before the completed word that is also replaced
+    pub fn add_completion_snippet(&mut self, label: &str, replaced_prefix: &str, body: &str) {
+        self.completion_snippets.push(CompletionSnippet {
+            label: label.into(),
+            replaced_prefix: replaced_prefix.into(),
+            body: body.into(),
+        });
+    }
+
+    pub fn find_completion_snippet(&self, label: &str) -> Option<(&str, &str)> {
+        self.completion_snippets
+            .iter()
+            .find(|s| s.label == label)
+            .map(|s| (&s.replaced_prefix[..], &s.body[..]))
+    }
+}
+
+#[derive(Default)]
+pub struct SnippetSession {
+    buffer_handle: Option<BufferHandle>,
+    tabstops: Vec<(u32, BufferRange)>,
+    current_order: u32,
+    pub placeholder_selected: bool,
+}
+
+impl SnippetSession {
+    pub fn begin(&mut self, buffer_handle: BufferHandle) {
+        self.buffer_handle = Some(buffer_handle);
+        self.tabstops.clear();
+        self.current_order = 0;
+        self.placeholder_selected = false;
+    }
+
+    pub fn add_tabstop(&mut self, index: u32, range: BufferRange) {
+        self.tabstops.push((index, range));
+    }
+
+    pub fn cancel(&mut self) {
+        self.buffer_handle = None;
+        self.tabstops.clear();
+        self.placeholder_selected = false;
+    }
+
+    pub fn is_active_in(&self, buffer_handle: BufferHandle) -> bool {
+        self.buffer_handle == Some(buffer_handle)
+    }
+
+    pub fn next_tabstop_ranges(&mut self, ranges: &mut Vec<BufferRange>) {
+        fn order(index: u32) -> u32 {
+            match index {
+                0 => u32::MAX,
+                _ => index,
+            }
+        }
+
+        ranges.clear();
+        let current_order = self.current_order;
+        let next_index = self
+            .tabstops
+            .iter()
+            .map(|&(index, _)| index)
+            .filter(|&index| order(index) > current_order)
+            .min_by_key(|&index| order(index));
+
+        let next_index = match next_index {
+            Some(index) => index,
+            None => {
+                self.cancel();
+                return;
+            }
+        };
+
+        for &(index, range) in &self.tabstops {
+            if index == next_index {
+                ranges.push(range);
+            }
+        }
+
+        self.current_order = order(next_index);
+        if next_index == 0 {
+            self.cancel();
+        }
+    }
+
+    pub fn on_buffer_insert_text(&mut self, handle: BufferHandle, range: BufferRange) {
+        if self.buffer_handle != Some(handle) {
+            return;
+        }
+
+        for (_, tabstop) in &mut self.tabstops {
+            if tabstop.from != range.from {
+                tabstop.from = tabstop.from.insert(range);
+            }
+            tabstop.to = tabstop.to.insert(range);
+        }
+    }
+
+    pub fn on_buffer_delete_text(&mut self, handle: BufferHandle, range: BufferRange) {
+        if self.buffer_handle != Some(handle) {
+            return;
+        }
+
+        for (_, tabstop) in &mut self.tabstops {
+            tabstop.from = tabstop.from.delete(range);
+            tabstop.to = tabstop.to.delete(range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(snippet: &str) -> (String, Vec<(u32, Range<usize>)>) {
+        let mut text = String::new();
+        let mut tabstops = Vec::new();
+        parse_snippet(snippet, "  ", &mut text, &mut tabstops);
+        let tabstops = tabstops.into_iter().map(|t| (t.index, t.range)).collect();
+        (text, tabstops)
+    }
+
+    #[test]
+    fn snippet_parsing() {
+        assert_eq!((String::new(), vec![(0, 0..0)]), parse(""));
+        assert_eq!(("abc".into(), vec![(0, 3..3)]), parse("abc"));
+        assert_eq!(
+            ("fn () {\n  }".into(), vec![(1, 3..3), (0, 10..10)]),
+            parse("fn $1() {\n$0}")
+        );
+        assert_eq!(
+            (
+                "let name = name;".into(),
+                vec![(1, 4..8), (1, 11..15), (0, 16..16)]
+            ),
+            parse("let ${1:name} = ${1:name};")
+        );
+        assert_eq!(
+            ("a b c".into(), vec![(1, 0..5), (2, 2..3), (0, 5..5)]),
+            parse("${1:a ${2:b} c}")
+        );
+        assert_eq!(("x".into(), vec![(1, 0..1), (0, 1..1)]), parse("${1|x,y|}"));
+        assert_eq!(
+            ("$} default".into(), vec![(0, 10..10)]),
+            parse("\\$\\} ${VAR:default}$NAME")
+        );
+        assert_eq!(("$ ${".into(), vec![(0, 4..4)]), parse("$ ${"));
+    }
+
+    #[test]
+    fn snippet_collection() {
+        let mut snippets = SnippetCollection::default();
+        assert!(snippets.add("**/*.rs", "fn", "fn $1() {}").is_ok());
+        assert!(snippets.add("**/*.rs", "fn", "fn $1() {\n}").is_ok());
+        assert!(snippets.add("**/*.lua", "fn", "function $1() end").is_ok());
+        assert!(snippets.add("[", "fn", "").is_err());
+
+        assert_eq!(Some("fn $1() {\n}"), snippets.find("src/main.rs", "fn"));
+        assert_eq!(Some("function $1() end"), snippets.find("main.lua", "fn"));
+        assert_eq!(None, snippets.find("main.c", "fn"));
+        assert_eq!(
+            vec![("fn", "fn $1() {\n}")],
+            snippets.triggers("main.rs").collect::<Vec<_>>()
+        );
+    }
+}
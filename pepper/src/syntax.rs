@@ -23,6 +23,10 @@ pub enum TokenKind {
     Comment,
     Text,
     Whitespace,
+    // only produced by semantic tokens
+    Function,
+    Variable,
+    Property,
 }
 impl FromStr for TokenKind {
     type Err = ();
@@ -78,6 +82,11 @@ pub struct Syntax {
     glob_hash: u64,
     glob: Glob,
     rules: [Pattern; 7],
+    auto_pairs: Vec<(char, char)>,
+    indent_increase: Pattern,
+    indent_decrease: Pattern,
+    line_comment_token: String,
+    block_comment_tokens: (String, String),
 }
 
 impl Syntax {
@@ -96,6 +105,11 @@ impl Syntax {
                 Pattern::new(),
                 text_pattern,
             ],
+            auto_pairs: Vec::new(),
+            indent_increase: Pattern::new(),
+            indent_decrease: Pattern::new(),
+            line_comment_token: String::new(),
+            block_comment_tokens: (String::new(), String::new()),
         }
     }
 
@@ -103,6 +117,12 @@ impl Syntax {
         for r in &mut self.rules {
             r.clear();
         }
+        self.auto_pairs.clear();
+        self.indent_increase.clear();
+        self.indent_decrease.clear();
+        self.line_comment_token.clear();
+        self.block_comment_tokens.0.clear();
+        self.block_comment_tokens.1.clear();
     }
 
     fn set_glob(&mut self, glob: &str, glob_hash: u64) -> Result<(), InvalidGlobError> {
@@ -114,6 +134,79 @@ impl Syntax {
         self.rules[kind as usize].compile(pattern)
     }
 
+    pub fn set_auto_pairs(&mut self, pairs: &str) -> bool {
+        self.auto_pairs.clear();
+        let mut chars = pairs.chars();
+        while let Some(open) = chars.next() {
+            match chars.next() {
+                Some(close) => self.auto_pairs.push((open, close)),
+                None => {
+                    self.auto_pairs.clear();
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn auto_pairs(&self) -> &[(char, char)] {
+        &self.auto_pairs
+    }
+
+    pub fn set_indent_increase_rule(&mut self, pattern: &str) -> Result<(), PatternError> {
+        self.indent_increase.compile(pattern)
+    }
+
+    pub fn set_indent_decrease_rule(&mut self, pattern: &str) -> Result<(), PatternError> {
+        self.indent_decrease.compile(pattern)
+    }
+
+    pub fn has_indent_rules(&self) -> bool {
+        !self.indent_increase.is_empty() || !self.indent_decrease.is_empty()
+    }
+
+    pub fn increases_indent(&self, line: &str) -> bool {
+        if self.indent_increase.is_empty() {
+            return false;
+        }
+
+        let line = line.trim();
+        line.char_indices()
+            .any(|(i, _)| self.indent_increase.matches(line, i) == MatchResult::Ok(line.len()))
+    }
+
+    pub fn decreases_indent(&self, line: &str) -> bool {
+        if self.indent_decrease.is_empty() {
+            return false;
+        }
+
+        let line = line.trim_start();
+        matches!(self.indent_decrease.matches(line, 0), MatchResult::Ok(len) if len > 0)
+    }
+
+    pub fn is_indent_decrease(&self, text: &str) -> bool {
+        !text.is_empty()
+            && !self.indent_decrease.is_empty()
+            && self.indent_decrease.matches(text, 0) == MatchResult::Ok(text.len())
+    }
+
+    pub fn set_comment_tokens(&mut self, line: &str, block_open: &str, block_close: &str) {
+        self.line_comment_token.clear();
+        self.line_comment_token.push_str(line);
+        self.block_comment_tokens.0.clear();
+        self.block_comment_tokens.0.push_str(block_open);
+        self.block_comment_tokens.1.clear();
+        self.block_comment_tokens.1.push_str(block_close);
+    }
+
+    pub fn line_comment_token(&self) -> &str {
+        &self.line_comment_token
+    }
+
+    pub fn block_comment_tokens(&self) -> (&str, &str) {
+        (&self.block_comment_tokens.0, &self.block_comment_tokens.1)
+    }
+
     fn parse_line(
         &self,
         line: &str,
@@ -0,0 +1,713 @@
+use std::path::Path;
+
+use crate::{
+    application::{ApplicationConfig, OnPanicConfig, ServerApplication},
+    buffer::{BufferHandle, BufferProperties},
+    buffer_position::{BufferPosition, BufferRange},
+    client::ClientHandle,
+    command::{CommandError, CommandManager},
+    cursor::Cursor,
+    editor::{EditorContext, EditorFlow},
+    editor_utils::MessageKind,
+    events::{ClientEvent, KeyParser, ServerEvent, TargetClient},
+    mode::ModeKind,
+    platform::{
+        drop_event, Key, PlatformEvent, PlatformRequest, PooledBuf, SyncProcess,
+        SyncProcessCollection,
+    },
+    plugin::{PluginDefinition, PluginHandle},
+    serialization::{DeserializeError, Serialize},
+    Args,
+};
+
+pub const TEST_VIEWPORT_SIZE: (u16, u16) = (80, 24);
+const MAX_UPDATE_ITERATIONS: usize = 1000;
+
+pub type ProcessStandIn = fn(args: &[String], stdin: &[u8]) -> Vec<u8>;
+
+struct TestProcess {
+    stand_in: ProcessStandIn,
+    args: Vec<String>,
+}
+impl SyncProcess for TestProcess {
+    fn kill(self) {}
+
+    fn run(self, stdin: Vec<u8>) -> Vec<u8> {
+        (self.stand_in)(&self.args, &stdin)
+    }
+}
+
+pub struct TestEditor {
+    application: ServerApplication,
+    client_handle: ClientHandle,
+    process_stand_ins: Vec<(String, ProcessStandIn)>,
+    processes: SyncProcessCollection<TestProcess>,
+    display: Vec<u8>,
+    stdout: Vec<u8>,
+    eval_result: Option<Result<Vec<u8>, Vec<u8>>>,
+    client_closed: bool,
+    quit: bool,
+}
+impl TestEditor {
+    pub fn new(plugin_definitions: Vec<PluginDefinition>) -> Self {
+        let config = ApplicationConfig {
+            args: Args::default(),
+            plugin_definitions,
+            static_configs: vec![
+                crate::DEFAULT_BINDINGS_CONFIG,
+                crate::DEFAULT_ALIASES_CONFIG,
+                crate::DEFAULT_SYNTAXES_CONFIG,
+            ],
+            on_panic_config: OnPanicConfig::default(),
+        };
+        let application = match ServerApplication::new(config) {
+            Some(application) => application,
+            None => panic!("could not load default configs"),
+        };
+
+        let mut editor = Self {
+            application,
+            client_handle: ClientHandle(0),
+            process_stand_ins: Vec::new(),
+            processes: SyncProcessCollection::default(),
+            display: Vec::new(),
+            stdout: Vec::new(),
+            eval_result: None,
+            client_closed: false,
+            quit: false,
+        };
+
+        let handle = editor.client_handle;
+        editor.update(vec![PlatformEvent::ConnectionOpen { handle }]);
+        let (width, height) = TEST_VIEWPORT_SIZE;
+        editor.client_event(ClientEvent::Resize(width, height));
+        editor.client_event(ClientEvent::Key(TargetClient::Sender, Key::default()));
+        editor
+    }
+
+    pub fn ctx(&self) -> &EditorContext {
+        &self.application.ctx
+    }
+
+    pub fn ctx_mut(&mut self) -> &mut EditorContext {
+        &mut self.application.ctx
+    }
+
+    pub fn client_handle(&self) -> ClientHandle {
+        self.client_handle
+    }
+
+    pub fn plugin_handle(&self, index: usize) -> PluginHandle {
+        match self.application.ctx.plugins.handles().nth(index) {
+            Some(handle) => handle,
+            None => panic!("no plugin at index {}", index),
+        }
+    }
+
+    pub fn register_process(&mut self, program: &str, stand_in: ProcessStandIn) {
+        self.process_stand_ins.push((program.into(), stand_in));
+    }
+
+    pub fn open_buffer(&mut self, path: &str, text: &str) -> BufferHandle {
+        let ctx = &mut self.application.ctx;
+        let buffer = ctx.editor.buffers.add_new();
+        buffer.set_path(Path::new(path));
+        buffer.properties = BufferProperties::scratch();
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            BufferPosition::zero(),
+            text,
+            &mut ctx.editor.events,
+        );
+        buffer.properties = BufferProperties::text();
+        buffer.properties.saving_enabled = false;
+        buffer.refresh_syntax(&ctx.editor.syntaxes);
+        let buffer_handle = buffer.handle();
+        ctx.trigger_event_handlers();
+
+        let buffer_view_handle = ctx
+            .editor
+            .buffer_views
+            .add_new(self.client_handle, buffer_handle);
+        ctx.clients
+            .get_mut(self.client_handle)
+            .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+        ctx.trigger_event_handlers();
+
+        self.update(Vec::new());
+        buffer_handle
+    }
+
+    pub fn keys(&mut self, keys: &str) -> EditorFlow {
+        let mut buf = self.application.ctx.platform.buf_pool.acquire();
+        let write = buf.write();
+        for key in KeyParser::new(keys) {
+            match key {
+                Ok(key) => ClientEvent::Key(TargetClient::Sender, key).serialize(write),
+                Err(error) => panic!("error parsing keys '{}'\n{}", keys, error),
+            }
+        }
+        self.send(buf)
+    }
+
+    pub fn client_event(&mut self, event: ClientEvent) -> EditorFlow {
+        let mut buf = self.application.ctx.platform.buf_pool.acquire();
+        event.serialize(buf.write());
+        self.send(buf)
+    }
+
+    pub fn command(&mut self, commands: &str) -> Result<EditorFlow, CommandError> {
+        let client_handle = Some(self.client_handle);
+        let result =
+            CommandManager::eval_commands(&mut self.application.ctx, client_handle, commands);
+        self.application.ctx.trigger_event_handlers();
+        self.update(Vec::new());
+        result
+    }
+
+    pub fn mode(&self) -> ModeKind {
+        self.application.ctx.editor.mode.kind()
+    }
+
+    pub fn buffer_text(&self) -> String {
+        let ctx = &self.application.ctx;
+        let mut text = String::new();
+        if let Some(buffer_handle) = self.current_buffer_handle() {
+            let content = ctx.editor.buffers.get(buffer_handle).content();
+            let range = BufferRange::between(BufferPosition::zero(), content.end());
+            for slice in content.text_range(range) {
+                text.push_str(slice);
+            }
+        }
+        text
+    }
+
+    pub fn current_buffer_handle(&self) -> Option<BufferHandle> {
+        let ctx = &self.application.ctx;
+        let buffer_view_handle = ctx.clients.get(self.client_handle).buffer_view_handle()?;
+        Some(
+            ctx.editor
+                .buffer_views
+                .get(buffer_view_handle)
+                .buffer_handle,
+        )
+    }
+
+    pub fn cursors(&self) -> &[Cursor] {
+        let ctx = &self.application.ctx;
+        match ctx.clients.get(self.client_handle).buffer_view_handle() {
+            Some(handle) => &ctx.editor.buffer_views.get(handle).cursors[..],
+            None => &[],
+        }
+    }
+
+    pub fn status_bar(&self) -> (MessageKind, &str) {
+        self.application.ctx.editor.status_bar.message()
+    }
+
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    pub fn eval_result(&self) -> Option<Result<&[u8], &[u8]>> {
+        match &self.eval_result {
+            Some(Ok(output)) => Some(Ok(output)),
+            Some(Err(error)) => Some(Err(error)),
+            None => None,
+        }
+    }
+
+    pub fn client_closed(&self) -> bool {
+        self.client_closed
+    }
+
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn screen_lines(&self) -> Vec<String> {
+        parse_display(&self.display, TEST_VIEWPORT_SIZE)
+    }
+
+    fn send(&mut self, buf: PooledBuf) -> EditorFlow {
+        if self.client_closed {
+            self.application.ctx.platform.buf_pool.release(buf);
+            return EditorFlow::Quit;
+        }
+
+        let handle = self.client_handle;
+        self.update(vec![PlatformEvent::ConnectionOutput { handle, buf }]);
+        if self.client_closed {
+            EditorFlow::Quit
+        } else if self.quit {
+            EditorFlow::QuitAll
+        } else {
+            EditorFlow::Continue
+        }
+    }
+
+    fn update(&mut self, mut events: Vec<PlatformEvent>) {
+        for _ in 0..MAX_UPDATE_ITERATIONS {
+            if self.quit {
+                let buf_pool = &mut self.application.ctx.platform.buf_pool;
+                for event in events.drain(..) {
+                    drop_event(buf_pool, event);
+                }
+                return;
+            }
+
+            self.application.update(events.drain(..));
+            let redraw = self.handle_requests(&mut events);
+
+            if events.is_empty() {
+                let buf_pool = &mut self.application.ctx.platform.buf_pool;
+                self.processes.finish_all(buf_pool, &mut events);
+            }
+            if events.is_empty() && !redraw {
+                return;
+            }
+        }
+
+        panic!(
+            "editor did not settle after {} updates",
+            MAX_UPDATE_ITERATIONS
+        );
+    }
+
+    fn handle_requests(&mut self, events: &mut Vec<PlatformEvent>) -> bool {
+        let mut redraw = false;
+        let requests: Vec<_> = self.application.ctx.platform.requests.drain().collect();
+        for request in requests {
+            match request {
+                PlatformRequest::Quit => self.quit = true,
+                PlatformRequest::Redraw => redraw = true,
+                PlatformRequest::WriteToClient { handle, buf } => {
+                    if handle == self.client_handle {
+                        self.on_server_bytes(buf.as_bytes());
+                    }
+                    self.application.ctx.platform.buf_pool.release(buf);
+                }
+                PlatformRequest::CloseClient { handle } => {
+                    if handle == self.client_handle {
+                        self.client_closed = true;
+                    }
+                    events.push(PlatformEvent::ConnectionClose { handle });
+                }
+                request => {
+                    let process_stand_ins = &self.process_stand_ins;
+                    let buf_pool = &mut self.application.ctx.platform.buf_pool;
+                    self.processes
+                        .on_request(buf_pool, request, events, |command| {
+                            let program = command.get_program().to_string_lossy();
+                            let (_, stand_in) = process_stand_ins
+                                .iter()
+                                .find(|(name, _)| *name == program)?;
+                            let args = command
+                                .get_args()
+                                .map(|arg| arg.to_string_lossy().into_owned())
+                                .collect();
+                            Some(TestProcess {
+                                stand_in: *stand_in,
+                                args,
+                            })
+                        });
+                }
+            }
+        }
+        redraw
+    }
+
+    fn on_server_bytes(&mut self, mut bytes: &[u8]) {
+        loop {
+            match ServerEvent::deserialize(&mut bytes) {
+                Ok(ServerEvent::Display(display)) => {
+                    self.display.clear();
+                    self.display.extend_from_slice(display);
+                }
+                Ok(ServerEvent::StdoutOutput(output)) => {
+                    self.stdout.clear();
+                    self.stdout.extend_from_slice(output);
+                }
+                Ok(ServerEvent::EvalOutput(output)) => self.eval_result = Some(Ok(output.into())),
+                Ok(ServerEvent::EvalError(error)) => self.eval_result = Some(Err(error.into())),
+                Ok(_) => (),
+                Err(DeserializeError::InsufficientData) => break,
+                Err(DeserializeError::InvalidData) => panic!("received invalid data from server"),
+            }
+        }
+    }
+}
+impl Default for TestEditor {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+fn parse_display(display: &[u8], size: (u16, u16)) -> Vec<String> {
+    let width = size.0 as usize;
+    let height = size.1 as usize;
+    let mut screen = vec![vec![' '; width]; height];
+    let (mut x, mut y) = (0, 0);
+
+    let display = String::from_utf8_lossy(display);
+    let mut chars = display.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut command = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            command = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    let mut numbers = params
+                        .trim_start_matches(&['?', '='][..])
+                        .split(';')
+                        .map(|n| n.parse::<usize>().unwrap_or(0));
+                    let first = numbers.next().unwrap_or(0);
+                    match command {
+                        Some('H') => {
+                            y = first.saturating_sub(1);
+                            x = numbers.next().unwrap_or(0).saturating_sub(1);
+                        }
+                        Some('E') => {
+                            y += first.max(1);
+                            x = 0;
+                        }
+                        Some('A') => y = y.saturating_sub(first.max(1)),
+                        Some('K') => {
+                            if let Some(line) = screen.get_mut(y) {
+                                let start = if first == 2 { 0 } else { x.min(width) };
+                                for c in &mut line[start..] {
+                                    *c = ' ';
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                Some(']') => {
+                    for c in chars.by_ref() {
+                        if c == '\x07' {
+                            break;
+                        }
+                    }
+                }
+                _ => (),
+            },
+            '\r' => x = 0,
+            '\n' => {
+                y += 1;
+                x = 0;
+            }
+            _ => {
+                if x >= width {
+                    x = 0;
+                    y += 1;
+                }
+                if let Some(cell) = screen.get_mut(y).and_then(|line| line.get_mut(x)) {
+                    *cell = c;
+                }
+                x += 1;
+            }
+        }
+    }
+
+    screen
+        .into_iter()
+        .map(|line| {
+            let line: String = line.into_iter().collect();
+            line.trim_end().into()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ops::Range;
+
+    use crate::{
+        application::{run_batch_script, BatchPlatform},
+        buffer_position::BufferPositionIndex,
+    };
+
+    #[test]
+    fn normal_mode_editing() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "first line\nsecond line");
+        assert_eq!(ModeKind::Normal, editor.mode());
+
+        editor.keys("jwihello <esc>");
+        assert_eq!("first line\nsecond hello line", editor.buffer_text());
+        assert_eq!(1, editor.cursors().len());
+        assert_eq!(
+            BufferPosition::line_col(1, 13),
+            editor.cursors()[0].position
+        );
+
+        editor.keys("u");
+        assert_eq!("first line\nsecond line", editor.buffer_text());
+
+        let screen = editor.screen_lines();
+        assert_eq!(TEST_VIEWPORT_SIZE.1 as usize, screen.len());
+        assert_eq!("first.line", screen[0]);
+        assert_eq!("second.line", screen[1]);
+        assert_eq!("~", screen[2]);
+        assert!(screen[screen.len() - 1].ends_with("test.txt:2,8"));
+    }
+
+    #[test]
+    fn commands_and_keymaps() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        assert!(editor.command("map-normal X [[iX<esc>]]").is_ok());
+        editor.keys("X");
+        assert_eq!("Xtext", editor.buffer_text());
+
+        assert!(matches!(
+            editor.command("no-such-command"),
+            Err(CommandError::NoSuchCommand)
+        ));
+
+        editor.keys(":config tab_size<enter>");
+        assert!(matches!(editor.status_bar(), (MessageKind::Info, "4")));
+
+        assert!(matches!(editor.keys(":quit<enter>"), EditorFlow::Quit));
+        assert!(editor.client_closed());
+    }
+
+    #[test]
+    fn process_stand_ins() {
+        let mut editor = TestEditor::default();
+        editor.register_process("upper", |_, stdin| stdin.to_ascii_uppercase());
+        editor.open_buffer("test.txt", "hello world");
+
+        editor.keys("aw|upper<enter>");
+        assert_eq!("HELLO world", editor.buffer_text());
+    }
+
+    #[test]
+    fn batch_script() {
+        let dir = std::env::temp_dir().join(format!("pepper-batch-test-{}", std::process::id()));
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join("file.txt");
+        let _ = std::fs::write(&path, "first line\nsecond line\n");
+        let path_text = path.to_string_lossy();
+
+        let mut editor = TestEditor::default();
+        let mut platform = BatchPlatform::default();
+        let client_handle = editor.client_handle;
+
+        let script = "wiLINE <esc>\n\njghwiother <esc>\n";
+        let result = run_batch_script(
+            &mut editor.application,
+            &mut platform,
+            client_handle,
+            "script.pp",
+            script,
+            &path_text,
+        );
+        assert!(matches!(result, Ok(EditorFlow::Continue)));
+        assert_eq!(
+            "first LINE line\nsecond other line\n",
+            std::fs::read_to_string(&path).unwrap_or_default()
+        );
+        assert_eq!(ModeKind::Normal, editor.mode());
+
+        if cfg!(unix) {
+            let result = run_batch_script(
+                &mut editor.application,
+                &mut platform,
+                client_handle,
+                "script.pp",
+                "gkgh\naw|tr a-z A-Z<enter>\n",
+                &path_text,
+            );
+            assert!(matches!(result, Ok(EditorFlow::Continue)));
+            assert_eq!(
+                "FIRST LINE line\nsecond other line\n",
+                std::fs::read_to_string(&path).unwrap_or_default()
+            );
+        }
+
+        let result = run_batch_script(
+            &mut editor.application,
+            &mut platform,
+            client_handle,
+            "script.pp",
+            "gk\n:no-such-command<enter>\n",
+            &path_text,
+        );
+        assert_eq!(
+            Err("script.pp:2: no such command".into()),
+            result.map(|_| ())
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn eval() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        let flow = editor.client_event(ClientEvent::Eval(
+            TargetClient::Sender,
+            false,
+            "print first\nconfig tab_size\nprint last",
+        ));
+        assert!(matches!(flow, EditorFlow::Quit));
+        assert!(matches!(
+            editor.eval_result(),
+            Some(Ok(b"first\n4\nlast\n"))
+        ));
+        assert_eq!("last", editor.status_bar().1);
+
+        let mut editor = TestEditor::default();
+        let flow = editor.client_event(ClientEvent::Eval(
+            TargetClient::Sender,
+            false,
+            "no-such-command",
+        ));
+        assert!(matches!(flow, EditorFlow::Quit));
+        assert!(matches!(editor.eval_result(), Some(Err(_))));
+    }
+
+    #[test]
+    fn eval_and_wait() {
+        let mut editor = TestEditor::default();
+        editor.open_buffer("test.txt", "text");
+
+        let flow = editor.client_event(ClientEvent::Eval(
+            TargetClient::Sender,
+            true,
+            "print opened",
+        ));
+        assert!(matches!(flow, EditorFlow::Continue));
+        assert!(matches!(editor.eval_result(), Some(Ok(b"opened\n"))));
+        assert!(!editor.client_closed());
+
+        assert!(editor.command("close!").is_ok());
+        assert!(editor.client_closed());
+    }
+
+    #[test]
+    fn annotations_drawing() {
+        let mut editor = TestEditor::default();
+        let buffer_handle = editor.open_buffer("test.txt", "let x = 1;\nsecond");
+
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(buffer_handle);
+        {
+            let mut annotations = buffer.annotations.mut_guard(PluginHandle(0), 0);
+            annotations.add(": u32\t\x1b[2J\n", BufferPosition::line_col(0, 5));
+            annotations.add(&"a".repeat(100), BufferPosition::line_col(0, 10));
+        }
+        editor.keys("gk");
+
+        let screen = editor.screen_lines();
+        let wrapped_len = TEST_VIEWPORT_SIZE.0 as usize - 21;
+        assert_eq!(
+            format!("let.x: u32  [2J .=.1;{}", "a".repeat(wrapped_len)),
+            screen[0]
+        );
+        assert_eq!("a".repeat(100 - wrapped_len), screen[1]);
+        assert_eq!("second", screen[2]);
+        assert_eq!("~", screen[3]);
+    }
+
+    #[test]
+    fn annotations_scroll() {
+        let text: Vec<String> = (0..30).map(|i| format!("l{}", i)).collect();
+        let mut editor = TestEditor::default();
+        let buffer_handle = editor.open_buffer("test.txt", &text.join("\n"));
+
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(buffer_handle);
+        buffer
+            .annotations
+            .mut_guard(PluginHandle(0), 0)
+            .add(&"a".repeat(200), BufferPosition::line_col(0, 2));
+        editor.keys("gk");
+
+        let ctx = editor.ctx();
+        let client = ctx.clients.get(editor.client_handle());
+        let (_, range) = client.visible_line_range(&ctx.editor).unwrap();
+        assert_eq!(0..21, range);
+
+        editor.keys("gj");
+        let screen = editor.screen_lines();
+        assert_eq!("l7", screen[0]);
+        assert_eq!("l29", screen[22]);
+
+        let ctx = editor.ctx();
+        let client = ctx.clients.get(editor.client_handle());
+        let (_, range) = client.visible_line_range(&ctx.editor).unwrap();
+        assert_eq!(7..30, range);
+    }
+
+    fn virtual_lines_editor() -> TestEditor {
+        let mut text: Vec<String> = (0..30).map(|i| format!("l{}", i)).collect();
+        text[10].insert(0, '\t');
+        let mut editor = TestEditor::default();
+        let buffer_handle = editor.open_buffer("test.txt", &text.join("\n"));
+
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(buffer_handle);
+        let mut virtual_lines = buffer.virtual_lines.mut_guard(PluginHandle(0), 0);
+        virtual_lines.add("v0a", 0);
+        virtual_lines.add("v0b\x1b[2J", 0);
+        virtual_lines.add("v10", 10);
+        drop(virtual_lines);
+        editor
+    }
+
+    fn visible_line_range(editor: &TestEditor) -> Range<BufferPositionIndex> {
+        let ctx = editor.ctx();
+        let client = ctx.clients.get(editor.client_handle());
+        client.visible_line_range(&ctx.editor).unwrap().1
+    }
+
+    #[test]
+    fn virtual_lines_drawing() {
+        let mut editor = virtual_lines_editor();
+        editor.keys("gk");
+
+        let screen = editor.screen_lines();
+        assert_eq!("v0a", screen[0]);
+        assert_eq!("v0b [2J", screen[1]);
+        assert_eq!("l0", screen[2]);
+        assert_eq!("l9", screen[11]);
+        assert_eq!("    v10", screen[12]);
+        assert_eq!("l19", screen[22]);
+        assert_eq!(0..20, visible_line_range(&editor));
+    }
+
+    #[test]
+    fn virtual_lines_scroll() {
+        let mut editor = virtual_lines_editor();
+        editor.keys("gj");
+
+        let screen = editor.screen_lines();
+        assert_eq!("l8", screen[0]);
+        assert_eq!("    v10", screen[2]);
+        assert_eq!("l29", screen[22]);
+        assert_eq!(8..30, visible_line_range(&editor));
+
+        // scrolled halfway through the virtual lines of the first line
+        editor.keys("gk");
+        let client_handle = editor.client_handle();
+        editor.ctx_mut().clients.get_mut(client_handle).scroll = 1;
+        editor.keys("l");
+
+        let screen = editor.screen_lines();
+        assert_eq!("v0b [2J", screen[0]);
+        assert_eq!("l0", screen[1]);
+        assert_eq!("l20", screen[22]);
+        assert_eq!(0..21, visible_line_range(&editor));
+    }
+}
@@ -41,12 +41,18 @@ theme_colors! {
     background,
     active_line_background,
     highlight,
+    symbol_highlight,
+    symbol_write_highlight,
     normal_cursor,
     select_cursor,
     insert_cursor,
     inactive_cursor,
     statusbar_active_background,
     statusbar_inactive_background,
+    lint_error,
+    lint_warning,
+    lint_info,
+    lint_hint,
 
     token_whitespace,
     token_text,
@@ -56,6 +62,9 @@ theme_colors! {
     token_symbol,
     token_string,
     token_literal,
+    token_function,
+    token_variable,
+    token_property,
 }
 
 impl Default for Theme {
@@ -69,12 +78,18 @@ pub fn gruvbox_theme() -> Theme {
         background: Color::from_u32(0x1d2021),
         active_line_background: Color::from_u32(0x282828),
         highlight: Color::from_u32(0xfabd2f),
+        symbol_highlight: Color::from_u32(0x3c3836),
+        symbol_write_highlight: Color::from_u32(0x4a3d2a),
         normal_cursor: Color::from_u32(0xcc241d),
         insert_cursor: Color::from_u32(0xfabd2f),
         select_cursor: Color::from_u32(0x458588),
         inactive_cursor: Color::from_u32(0x504945),
         statusbar_active_background: Color::from_u32(0x504945),
         statusbar_inactive_background: Color::from_u32(0x282828),
+        lint_error: Color::from_u32(0xfb4934),
+        lint_warning: Color::from_u32(0xfabd2f),
+        lint_info: Color::from_u32(0x83a598),
+        lint_hint: Color::from_u32(0xa89984),
 
         token_whitespace: Color::from_u32(0x504945),
         token_text: Color::from_u32(0xebdbb2),
@@ -84,5 +99,8 @@ pub fn gruvbox_theme() -> Theme {
         token_symbol: Color::from_u32(0xa89984),
         token_string: Color::from_u32(0xb8bb26),
         token_literal: Color::from_u32(0xd3869b),
+        token_function: Color::from_u32(0xfabd2f),
+        token_variable: Color::from_u32(0xebdbb2),
+        token_property: Color::from_u32(0x83a598),
     }
 }
@@ -1,7 +1,9 @@
 use std::{io, iter};
 
 use crate::{
-    buffer::CharDisplayDistances,
+    buffer::{
+        char_display_len, BufferLintSeverity, BufferSymbolHighlightKind, CharDisplayDistances,
+    },
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovementKind},
     editor::Editor,
@@ -58,6 +60,11 @@ pub fn set_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[4m");
 }
 
+pub fn set_underline_color(buf: &mut Vec<u8>, color: Color) {
+    use io::Write;
+    let _ = write!(buf, "\x1b[58;2;{};{};{}m", color.0, color.1, color.2);
+}
+
 pub fn set_not_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[24m");
 }
@@ -159,7 +166,7 @@ fn draw_buffer_view(
     let cursors = &buffer_view.cursors[..];
     let active_line_index = buffer_view.cursors.main_cursor().position.line_index as usize;
 
-    let tab_size = ctx.editor.config.tab_size.get();
+    let tab_size = buffer.editorconfig.tab_size(&ctx.editor.config).get();
 
     let draw_width = ctx.viewport_size.0 as usize;
     let draw_height = ctx.viewport_size.1.saturating_sub(1);
@@ -196,8 +203,14 @@ fn draw_buffer_view(
     let lints = buffer.lints.all();
     let lints_end_index = lints.len().saturating_sub(1);
 
+    let annotations = buffer.annotations.all();
+
+    let semantic_tokens = buffer.semantic_tokens.all();
+    let symbol_highlights = buffer.symbol_highlights.all();
+
     let mut scroll_offset = BufferPosition::zero();
     let mut scroll_padding_top = ctx.scroll as usize;
+    let mut skipped_virtual_lines_count = 0;
     for (line_index, display_len) in buffer_content.line_display_lens().iter().enumerate() {
         scroll_offset.line_index = line_index as _;
 
@@ -205,16 +218,32 @@ fn draw_buffer_view(
             break;
         }
 
-        let line_height = 1 + display_len.total_len(tab_size) / draw_width;
+        let virtual_lines_count = buffer.virtual_lines.at(line_index as _).len();
+        let annotations_len = buffer.annotations.line_display_len(line_index as _);
+        let line_height = virtual_lines_count
+            + 1
+            + (display_len.total_len(tab_size) + annotations_len) / draw_width;
         if line_height <= scroll_padding_top {
             scroll_padding_top -= line_height;
             continue;
         }
 
+        if scroll_padding_top < virtual_lines_count {
+            skipped_virtual_lines_count = scroll_padding_top;
+            break;
+        }
+        scroll_padding_top -= virtual_lines_count;
+        skipped_virtual_lines_count = virtual_lines_count;
+        if scroll_padding_top == 0 {
+            break;
+        }
+
         let line = buffer_content.lines()[line_index].as_str();
-        let target_display_len = (scroll_padding_top * draw_width) as _;
+        let target_display_len = scroll_padding_top * draw_width;
         for d in CharDisplayDistances::new(line, tab_size) {
-            if d.distance >= target_display_len {
+            let position = BufferPosition::line_col(line_index as _, d.char_index);
+            let annotations_len = buffer.annotations.display_len_until(position);
+            if d.distance as usize + annotations_len >= target_display_len {
                 let index = d.char_index as usize + d.char.len_utf8();
                 scroll_offset.column_byte_index = index as _;
                 break;
@@ -257,6 +286,21 @@ fn draw_buffer_view(
         }
     }
 
+    let mut current_semantic_token_index = semantic_tokens
+        .iter()
+        .position(|t| scroll_offset < t.range.to)
+        .unwrap_or(semantic_tokens.len());
+
+    let mut current_annotation_index = annotations
+        .iter()
+        .position(|a| scroll_offset <= a.position)
+        .unwrap_or(annotations.len());
+
+    let mut current_symbol_highlight_index = symbol_highlights
+        .iter()
+        .position(|h| scroll_offset < h.range.to)
+        .unwrap_or(symbol_highlights.len());
+
     move_cursor_to(buf, 0, 0);
     set_background_color(buf, ctx.editor.theme.background);
     set_not_underlined(buf);
@@ -307,8 +351,53 @@ fn draw_buffer_view(
             Token(TokenKind),
             Selection(TokenKind),
             Highlight,
+            SymbolHighlight(TokenKind, BufferSymbolHighlightKind),
             Cursor,
+            Annotation,
+        }
+
+        if scroll_offset.column_byte_index == 0 {
+            let virtual_lines = buffer.virtual_lines.at(line_index as _);
+            if skipped_virtual_lines_count < virtual_lines.len() {
+                let indentation_len: usize = line
+                    .as_str()
+                    .chars()
+                    .take_while(|c| c.is_ascii_whitespace())
+                    .map(|c| if c == '\t' { tab_size as usize } else { 1 })
+                    .sum();
+
+                set_not_underlined(buf);
+                set_background_color(buf, ctx.editor.theme.background);
+                set_foreground_color(buf, ctx.editor.theme.token_comment);
+
+                for virtual_line in &virtual_lines[skipped_virtual_lines_count..] {
+                    if lines_drawn_count == draw_height {
+                        break;
+                    }
+                    lines_drawn_count += 1;
+
+                    let mut x = 0;
+                    while x < indentation_len.min(draw_width) {
+                        x += 1;
+                        buf.push(b' ');
+                    }
+                    for c in virtual_line.text.chars() {
+                        if x >= draw_width {
+                            break;
+                        }
+                        x += 1;
+                        let c = printable_char(c);
+                        buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+                    }
+
+                    if x < draw_width {
+                        clear_until_new_line(buf);
+                    }
+                    move_cursor_to_next_line(buf);
+                }
+            }
         }
+        skipped_virtual_lines_count = 0;
 
         if lines_drawn_count == draw_height {
             break;
@@ -317,7 +406,7 @@ fn draw_buffer_view(
 
         let line = &line.as_str()[scroll_offset.column_byte_index as usize..];
         let mut draw_state = DrawState::Token(TokenKind::Text);
-        let mut was_inside_lint_range = false;
+        let mut current_lint_severity = None;
         let mut x = 0;
         let mut last_line_token = Token::default();
         let mut line_tokens = highlighted_buffer.line_tokens(line_index).iter();
@@ -331,22 +420,82 @@ fn draw_buffer_view(
         set_background_color(buf, background_color);
         set_foreground_color(buf, ctx.editor.theme.token_text);
 
+        let mut filled_view = false;
         for (char_index, c) in line.char_indices().chain(iter::once((line.len(), '\n'))) {
             let char_index = char_index + scroll_offset.column_byte_index as usize;
             let char_position = BufferPosition::line_col(line_index as _, char_index as _);
 
+            while current_annotation_index < annotations.len() {
+                let annotation = &annotations[current_annotation_index];
+                if annotation.position > char_position {
+                    break;
+                }
+                current_annotation_index += 1;
+                if annotation.position < char_position {
+                    continue;
+                }
+
+                if draw_state != DrawState::Annotation {
+                    draw_state = DrawState::Annotation;
+                    set_background_color(buf, background_color);
+                    set_foreground_color(buf, ctx.editor.theme.token_comment);
+                }
+                if current_lint_severity.is_some() {
+                    current_lint_severity = None;
+                    set_not_underlined(buf);
+                }
+
+                for c in annotation.text.chars() {
+                    let previous_x = x;
+                    let previous_buf_len = buf.len();
+
+                    x += char_display_len(c) as usize;
+                    let c = printable_char(c);
+                    buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+
+                    if x > draw_width {
+                        x -= draw_width;
+                        lines_drawn_count += 1;
+                        if lines_drawn_count > draw_height {
+                            lines_drawn_count = draw_height;
+                            buf.truncate(previous_buf_len);
+                            x = previous_x;
+                            filled_view = true;
+                            break;
+                        }
+                    }
+                }
+                if filled_view {
+                    break;
+                }
+            }
+            if filled_view {
+                break;
+            }
+
+            while current_semantic_token_index < semantic_tokens.len()
+                && semantic_tokens[current_semantic_token_index].range.to <= char_position
+            {
+                current_semantic_token_index += 1;
+            }
+
             let token_kind = if c.is_ascii_whitespace() {
                 TokenKind::Whitespace
             } else {
-                if !last_line_token.contains(char_index as _) {
-                    while let Some(token) = line_tokens.next() {
-                        if token.contains(char_index as _) {
-                            last_line_token = token.clone();
-                            break;
+                match semantic_tokens.get(current_semantic_token_index) {
+                    Some(token) if token.range.from <= char_position => token.kind,
+                    _ => {
+                        if !last_line_token.contains(char_index as _) {
+                            for token in line_tokens.by_ref() {
+                                if token.contains(char_index as _) {
+                                    last_line_token = token.clone();
+                                    break;
+                                }
+                            }
                         }
+                        last_line_token.kind
                     }
                 }
-                last_line_token.kind
             };
 
             let text_color = match token_kind {
@@ -358,6 +507,9 @@ fn draw_buffer_view(
                 TokenKind::Comment => ctx.editor.theme.token_comment,
                 TokenKind::Text => ctx.editor.theme.token_text,
                 TokenKind::Whitespace => ctx.editor.theme.token_whitespace,
+                TokenKind::Function => ctx.editor.theme.token_function,
+                TokenKind::Variable => ctx.editor.theme.token_variable,
+                TokenKind::Property => ctx.editor.theme.token_property,
             };
 
             while current_cursor_index < cursors_end_index
@@ -380,19 +532,44 @@ fn draw_buffer_view(
             let inside_search_range = current_search_range.from <= char_position
                 && char_position < current_search_range.to;
 
+            while current_symbol_highlight_index < symbol_highlights.len()
+                && symbol_highlights[current_symbol_highlight_index].range.to <= char_position
+            {
+                current_symbol_highlight_index += 1;
+            }
+            let symbol_highlight_kind = match symbol_highlights.get(current_symbol_highlight_index)
+            {
+                Some(highlight) if highlight.range.from <= char_position => Some(highlight.kind),
+                _ => None,
+            };
+
             while current_lint_range.to < char_position && current_lint_index < lints_end_index {
                 current_lint_index += 1;
                 current_lint_range = lints[current_lint_index].range;
             }
-            let inside_lint_range =
-                current_lint_range.from <= char_position && char_position < current_lint_range.to;
-
-            if inside_lint_range != was_inside_lint_range {
-                was_inside_lint_range = inside_lint_range;
-                if inside_lint_range {
-                    set_underlined(buf);
-                } else {
-                    set_not_underlined(buf);
+            let lint_severity = if current_lint_range.from <= char_position
+                && char_position < current_lint_range.to
+            {
+                Some(lints[current_lint_index].severity)
+            } else {
+                None
+            };
+
+            if lint_severity != current_lint_severity {
+                current_lint_severity = lint_severity;
+                match lint_severity {
+                    Some(severity) => {
+                        let theme = &ctx.editor.theme;
+                        let color = match severity {
+                            BufferLintSeverity::Error => theme.lint_error,
+                            BufferLintSeverity::Warning => theme.lint_warning,
+                            BufferLintSeverity::Info => theme.lint_info,
+                            BufferLintSeverity::Hint => theme.lint_hint,
+                        };
+                        set_underlined(buf);
+                        set_underline_color(buf, color);
+                    }
+                    None => set_not_underlined(buf),
                 }
             }
 
@@ -414,6 +591,16 @@ fn draw_buffer_view(
                     set_background_color(buf, ctx.editor.theme.highlight);
                     set_foreground_color(buf, background_color);
                 }
+            } else if let Some(kind) = symbol_highlight_kind {
+                if draw_state != DrawState::SymbolHighlight(token_kind, kind) {
+                    draw_state = DrawState::SymbolHighlight(token_kind, kind);
+                    let color = match kind {
+                        BufferSymbolHighlightKind::Read => ctx.editor.theme.symbol_highlight,
+                        BufferSymbolHighlightKind::Write => ctx.editor.theme.symbol_write_highlight,
+                    };
+                    set_background_color(buf, color);
+                    set_foreground_color(buf, text_color);
+                }
             } else if draw_state != DrawState::Token(token_kind) {
                 draw_state = DrawState::Token(token_kind);
                 set_background_color(buf, background_color);
@@ -479,6 +666,14 @@ fn draw_buffer_view(
     }
 }
 
+fn printable_char(c: char) -> char {
+    if c.is_control() {
+        ' '
+    } else {
+        c
+    }
+}
+
 fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     if !ctx.has_focus {
         return;
@@ -487,7 +682,7 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     let cursor = ctx.editor.picker.cursor().unwrap_or(usize::MAX - 1);
     let scroll = ctx.editor.picker.scroll();
 
-    let width = ctx.viewport_size.0 as _;
+    let width = ctx.viewport_size.0 as usize;
     let height = ctx
         .editor
         .picker
@@ -497,11 +692,33 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     let background_normal_color = ctx.editor.theme.statusbar_inactive_background;
     let background_selected_color = ctx.editor.theme.statusbar_active_background;
     let foreground_color = ctx.editor.theme.token_text;
+    let kind_color = ctx.editor.theme.token_comment;
+
+    let details = ctx
+        .editor
+        .picker
+        .current_custom_entry()
+        .filter(|e| !e.detail.is_empty() || !e.documentation.is_empty());
+    let entries_width = match details {
+        Some(_) => width / 2,
+        None => width,
+    };
+    let mut detail_lines = details
+        .into_iter()
+        .flat_map(|e| e.detail.lines().chain(e.documentation.lines()));
 
     set_background_color(buf, background_normal_color);
     set_foreground_color(buf, foreground_color);
 
-    for (i, entry) in ctx
+    fn print_char(buf: &mut Vec<u8>, x: &mut usize, c: char) {
+        let mut char_buf = [0; std::mem::size_of::<char>()];
+
+        *x += 1;
+        let c = printable_char(c);
+        buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+    }
+
+    for (i, (kind, entry)) in ctx
         .editor
         .picker
         .entries(&ctx.editor.word_database)
@@ -511,24 +728,20 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
     {
         if i == cursor {
             set_background_color(buf, background_selected_color);
-        } else if i == cursor + 1 {
+        } else if i == cursor + 1 || details.is_some() {
             set_background_color(buf, background_normal_color);
         }
 
         let mut x = 0;
 
-        fn print_char(buf: &mut Vec<u8>, x: &mut usize, c: char) {
-            let mut char_buf = [0; std::mem::size_of::<char>()];
-
-            *x += 1;
-            match c {
-                '\t' => buf.push(b' '),
-                c => buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes()),
-            }
-        }
+        let kind = kind.as_str();
+        let name_width = match kind.len() {
+            0 => entries_width,
+            len => entries_width.saturating_sub(len + 1),
+        };
 
         let name_char_count = entry.chars().count();
-        if name_char_count < width {
+        if name_char_count < name_width {
             for c in entry.chars() {
                 print_char(buf, &mut x, c);
             }
@@ -536,14 +749,36 @@ fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
             buf.extend_from_slice(b"...");
             x += 3;
             let name_char_count = name_char_count + 3;
-            for c in entry.chars().skip(name_char_count.saturating_sub(width)) {
+            for c in entry
+                .chars()
+                .skip(name_char_count.saturating_sub(name_width))
+            {
                 print_char(buf, &mut x, c);
             }
         }
-        for _ in x..width {
+        for _ in x..name_width {
             buf.push(b' ');
         }
-        x = 0;
+        x = name_width;
+
+        if !kind.is_empty() && x + kind.len() < entries_width {
+            set_foreground_color(buf, kind_color);
+            buf.push(b' ');
+            buf.extend_from_slice(kind.as_bytes());
+            set_foreground_color(buf, foreground_color);
+            x += kind.len() + 1;
+        }
+
+        if details.is_some() {
+            set_background_color(buf, background_normal_color);
+            buf.push(b' ');
+            x += 1;
+            if let Some(line) = detail_lines.next() {
+                for c in line.chars().take(width.saturating_sub(x)) {
+                    print_char(buf, &mut x, c);
+                }
+            }
+        }
 
         if x < width {
             clear_until_new_line(buf);
@@ -697,6 +932,11 @@ fn draw_statusbar(
             buf.push(b' ');
         }
 
+        for indicator in ctx.editor.status_bar.indicators() {
+            buf.extend_from_slice(indicator.as_bytes());
+            buf.push(b' ');
+        }
+
         if needs_save {
             buf.push(b'*');
         }
@@ -53,8 +53,13 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
     {
         let mut workspace_capabilities = JsonObject::default();
         workspace_capabilities.set("applyEdit".into(), true.into(), json);
-        workspace_capabilities.set("workspaceFolders".into(), false.into(), json);
-        workspace_capabilities.set("configuration".into(), false.into(), json);
+        workspace_capabilities.set("workspaceFolders".into(), true.into(), json);
+        workspace_capabilities.set("configuration".into(), true.into(), json);
+        workspace_capabilities.set(
+            "didChangeConfiguration".into(),
+            JsonObject::default().into(),
+            json,
+        );
 
         workspace_capabilities.set(
             "didChangeWatchedFiles".into(),
@@ -64,10 +69,28 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
 
         workspace_capabilities.set("executeCommand".into(), JsonObject::default().into(), json);
 
+        {
+            let mut inlay_hint = JsonObject::default();
+            inlay_hint.set("refreshSupport".into(), true.into(), json);
+            workspace_capabilities.set("inlayHint".into(), inlay_hint.into(), json);
+        }
+
+        {
+            let mut semantic_tokens = JsonObject::default();
+            semantic_tokens.set("refreshSupport".into(), true.into(), json);
+            workspace_capabilities.set("semanticTokens".into(), semantic_tokens.into(), json);
+        }
+
+        {
+            let mut code_lens = JsonObject::default();
+            code_lens.set("refreshSupport".into(), true.into(), json);
+            workspace_capabilities.set("codeLens".into(), code_lens.into(), json);
+        }
+
         {
             let mut workspace_edit_capabilities = JsonObject::default();
             workspace_edit_capabilities.set("documentChanges".into(), true.into(), json);
-            workspace_edit_capabilities.set("failureHandling".into(), "undo".into(), json);
+            workspace_edit_capabilities.set("failureHandling".into(), "abort".into(), json);
 
             let mut resource_operation_kinds = JsonArray::default();
             resource_operation_kinds.push("create".into(), json);
@@ -120,7 +143,7 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
 
             {
                 let mut completion_item = JsonObject::default();
-                completion_item.set("snippetSupport".into(), false.into(), json);
+                completion_item.set("snippetSupport".into(), true.into(), json);
                 completion_item.set("commitCharactersSupport".into(), false.into(), json);
 
                 let mut documentation_formats = JsonArray::default();
@@ -290,6 +313,72 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
         }
 
         text_document_capabilities.set("selectionRange".into(), JsonObject::default().into(), json);
+        text_document_capabilities.set("inlayHint".into(), JsonObject::default().into(), json);
+        text_document_capabilities.set("callHierarchy".into(), JsonObject::default().into(), json);
+        text_document_capabilities.set(
+            "documentHighlight".into(),
+            JsonObject::default().into(),
+            json,
+        );
+        text_document_capabilities.set("codeLens".into(), JsonObject::default().into(), json);
+        text_document_capabilities.set("typeHierarchy".into(), JsonObject::default().into(), json);
+
+        {
+            let mut semantic_tokens = JsonObject::default();
+
+            {
+                let mut full = JsonObject::default();
+                full.set("delta".into(), true.into(), json);
+
+                let mut requests = JsonObject::default();
+                requests.set("range".into(), false.into(), json);
+                requests.set("full".into(), full.into(), json);
+                semantic_tokens.set("requests".into(), requests.into(), json);
+            }
+
+            let mut token_types = JsonArray::default();
+            for token_type in &[
+                "namespace",
+                "type",
+                "class",
+                "enum",
+                "interface",
+                "struct",
+                "typeParameter",
+                "parameter",
+                "variable",
+                "property",
+                "enumMember",
+                "event",
+                "function",
+                "method",
+                "macro",
+                "keyword",
+                "modifier",
+                "comment",
+                "string",
+                "number",
+                "regexp",
+                "operator",
+                "decorator",
+            ] {
+                token_types.push((*token_type).into(), json);
+            }
+            semantic_tokens.set("tokenTypes".into(), token_types.into(), json);
+
+            let mut token_modifiers = JsonArray::default();
+            token_modifiers.push("readonly".into(), json);
+            semantic_tokens.set("tokenModifiers".into(), token_modifiers.into(), json);
+
+            let mut formats = JsonArray::default();
+            formats.push("relative".into(), json);
+            semantic_tokens.set("formats".into(), formats.into(), json);
+
+            semantic_tokens.set("overlappingTokenSupport".into(), false.into(), json);
+            semantic_tokens.set("multilineTokenSupport".into(), false.into(), json);
+
+            text_document_capabilities.set("semanticTokens".into(), semantic_tokens.into(), json);
+        }
 
         capabilities.set(
             "textDocument".into(),
@@ -301,7 +390,7 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
     {
         let mut window_capabilities = JsonObject::default();
 
-        window_capabilities.set("workDoneProgress".into(), false.into(), json);
+        window_capabilities.set("workDoneProgress".into(), true.into(), json);
         window_capabilities.set("showMessage".into(), JsonObject::default().into(), json);
 
         {
@@ -8,8 +8,8 @@ use std::{
 };
 
 use pepper::{
-    buffer::{BufferHandle, BufferProperties},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::{Buffer, BufferHandle, BufferLintSeverity, BufferProperties},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::BufferViewHandle,
     client,
     cursor::Cursor,
@@ -19,6 +19,7 @@ use pepper::{
     navigation_history::NavigationHistory,
     platform::Platform,
     plugin::PluginHandle,
+    syntax::TokenKind,
 };
 
 use crate::{
@@ -26,8 +27,9 @@ use crate::{
     json::{FromJson, Json, JsonArray, JsonConvertError, JsonObject, JsonValue},
     mode::read_line,
     protocol::{
-        self, DocumentCodeAction, DocumentDiagnostic, DocumentPosition, DocumentRange,
-        DocumentSymbolInformation, PendingRequestColection, Protocol, ResponseError, Uri,
+        self, DocumentCodeAction, DocumentCommand, DocumentDiagnostic, DocumentPosition,
+        DocumentRange, DocumentSymbolInformation, PendingRequestColection, Protocol, RequestId,
+        ResponseError, Uri,
     },
 };
 
@@ -44,6 +46,27 @@ impl<'json> FromJson<'json> for GenericCapability {
     }
 }
 
+#[derive(Default)]
+struct WorkspaceCapability {
+    pub workspace_folders_change_notifications: bool,
+}
+impl<'json> FromJson<'json> for WorkspaceCapability {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let change_notifications = value
+            .get("workspaceFolders", json)
+            .get("changeNotifications", json);
+        let workspace_folders_change_notifications = match change_notifications {
+            JsonValue::Null => false,
+            JsonValue::Boolean(b) => b,
+            JsonValue::String(_) => true,
+            _ => return Err(JsonConvertError),
+        };
+        Ok(Self {
+            workspace_folders_change_notifications,
+        })
+    }
+}
+
 #[derive(Default)]
 struct TriggerCharactersCapability {
     pub on: bool,
@@ -102,6 +125,30 @@ impl<'json> FromJson<'json> for RenameCapability {
     }
 }
 
+#[derive(Default)]
+struct CodeLensCapability {
+    pub on: bool,
+    pub resolve_provider: bool,
+}
+impl<'json> FromJson<'json> for CodeLensCapability {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        match value {
+            JsonValue::Null => Ok(Self {
+                on: false,
+                resolve_provider: false,
+            }),
+            JsonValue::Object(options) => Ok(Self {
+                on: true,
+                resolve_provider: matches!(
+                    options.get("resolveProvider", json),
+                    JsonValue::Boolean(true)
+                ),
+            }),
+            _ => Err(JsonConvertError),
+        }
+    }
+}
+
 enum TextDocumentSyncKind {
     None,
     Full,
@@ -186,6 +233,94 @@ impl<'json> FromJson<'json> for TextDocumentSyncCapability {
     }
 }
 
+#[derive(Default)]
+struct SemanticTokensCapability {
+    pub on: bool,
+    pub delta: bool,
+    pub token_kinds: Vec<Option<TokenKind>>,
+    pub readonly_modifier: u32,
+    pub deprecated_modifier: u32,
+}
+impl SemanticTokensCapability {
+    pub fn token_kind(&self, token_type: u32, modifiers: u32) -> Option<TokenKind> {
+        let kind = (*self.token_kinds.get(token_type as usize)?)?;
+        if modifiers & self.deprecated_modifier != 0 {
+            return Some(TokenKind::Comment);
+        }
+        match kind {
+            TokenKind::Variable | TokenKind::Property
+                if modifiers & self.readonly_modifier != 0 =>
+            {
+                Some(TokenKind::Literal)
+            }
+            _ => Some(kind),
+        }
+    }
+}
+impl<'json> FromJson<'json> for SemanticTokensCapability {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        fn token_kind(token_type: &str) -> Option<TokenKind> {
+            match token_type {
+                "namespace" | "type" | "class" | "enum" | "interface" | "struct"
+                | "typeParameter" | "builtinType" | "typeAlias" => Some(TokenKind::Type),
+                "keyword" | "modifier" | "selfKeyword" => Some(TokenKind::Keyword),
+                "comment" => Some(TokenKind::Comment),
+                "string" => Some(TokenKind::String),
+                "number" | "regexp" | "boolean" | "enumMember" => Some(TokenKind::Literal),
+                "operator" | "macro" | "decorator" | "attribute" => Some(TokenKind::Symbol),
+                "function" | "method" => Some(TokenKind::Function),
+                "variable" | "parameter" => Some(TokenKind::Variable),
+                "property" => Some(TokenKind::Property),
+                "event" | "label" => Some(TokenKind::Text),
+                _ => None,
+            }
+        }
+
+        let options = match value {
+            JsonValue::Null | JsonValue::Boolean(false) => return Ok(Self::default()),
+            JsonValue::Object(options) => options,
+            _ => return Err(JsonConvertError),
+        };
+
+        let mut this = Self::default();
+        match options.clone().get("full", json) {
+            JsonValue::Boolean(b) => this.on = b,
+            JsonValue::Object(full) => {
+                this.on = true;
+                this.delta = matches!(full.get("delta", json), JsonValue::Boolean(true));
+            }
+            _ => (),
+        }
+
+        let legend = options.get("legend", json);
+        for token_type in legend.clone().get("tokenTypes", json).elements(json) {
+            let kind = match token_type {
+                JsonValue::String(token_type) => token_kind(token_type.as_str(json)),
+                _ => None,
+            };
+            this.token_kinds.push(kind);
+        }
+        for (i, modifier) in legend
+            .get("tokenModifiers", json)
+            .elements(json)
+            .enumerate()
+        {
+            if i >= 32 {
+                break;
+            }
+            if let JsonValue::String(modifier) = modifier {
+                match modifier.as_str(json) {
+                    "readonly" => this.readonly_modifier = 1 << i,
+                    "deprecated" => this.deprecated_modifier = 1 << i,
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct ServerCapabilities {
     text_document_sync: TextDocumentSyncCapability,
@@ -201,6 +336,46 @@ pub(crate) struct ServerCapabilities {
     document_formatting_provider: GenericCapability,
     rename_provider: RenameCapability,
     workspace_symbol_provider: GenericCapability,
+    inlay_hint_provider: GenericCapability,
+    semantic_tokens_provider: SemanticTokensCapability,
+    call_hierarchy_provider: GenericCapability,
+    type_hierarchy_provider: GenericCapability,
+    document_highlight_provider: GenericCapability,
+    code_lens_provider: CodeLensCapability,
+    workspace: WorkspaceCapability,
+}
+impl ServerCapabilities {
+    fn write_names(&self, buf: &mut String) {
+        let capabilities = [
+            (self.completion_provider.on, "completion"),
+            (self.hover_provider.0, "hover"),
+            (self.signature_help_provider.on, "signature-help"),
+            (self.declaration_provider.0, "declaration"),
+            (self.definition_provider.0, "definition"),
+            (self.implementation_provider.0, "implementation"),
+            (self.references_provider.0, "references"),
+            (self.document_symbol_provider.0, "document-symbols"),
+            (self.workspace_symbol_provider.0, "workspace-symbols"),
+            (self.code_action_provider.0, "code-action"),
+            (self.document_formatting_provider.0, "formatting"),
+            (self.rename_provider.on, "rename"),
+            (self.inlay_hint_provider.0, "inlay-hints"),
+            (self.semantic_tokens_provider.on, "semantic-tokens"),
+            (self.call_hierarchy_provider.0, "call-hierarchy"),
+            (self.type_hierarchy_provider.0, "type-hierarchy"),
+            (self.document_highlight_provider.0, "document-highlight"),
+            (self.code_lens_provider.on, "code-lens"),
+        ];
+        let mut names = capabilities.iter().filter(|(on, _)| *on).map(|(_, n)| n);
+        match names.next() {
+            Some(name) => buf.push_str(name),
+            None => buf.push_str("none"),
+        }
+        for name in names {
+            buf.push_str(", ");
+            buf.push_str(name);
+        }
+    }
 }
 impl<'json> FromJson<'json> for ServerCapabilities {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
@@ -240,6 +415,21 @@ impl<'json> FromJson<'json> for ServerCapabilities {
                 "workspaceSymbolProvider" => {
                     this.workspace_symbol_provider = FromJson::from_json(value, json)?
                 }
+                "inlayHintProvider" => this.inlay_hint_provider = FromJson::from_json(value, json)?,
+                "semanticTokensProvider" => {
+                    this.semantic_tokens_provider = FromJson::from_json(value, json)?
+                }
+                "callHierarchyProvider" => {
+                    this.call_hierarchy_provider = FromJson::from_json(value, json)?
+                }
+                "typeHierarchyProvider" => {
+                    this.type_hierarchy_provider = FromJson::from_json(value, json)?
+                }
+                "documentHighlightProvider" => {
+                    this.document_highlight_provider = FromJson::from_json(value, json)?
+                }
+                "codeLensProvider" => this.code_lens_provider = FromJson::from_json(value, json)?,
+                "workspace" => this.workspace = FromJson::from_json(value, json)?,
                 _ => (),
             }
         }
@@ -247,6 +437,117 @@ impl<'json> FromJson<'json> for ServerCapabilities {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HierarchyKind {
+    IncomingCalls,
+    OutgoingCalls,
+    Supertypes,
+    Subtypes,
+}
+impl HierarchyKind {
+    pub fn buffer_name(self) -> &'static str {
+        match self {
+            Self::IncomingCalls => "lsp-incoming-calls.refs",
+            Self::OutgoingCalls => "lsp-outgoing-calls.refs",
+            Self::Supertypes => "lsp-supertypes.refs",
+            Self::Subtypes => "lsp-subtypes.refs",
+        }
+    }
+
+    pub fn feature(self) -> ClientFeature {
+        match self {
+            Self::IncomingCalls | Self::OutgoingCalls => ClientFeature::CallHierarchy,
+            Self::Supertypes | Self::Subtypes => ClientFeature::TypeHierarchy,
+        }
+    }
+
+    fn prepare_method(self) -> &'static str {
+        match self {
+            Self::IncomingCalls | Self::OutgoingCalls => "textDocument/prepareCallHierarchy",
+            Self::Supertypes | Self::Subtypes => "textDocument/prepareTypeHierarchy",
+        }
+    }
+
+    fn method(self) -> &'static str {
+        match self {
+            Self::IncomingCalls => "callHierarchy/incomingCalls",
+            Self::OutgoingCalls => "callHierarchy/outgoingCalls",
+            Self::Supertypes => "typeHierarchy/supertypes",
+            Self::Subtypes => "typeHierarchy/subtypes",
+        }
+    }
+}
+
+pub(crate) struct HierarchyEntry {
+    pub depth: u32,
+    pub expanded: bool,
+    pub item: Vec<u8>,
+}
+
+pub(crate) struct Hierarchy {
+    pub kind: HierarchyKind,
+    pub buffer_handle: BufferHandle,
+    pub entries: Vec<HierarchyEntry>,
+}
+
+pub(crate) struct WorkDoneProgress {
+    pub token: String,
+    pub title: String,
+    pub message: String,
+    pub percentage: Option<u32>,
+}
+
+pub(crate) struct InlayHintRequest {
+    pub buffer_handle: BufferHandle,
+    pub line_range: Range<BufferPositionIndex>,
+    pub request_id: Option<RequestId>,
+    pub dirty: bool,
+}
+
+pub(crate) struct SemanticTokensRequest {
+    pub buffer_handle: BufferHandle,
+    pub request_id: Option<RequestId>,
+    pub dirty: bool,
+    pub result_id: String,
+    pub data: Vec<u32>,
+}
+
+pub(crate) struct DocumentHighlightRequest {
+    pub buffer_handle: BufferHandle,
+    pub position: BufferPosition,
+    pub request_id: Option<RequestId>,
+    pub dirty: bool,
+}
+
+pub(crate) struct CodeLens {
+    pub line_index: BufferPositionIndex,
+    pub title: String,
+    pub raw_json: Vec<u8>,
+    pub resolve_request_id: Option<RequestId>,
+}
+
+pub(crate) struct CodeLensRequest {
+    pub buffer_handle: BufferHandle,
+    pub request_id: Option<RequestId>,
+    pub dirty: bool,
+    pub lenses: Vec<CodeLens>,
+}
+impl CodeLensRequest {
+    pub fn insert_range(&mut self, range: BufferRange) {
+        for lens in &mut self.lenses {
+            let position = BufferPosition::line_col(lens.line_index, 0).insert(range);
+            lens.line_index = position.line_index;
+        }
+    }
+
+    pub fn delete_range(&mut self, range: BufferRange) {
+        for lens in &mut self.lenses {
+            let position = BufferPosition::line_col(lens.line_index, 0).delete(range);
+            lens.line_index = position.line_index;
+        }
+    }
+}
+
 struct VersionedBufferEdit {
     buffer_range: BufferRange,
     text_range: Range<u32>,
@@ -350,11 +651,50 @@ impl BufferDiagnosticDataCollection {
     }
 }
 
+pub(crate) struct WorkspaceDiagnostic {
+    pub message: String,
+    pub range: BufferRange,
+    pub severity: BufferLintSeverity,
+}
+
+struct PathDiagnostics {
+    path: PathBuf,
+    diagnostics: Vec<WorkspaceDiagnostic>,
+}
+
 #[derive(Default)]
 pub(crate) struct DiagnosticCollection {
     buffer_data_diagnostics: Vec<BufferDiagnosticDataCollection>,
+    path_diagnostics: Vec<PathDiagnostics>,
 }
 impl DiagnosticCollection {
+    pub fn get_path_diagnostics(&mut self, path: &Path) -> &mut Vec<WorkspaceDiagnostic> {
+        let index = match self
+            .path_diagnostics
+            .binary_search_by(|d| d.path.as_path().cmp(path))
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.path_diagnostics.insert(
+                    index,
+                    PathDiagnostics {
+                        path: path.into(),
+                        diagnostics: Vec::new(),
+                    },
+                );
+                index
+            }
+        };
+        &mut self.path_diagnostics[index].diagnostics
+    }
+
+    pub fn workspace_diagnostics(&self) -> impl Iterator<Item = (&Path, &[WorkspaceDiagnostic])> {
+        self.path_diagnostics
+            .iter()
+            .filter(|d| !d.diagnostics.is_empty())
+            .map(|d| (d.path.as_path(), &d.diagnostics[..]))
+    }
+
     pub fn get_buffer_diagnostics(
         &mut self,
         buffer_handle: BufferHandle,
@@ -397,6 +737,7 @@ pub(crate) enum RequestState {
     },
     CodeAction,
     FinishCodeAction,
+    FinishCodeLens,
     DocumentSymbols {
         buffer_view_handle: BufferViewHandle,
     },
@@ -412,6 +753,14 @@ pub(crate) enum RequestState {
         client_handle: client::ClientHandle,
         buffer_handle: BufferHandle,
     },
+    PrepareHierarchy {
+        client_handle: client::ClientHandle,
+        kind: HierarchyKind,
+    },
+    Hierarchy {
+        kind: HierarchyKind,
+        entry_index: usize,
+    },
 }
 impl RequestState {
     pub fn is_idle(&self) -> bool {
@@ -419,6 +768,67 @@ impl RequestState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClientFeature {
+    Completion,
+    SignatureHelp,
+    Hover,
+    Definition,
+    References,
+    Rename,
+    CodeAction,
+    Symbols,
+    Formatting,
+    Diagnostics,
+    InlayHints,
+    SemanticTokens,
+    CallHierarchy,
+    TypeHierarchy,
+    DocumentHighlight,
+    CodeLens,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ClientFeatures(u32);
+impl ClientFeatures {
+    pub const ALL: Self = Self(u32::MAX);
+
+    pub fn parse(features: &str) -> Result<Self, &str> {
+        if features.is_empty() {
+            return Ok(Self::ALL);
+        }
+
+        let mut flags = 0;
+        for name in features.split(',') {
+            let feature = match name {
+                "completion" => ClientFeature::Completion,
+                "signature-help" => ClientFeature::SignatureHelp,
+                "hover" => ClientFeature::Hover,
+                "definition" => ClientFeature::Definition,
+                "references" => ClientFeature::References,
+                "rename" => ClientFeature::Rename,
+                "code-action" => ClientFeature::CodeAction,
+                "symbols" => ClientFeature::Symbols,
+                "formatting" => ClientFeature::Formatting,
+                "diagnostics" => ClientFeature::Diagnostics,
+                "inlay-hints" => ClientFeature::InlayHints,
+                "semantic-tokens" => ClientFeature::SemanticTokens,
+                "call-hierarchy" => ClientFeature::CallHierarchy,
+                "type-hierarchy" => ClientFeature::TypeHierarchy,
+                "document-highlight" => ClientFeature::DocumentHighlight,
+                "code-lens" => ClientFeature::CodeLens,
+                _ => return Err(name),
+            };
+            flags |= 1 << feature as u32;
+        }
+        Ok(Self(flags))
+    }
+
+    pub fn contains(self, feature: ClientFeature) -> bool {
+        self.0 & (1 << feature as u32) != 0
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ClientHandle(pub(crate) u8);
 impl fmt::Display for ClientHandle {
@@ -444,11 +854,25 @@ pub struct Client {
     pub(crate) pending_requests: PendingRequestColection,
 
     pub(crate) initialized: bool,
+    pub(crate) server_name: String,
     pub(crate) server_capabilities: ServerCapabilities,
-
+    pub(crate) progress: Vec<WorkDoneProgress>,
+
+    pub(crate) path_glob: Option<Glob>,
+    pub(crate) features: ClientFeatures,
+    pub(crate) settings: String,
+    pub(crate) initialization_options: String,
+    pub(crate) workspace_folders: Vec<PathBuf>,
+    sent_workspace_folders_len: usize,
+    pub(crate) restrict_to_workspace_folders: bool,
     pub(crate) document_selectors: Vec<Glob>,
     pub(crate) versioned_buffers: VersionedBufferCollection,
     pub(crate) diagnostics: DiagnosticCollection,
+    pub(crate) inlay_hint_requests: Vec<InlayHintRequest>,
+    pub(crate) semantic_tokens_requests: Vec<SemanticTokensRequest>,
+    pub(crate) document_highlight_requests: Vec<DocumentHighlightRequest>,
+    pub(crate) code_lens_requests: Vec<CodeLensRequest>,
+    pub(crate) hierarchies: Vec<Hierarchy>,
 
     pub(crate) temp_edits: Vec<(BufferRange, BufferRange)>,
 
@@ -477,11 +901,25 @@ impl Client {
             pending_requests: PendingRequestColection::default(),
 
             initialized: false,
+            server_name: String::new(),
             server_capabilities: ServerCapabilities::default(),
-
+            progress: Vec::new(),
+
+            path_glob: None,
+            features: ClientFeatures::ALL,
+            settings: String::new(),
+            initialization_options: String::new(),
+            workspace_folders: Vec::new(),
+            sent_workspace_folders_len: 0,
+            restrict_to_workspace_folders: false,
             document_selectors: Vec::new(),
             versioned_buffers: VersionedBufferCollection::default(),
             diagnostics: DiagnosticCollection::default(),
+            inlay_hint_requests: Vec::new(),
+            semantic_tokens_requests: Vec::new(),
+            document_highlight_requests: Vec::new(),
+            code_lens_requests: Vec::new(),
+            hierarchies: Vec::new(),
 
             request_state: RequestState::Idle,
             request_raw_json: Vec::new(),
@@ -496,8 +934,31 @@ impl Client {
         self.handle
     }
 
+    pub fn syncs_path(&self, path: &str) -> bool {
+        let matches_glob = match &self.path_glob {
+            Some(glob) => glob.matches(path),
+            None => true,
+        };
+        matches_glob && self.is_path_in_workspace_folders(path)
+    }
+
+    fn is_path_in_workspace_folders(&self, path: &str) -> bool {
+        if !self.restrict_to_workspace_folders || self.workspace_folders.is_empty() {
+            return true;
+        }
+        let path = self.root.join(path);
+        self.workspace_folders.iter().any(|f| path.starts_with(f))
+    }
+
+    pub fn syncs_buffer(&self, buffer: &Buffer) -> bool {
+        let path = buffer.path.to_str();
+        path != self.log_file_path() && self.syncs_path(path.unwrap_or(""))
+    }
+
     pub fn handles_path(&self, path: &str) -> bool {
-        if self.document_selectors.is_empty() {
+        if !self.syncs_path(path) {
+            false
+        } else if self.document_selectors.is_empty() {
             true
         } else {
             self.document_selectors.iter().any(|g| g.matches(path))
@@ -512,6 +973,116 @@ impl Client {
         }
     }
 
+    /// Applies a `$/progress` notification's `begin`, `report` or `end` value to the progress
+    /// in flight, returning false if it was ignored.
+    pub fn update_progress(
+        &mut self,
+        token: String,
+        kind: &str,
+        title: Option<String>,
+        message: Option<String>,
+        percentage: Option<u32>,
+    ) -> bool {
+        let index = self.progress.iter().position(|p| p.token == token);
+        match (kind, index) {
+            ("begin", _) => {
+                if let Some(index) = index {
+                    self.progress.remove(index);
+                }
+                self.progress.push(WorkDoneProgress {
+                    token,
+                    title: title.unwrap_or_default(),
+                    message: message.unwrap_or_default(),
+                    percentage,
+                });
+            }
+            ("report", Some(index)) => {
+                let progress = &mut self.progress[index];
+                if let Some(message) = message {
+                    progress.message = message;
+                }
+                if percentage.is_some() {
+                    progress.percentage = percentage;
+                }
+            }
+            ("end", Some(index)) => {
+                self.progress.remove(index);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn write_progress_indicator(&self, buf: &mut String) {
+        use fmt::Write;
+
+        let progress = match self.progress.last() {
+            Some(progress) => progress,
+            None => return,
+        };
+
+        match self.server_name.as_str() {
+            "" => buf.push_str("lsp"),
+            name => buf.push_str(name),
+        }
+        let _ = write!(buf, ": {}", progress.title);
+        match progress.percentage {
+            Some(percentage) => {
+                let _ = write!(buf, " {}%", percentage);
+            }
+            None if !progress.message.is_empty() => {
+                let _ = write!(buf, " {}", progress.message);
+            }
+            None => (),
+        }
+    }
+
+    pub fn write_status(&self, buf: &mut String) {
+        use fmt::Write;
+
+        let _ = write!(buf, "lsp server {}", self.handle);
+        if !self.server_name.is_empty() {
+            let _ = write!(buf, " '{}'", self.server_name);
+        }
+        if !self.initialized {
+            buf.push_str(" (starting)");
+        }
+
+        let _ = write!(buf, "\n  root: {}", self.root.display());
+        if !self.workspace_folders.is_empty() {
+            buf.push_str("\n  workspace folders:");
+            for folder in &self.workspace_folders {
+                let _ = write!(buf, " {}", folder.display());
+            }
+        }
+        if let Some(path) = self.log_file_path() {
+            let _ = write!(buf, "\n  log: {}", path);
+        }
+
+        buf.push_str("\n  pending requests:");
+        let mut has_pending_requests = false;
+        for method in self.pending_requests.methods() {
+            has_pending_requests = true;
+            let _ = write!(buf, " {}", method);
+        }
+        if !has_pending_requests {
+            buf.push_str(" none");
+        }
+
+        for progress in &self.progress {
+            let _ = write!(buf, "\n  progress: {}", progress.title);
+            if !progress.message.is_empty() {
+                let _ = write!(buf, " {}", progress.message);
+            }
+            if let Some(percentage) = progress.percentage {
+                let _ = write!(buf, " {}%", percentage);
+            }
+        }
+
+        buf.push_str("\n  capabilities: ");
+        self.server_capabilities.write_names(buf);
+    }
+
     pub fn signature_help_triggers(&self) -> &str {
         &self
             .server_capabilities
@@ -678,6 +1249,126 @@ impl Client {
         self.request(platform, "textDocument/references", params);
     }
 
+    fn has_hierarchy_provider(&self, kind: HierarchyKind) -> bool {
+        match kind.feature() {
+            ClientFeature::CallHierarchy => self.server_capabilities.call_hierarchy_provider.0,
+            _ => self.server_capabilities.type_hierarchy_provider.0,
+        }
+    }
+
+    pub(crate) fn prepare_hierarchy(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        kind: HierarchyKind,
+        buffer_handle: BufferHandle,
+        buffer_position: BufferPosition,
+        client_handle: client::ClientHandle,
+    ) {
+        if !self.has_hierarchy_provider(kind) || !self.request_state.is_idle() {
+            return;
+        }
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let position = DocumentPosition::from_buffer_position(buffer_position);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+        params.set(
+            "position".into(),
+            position.to_json_value(&mut self.json),
+            &mut self.json,
+        );
+
+        self.request_state = RequestState::PrepareHierarchy {
+            client_handle,
+            kind,
+        };
+        self.request(platform, kind.prepare_method(), params);
+    }
+
+    pub(crate) fn has_hierarchy_buffer(
+        &self,
+        kind: HierarchyKind,
+        buffer_handle: BufferHandle,
+    ) -> bool {
+        self.hierarchies
+            .iter()
+            .any(|h| h.kind == kind && h.buffer_handle == buffer_handle)
+    }
+
+    pub(crate) fn toggle_hierarchy_entry(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        kind: HierarchyKind,
+        line_index: BufferPositionIndex,
+    ) {
+        let hierarchy = match self.hierarchies.iter_mut().find(|h| h.kind == kind) {
+            Some(hierarchy) => hierarchy,
+            None => return,
+        };
+        let index = line_index as usize;
+        let entry = match hierarchy.entries.get_mut(index) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        if entry.expanded {
+            entry.expanded = false;
+            let depth = entry.depth;
+            let count = hierarchy.entries[index + 1..]
+                .iter()
+                .take_while(|e| e.depth > depth)
+                .count();
+            hierarchy.entries.drain(index + 1..index + 1 + count);
+
+            let buffer = editor.buffers.get_mut(hierarchy.buffer_handle);
+            let range = BufferRange::between(
+                BufferPosition::line_col(line_index + 1, 0),
+                BufferPosition::line_col(line_index + 1 + count as BufferPositionIndex, 0),
+            );
+            buffer.delete_range(&mut editor.word_database, range, &mut editor.events);
+        } else {
+            self.request_hierarchy_children(platform, kind, index);
+        }
+    }
+
+    pub(crate) fn request_hierarchy_children(
+        &mut self,
+        platform: &mut Platform,
+        kind: HierarchyKind,
+        entry_index: usize,
+    ) {
+        if !self.request_state.is_idle() {
+            return;
+        }
+
+        let entry = match self
+            .hierarchies
+            .iter()
+            .find(|h| h.kind == kind)
+            .and_then(|h| h.entries.get(entry_index))
+        {
+            Some(entry) => entry,
+            None => return,
+        };
+        let mut reader = io::Cursor::new(&entry.item);
+        let item = match self.json.read(&mut reader) {
+            Ok(item) => item,
+            Err(_) => return,
+        };
+
+        let mut params = JsonObject::default();
+        params.set("item".into(), item, &mut self.json);
+
+        self.request_state = RequestState::Hierarchy { kind, entry_index };
+        self.request(platform, kind.method(), params);
+    }
+
     pub fn rename(
         &mut self,
         ctx: &mut EditorContext,
@@ -751,31 +1442,389 @@ impl Client {
         self.request(platform, "textDocument/rename", params);
     }
 
-    pub fn code_action(
+    pub fn inlay_hints(
         &mut self,
         editor: &Editor,
         platform: &mut Platform,
-        plugin_handle: PluginHandle,
         buffer_handle: BufferHandle,
-        range: BufferRange,
+        line_range: Range<BufferPositionIndex>,
     ) {
-        if !self.server_capabilities.code_action_provider.0 || !self.request_state.is_idle() {
+        if !self.server_capabilities.inlay_hint_provider.0 {
             return;
         }
 
+        let index = match self
+            .inlay_hint_requests
+            .iter()
+            .position(|r| r.buffer_handle == buffer_handle)
+        {
+            Some(index) => {
+                let request = &self.inlay_hint_requests[index];
+                if !request.dirty && request.line_range == line_range {
+                    return;
+                }
+                index
+            }
+            None => {
+                self.inlay_hint_requests.push(InlayHintRequest {
+                    buffer_handle,
+                    line_range: 0..0,
+                    request_id: None,
+                    dirty: true,
+                });
+                self.inlay_hint_requests.len() - 1
+            }
+        };
+
         util::send_pending_did_change(self, editor, platform);
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let range = DocumentRange::from_buffer_range(BufferRange::between(
+            BufferPosition::line_col(line_range.start, 0),
+            BufferPosition::line_col(line_range.end, 0),
+        ));
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+        params.set(
+            "range".into(),
+            range.to_json_value(&mut self.json),
+            &mut self.json,
+        );
+
+        let request_id = self.request(platform, "textDocument/inlayHint", params);
+        let request = &mut self.inlay_hint_requests[index];
+        request.line_range = line_range;
+        request.request_id = request_id;
+        request.dirty = request_id.is_none();
+    }
+
+    pub fn invalidate_inlay_hints(&mut self, buffer_handle: Option<BufferHandle>) {
+        for request in &mut self.inlay_hint_requests {
+            match buffer_handle {
+                Some(handle) if handle != request.buffer_handle => (),
+                _ => request.dirty = true,
+            }
+        }
+    }
+
+    pub fn semantic_tokens(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+    ) {
+        if !self.server_capabilities.semantic_tokens_provider.on {
+            return;
+        }
+
+        let index = match self
+            .semantic_tokens_requests
+            .iter()
+            .position(|r| r.buffer_handle == buffer_handle)
+        {
+            Some(index) => index,
+            None => {
+                self.semantic_tokens_requests.push(SemanticTokensRequest {
+                    buffer_handle,
+                    request_id: None,
+                    dirty: true,
+                    result_id: String::new(),
+                    data: Vec::new(),
+                });
+                self.semantic_tokens_requests.len() - 1
+            }
+        };
+        if !self.semantic_tokens_requests[index].dirty {
+            return;
+        }
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+
+        let result_id = &self.semantic_tokens_requests[index].result_id;
+        let method =
+            if self.server_capabilities.semantic_tokens_provider.delta && !result_id.is_empty() {
+                let result_id = self.json.create_string(result_id);
+                params.set("previousResultId".into(), result_id.into(), &mut self.json);
+                "textDocument/semanticTokens/full/delta"
+            } else {
+                "textDocument/semanticTokens/full"
+            };
+
+        let request_id = self.request(platform, method, params);
+        let request = &mut self.semantic_tokens_requests[index];
+        request.request_id = request_id;
+        request.dirty = request_id.is_none();
+    }
+
+    pub fn invalidate_semantic_tokens(&mut self, buffer_handle: Option<BufferHandle>) {
+        for request in &mut self.semantic_tokens_requests {
+            match buffer_handle {
+                Some(handle) if handle != request.buffer_handle => (),
+                _ => request.dirty = true,
+            }
+        }
+    }
+
+    pub fn document_highlights(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        position: BufferPosition,
+    ) {
+        if !self.server_capabilities.document_highlight_provider.0 {
+            return;
+        }
+
+        let index = match self
+            .document_highlight_requests
+            .iter()
+            .position(|r| r.buffer_handle == buffer_handle)
+        {
+            Some(index) => {
+                let request = &self.document_highlight_requests[index];
+                if !request.dirty && request.position == position {
+                    return;
+                }
+                index
+            }
+            None => {
+                self.document_highlight_requests
+                    .push(DocumentHighlightRequest {
+                        buffer_handle,
+                        position,
+                        request_id: None,
+                        dirty: true,
+                    });
+                self.document_highlight_requests.len() - 1
+            }
+        };
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+        let document_position = DocumentPosition::from_buffer_position(position);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+        params.set(
+            "position".into(),
+            document_position.to_json_value(&mut self.json),
+            &mut self.json,
+        );
+
+        let request_id = self.request(platform, "textDocument/documentHighlight", params);
+        let request = &mut self.document_highlight_requests[index];
+        request.position = position;
+        request.request_id = request_id;
+        request.dirty = request_id.is_none();
+    }
+
+    pub fn invalidate_document_highlights(&mut self, buffer_handle: Option<BufferHandle>) {
+        for request in &mut self.document_highlight_requests {
+            match buffer_handle {
+                Some(handle) if handle != request.buffer_handle => (),
+                _ => request.dirty = true,
+            }
+        }
+    }
+
+    pub fn code_lenses(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+    ) {
+        if !self.server_capabilities.code_lens_provider.on {
+            return;
+        }
+
+        let index = match self
+            .code_lens_requests
+            .iter()
+            .position(|r| r.buffer_handle == buffer_handle)
+        {
+            Some(index) => index,
+            None => {
+                self.code_lens_requests.push(CodeLensRequest {
+                    buffer_handle,
+                    request_id: None,
+                    dirty: true,
+                    lenses: Vec::new(),
+                });
+                self.code_lens_requests.len() - 1
+            }
+        };
+        if !self.code_lens_requests[index].dirty {
+            return;
+        }
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+
+        let request_id = self.request(platform, "textDocument/codeLens", params);
+        let request = &mut self.code_lens_requests[index];
+        request.request_id = request_id;
+        request.dirty = request_id.is_none();
+    }
+
+    pub fn code_lens_request_mut(
+        &mut self,
+        buffer_handle: BufferHandle,
+    ) -> Option<&mut CodeLensRequest> {
+        self.code_lens_requests
+            .iter_mut()
+            .find(|r| r.buffer_handle == buffer_handle)
+    }
+
+    pub fn invalidate_code_lenses(&mut self, buffer_handle: Option<BufferHandle>) {
+        for request in &mut self.code_lens_requests {
+            match buffer_handle {
+                Some(handle) if handle != request.buffer_handle => (),
+                _ => request.dirty = true,
+            }
+        }
+    }
+
+    pub fn resolve_code_lens(
+        &mut self,
+        platform: &mut Platform,
+        code_lens: JsonObject,
+    ) -> Option<RequestId> {
+        if !self.server_capabilities.code_lens_provider.resolve_provider {
+            return None;
+        }
+        self.request(platform, "codeLens/resolve", code_lens)
+    }
+
+    pub fn has_line_code_lens(
+        &self,
+        buffer_handle: BufferHandle,
+        line_index: BufferPositionIndex,
+    ) -> bool {
+        self.code_lens_requests
+            .iter()
+            .filter(|r| r.buffer_handle == buffer_handle)
+            .flat_map(|r| r.lenses.iter())
+            .any(|l| l.line_index == line_index && !l.title.is_empty())
+    }
+
+    pub fn line_code_lenses(
+        &mut self,
+        editor: &mut Editor,
+        buffer_handle: BufferHandle,
+        line_index: BufferPositionIndex,
+    ) -> bool {
+        if !self.server_capabilities.code_lens_provider.on || !self.request_state.is_idle() {
+            return false;
+        }
+
+        let request = match self
+            .code_lens_requests
+            .iter()
+            .find(|r| r.buffer_handle == buffer_handle)
+        {
+            Some(request) => request,
+            None => return false,
+        };
+
+        editor.picker.clear();
+        let mut commands = JsonArray::default();
+        for lens in request
+            .lenses
+            .iter()
+            .filter(|l| l.line_index == line_index && !l.title.is_empty())
+        {
+            let mut reader = io::Cursor::new(&lens.raw_json);
+            let lens_json = match self.json.read(&mut reader) {
+                Ok(lens_json) => lens_json,
+                Err(_) => continue,
+            };
+            let command = lens_json.get("command", &self.json);
+            editor.picker.add_custom_entry(&lens.title);
+            commands.push(command, &mut self.json);
+        }
+
+        self.request_state = RequestState::FinishCodeLens;
+        self.request_raw_json.clear();
+        let _ = self
+            .json
+            .write(&mut self.request_raw_json, &commands.into());
+        true
+    }
+
+    pub(crate) fn finish_code_lens(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        index: usize,
+    ) {
+        match self.request_state {
+            RequestState::FinishCodeLens => (),
+            _ => return,
+        }
+        self.request_state = RequestState::Idle;
+
+        let mut reader = io::Cursor::new(&self.request_raw_json);
+        let commands = match self.json.read(&mut reader) {
+            Ok(commands) => commands,
+            Err(_) => return,
+        };
+        if let Some(command) = commands
+            .elements(&self.json)
+            .nth(index)
+            .and_then(|c| DocumentCommand::from_json(c, &self.json).ok())
+        {
+            util::send_pending_did_change(self, editor, platform);
+            self.execute_command(platform, command);
+        }
+    }
+
+    pub fn semantic_token_kind(&self, token_type: u32, modifiers: u32) -> Option<TokenKind> {
+        self.server_capabilities
+            .semantic_tokens_provider
+            .token_kind(token_type, modifiers)
+    }
+
+    pub fn code_action(
+        &mut self,
+        editor: &Editor,
+        platform: &mut Platform,
+        plugin_handle: PluginHandle,
+        buffer_handle: BufferHandle,
+        range: BufferRange,
+    ) {
+        if !self.server_capabilities.code_action_provider.0 || !self.request_state.is_idle() {
+            return;
+        }
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
+
+        let mut diagnostics = JsonArray::default();
 
-        let mut diagnostics = JsonArray::default();
-
         let buffer_diagnostics = self.diagnostics.get_buffer_diagnostics(buffer_handle);
         for (i, lint) in buffer
             .lints
             .all()
             .iter()
-            .filter(|l| l.plugin_handle == plugin_handle)
+            .filter(|l| l.plugin_handle == plugin_handle && l.source == self.handle.0 as u32)
             .enumerate()
         {
             if lint.range.from <= range.from && range.from < lint.range.to
@@ -810,7 +1859,12 @@ impl Client {
         self.request(platform, "textDocument/codeAction", params);
     }
 
-    pub(crate) fn finish_code_action(&mut self, editor: &mut Editor, index: usize) {
+    pub(crate) fn finish_code_action(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        index: usize,
+    ) {
         match self.request_state {
             RequestState::FinishCodeAction => (),
             _ => return,
@@ -825,15 +1879,32 @@ impl Client {
             Ok(actions) => actions,
             Err(_) => return,
         };
-        if let Some(edit) = code_actions
+        if let Some(action) = code_actions
             .elements(&self.json)
             .filter_map(|a| DocumentCodeAction::from_json(a, &self.json).ok())
             .filter(|a| !a.disabled)
-            .map(|a| a.edit)
             .nth(index)
         {
-            edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+            if !action
+                .edit
+                .apply(editor, &mut self.temp_edits, &self.root, &self.json)
+            {
+                return;
+            }
+            if let Some(command) = action.command {
+                util::send_pending_did_change(self, editor, platform);
+                self.execute_command(platform, command);
+            }
+        }
+    }
+
+    fn execute_command(&mut self, platform: &mut Platform, command: DocumentCommand) {
+        let mut params = JsonObject::default();
+        params.set("command".into(), command.command.into(), &mut self.json);
+        if let Some(arguments) = command.arguments {
+            params.set("arguments".into(), arguments.into(), &mut self.json);
         }
+        self.request(platform, "workspace/executeCommand", params);
     }
 
     pub fn document_symbols(
@@ -1001,17 +2072,17 @@ impl Client {
 
         util::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = util::text_document_with_id(&self.root, buffer_path, &mut self.json);
+        let buffer = editor.buffers.get(buffer_handle);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
         let mut options = JsonObject::default();
         options.set(
             "tabSize".into(),
-            JsonValue::Integer(editor.config.tab_size.get() as _),
+            JsonValue::Integer(buffer.editorconfig.indent_size(&editor.config).get() as _),
             &mut self.json,
         );
         options.set(
             "insertSpaces".into(),
-            (!editor.config.indent_with_tabs).into(),
+            (!buffer.editorconfig.indent_with_tabs(&editor.config)).into(),
             &mut self.json,
         );
         options.set("trimTrailingWhitespace".into(), true.into(), &mut self.json);
@@ -1071,9 +2142,14 @@ impl Client {
         }
     }
 
-    fn request(&mut self, platform: &mut Platform, method: &'static str, params: JsonObject) {
+    fn request(
+        &mut self,
+        platform: &mut Platform,
+        method: &'static str,
+        params: JsonObject,
+    ) -> Option<RequestId> {
         if !self.initialized {
-            return;
+            return None;
         }
 
         let params = params.into();
@@ -1087,6 +2163,7 @@ impl Client {
             .request(platform, &mut self.json, method, params);
 
         self.pending_requests.add(id, method);
+        Some(id)
     }
 
     pub(crate) fn respond(
@@ -1135,6 +2212,70 @@ impl Client {
             .notify(platform, &mut self.json, method, params);
     }
 
+    pub fn has_workspace_folder(&self, folder: &Path) -> bool {
+        self.workspace_folders.iter().any(|f| f == folder)
+    }
+
+    pub fn supports_workspace_folder_changes(&self) -> bool {
+        self.initialized
+            && self
+                .server_capabilities
+                .workspace
+                .workspace_folders_change_notifications
+    }
+
+    /// Returns false when the server can't be notified of the new folder
+    /// (in which case another server should be started for it).
+    pub fn add_workspace_folder(&mut self, platform: &mut Platform, folder: PathBuf) -> bool {
+        if self.has_workspace_folder(&folder) {
+            return true;
+        }
+        if !self.supports_workspace_folder_changes() {
+            return false;
+        }
+        self.workspace_folders.push(folder);
+        self.send_pending_workspace_folders(platform);
+        true
+    }
+
+    pub fn send_pending_workspace_folders(&mut self, platform: &mut Platform) {
+        if !self.supports_workspace_folder_changes()
+            || self.sent_workspace_folders_len == self.workspace_folders.len()
+        {
+            return;
+        }
+
+        let mut added = JsonArray::default();
+        for folder in &self.workspace_folders[self.sent_workspace_folders_len..] {
+            let folder = util::workspace_folder(folder, &mut self.json);
+            added.push(folder.into(), &mut self.json);
+        }
+        self.sent_workspace_folders_len = self.workspace_folders.len();
+
+        let mut event = JsonObject::default();
+        event.set("added".into(), added.into(), &mut self.json);
+        event.set(
+            "removed".into(),
+            JsonArray::default().into(),
+            &mut self.json,
+        );
+
+        let mut params = JsonObject::default();
+        params.set("event".into(), event.into(), &mut self.json);
+        self.notify(platform, "workspace/didChangeWorkspaceFolders", params);
+    }
+
+    pub fn did_change_configuration(&mut self, platform: &mut Platform) {
+        if !self.initialized {
+            return;
+        }
+
+        let settings = util::parse_json_text(&self.settings, &mut self.json);
+        let mut params = JsonObject::default();
+        params.set("settings".into(), settings, &mut self.json);
+        self.notify(platform, "workspace/didChangeConfiguration", params);
+    }
+
     pub fn initialize(&mut self, platform: &mut Platform) {
         let mut params = JsonObject::default();
         params.set(
@@ -1152,17 +2293,35 @@ impl Client {
         );
         params.set("clientInfo".into(), client_info.into(), &mut self.json);
 
-        let root = self
-            .json
-            .fmt_string(format_args!("{}", Uri::Path(&self.root)));
+        let root = self.workspace_folders.first().unwrap_or(&self.root);
+        let root = self.json.fmt_string(format_args!("{}", Uri::Path(root)));
         params.set("rootUri".into(), root.into(), &mut self.json);
 
+        if !self.workspace_folders.is_empty() {
+            let mut workspace_folders = JsonArray::default();
+            for folder in &self.workspace_folders {
+                let folder = util::workspace_folder(folder, &mut self.json);
+                workspace_folders.push(folder.into(), &mut self.json);
+            }
+            params.set(
+                "workspaceFolders".into(),
+                workspace_folders.into(),
+                &mut self.json,
+            );
+        }
+        self.sent_workspace_folders_len = self.workspace_folders.len();
+
         params.set(
             "capabilities".into(),
             capabilities::client_capabilities(&mut self.json),
             &mut self.json,
         );
 
+        if !self.initialization_options.is_empty() {
+            let options = util::parse_json_text(&self.initialization_options, &mut self.json);
+            params.set("initializationOptions".into(), options, &mut self.json);
+        }
+
         self.initialized = true;
         self.request(platform, "initialize", params);
         self.initialized = false;
@@ -1189,6 +2348,33 @@ pub(crate) mod util {
         }
     }
 
+    pub fn workspace_folder(path: &Path, json: &mut Json) -> JsonObject {
+        let uri = json.fmt_string(format_args!("{}", Uri::Path(path)));
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let name = json.create_string(name);
+
+        let mut folder = JsonObject::default();
+        folder.set("uri".into(), uri.into(), json);
+        folder.set("name".into(), name.into(), json);
+        folder
+    }
+
+    pub fn parse_json_text(text: &str, json: &mut Json) -> JsonValue {
+        if text.is_empty() {
+            return JsonValue::Null;
+        }
+        let mut reader = text.as_bytes();
+        json.read(&mut reader).unwrap_or(JsonValue::Null)
+    }
+
+    pub fn settings_section(settings: JsonValue, section: &str, json: &Json) -> JsonValue {
+        let mut value = settings;
+        for key in section.split('.') {
+            value = value.get(key, json);
+        }
+        value
+    }
+
     pub fn write_response_error(status_bar: &mut StatusBar, error: ResponseError, json: &Json) {
         status_bar
             .write(MessageKind::Error)
@@ -1260,7 +2446,7 @@ pub(crate) mod util {
         }
 
         let buffer = editor.buffers.get(buffer_handle);
-        if !buffer.properties.saving_enabled {
+        if !buffer.properties.saving_enabled || !client.syncs_buffer(buffer) {
             return;
         }
 
@@ -1407,3 +2593,154 @@ pub(crate) mod util {
         client.notify(platform, "textDocument/didClose", params);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_features_parse() {
+        let all = ClientFeatures::parse("").unwrap();
+        assert!(all.contains(ClientFeature::Completion));
+        assert!(all.contains(ClientFeature::CodeLens));
+
+        let features = ClientFeatures::parse("completion,hover,code-lens").unwrap();
+        assert!(features.contains(ClientFeature::Completion));
+        assert!(features.contains(ClientFeature::Hover));
+        assert!(features.contains(ClientFeature::CodeLens));
+        assert!(!features.contains(ClientFeature::Diagnostics));
+        assert!(!features.contains(ClientFeature::Definition));
+
+        let features = ClientFeatures::parse("diagnostics").unwrap();
+        assert!(features.contains(ClientFeature::Diagnostics));
+        assert!(!features.contains(ClientFeature::Completion));
+
+        assert_eq!(Err("hovr"), ClientFeatures::parse("hover,hovr").map(|_| ()));
+        assert_eq!(Err(""), ClientFeatures::parse("hover,").map(|_| ()));
+    }
+
+    #[test]
+    fn workspace_folders_restriction() {
+        let root = PathBuf::from("/project");
+        let mut client = Client::new(ClientHandle(0), root.clone(), None);
+        client.workspace_folders.push(root.join("a"));
+
+        assert!(client.syncs_path("a/src/main.rs"));
+        assert!(client.syncs_path("b/src/main.rs"));
+
+        client.restrict_to_workspace_folders = true;
+        assert!(client.syncs_path("a/src/main.rs"));
+        assert!(client.syncs_path("/project/a/src/main.rs"));
+        assert!(!client.syncs_path("b/src/main.rs"));
+        assert!(!client.syncs_path("ab/src/main.rs"));
+
+        assert!(client.has_workspace_folder(&root.join("a")));
+        assert!(!client.supports_workspace_folder_changes());
+    }
+
+    #[test]
+    fn work_done_progress() {
+        fn indicator(client: &Client) -> String {
+            let mut buf = String::new();
+            client.write_progress_indicator(&mut buf);
+            buf
+        }
+        fn some(text: &str) -> Option<String> {
+            Some(text.into())
+        }
+
+        let mut client = Client::new(ClientHandle(0), PathBuf::new(), None);
+        client.server_name.push_str("server");
+        assert_eq!("", indicator(&client));
+
+        assert!(!client.update_progress("1".into(), "report", None, some("x"), None));
+        assert!(!client.update_progress("1".into(), "end", None, None, None));
+        assert!(!client.update_progress("1".into(), "other", some("title"), None, None));
+        assert!(client.progress.is_empty());
+
+        assert!(client.update_progress("1".into(), "begin", some("indexing"), None, None));
+        assert_eq!("server: indexing", indicator(&client));
+
+        assert!(client.update_progress("1".into(), "report", None, some("1/3"), None));
+        assert_eq!("server: indexing 1/3", indicator(&client));
+
+        assert!(client.update_progress("2".into(), "begin", some("build"), None, Some(0)));
+        assert_eq!("server: build 0%", indicator(&client));
+
+        assert!(client.update_progress("2".into(), "report", None, None, Some(50)));
+        assert_eq!("server: build 50%", indicator(&client));
+        assert_eq!(2, client.progress.len());
+
+        assert!(client.update_progress("2".into(), "end", None, None, None));
+        assert_eq!("server: indexing 1/3", indicator(&client));
+
+        assert!(client.update_progress("1".into(), "begin", some("again"), None, None));
+        assert_eq!(1, client.progress.len());
+        assert_eq!("server: again", indicator(&client));
+
+        assert!(client.update_progress("1".into(), "end", None, None, None));
+        assert!(client.progress.is_empty());
+        assert_eq!("", indicator(&client));
+    }
+
+    #[test]
+    fn semantic_token_kinds() {
+        let mut json = Json::new();
+        let options = util::parse_json_text(
+            r#"{"full":{"delta":true},"legend":{
+                "tokenTypes":["function","method","variable","parameter","property","label","type","unknown"],
+                "tokenModifiers":["declaration","readonly","deprecated"]
+            }}"#,
+            &mut json,
+        );
+        let capability = match SemanticTokensCapability::from_json(options, &json) {
+            Ok(capability) => capability,
+            Err(_) => panic!("could not parse semantic tokens capability"),
+        };
+        assert!(capability.on);
+        assert!(capability.delta);
+
+        let kind = |token_type, modifiers| capability.token_kind(token_type, modifiers);
+        assert_eq!(Some(TokenKind::Function), kind(0, 0));
+        assert_eq!(Some(TokenKind::Function), kind(1, 0b001));
+        assert_eq!(Some(TokenKind::Variable), kind(2, 0));
+        assert_eq!(Some(TokenKind::Variable), kind(3, 0));
+        assert_eq!(Some(TokenKind::Property), kind(4, 0));
+        assert_eq!(Some(TokenKind::Text), kind(5, 0));
+        assert_eq!(Some(TokenKind::Type), kind(6, 0));
+        assert_eq!(None, kind(7, 0));
+        assert_eq!(None, kind(8, 0));
+
+        assert_eq!(Some(TokenKind::Literal), kind(2, 0b010));
+        assert_eq!(Some(TokenKind::Literal), kind(4, 0b011));
+        assert_eq!(Some(TokenKind::Function), kind(0, 0b010));
+        assert_eq!(Some(TokenKind::Comment), kind(0, 0b100));
+        assert_eq!(Some(TokenKind::Comment), kind(2, 0b110));
+    }
+
+    #[test]
+    fn settings_section_lookup() {
+        fn section(settings: &str, section: &str) -> String {
+            let mut json = Json::new();
+            let settings = util::parse_json_text(settings, &mut json);
+            let value = util::settings_section(settings, section, &json);
+            let mut buf = Vec::new();
+            json.write(&mut buf, &value).unwrap();
+            String::from_utf8(buf).unwrap()
+        }
+
+        let settings = r#"{"rust-analyzer": {"checkOnSave": {"command": "clippy"}}, "x": 1}"#;
+        assert_eq!("1", section(settings, "x"));
+        assert_eq!(
+            r#"{"command":"clippy"}"#,
+            section(settings, "rust-analyzer.checkOnSave")
+        );
+        assert_eq!(
+            r#""clippy""#,
+            section(settings, "rust-analyzer.checkOnSave.command")
+        );
+        assert_eq!("null", section(settings, "rust-analyzer.missing"));
+        assert_eq!("null", section(settings, "x.y"));
+        assert_eq!("null", section("", "x"));
+    }
+}
@@ -1,29 +1,33 @@
 use std::{cmp::Ord, fmt, fs::File, io, path::Path};
 
 use pepper::{
-    buffer::{BufferContent, BufferProperties},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::{BufferContent, BufferProperties, BufferSymbolHighlightKind},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     client,
     cursor::Cursor,
     editor::EditorContext,
     editor_utils::MessageKind,
     glob::Glob,
     mode::ModeKind,
-    picker::Picker,
+    picker::{CompletionSource, Picker},
     plugin::PluginHandle,
     word_database::WordKind,
 };
 
 use crate::{
-    client::{util, Client, RequestState, ServerCapabilities},
+    client::{
+        util, Client, ClientFeature, CodeLens, Hierarchy, HierarchyEntry, HierarchyKind,
+        RequestState, ServerCapabilities, WorkspaceDiagnostic,
+    },
     json::{
         FromJson, Json, JsonArray, JsonConvertError, JsonInteger, JsonObject, JsonString, JsonValue,
     },
     mode::{picker, read_line},
     protocol::{
-        DocumentCodeAction, DocumentCompletionItem, DocumentDiagnostic, DocumentLocation,
-        DocumentPosition, DocumentRange, DocumentSymbolInformation, ProtocolError,
-        ServerNotification, ServerRequest, ServerResponse, TextEdit, Uri, WorkspaceEdit,
+        self, DocumentCodeAction, DocumentCodeLens, DocumentCompletionItem, DocumentDiagnostic,
+        DocumentHierarchyItem, DocumentInlayHint, DocumentLocation, DocumentPosition,
+        DocumentRange, DocumentSymbolInformation, ProtocolError, ServerNotification, ServerRequest,
+        ServerResponse, TextEdit, Uri, WorkspaceEdit,
     },
 };
 
@@ -114,6 +118,58 @@ pub(crate) fn on_request(
             }
             Ok(JsonValue::Null)
         }
+        "workspace/semanticTokens/refresh" => {
+            client.invalidate_semantic_tokens(None);
+            Ok(JsonValue::Null)
+        }
+        "workspace/inlayHint/refresh" => {
+            client.invalidate_inlay_hints(None);
+            Ok(JsonValue::Null)
+        }
+        "workspace/codeLens/refresh" => {
+            client.invalidate_code_lenses(None);
+            Ok(JsonValue::Null)
+        }
+        "window/workDoneProgress/create" => Ok(JsonValue::Null),
+        "workspace/applyEdit" => {
+            let edit = request.params.get("edit", &client.json);
+            let edit = WorkspaceEdit::from_json(edit, &client.json)?;
+            let applied = edit.apply(
+                &mut ctx.editor,
+                &mut client.temp_edits,
+                &client.root,
+                &client.json,
+            );
+
+            let mut result = JsonObject::default();
+            result.set("applied".into(), applied.into(), &mut client.json);
+            Ok(result.into())
+        }
+        "workspace/configuration" => {
+            let settings = util::parse_json_text(&client.settings, &mut client.json);
+            let mut values = Vec::new();
+            for item in request
+                .params
+                .get("items", &client.json)
+                .elements(&client.json)
+            {
+                let value = match item.get("section", &client.json) {
+                    JsonValue::String(section) => util::settings_section(
+                        settings.clone(),
+                        section.as_str(&client.json),
+                        &client.json,
+                    ),
+                    _ => settings.clone(),
+                };
+                values.push(value);
+            }
+
+            let mut results = JsonArray::default();
+            for value in values {
+                results.push(value, &mut client.json);
+            }
+            Ok(results.into())
+        }
         "window/showMessage" => {
             fn parse_params(
                 params: JsonValue,
@@ -297,10 +353,27 @@ pub(crate) fn on_notification(
                 }
             }
 
+            if !client.features.contains(ClientFeature::Diagnostics) {
+                return Ok(());
+            }
+
             let params = Params::from_json(notification.params, &client.json)?;
             let uri = params.uri.as_str(&client.json);
             let Uri::Path(path) = Uri::parse(&client.root, uri)?;
 
+            let workspace_diagnostics = client
+                .diagnostics
+                .get_path_diagnostics(&client.root.join(path));
+            workspace_diagnostics.clear();
+            for diagnostic in params.diagnostics.clone().elements(&client.json) {
+                let diagnostic = DocumentDiagnostic::from_json(diagnostic, &client.json)?;
+                workspace_diagnostics.push(WorkspaceDiagnostic {
+                    message: diagnostic.message.as_str(&client.json).into(),
+                    range: diagnostic.range.into_buffer_range(),
+                    severity: protocol::lint_severity(diagnostic.severity),
+                });
+            }
+
             let mut buffer_handle = None;
             for buffer in ctx.editor.buffers.iter() {
                 if util::is_editor_path_equals_to_lsp_path(
@@ -319,7 +392,7 @@ pub(crate) fn on_notification(
                     .buffers
                     .get_mut(buffer_handle)
                     .lints
-                    .mut_guard(plugin_handle);
+                    .source_mut_guard(plugin_handle, client.handle().0 as _);
                 lints.clear();
 
                 let diagnostics = client.diagnostics.get_buffer_diagnostics(buffer_handle);
@@ -329,7 +402,11 @@ pub(crate) fn on_notification(
                     let diagnostic = DocumentDiagnostic::from_json(diagnostic, &client.json)?;
                     let range = diagnostic.range.into_buffer_range();
 
-                    lints.add(diagnostic.message.as_str(&client.json), range);
+                    lints.add(
+                        diagnostic.message.as_str(&client.json),
+                        range,
+                        protocol::lint_severity(diagnostic.severity),
+                    );
                     diagnostics.add(range.from, &diagnostic.data, &client.json);
                 }
 
@@ -338,6 +415,46 @@ pub(crate) fn on_notification(
 
             Ok(())
         }
+        "$/progress" => {
+            let token = match notification.params.clone().get("token", &client.json) {
+                JsonValue::String(token) => token.as_str(&client.json).into(),
+                JsonValue::Integer(token) => token.to_string(),
+                _ => return Ok(()),
+            };
+            let value = notification.params.get("value", &client.json);
+
+            let mut kind = "";
+            let mut title = None;
+            let mut message = None;
+            let mut percentage = None;
+            for (key, value) in value.members(&client.json) {
+                match (key, value) {
+                    ("kind", JsonValue::String(value)) => kind = value.as_str(&client.json),
+                    ("title", JsonValue::String(value)) => title = Some(value.as_str(&client.json)),
+                    ("message", JsonValue::String(value)) => {
+                        message = Some(value.as_str(&client.json))
+                    }
+                    ("percentage", JsonValue::Integer(value)) => percentage = Some(value as _),
+                    ("percentage", JsonValue::Number(value)) => percentage = Some(value as _),
+                    _ => (),
+                }
+            }
+
+            let kind = String::from(kind);
+            let title = title.map(String::from);
+            let message = message.map(String::from);
+            if !client.update_progress(token, &kind, title, message, percentage) {
+                return Ok(());
+            }
+
+            let mut indicator = String::new();
+            client.write_progress_indicator(&mut indicator);
+            ctx.editor
+                .status_bar
+                .set_indicator(plugin_handle, client.handle().0 as _, &indicator);
+
+            Ok(())
+        }
         _ => Ok(()),
     }
 }
@@ -379,6 +496,19 @@ pub(crate) fn on_response(
 
     let result = match response.result {
         Ok(result) => result,
+        Err(_)
+            if matches!(
+                method,
+                "textDocument/inlayHint"
+                    | "textDocument/semanticTokens/full"
+                    | "textDocument/semanticTokens/full/delta"
+                    | "textDocument/documentHighlight"
+                    | "textDocument/codeLens"
+                    | "codeLens/resolve"
+            ) =>
+        {
+            return Ok(())
+        }
         Err(error) => {
             client.request_state = RequestState::Idle;
             util::write_response_error(&mut ctx.editor.status_bar, error, &client.json);
@@ -398,6 +528,8 @@ pub(crate) fn on_response(
                     "serverInfo" => {
                         if let JsonValue::String(name) = value.get("name", &client.json) {
                             server_name = name.as_str(&client.json);
+                            client.server_name.clear();
+                            client.server_name.push_str(server_name);
                         }
                     }
                     _ => (),
@@ -420,12 +552,190 @@ pub(crate) fn on_response(
             client.initialized = true;
             client.notify(&mut ctx.platform, "initialized", JsonObject::default());
 
+            if !client.settings.is_empty() {
+                client.did_change_configuration(&mut ctx.platform);
+            }
+            client.send_pending_workspace_folders(&mut ctx.platform);
+
             for buffer in ctx.editor.buffers.iter() {
                 util::send_did_open(client, &ctx.editor, &mut ctx.platform, buffer.handle());
             }
 
             Ok(())
         }
+        "textDocument/inlayHint" => {
+            let buffer_handle = match client
+                .inlay_hint_requests
+                .iter()
+                .find(|r| r.request_id == Some(response.id))
+            {
+                Some(request) => request.buffer_handle,
+                None => return Ok(()),
+            };
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let mut annotations = buffer
+                .annotations
+                .mut_guard(plugin_handle, client.handle().0 as _);
+            annotations.clear();
+
+            let mut text = String::new();
+            for hint in result.elements(&client.json) {
+                let hint = DocumentInlayHint::from_json(hint, &client.json)?;
+                text.clear();
+                hint.write_text(&mut text, &client.json);
+                annotations.add(&text, hint.position.into_buffer_position());
+            }
+
+            Ok(())
+        }
+        "textDocument/semanticTokens/full" | "textDocument/semanticTokens/full/delta" => {
+            let index = match client
+                .semantic_tokens_requests
+                .iter()
+                .position(|r| r.request_id == Some(response.id))
+            {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+
+            let request = &mut client.semantic_tokens_requests[index];
+            request.result_id.clear();
+            if let JsonValue::String(result_id) = result.clone().get("resultId", &client.json) {
+                request.result_id.push_str(result_id.as_str(&client.json));
+            }
+            match result.clone().get("edits", &client.json) {
+                JsonValue::Array(edits) => {
+                    protocol::apply_semantic_tokens_edits(&mut request.data, edits, &client.json)
+                }
+                _ => {
+                    request.data.clear();
+                    for value in result.get("data", &client.json).elements(&client.json) {
+                        if let JsonValue::Integer(value) = value {
+                            request.data.push(value as _);
+                        }
+                    }
+                }
+            }
+
+            let client_handle = client.handle();
+            let request = &client.semantic_tokens_requests[index];
+            let buffer = ctx.editor.buffers.get_mut(request.buffer_handle);
+            let mut semantic_tokens = buffer
+                .semantic_tokens
+                .mut_guard(plugin_handle, client_handle.0 as _);
+            semantic_tokens.clear();
+
+            for (range, token_type, modifiers) in protocol::decode_semantic_tokens(&request.data) {
+                if let Some(kind) = client.semantic_token_kind(token_type, modifiers) {
+                    semantic_tokens.add(range, kind);
+                }
+            }
+
+            Ok(())
+        }
+        "textDocument/documentHighlight" => {
+            let buffer_handle = match client
+                .document_highlight_requests
+                .iter()
+                .find(|r| r.request_id == Some(response.id))
+            {
+                Some(request) => request.buffer_handle,
+                None => return Ok(()),
+            };
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let mut symbol_highlights = buffer
+                .symbol_highlights
+                .mut_guard(plugin_handle, client.handle().0 as _);
+            symbol_highlights.clear();
+
+            for highlight in result.elements(&client.json) {
+                let range = DocumentRange::from_json(
+                    highlight.clone().get("range", &client.json),
+                    &client.json,
+                )?;
+                let kind = match highlight.get("kind", &client.json) {
+                    JsonValue::Integer(3) => BufferSymbolHighlightKind::Write,
+                    _ => BufferSymbolHighlightKind::Read,
+                };
+                symbol_highlights.add(range.into_buffer_range(), kind);
+            }
+
+            Ok(())
+        }
+        "textDocument/codeLens" => {
+            let index = match client
+                .code_lens_requests
+                .iter()
+                .position(|r| r.request_id == Some(response.id))
+            {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+
+            let mut lenses = std::mem::take(&mut client.code_lens_requests[index].lenses);
+            lenses.clear();
+
+            let values: Vec<_> = result.elements(&client.json).collect();
+            for value in values {
+                let lens = DocumentCodeLens::from_json(value.clone(), &client.json)?;
+                let mut raw_json = Vec::new();
+                let _ = client.json.write(&mut raw_json, &value);
+
+                let (title, resolve_request_id) = match lens.command {
+                    Some(command) => (command.title.as_str(&client.json).into(), None),
+                    None => match value {
+                        JsonValue::Object(value) => (
+                            String::new(),
+                            client.resolve_code_lens(&mut ctx.platform, value),
+                        ),
+                        _ => continue,
+                    },
+                };
+
+                lenses.push(CodeLens {
+                    line_index: lens.range.start.line as _,
+                    title,
+                    raw_json,
+                    resolve_request_id,
+                });
+            }
+            lenses.sort_by_key(|l| l.line_index);
+            client.code_lens_requests[index].lenses = lenses;
+
+            set_code_lens_virtual_lines(ctx, plugin_handle, client, index);
+            Ok(())
+        }
+        "codeLens/resolve" => {
+            let (index, lens_index) =
+                match client
+                    .code_lens_requests
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, r)| {
+                        r.lenses
+                            .iter()
+                            .position(|l| l.resolve_request_id == Some(response.id))
+                            .map(|j| (i, j))
+                    }) {
+                    Some(indices) => indices,
+                    None => return Ok(()),
+                };
+
+            let lens = DocumentCodeLens::from_json(result.clone(), &client.json)?;
+            let code_lens = &mut client.code_lens_requests[index].lenses[lens_index];
+            code_lens.resolve_request_id = None;
+            code_lens.title.clear();
+            if let Some(command) = lens.command {
+                code_lens.title.push_str(command.title.as_str(&client.json));
+            }
+            code_lens.raw_json.clear();
+            let _ = client.json.write(&mut code_lens.raw_json, &result);
+
+            set_code_lens_virtual_lines(ctx, plugin_handle, client, index);
+            Ok(())
+        }
         "textDocument/hover" => {
             let contents = result.get("contents", &client.json);
             let info = util::extract_markup_content(contents, &client.json);
@@ -690,6 +1000,153 @@ pub(crate) fn on_response(
 
             Ok(())
         }
+        "textDocument/prepareCallHierarchy" | "textDocument/prepareTypeHierarchy" => {
+            let (client_handle, kind) = match client.request_state {
+                RequestState::PrepareHierarchy {
+                    client_handle,
+                    kind,
+                } => (client_handle, kind),
+                _ => return Ok(()),
+            };
+            client.request_state = RequestState::Idle;
+            let items = match result {
+                JsonValue::Array(items) => items,
+                _ => {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .str("no hierarchy item under cursor");
+                    return Ok(());
+                }
+            };
+
+            let buffer_view_handle = match ctx.editor.buffer_view_handle_from_path(
+                client_handle,
+                Path::new(kind.buffer_name()),
+                BufferProperties::scratch(),
+                true,
+            ) {
+                Ok(handle) => handle,
+                Err(error) => {
+                    ctx.editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .fmt(format_args!("{}", error));
+                    return Ok(());
+                }
+            };
+            let buffer_handle = ctx
+                .editor
+                .buffer_views
+                .get(buffer_view_handle)
+                .buffer_handle;
+
+            let mut hierarchy = Hierarchy {
+                kind,
+                buffer_handle,
+                entries: Vec::new(),
+            };
+            let mut text = ctx.editor.string_pool.acquire();
+            for item in items.elements(&client.json) {
+                if let Some(entry) = write_hierarchy_entry(client, ctx, &mut text, 0, item) {
+                    hierarchy.entries.push(entry);
+                }
+            }
+            let has_entries = !hierarchy.entries.is_empty();
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            buffer.properties = BufferProperties::scratch();
+            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+            buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                BufferPosition::zero(),
+                &text,
+                &mut ctx.editor.events,
+            );
+            ctx.editor.string_pool.release(text);
+
+            client.hierarchies.retain(|h| h.kind != kind);
+            client.hierarchies.push(hierarchy);
+
+            ctx.clients
+                .get_mut(client_handle)
+                .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+
+            let mut cursors = ctx
+                .editor
+                .buffer_views
+                .get_mut(buffer_view_handle)
+                .cursors
+                .mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: BufferPosition::zero(),
+                position: BufferPosition::zero(),
+            });
+            drop(cursors);
+
+            if has_entries {
+                client.request_hierarchy_children(&mut ctx.platform, kind, 0);
+            }
+            Ok(())
+        }
+        "callHierarchy/incomingCalls"
+        | "callHierarchy/outgoingCalls"
+        | "typeHierarchy/supertypes"
+        | "typeHierarchy/subtypes" => {
+            let (kind, entry_index) = match client.request_state {
+                RequestState::Hierarchy { kind, entry_index } => (kind, entry_index),
+                _ => return Ok(()),
+            };
+            client.request_state = RequestState::Idle;
+            let items = match result {
+                JsonValue::Array(items) => items,
+                _ => return Ok(()),
+            };
+
+            let (buffer_handle, depth) = match client
+                .hierarchies
+                .iter_mut()
+                .find(|h| h.kind == kind)
+                .and_then(|h| Some((h.buffer_handle, h.entries.get_mut(entry_index)?)))
+            {
+                Some((buffer_handle, entry)) if !entry.expanded => {
+                    entry.expanded = true;
+                    (buffer_handle, entry.depth + 1)
+                }
+                _ => return Ok(()),
+            };
+
+            let mut entries = Vec::new();
+            let mut text = ctx.editor.string_pool.acquire();
+            for item in items.elements(&client.json) {
+                let item = match kind {
+                    HierarchyKind::IncomingCalls => item.get("from", &client.json),
+                    HierarchyKind::OutgoingCalls => item.get("to", &client.json),
+                    HierarchyKind::Supertypes | HierarchyKind::Subtypes => item,
+                };
+                if let Some(entry) = write_hierarchy_entry(client, ctx, &mut text, depth, item) {
+                    entries.push(entry);
+                }
+            }
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                BufferPosition::line_col(entry_index as BufferPositionIndex + 1, 0),
+                &text,
+                &mut ctx.editor.events,
+            );
+            ctx.editor.string_pool.release(text);
+
+            if let Some(hierarchy) = client.hierarchies.iter_mut().find(|h| h.kind == kind) {
+                let index = entry_index + 1;
+                hierarchy.entries.splice(index..index, entries);
+            }
+
+            Ok(())
+        }
         "textDocument/prepareRename" => {
             let (buffer_handle, buffer_position) = match client.request_state {
                 RequestState::Rename {
@@ -951,6 +1408,14 @@ pub(crate) fn on_response(
             }
             let buffer = ctx.editor.buffers.get(buffer_handle).content();
 
+            let position = buffer_view.cursors.main_cursor().position;
+            let word = buffer.word_at(buffer.position_before(position));
+            let (filter, word_position) = match word.kind {
+                WordKind::Identifier => (word.text, word.position),
+                _ => ("", position),
+            };
+            let line = buffer.lines()[position.line_index as usize].as_str();
+
             let completions = match result {
                 JsonValue::Array(completions) => completions,
                 JsonValue::Object(completions) => match completions.get("items", &client.json) {
@@ -960,22 +1425,70 @@ pub(crate) fn on_response(
                 _ => return Ok(()),
             };
 
-            ctx.editor.picker.clear();
             for completion in completions.elements(&client.json) {
                 if let Ok(completion) = DocumentCompletionItem::from_json(completion, &client.json)
                 {
-                    let text = completion.text.as_str(&client.json);
-                    ctx.editor.picker.add_custom_entry(text);
+                    let mut text = completion.text(&client.json);
+
+                    // the edit may also replace text before the word being completed
+                    let mut replaced_prefix = "";
+                    if let Some(edit) = &completion.text_edit {
+                        let start = edit.range.start.into_buffer_position();
+                        if start.line_index == word_position.line_index
+                            && start.column_byte_index < word_position.column_byte_index
+                        {
+                            let prefix = line
+                                .get(
+                                    start.column_byte_index as usize
+                                        ..word_position.column_byte_index as usize,
+                                )
+                                .unwrap_or("");
+                            match text.strip_prefix(prefix) {
+                                Some(rest) => text = rest,
+                                None => replaced_prefix = prefix,
+                            }
+                        }
+                    }
+
+                    let name = if completion.is_snippet {
+                        let label = completion.label.as_str(&client.json);
+                        ctx.editor
+                            .snippets
+                            .add_completion_snippet(label, replaced_prefix, text);
+                        label
+                    } else if !replaced_prefix.is_empty() {
+                        let mut body = ctx.editor.string_pool.acquire();
+                        for c in text.chars() {
+                            if let '$' | '}' | '\\' = c {
+                                body.push('\\');
+                            }
+                            body.push(c);
+                        }
+                        let label = completion.label.as_str(&client.json);
+                        ctx.editor
+                            .snippets
+                            .add_completion_snippet(label, replaced_prefix, &body);
+                        ctx.editor.string_pool.release(body);
+                        label
+                    } else {
+                        text
+                    };
+
+                    let entry = ctx.editor.picker.add_completion_entry(
+                        CompletionSource::Plugin(plugin_handle),
+                        completion.completion_kind(),
+                        name,
+                    );
+                    entry
+                        .detail
+                        .push_str(completion.detail.as_str(&client.json));
+                    entry.documentation.push_str(util::extract_markup_content(
+                        completion.documentation,
+                        &client.json,
+                    ));
                 }
             }
 
-            let position = buffer_view.cursors.main_cursor().position;
-            let position = buffer.position_before(position);
-            let word = buffer.word_at(position);
-            let filter = match word.kind {
-                WordKind::Identifier => word.text,
-                _ => "",
-            };
             ctx.editor
                 .picker
                 .filter_completion(ctx.editor.word_database.word_indices(), filter);
@@ -1088,3 +1601,71 @@ fn goto_definition(
         DefinitionLocation::Invalid => Ok(()),
     }
 }
+
+fn set_code_lens_virtual_lines(
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    client: &Client,
+    index: usize,
+) {
+    let request = &client.code_lens_requests[index];
+    let buffer = ctx.editor.buffers.get_mut(request.buffer_handle);
+    let mut virtual_lines = buffer
+        .virtual_lines
+        .mut_guard(plugin_handle, client.handle().0 as _);
+    virtual_lines.clear();
+
+    let mut text = String::new();
+    let mut lenses = request
+        .lenses
+        .iter()
+        .filter(|l| !l.title.is_empty())
+        .peekable();
+    while let Some(lens) = lenses.next() {
+        text.clear();
+        text.push_str(&lens.title);
+        while let Some(next) = lenses.next_if(|l| l.line_index == lens.line_index) {
+            text.push_str(" | ");
+            text.push_str(&next.title);
+        }
+        virtual_lines.add(&text, lens.line_index);
+    }
+}
+
+fn write_hierarchy_entry(
+    client: &Client,
+    ctx: &EditorContext,
+    text: &mut String,
+    depth: u32,
+    item: JsonValue,
+) -> Option<HierarchyEntry> {
+    use fmt::Write;
+
+    let hierarchy_item = DocumentHierarchyItem::from_json(item.clone(), &client.json).ok()?;
+    let Uri::Path(path) = Uri::parse(&client.root, hierarchy_item.uri.as_str(&client.json)).ok()?;
+    let path = client.root.join(path);
+    let path = path
+        .strip_prefix(&ctx.editor.current_directory)
+        .unwrap_or(&path);
+    let position = hierarchy_item.selection_range.start.into_buffer_position();
+
+    let _ = write!(
+        text,
+        "{}:{},{} ",
+        path.display(),
+        position.line_index + 1,
+        position.column_byte_index + 1,
+    );
+    for _ in 0..depth {
+        text.push_str("  ");
+    }
+    let _ = writeln!(text, "{}", hierarchy_item.name.as_str(&client.json));
+
+    let mut item_raw_json = Vec::new();
+    let _ = client.json.write(&mut item_raw_json, &item);
+    Some(HierarchyEntry {
+        depth,
+        expanded: false,
+        item: item_raw_json,
+    })
+}
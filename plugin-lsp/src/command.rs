@@ -1,17 +1,22 @@
-use std::path::Path;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
 use pepper::{
-    buffer::{BufferHandle, BufferProperties},
+    buffer::{BufferHandle, BufferLintSeverity, BufferProperties},
+    buffer_position::{BufferPosition, BufferRange},
     command::{CommandError, CommandIO, CommandManager},
     cursor::Cursor,
     editor::{Editor, EditorContext},
-    editor_utils::parse_process_command,
+    editor_utils::{parse_process_command, MessageKind},
     plugin::PluginHandle,
 };
 
 use crate::{
-    client::Client,
-    {ClientGuard, LspPlugin},
+    client::{Client, ClientFeature, ClientFeatures, HierarchyKind},
+    mode::picker,
+    {is_valid_json, read_json_file, ClientGuard, LspPlugin},
 };
 
 pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHandle) {
@@ -19,25 +24,58 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         commands.register(Some(plugin_handle), name, completions, command_fn);
     };
 
-    r("lsp", &[], |ctx, io| {
-        let command = io.args.next()?;
+    r("lsp", &[], |ctx, io| add_recipe(ctx, io, false));
+    r("lsp-add", &[], |ctx, io| add_recipe(ctx, io, true));
+
+    r("lsp-settings", &[], |ctx, io| {
         let glob = io.args.next()?;
-        let log_path = io.args.try_next();
+        let settings = io.args.next()?;
+        let command = io.args.try_next();
         io.args.assert_empty()?;
 
+        let (settings, settings_path) = read_json_arg(ctx, settings)?;
         let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
-        let result = match lsp.add_recipe(glob, command, None, log_path) {
-            Ok(()) => Ok(()),
-            Err(error) => Err(CommandError::InvalidGlob(error)),
-        };
-        result
+        if lsp.set_recipe_settings(&mut ctx.platform, glob, command, &settings, &settings_path) {
+            Ok(())
+        } else {
+            Err(no_recipe_error(glob, command))
+        }
+    });
+
+    r("lsp-initialization-options", &[], |ctx, io| {
+        let glob = io.args.next()?;
+        let options = io.args.next()?;
+        let command = io.args.try_next();
+        io.args.assert_empty()?;
+
+        let (options, _) = read_json_arg(ctx, options)?;
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        if lsp.set_recipe_initialization_options(glob, command, &options) {
+            Ok(())
+        } else {
+            Err(no_recipe_error(glob, command))
+        }
+    });
+
+    r("lsp-root-markers", &[], |ctx, io| {
+        let glob = io.args.next()?;
+        let markers = io.args.next()?;
+        let command = io.args.try_next();
+        io.args.assert_empty()?;
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        if lsp.set_recipe_root_markers(glob, command, markers) {
+            Ok(())
+        } else {
+            Err(no_recipe_error(glob, command))
+        }
     });
 
     r("lsp-open-log", &[], |ctx, io| {
         io.args.assert_empty()?;
         let client_handle = io.client_handle()?;
         let buffer_handle = io.current_buffer_handle(ctx).ok();
-        access(ctx, io, buffer_handle, |ctx, client| {
+        access(ctx, io, buffer_handle, None, |ctx, client| {
             let path = client
                 .log_file_path()
                 .ok_or(CommandError::OtherStatic("lsp server is not logging"))?;
@@ -90,14 +128,18 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
 
         let buffer_handle = io.current_buffer_handle(ctx).ok();
         let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
-        let any_stopped = match find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
-            Some(client) => {
-                let handle = client.handle();
-                lsp.release(client);
-                lsp.stop(&mut ctx.platform, handle)
+        let mut any_stopped = false;
+        while let Some(client) = find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle, None) {
+            let handle = client.handle();
+            lsp.release(client);
+            any_stopped = lsp.stop(&mut ctx.platform, handle) || any_stopped;
+            if buffer_handle.is_none() {
+                break;
             }
-            None => lsp.stop_all(&mut ctx.platform),
-        };
+        }
+        if !any_stopped {
+            any_stopped = lsp.stop_all(&mut ctx.platform);
+        }
         if any_stopped {
             Ok(())
         } else {
@@ -116,19 +158,124 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         }
     });
 
+    r("lsp-status", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        let mut status = String::new();
+        for client in lsp.clients() {
+            if !status.is_empty() {
+                status.push('\n');
+            }
+            client.write_status(&mut status);
+        }
+
+        if status.is_empty() {
+            Err(CommandError::OtherStatic("no lsp server running"))
+        } else {
+            ctx.editor.status_bar.write(MessageKind::Info).str(&status);
+            Ok(())
+        }
+    });
+
+    r("lsp-diagnostics", &[], |ctx, io| {
+        let severity = parse_lint_severity(io.args.try_next())?;
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let diagnostics = collect_diagnostics(ctx, io.plugin_handle(), severity);
+
+        let buffer_view_handle = ctx
+            .editor
+            .buffer_view_handle_from_path(
+                client_handle,
+                Path::new("lsp-diagnostics.refs"),
+                BufferProperties::scratch(),
+                true,
+            )
+            .map_err(CommandError::BufferReadError)?;
+
+        let mut text = ctx.editor.string_pool.acquire();
+        for diagnostic in &diagnostics {
+            use fmt::Write;
+            let path = diagnostic
+                .path
+                .strip_prefix(&ctx.editor.current_directory)
+                .unwrap_or(&diagnostic.path);
+            let _ = writeln!(
+                text,
+                "{}:{},{}\n{}: {}\n",
+                path.display(),
+                diagnostic.position.line_index + 1,
+                diagnostic.position.column_byte_index + 1,
+                lint_severity_name(diagnostic.severity),
+                diagnostic.message,
+            );
+        }
+        if diagnostics.len() == 1 {
+            text.insert_str(0, "1 diagnostic found\n\n");
+        } else {
+            text.insert_str(0, &format!("{} diagnostics found\n\n", diagnostics.len()));
+        }
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        buffer.properties = BufferProperties::scratch();
+        let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+        buffer.delete_range(&mut ctx.editor.word_database, range, &mut ctx.editor.events);
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            BufferPosition::zero(),
+            &text,
+            &mut ctx.editor.events,
+        );
+        ctx.editor.string_pool.release(text);
+
+        let client = ctx.clients.get_mut(client_handle);
+        client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+
+        let mut cursors = ctx
+            .editor
+            .buffer_views
+            .get_mut(buffer_view_handle)
+            .cursors
+            .mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: BufferPosition::zero(),
+            position: BufferPosition::zero(),
+        });
+
+        Ok(())
+    });
+
+    r("lsp-next-diagnostic", &[], |ctx, io| {
+        move_to_diagnostic(ctx, io, true)
+    });
+
+    r("lsp-previous-diagnostic", &[], |ctx, io| {
+        move_to_diagnostic(ctx, io, false)
+    });
+
     r("lsp-hover", &[], |ctx, io| {
         io.args.assert_empty()?;
 
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.hover(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Hover),
+            |ctx, client| {
+                let op = client.hover(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-definition", &[], |ctx, io| {
@@ -137,16 +284,22 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.definition(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Definition),
+            |ctx, client| {
+                let op = client.definition(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-declaration", &[], |ctx, io| {
@@ -155,32 +308,44 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.declaration(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Definition),
+            |ctx, client| {
+                let op = client.declaration(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-implementation", &[], |ctx, io| {
         io.args.assert_empty()?;
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.implementation(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Definition),
+            |ctx, client| {
+                let op = client.implementation(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-references", &[], |ctx, io| {
@@ -201,17 +366,39 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.references(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                context_len,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::References),
+            |ctx, client| {
+                let op = client.references(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    context_len,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
+    });
+
+    r("lsp-incoming-calls", &[], |ctx, io| {
+        hierarchy(ctx, io, HierarchyKind::IncomingCalls)
+    });
+
+    r("lsp-outgoing-calls", &[], |ctx, io| {
+        hierarchy(ctx, io, HierarchyKind::OutgoingCalls)
+    });
+
+    r("lsp-supertypes", &[], |ctx, io| {
+        hierarchy(ctx, io, HierarchyKind::Supertypes)
+    });
+
+    r("lsp-subtypes", &[], |ctx, io| {
+        hierarchy(ctx, io, HierarchyKind::Subtypes)
     });
 
     r("lsp-rename", &[], |ctx, io| {
@@ -220,10 +407,16 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
         let plugin_handle = io.plugin_handle();
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.rename(ctx, plugin_handle, buffer_handle, cursor.position);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Rename),
+            |ctx, client| {
+                let op = client.rename(ctx, plugin_handle, buffer_handle, cursor.position);
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-code-action", &[], |ctx, io| {
@@ -232,16 +425,46 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
         let plugin_handle = io.plugin_handle();
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.code_action(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                plugin_handle,
-                buffer_handle,
-                cursor.to_range(),
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::CodeAction),
+            |ctx, client| {
+                let op = client.code_action(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    plugin_handle,
+                    buffer_handle,
+                    cursor.to_range(),
+                );
+                Ok(op)
+            },
+        )
+    });
+
+    r("lsp-code-lens", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
+        let plugin_handle = io.plugin_handle();
+
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::CodeLens),
+            |ctx, client| {
+                let line_index = cursor.position.line_index;
+                if !client.has_line_code_lens(buffer_handle, line_index) {
+                    return Err(CommandError::OtherStatic("no code lens on this line"));
+                }
+                if client.line_code_lenses(&mut ctx.editor, buffer_handle, line_index) {
+                    picker::enter_code_lens_mode(ctx, plugin_handle, client);
+                }
+                Ok(())
+            },
+        )
     });
 
     r("lsp-document-symbols", &[], |ctx, io| {
@@ -250,10 +473,16 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let view_handle = io.current_buffer_view_handle(ctx)?;
         let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.document_symbols(&mut ctx.editor, &mut ctx.platform, view_handle);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Symbols),
+            |ctx, client| {
+                let op = client.document_symbols(&mut ctx.editor, &mut ctx.platform, view_handle);
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-workspace-symbols", &[], |ctx, io| {
@@ -261,20 +490,32 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         io.args.assert_empty()?;
 
         let buffer_handle = io.current_buffer_handle(ctx).ok();
-        access(ctx, io, buffer_handle, |ctx, client| {
-            let op = client.workspace_symbols(&mut ctx.editor, &mut ctx.platform, query);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            buffer_handle,
+            Some(ClientFeature::Symbols),
+            |ctx, client| {
+                let op = client.workspace_symbols(&mut ctx.editor, &mut ctx.platform, query);
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-format", &[], |ctx, io| {
         io.args.assert_empty()?;
 
         let buffer_handle = io.current_buffer_handle(ctx)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            Some(ClientFeature::Formatting),
+            |ctx, client| {
+                let op = client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
+                Ok(op)
+            },
+        )
     });
 }
 
@@ -290,17 +531,276 @@ fn current_buffer_and_main_cursor(
     Ok((buffer_handle, cursor))
 }
 
+fn add_recipe(
+    ctx: &mut EditorContext,
+    io: &mut CommandIO,
+    extra: bool,
+) -> Result<(), CommandError> {
+    let command = io.args.next()?;
+    let glob = io.args.next()?;
+    let log_path = io.args.try_next().filter(|p| !p.is_empty());
+    let features = io.args.try_next().unwrap_or("");
+    io.args.assert_empty()?;
+
+    let features = ClientFeatures::parse(features).map_err(|feature| {
+        CommandError::OtherOwned(format!("invalid lsp feature '{}'", feature))
+    })?;
+
+    let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+    let result = if extra {
+        lsp.add_extra_recipe(glob, command, None, log_path, features)
+    } else {
+        lsp.add_recipe(glob, command, None, log_path, features)
+    };
+    result.map_err(CommandError::InvalidGlob)
+}
+
+fn no_recipe_error(glob: &str, command: Option<&str>) -> CommandError {
+    match command {
+        Some(command) => CommandError::OtherOwned(format!(
+            "no lsp registered with glob '{}' and command '{}'",
+            glob, command
+        )),
+        None => CommandError::OtherOwned(format!("no lsp registered with glob '{}'", glob)),
+    }
+}
+
+fn parse_lint_severity(arg: Option<&str>) -> Result<BufferLintSeverity, CommandError> {
+    match arg {
+        Some("error") => Ok(BufferLintSeverity::Error),
+        Some("warning") => Ok(BufferLintSeverity::Warning),
+        Some("info") => Ok(BufferLintSeverity::Info),
+        Some("hint") | None => Ok(BufferLintSeverity::Hint),
+        Some(severity) => Err(CommandError::OtherOwned(format!(
+            "invalid diagnostic severity '{}'",
+            severity
+        ))),
+    }
+}
+
+fn lint_severity_name(severity: BufferLintSeverity) -> &'static str {
+    match severity {
+        BufferLintSeverity::Error => "error",
+        BufferLintSeverity::Warning => "warning",
+        BufferLintSeverity::Info => "info",
+        BufferLintSeverity::Hint => "hint",
+    }
+}
+
+struct Diagnostic {
+    path: PathBuf,
+    position: BufferPosition,
+    severity: BufferLintSeverity,
+    message: String,
+}
+
+fn collect_diagnostics(
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    min_severity: BufferLintSeverity,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+    for client in lsp.clients() {
+        let source = client.handle().0 as u32;
+        for (path, workspace_diagnostics) in client.diagnostics.workspace_diagnostics() {
+            let buffer = ctx
+                .editor
+                .buffers
+                .iter()
+                .find(|b| ctx.editor.current_directory.join(&b.path) == path);
+            match buffer {
+                Some(buffer) => {
+                    for lint in buffer.lints.all() {
+                        if lint.plugin_handle == plugin_handle
+                            && lint.source == source
+                            && lint.severity <= min_severity
+                        {
+                            diagnostics.push(Diagnostic {
+                                path: path.into(),
+                                position: lint.range.from,
+                                severity: lint.severity,
+                                message: lint.message.clone(),
+                            });
+                        }
+                    }
+                }
+                None => {
+                    for diagnostic in workspace_diagnostics {
+                        if diagnostic.severity <= min_severity {
+                            diagnostics.push(Diagnostic {
+                                path: path.into(),
+                                position: diagnostic.range.from,
+                                severity: diagnostic.severity,
+                                message: diagnostic.message.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    diagnostics.sort_by(|a, b| (&a.path, a.position).cmp(&(&b.path, b.position)));
+    diagnostics
+}
+
+fn move_to_diagnostic(
+    ctx: &mut EditorContext,
+    io: &mut CommandIO,
+    forward: bool,
+) -> Result<(), CommandError> {
+    let severity = parse_lint_severity(io.args.try_next())?;
+    io.args.assert_empty()?;
+
+    let client_handle = io.client_handle()?;
+    let diagnostics = collect_diagnostics(ctx, io.plugin_handle(), severity);
+
+    let (current_path, current_position) = match ctx.clients.get(client_handle).buffer_view_handle()
+    {
+        Some(handle) => {
+            let buffer_view = ctx.editor.buffer_views.get(handle);
+            let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+            let path = ctx.editor.current_directory.join(&buffer.path);
+            (path, buffer_view.cursors.main_cursor().position)
+        }
+        None => (PathBuf::new(), BufferPosition::zero()),
+    };
+    let current = (current_path.as_path(), current_position);
+
+    let diagnostic = if forward {
+        diagnostics
+            .iter()
+            .find(|d| (d.path.as_path(), d.position) > current)
+            .or_else(|| diagnostics.first())
+    } else {
+        diagnostics
+            .iter()
+            .rev()
+            .find(|d| (d.path.as_path(), d.position) < current)
+            .or_else(|| diagnostics.last())
+    };
+    let diagnostic = match diagnostic {
+        Some(diagnostic) => diagnostic,
+        None => return Err(CommandError::OtherStatic("no diagnostics")),
+    };
+
+    let path = diagnostic
+        .path
+        .strip_prefix(&ctx.editor.current_directory)
+        .unwrap_or(&diagnostic.path);
+    let buffer_view_handle = ctx
+        .editor
+        .buffer_view_handle_from_path(client_handle, path, BufferProperties::text(), false)
+        .map_err(CommandError::BufferReadError)?;
+
+    let client = ctx.clients.get_mut(client_handle);
+    client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+
+    let mut cursors = ctx
+        .editor
+        .buffer_views
+        .get_mut(buffer_view_handle)
+        .cursors
+        .mut_guard();
+    cursors.clear();
+    cursors.add(Cursor {
+        anchor: diagnostic.position,
+        position: diagnostic.position,
+    });
+
+    ctx.editor
+        .status_bar
+        .write(MessageKind::Info)
+        .fmt(format_args!(
+            "{}: {}",
+            lint_severity_name(diagnostic.severity),
+            diagnostic.message
+        ));
+
+    Ok(())
+}
+
+fn hierarchy(
+    ctx: &mut EditorContext,
+    io: &mut CommandIO,
+    kind: HierarchyKind,
+) -> Result<(), CommandError> {
+    io.args.assert_empty()?;
+
+    let client_handle = io.client_handle()?;
+    let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
+
+    let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+    if let Some(mut client) = lsp.find_client(|c| c.has_hierarchy_buffer(kind, buffer_handle)) {
+        client.toggle_hierarchy_entry(
+            &mut ctx.editor,
+            &mut ctx.platform,
+            kind,
+            cursor.position.line_index,
+        );
+        lsp.release(client);
+        return Ok(());
+    }
+
+    for client in lsp.clients_mut() {
+        client.hierarchies.retain(|h| h.kind != kind);
+    }
+
+    access(
+        ctx,
+        io,
+        Some(buffer_handle),
+        Some(kind.feature()),
+        |ctx, client| {
+            client.prepare_hierarchy(
+                &ctx.editor,
+                &mut ctx.platform,
+                kind,
+                buffer_handle,
+                cursor.position,
+                client_handle,
+            );
+            Ok(())
+        },
+    )
+}
+
+fn read_json_arg(ctx: &EditorContext, arg: &str) -> Result<(String, PathBuf), CommandError> {
+    if arg.trim_start().starts_with('{') {
+        if is_valid_json(arg) {
+            Ok((arg.into(), PathBuf::new()))
+        } else {
+            Err(CommandError::OtherStatic("invalid json"))
+        }
+    } else {
+        let path = ctx.editor.current_directory.join(arg);
+        match read_json_file(&path) {
+            Some(text) => Ok((text, path)),
+            None => Err(CommandError::OtherOwned(format!(
+                "could not read json file '{}'",
+                arg
+            ))),
+        }
+    }
+}
+
 fn find_lsp_client_for_buffer(
     lsp: &mut LspPlugin,
     editor: &Editor,
     buffer_handle: Option<BufferHandle>,
+    feature: Option<ClientFeature>,
 ) -> Option<ClientGuard> {
+    let supports_feature = |c: &Client| match feature {
+        Some(feature) => c.features.contains(feature),
+        None => true,
+    };
     match buffer_handle {
         Some(buffer_handle) => {
             let buffer_path = editor.buffers.get(buffer_handle).path.to_str()?;
-            lsp.find_client(|c| c.handles_path(buffer_path))
+            lsp.find_client(|c| c.handles_path(buffer_path) && supports_feature(c))
         }
-        None => lsp.find_client(|_| true),
+        None => lsp.find_client(supports_feature),
     }
 }
 
@@ -308,13 +808,14 @@ fn access<A>(
     ctx: &mut EditorContext,
     io: &mut CommandIO,
     buffer_handle: Option<BufferHandle>,
+    feature: Option<ClientFeature>,
     accessor: A,
 ) -> Result<(), CommandError>
 where
     A: FnOnce(&mut EditorContext, &mut Client) -> Result<(), CommandError>,
 {
     let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
-    if let Some(mut client) = find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
+    if let Some(mut client) = find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle, feature) {
         let result = accessor(ctx, &mut client);
         let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
         lsp.release(client);
@@ -323,3 +824,298 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use pepper::testing::TestEditor;
+
+    use crate::{
+        client::{
+            ClientHandle, CodeLens, CodeLensRequest, Hierarchy, HierarchyEntry, RequestState,
+            WorkspaceDiagnostic,
+        },
+        client_event_handler,
+        protocol::{RequestId, ServerResponse},
+        ClientEntry, DEFINITION,
+    };
+
+    fn range(line_index: u32) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(line_index, 0),
+            BufferPosition::line_col(line_index, 1),
+        )
+    }
+
+    fn add_diagnostic(
+        editor: &mut TestEditor,
+        path: &Path,
+        line_index: u32,
+        severity: BufferLintSeverity,
+    ) {
+        let plugin_handle = editor.plugin_handle(0);
+        let ctx = editor.ctx_mut();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let message = format!("{}:{}", file_name, line_index);
+
+        if let Some(buffer) = ctx.editor.buffers.iter_mut().find(|b| b.path == path) {
+            let mut lints = buffer.lints.source_mut_guard(plugin_handle, 0);
+            lints.add(&message, range(line_index), severity);
+        }
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        if let ClientEntry::Occupied(client) = &mut lsp.entries[0] {
+            client
+                .diagnostics
+                .get_path_diagnostics(path)
+                .push(WorkspaceDiagnostic {
+                    message,
+                    range: range(line_index),
+                    severity,
+                });
+        }
+    }
+
+    // only `a.rs` is open while `b.rs` only exists on disk
+    fn setup_editor(dir: &Path) -> TestEditor {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("b.rs"), "b0\nb1\nb2\nb3").unwrap();
+
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        let plugin_handle = editor.plugin_handle(0);
+        let ctx = editor.ctx_mut();
+        let client = Client::new(ClientHandle(0), dir.into(), None);
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        lsp.entries.push(ClientEntry::Occupied(Box::new(client)));
+
+        let a = dir.join("a.rs");
+        let b = dir.join("b.rs");
+        editor.open_buffer(a.to_str().unwrap(), "a0\na1\na2\na3");
+        add_diagnostic(&mut editor, &a, 3, BufferLintSeverity::Warning);
+        add_diagnostic(&mut editor, &a, 1, BufferLintSeverity::Error);
+        add_diagnostic(&mut editor, &b, 2, BufferLintSeverity::Error);
+        add_diagnostic(&mut editor, &b, 0, BufferLintSeverity::Hint);
+        editor
+    }
+
+    #[test]
+    fn diagnostics_ordering() {
+        let dir = std::env::temp_dir().join("pepper-lsp-diagnostics-ordering-test");
+        let mut editor = setup_editor(&dir);
+
+        let plugin_handle = editor.plugin_handle(0);
+        let mut messages = |severity| -> Vec<String> {
+            collect_diagnostics(editor.ctx_mut(), plugin_handle, severity)
+                .into_iter()
+                .map(|d| d.message)
+                .collect()
+        };
+
+        assert_eq!(
+            vec!["a.rs:1", "a.rs:3", "b.rs:0", "b.rs:2"],
+            messages(BufferLintSeverity::Hint)
+        );
+        assert_eq!(
+            vec!["a.rs:1", "a.rs:3", "b.rs:2"],
+            messages(BufferLintSeverity::Warning)
+        );
+        assert_eq!(
+            vec!["a.rs:1", "b.rs:2"],
+            messages(BufferLintSeverity::Error)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn diagnostics_navigation() {
+        let dir = std::env::temp_dir().join("pepper-lsp-diagnostics-navigation-test");
+        let mut editor = setup_editor(&dir);
+
+        fn current(editor: &TestEditor) -> (String, BufferPosition, String) {
+            let ctx = editor.ctx();
+            let handle = ctx.clients.get(editor.client_handle()).buffer_view_handle();
+            let buffer_handle = ctx.editor.buffer_views.get(handle.unwrap()).buffer_handle;
+            let path = &ctx.editor.buffers.get(buffer_handle).path;
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            let position = editor.cursors()[0].position;
+            (file_name.into(), position, editor.status_bar().1.into())
+        }
+        fn at(file_name: &str, line_index: u32, message: &str) -> (String, BufferPosition, String) {
+            let position = BufferPosition::line_col(line_index, 0);
+            (file_name.into(), position, message.into())
+        }
+
+        assert!(editor.command("lsp-next-diagnostic").is_ok());
+        assert_eq!(at("a.rs", 1, "error: a.rs:1"), current(&editor));
+        assert!(editor.command("lsp-next-diagnostic").is_ok());
+        assert_eq!(at("a.rs", 3, "warning: a.rs:3"), current(&editor));
+        assert!(editor.command("lsp-next-diagnostic").is_ok());
+        assert_eq!(at("b.rs", 0, "hint: b.rs:0"), current(&editor));
+
+        // now that it's open, the server publishes b.rs diagnostics as lints
+        let b = dir.join("b.rs");
+        add_diagnostic(&mut editor, &b, 0, BufferLintSeverity::Hint);
+        add_diagnostic(&mut editor, &b, 2, BufferLintSeverity::Error);
+
+        assert!(editor.command("lsp-next-diagnostic").is_ok());
+        assert_eq!(at("b.rs", 2, "error: b.rs:2"), current(&editor));
+        assert!(editor.command("lsp-next-diagnostic").is_ok());
+        assert_eq!(at("a.rs", 1, "error: a.rs:1"), current(&editor));
+        assert!(editor.command("lsp-previous-diagnostic").is_ok());
+        assert_eq!(at("b.rs", 2, "error: b.rs:2"), current(&editor));
+        assert!(editor.command("lsp-previous-diagnostic warning").is_ok());
+        assert_eq!(at("a.rs", 3, "warning: a.rs:3"), current(&editor));
+        assert!(editor.command("lsp-next-diagnostic error").is_ok());
+        assert_eq!(at("b.rs", 2, "error: b.rs:2"), current(&editor));
+        assert!(editor.command("lsp-next-diagnostic error").is_ok());
+        assert_eq!(at("a.rs", 1, "error: a.rs:1"), current(&editor));
+
+        assert!(editor.command("lsp-next-diagnostic fatal").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hierarchy_toggle() {
+        let dir = std::env::temp_dir().join("pepper-lsp-hierarchy-toggle-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.rs"), "b0\nb1").unwrap();
+
+        let item = |name: &str, file_name: &str, line: u32, character: u32| {
+            format!(
+                r#"{{"name":"{}","uri":"file://{}","selectionRange":{{"start":{{"line":{},"character":{}}},"end":{{"line":{},"character":{}}}}}}}"#,
+                name,
+                dir.join(file_name).display(),
+                line,
+                character,
+                line,
+                character,
+            )
+        };
+        let line = |file_name: &str, position: &str, name: &str| {
+            format!("{}:{} {}\n", dir.join(file_name).display(), position, name)
+        };
+
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        let plugin_handle = editor.plugin_handle(0);
+        let root_text = line("a.rs", "1,1", "A");
+        let buffer_handle = editor.open_buffer(
+            dir.join("lsp-supertypes.refs").to_str().unwrap(),
+            &root_text,
+        );
+
+        let mut client = Client::new(ClientHandle(0), dir.clone(), None);
+        client.initialized = true;
+        client.hierarchies.push(Hierarchy {
+            kind: HierarchyKind::Supertypes,
+            buffer_handle,
+            entries: vec![HierarchyEntry {
+                depth: 0,
+                expanded: false,
+                item: item("A", "a.rs", 0, 0).into_bytes(),
+            }],
+        });
+        let lsp = editor.ctx_mut().plugins.get_as::<LspPlugin>(plugin_handle);
+        lsp.entries.push(ClientEntry::Occupied(Box::new(client)));
+
+        assert!(editor.command("lsp-supertypes").is_ok());
+
+        let ctx = editor.ctx_mut();
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        let mut client = lsp.acquire(ClientHandle(0)).unwrap();
+        assert!(matches!(
+            client.request_state,
+            RequestState::Hierarchy { entry_index: 0, .. }
+        ));
+        let children = format!("[{},{}]", item("B", "b.rs", 1, 1), item("C", "c.rs", 0, 0));
+        let result = client.json.read(&mut children.as_bytes()).unwrap();
+        let response = ServerResponse {
+            id: RequestId(1),
+            result: Ok(result),
+        };
+        assert!(
+            client_event_handler::on_response(&mut client, ctx, plugin_handle, response).is_ok()
+        );
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        lsp.release(client);
+
+        let expanded_text = format!(
+            "{}{}{}",
+            root_text,
+            line("b.rs", "2,2", "  B"),
+            line("c.rs", "1,1", "  C"),
+        );
+        assert_eq!(expanded_text, editor.buffer_text());
+
+        // the location comes first so `gf` works from the start of the line
+        editor.keys("gkjgf");
+        let ctx = editor.ctx();
+        let buffer_handle = editor.current_buffer_handle().unwrap();
+        assert_eq!(dir.join("b.rs"), ctx.editor.buffers.get(buffer_handle).path);
+        assert_eq!(BufferPosition::line_col(1, 1), editor.cursors()[0].position);
+
+        editor.keys("gbgk");
+        assert!(editor.command("lsp-supertypes").is_ok());
+        assert_eq!(root_text, editor.buffer_text());
+        let ctx = editor.ctx_mut();
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        let client = lsp.acquire(ClientHandle(0)).unwrap();
+        assert_eq!(1, client.hierarchies[0].entries.len());
+        assert!(!client.hierarchies[0].entries[0].expanded);
+        lsp.release(client);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn code_lens_line() {
+        let dir = std::env::temp_dir().join("pepper-lsp-code-lens-line-test");
+
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        let plugin_handle = editor.plugin_handle(0);
+        let buffer_handle = editor.open_buffer(dir.join("a.rs").to_str().unwrap(), "a0\na1\na2");
+
+        let mut client = Client::new(ClientHandle(0), dir, None);
+        client.initialized = true;
+        client.code_lens_requests.push(CodeLensRequest {
+            buffer_handle,
+            request_id: None,
+            dirty: false,
+            lenses: vec![CodeLens {
+                line_index: 1,
+                title: "run".into(),
+                raw_json: Vec::new(),
+                resolve_request_id: None,
+            }],
+        });
+        let lsp = editor.ctx_mut().plugins.get_as::<LspPlugin>(plugin_handle);
+        lsp.entries.push(ClientEntry::Occupied(Box::new(client)));
+
+        fn lens_line(editor: &mut TestEditor, plugin_handle: PluginHandle) -> u32 {
+            let lsp = editor.ctx_mut().plugins.get_as::<LspPlugin>(plugin_handle);
+            match &lsp.entries[0] {
+                ClientEntry::Occupied(client) => client.code_lens_requests[0].lenses[0].line_index,
+                _ => unreachable!(),
+            }
+        }
+
+        editor.keys("gk");
+        match editor.command("lsp-code-lens") {
+            Err(error) => assert_eq!("no code lens on this line", error.to_string()),
+            Ok(_) => panic!("expected an error"),
+        }
+        editor.keys("j");
+        assert!(editor.command("lsp-code-lens").is_ok());
+
+        editor.keys("gkix<enter>y<enter><esc>");
+        assert_eq!(3, lens_line(&mut editor, plugin_handle));
+        editor.keys("u");
+        assert_eq!(1, lens_line(&mut editor, plugin_handle));
+    }
+}
@@ -304,6 +304,12 @@ pub struct Json {
     members: Vec<JsonObjectMember>,
 }
 
+impl Default for Json {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Json {
     pub fn new() -> Self {
         Self {
@@ -1,7 +1,7 @@
 use std::{
-    io,
+    fs, io,
     ops::{Deref, DerefMut},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
@@ -20,12 +20,12 @@ mod capabilities;
 mod client;
 mod client_event_handler;
 mod command;
-mod json;
+pub mod json;
 mod mode;
-mod protocol;
+pub mod protocol;
 
-use client::{util, Client, ClientHandle};
-use json::{JsonObject, JsonValue};
+use client::{util, Client, ClientFeature, ClientFeatures, ClientHandle};
+use json::{Json, JsonObject, JsonValue};
 use protocol::{ProtocolError, ResponseError, ServerEvent};
 
 const SERVER_PROCESS_BUFFER_LEN: usize = 4 * 1024;
@@ -59,12 +59,27 @@ pub static DEFINITION: PluginDefinition = PluginDefinition {
 };
 
 struct ClientRecipe {
-    glob_hash: u64,
+    hash: u64,
+    glob_text: String,
     glob: Glob,
     command: String,
+    features: ClientFeatures,
+    settings: String,
+    settings_path: PathBuf,
+    initialization_options: String,
+    root_markers: Vec<String>,
     root: PathBuf,
     log_file_path: String,
-    running_client: Option<ClientHandle>,
+    running_clients: Vec<ClientHandle>,
+}
+impl ClientRecipe {
+    fn matches(&self, glob: &str, command: Option<&str>) -> bool {
+        let same_command = match command {
+            Some(command) => self.command == command,
+            None => true,
+        };
+        self.glob_text == glob && same_command
+    }
 }
 
 enum ClientEntry {
@@ -114,18 +129,48 @@ pub(crate) struct LspPlugin {
 }
 
 impl LspPlugin {
+    /// Registers the server for `glob`, replacing the one previously registered with `add_recipe`
+    /// for the same glob.
     pub fn add_recipe(
         &mut self,
         glob: &str,
         command: &str,
         root: Option<&str>,
         log_file_path: Option<&str>,
+        features: ClientFeatures,
+    ) -> Result<(), InvalidGlobError> {
+        let hash = hash_bytes(glob.as_bytes());
+        self.insert_recipe(hash, glob, command, root, log_file_path, features)
+    }
+
+    /// Registers an additional server for `glob` that runs alongside the one registered with
+    /// `add_recipe`. It only replaces a previous extra server with both the same glob and command.
+    pub fn add_extra_recipe(
+        &mut self,
+        glob: &str,
+        command: &str,
+        root: Option<&str>,
+        log_file_path: Option<&str>,
+        features: ClientFeatures,
+    ) -> Result<(), InvalidGlobError> {
+        let hash = hash_bytes(glob.as_bytes()) ^ hash_bytes(command.as_bytes()).rotate_left(1);
+        self.insert_recipe(hash, glob, command, root, log_file_path, features)
+    }
+
+    fn insert_recipe(
+        &mut self,
+        hash: u64,
+        glob: &str,
+        command: &str,
+        root: Option<&str>,
+        log_file_path: Option<&str>,
+        features: ClientFeatures,
     ) -> Result<(), InvalidGlobError> {
-        let glob_hash = hash_bytes(glob.as_bytes());
         for recipe in &mut self.recipes {
-            if recipe.glob_hash == glob_hash {
+            if recipe.hash == hash {
                 recipe.command.clear();
                 recipe.command.push_str(command);
+                recipe.features = features;
                 recipe.root.clear();
                 if let Some(path) = root {
                     recipe.root.push(path);
@@ -134,7 +179,7 @@ impl LspPlugin {
                 if let Some(name) = log_file_path {
                     recipe.log_file_path.push_str(name);
                 }
-                recipe.running_client = None;
+                recipe.running_clients.clear();
                 return Ok(());
             }
         }
@@ -142,16 +187,92 @@ impl LspPlugin {
         let mut recipe_glob = Glob::default();
         recipe_glob.compile(glob)?;
         self.recipes.push(ClientRecipe {
-            glob_hash,
+            hash,
+            glob_text: glob.into(),
             glob: recipe_glob,
             command: command.into(),
+            features,
+            settings: String::new(),
+            settings_path: PathBuf::new(),
+            initialization_options: String::new(),
+            root_markers: Vec::new(),
             root: root.unwrap_or("").into(),
             log_file_path: log_file_path.unwrap_or("").into(),
-            running_client: None,
+            running_clients: Vec::new(),
         });
         Ok(())
     }
 
+    pub(crate) fn set_recipe_settings(
+        &mut self,
+        platform: &mut Platform,
+        glob: &str,
+        command: Option<&str>,
+        settings: &str,
+        settings_path: &Path,
+    ) -> bool {
+        let mut any_changed = false;
+        for recipe in &mut self.recipes {
+            if !recipe.matches(glob, command) {
+                continue;
+            }
+            any_changed = true;
+            recipe.settings.clear();
+            recipe.settings.push_str(settings);
+            recipe.settings_path.clear();
+            recipe.settings_path.push(settings_path);
+
+            for handle in &recipe.running_clients {
+                if let ClientEntry::Occupied(client) = &mut self.entries[handle.0 as usize] {
+                    client.settings.clear();
+                    client.settings.push_str(settings);
+                    client.json.clear();
+                    client.did_change_configuration(platform);
+                }
+            }
+        }
+        any_changed
+    }
+
+    pub(crate) fn set_recipe_initialization_options(
+        &mut self,
+        glob: &str,
+        command: Option<&str>,
+        options: &str,
+    ) -> bool {
+        let mut any_changed = false;
+        for recipe in &mut self.recipes {
+            if recipe.matches(glob, command) {
+                any_changed = true;
+                recipe.initialization_options.clear();
+                recipe.initialization_options.push_str(options);
+            }
+        }
+        any_changed
+    }
+
+    pub(crate) fn set_recipe_root_markers(
+        &mut self,
+        glob: &str,
+        command: Option<&str>,
+        markers: &str,
+    ) -> bool {
+        let mut any_changed = false;
+        for recipe in &mut self.recipes {
+            if recipe.matches(glob, command) {
+                any_changed = true;
+                recipe.root_markers.clear();
+                for marker in markers.split(',') {
+                    let marker = marker.trim();
+                    if !marker.is_empty() {
+                        recipe.root_markers.push(marker.into());
+                    }
+                }
+            }
+        }
+        any_changed
+    }
+
     pub fn start(
         &mut self,
         platform: &mut Platform,
@@ -204,9 +325,7 @@ impl LspPlugin {
 
                 self.entries[handle.0 as usize] = ClientEntry::Vacant;
                 for recipe in &mut self.recipes {
-                    if recipe.running_client == Some(handle) {
-                        recipe.running_client = None;
-                    }
+                    recipe.running_clients.retain(|&h| h != handle);
                 }
 
                 true
@@ -224,6 +343,20 @@ impl LspPlugin {
         any_stopped
     }
 
+    pub(crate) fn clients(&self) -> impl Iterator<Item = &Client> {
+        self.entries.iter().filter_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.deref()),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn clients_mut(&mut self) -> impl Iterator<Item = &mut Client> {
+        self.entries.iter_mut().filter_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.deref_mut()),
+            _ => None,
+        })
+    }
+
     pub(crate) fn get_mut(&mut self, handle: ClientHandle) -> Option<&mut Client> {
         match &mut self.entries[handle.0 as usize] {
             ClientEntry::Occupied(client) => Some(client.deref_mut()),
@@ -245,6 +378,38 @@ impl LspPlugin {
         self.entries[index] = ClientEntry::Occupied(client);
     }
 
+    /// Adds `folder` to one of the recipe's running servers, returning false if none of them
+    /// has it already nor can be notified of new workspace folders.
+    fn add_recipe_workspace_folder(
+        &mut self,
+        platform: &mut Platform,
+        recipe_index: usize,
+        folder: &Path,
+    ) -> bool {
+        let recipe = &self.recipes[recipe_index];
+        let mut clients =
+            recipe
+                .running_clients
+                .iter()
+                .filter_map(|h| match &self.entries[h.0 as usize] {
+                    ClientEntry::Occupied(client) => Some(client),
+                    _ => None,
+                });
+        if clients.any(|c| c.has_workspace_folder(folder)) {
+            return true;
+        }
+
+        for handle in &recipe.running_clients {
+            if let ClientEntry::Occupied(client) = &mut self.entries[handle.0 as usize] {
+                client.json.clear();
+                if client.add_workspace_folder(platform, folder.into()) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     pub(crate) fn find_client<P>(&mut self, mut predicate: P) -> Option<ClientGuard>
     where
         P: FnMut(&Client) -> bool,
@@ -262,59 +427,152 @@ impl LspPlugin {
     }
 }
 
+pub(crate) fn read_json_file(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    if is_valid_json(&text) {
+        Some(text)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn is_valid_json(text: &str) -> bool {
+    let mut reader = text.as_bytes();
+    Json::new().read(&mut reader).is_ok()
+}
+
+pub(crate) fn find_root_with_markers(dir: &Path, markers: &[String]) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| markers.iter().any(|m| dir.join(m).exists()))
+        .map(PathBuf::from)
+}
+
 fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
     let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
 
     let mut events = EditorEventIter::new();
     while let Some(event) = events.next(&ctx.editor.events) {
+        if let EditorEvent::BufferWrite { handle, .. } = *event {
+            let buffer_path = &ctx.editor.buffers.get(handle).path;
+            let buffer_path = ctx.editor.current_directory.join(buffer_path);
+            for index in 0..lsp.recipes.len() {
+                let recipe = &lsp.recipes[index];
+                if recipe.settings_path.as_os_str().is_empty()
+                    || recipe.settings_path != buffer_path
+                {
+                    continue;
+                }
+                match read_json_file(&buffer_path) {
+                    Some(settings) => {
+                        let glob = recipe.glob_text.clone();
+                        let command = recipe.command.clone();
+                        lsp.set_recipe_settings(
+                            &mut ctx.platform,
+                            &glob,
+                            Some(&command),
+                            &settings,
+                            &buffer_path,
+                        );
+                    }
+                    None => ctx
+                        .editor
+                        .status_bar
+                        .write(MessageKind::Error)
+                        .fmt(format_args!(
+                            "invalid lsp settings file '{}'",
+                            buffer_path.display()
+                        )),
+                }
+            }
+        }
+
         if let EditorEvent::BufferRead { handle } = *event {
             let buffer_path = match ctx.editor.buffers.get(handle).path.to_str() {
                 Some(path) => path,
                 None => continue,
             };
-            let (index, recipe) = match lsp
-                .recipes
-                .iter_mut()
-                .enumerate()
-                .find(|(_, r)| r.glob.matches(buffer_path))
-            {
-                Some(recipe) => recipe,
-                None => continue,
-            };
-            if recipe.running_client.is_some() {
-                continue;
-            }
-            let command = match parse_process_command(&recipe.command) {
-                Some(command) => command,
-                None => {
-                    ctx.editor
-                        .status_bar
-                        .write(MessageKind::Error)
-                        .fmt(format_args!("invalid lsp command '{}'", &recipe.command));
+            for index in 0..lsp.recipes.len() {
+                let recipe = &lsp.recipes[index];
+                if !recipe.glob.matches(buffer_path) {
                     continue;
                 }
-            };
 
-            let root = if recipe.root.as_os_str().is_empty() {
-                ctx.editor.current_directory.clone()
-            } else {
-                recipe.root.clone()
-            };
+                let workspace_folder = if recipe.root_markers.is_empty() {
+                    None
+                } else {
+                    let buffer_path = ctx.editor.current_directory.join(buffer_path);
+                    buffer_path
+                        .parent()
+                        .and_then(|dir| find_root_with_markers(dir, &recipe.root_markers))
+                };
+
+                let has_running_clients = !recipe.running_clients.is_empty();
+                let restrict_to_workspace_folders = match &workspace_folder {
+                    Some(folder) if has_running_clients => {
+                        if lsp.add_recipe_workspace_folder(&mut ctx.platform, index, folder) {
+                            continue;
+                        }
+                        true
+                    }
+                    _ if has_running_clients => continue,
+                    _ => false,
+                };
+                let recipe = &lsp.recipes[index];
+
+                let command = match parse_process_command(&recipe.command) {
+                    Some(command) => command,
+                    None => {
+                        ctx.editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("invalid lsp command '{}'", &recipe.command));
+                        continue;
+                    }
+                };
 
-            let log_file_path = if recipe.log_file_path.is_empty() {
-                None
-            } else {
-                Some(recipe.log_file_path.clone())
-            };
+                let root = if recipe.root.as_os_str().is_empty() {
+                    ctx.editor.current_directory.clone()
+                } else {
+                    recipe.root.clone()
+                };
 
-            let client_handle = lsp.start(
-                &mut ctx.platform,
-                plugin_handle,
-                command,
-                root,
-                log_file_path,
-            );
-            lsp.recipes[index].running_client = Some(client_handle);
+                let log_file_path = if recipe.log_file_path.is_empty() {
+                    None
+                } else {
+                    Some(recipe.log_file_path.clone())
+                };
+
+                let client_handle = lsp.start(
+                    &mut ctx.platform,
+                    plugin_handle,
+                    command,
+                    root,
+                    log_file_path,
+                );
+                let recipe = &mut lsp.recipes[index];
+                recipe.running_clients.push(client_handle);
+
+                if restrict_to_workspace_folders {
+                    for handle in &recipe.running_clients {
+                        if let ClientEntry::Occupied(client) = &mut lsp.entries[handle.0 as usize] {
+                            client.restrict_to_workspace_folders = true;
+                        }
+                    }
+                }
+
+                if let ClientEntry::Occupied(client) = &mut lsp.entries[client_handle.0 as usize] {
+                    let mut glob = Glob::default();
+                    if glob.compile(&recipe.glob_text).is_ok() {
+                        client.path_glob = Some(glob);
+                    }
+                    client.features = recipe.features;
+                    client.settings.clone_from(&recipe.settings);
+                    client
+                        .initialization_options
+                        .clone_from(&recipe.initialization_options);
+                    client.workspace_folders.extend(workspace_folder);
+                }
+            }
         }
     }
 
@@ -334,10 +592,53 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
             match *event {
                 EditorEvent::Idle => {
                     util::send_pending_did_change(client, &ctx.editor, &mut ctx.platform);
+
+                    for c in ctx.clients.iter() {
+                        let (buffer_handle, line_range) = match c.visible_line_range(&ctx.editor) {
+                            Some(range) => range,
+                            None => continue,
+                        };
+                        let buffer = ctx.editor.buffers.get(buffer_handle);
+                        if !client.syncs_buffer(buffer) {
+                            continue;
+                        }
+
+                        if client.features.contains(ClientFeature::InlayHints) {
+                            client.inlay_hints(
+                                &ctx.editor,
+                                &mut ctx.platform,
+                                buffer_handle,
+                                line_range,
+                            );
+                        }
+                        if client.features.contains(ClientFeature::SemanticTokens) {
+                            client.semantic_tokens(&ctx.editor, &mut ctx.platform, buffer_handle);
+                        }
+                        if client.features.contains(ClientFeature::CodeLens) {
+                            client.code_lenses(&ctx.editor, &mut ctx.platform, buffer_handle);
+                        }
+                        if client.features.contains(ClientFeature::DocumentHighlight) {
+                            if let Some(buffer_view_handle) = c.buffer_view_handle() {
+                                let position = ctx
+                                    .editor
+                                    .buffer_views
+                                    .get(buffer_view_handle)
+                                    .cursors
+                                    .main_cursor()
+                                    .position;
+                                client.document_highlights(
+                                    &ctx.editor,
+                                    &mut ctx.platform,
+                                    buffer_handle,
+                                    position,
+                                );
+                            }
+                        }
+                    }
                 }
                 EditorEvent::BufferRead { handle } => {
                     let buffer = ctx.editor.buffers.get(handle);
-                    if buffer.path.to_str() != client.log_file_path() {
+                    if client.syncs_buffer(buffer) {
                         client.versioned_buffers.dispose(handle);
                         util::send_did_open(client, &ctx.editor, &mut ctx.platform, handle);
                     }
@@ -349,35 +650,64 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
                     ..
                 } => {
                     let buffer = ctx.editor.buffers.get(handle);
-                    if buffer.path.to_str() != client.log_file_path() {
+                    if client.syncs_buffer(buffer) {
+                        if let Some(request) = client.code_lens_request_mut(handle) {
+                            request.insert_range(range);
+                        }
                         let text = text.as_str(&ctx.editor.events);
                         let range = BufferRange::between(range.from, range.from);
                         client.versioned_buffers.add_edit(handle, range, text);
+                        client.invalidate_inlay_hints(Some(handle));
+                        client.invalidate_semantic_tokens(Some(handle));
+                        client.invalidate_document_highlights(Some(handle));
+                        client.invalidate_code_lenses(Some(handle));
                     }
                 }
                 EditorEvent::BufferDeleteText { handle, range, .. } => {
                     let buffer = ctx.editor.buffers.get(handle);
-                    if buffer.path.to_str() != client.log_file_path() {
+                    if client.syncs_buffer(buffer) {
+                        if let Some(request) = client.code_lens_request_mut(handle) {
+                            request.delete_range(range);
+                        }
                         client.versioned_buffers.add_edit(handle, range, "");
+                        client.invalidate_inlay_hints(Some(handle));
+                        client.invalidate_semantic_tokens(Some(handle));
+                        client.invalidate_document_highlights(Some(handle));
+                        client.invalidate_code_lenses(Some(handle));
                     }
                 }
                 EditorEvent::BufferWrite { handle, .. } => {
                     let buffer = ctx.editor.buffers.get(handle);
-                    if buffer.path.to_str() != client.log_file_path() {
+                    if client.syncs_buffer(buffer) {
                         util::send_pending_did_change(client, &ctx.editor, &mut ctx.platform);
                         util::send_did_save(client, &ctx.editor, &mut ctx.platform, handle);
                     }
                 }
                 EditorEvent::BufferClose { handle } => {
+                    client.hierarchies.retain(|h| h.buffer_handle != handle);
                     let buffer = ctx.editor.buffers.get(handle);
-                    if buffer.path.to_str() != client.log_file_path() {
+                    if client.syncs_buffer(buffer) {
                         client.versioned_buffers.dispose(handle);
                         client.diagnostics.on_close_buffer(handle);
+                        client
+                            .inlay_hint_requests
+                            .retain(|r| r.buffer_handle != handle);
+                        client
+                            .semantic_tokens_requests
+                            .retain(|r| r.buffer_handle != handle);
+                        client
+                            .document_highlight_requests
+                            .retain(|r| r.buffer_handle != handle);
+                        client
+                            .code_lens_requests
+                            .retain(|r| r.buffer_handle != handle);
                         util::send_pending_did_change(client, &ctx.editor, &mut ctx.platform);
                         util::send_did_close(client, &ctx.editor, &mut ctx.platform, handle);
                     }
                 }
-                EditorEvent::FixCursors { .. } => (),
+                EditorEvent::FixCursors { .. }
+                | EditorEvent::ModeChange { .. }
+                | EditorEvent::ClientJoin { .. } => (),
             }
         }
     }
@@ -477,9 +807,20 @@ fn on_process_output(
 
 fn on_process_exit(handle: PluginHandle, ctx: &mut EditorContext, client_index: u32) {
     for buffer in ctx.editor.buffers.iter_mut() {
-        let mut lints = buffer.lints.mut_guard(handle);
+        let mut lints = buffer.lints.source_mut_guard(handle, client_index);
         lints.clear();
+        let mut annotations = buffer.annotations.mut_guard(handle, client_index);
+        annotations.clear();
+        let mut semantic_tokens = buffer.semantic_tokens.mut_guard(handle, client_index);
+        semantic_tokens.clear();
+        let mut symbol_highlights = buffer.symbol_highlights.mut_guard(handle, client_index);
+        symbol_highlights.clear();
+        let mut virtual_lines = buffer.virtual_lines.mut_guard(handle, client_index);
+        virtual_lines.clear();
     }
+    ctx.editor
+        .status_bar
+        .set_indicator(handle, client_index, "");
 
     let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
     if let ClientEntry::Occupied(client) = &mut lsp.entries[client_index as usize] {
@@ -490,9 +831,7 @@ fn on_process_exit(handle: PluginHandle, ctx: &mut EditorContext, client_index:
 
         let client_handle = client.handle();
         for recipe in &mut lsp.recipes {
-            if recipe.running_client == Some(client_handle) {
-                recipe.running_client = None;
-            }
+            recipe.running_clients.retain(|&h| h != client_handle);
         }
     }
 }
@@ -503,40 +842,49 @@ fn on_completion(
     completion_ctx: &CompletionContext,
 ) -> bool {
     let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+    let buffer = ctx.editor.buffers.get(completion_ctx.buffer_handle);
+    let buffer_path = buffer.path.to_str().unwrap_or("");
+    let trigger_char = buffer
+        .content()
+        .text_range(completion_ctx.word_range)
+        .next()
+        .and_then(|s| s.chars().next_back());
+
+    let mut requested_signature_help = false;
+    let mut requested_completion = false;
     for entry in &mut lsp.entries {
         let client = match entry {
             ClientEntry::Occupied(client) => client,
             _ => continue,
         };
+        if !client.handles_path(buffer_path) {
+            continue;
+        }
         client.json.clear();
 
         let mut should_complete = completion_ctx.completion_requested;
 
         if !should_complete {
-            if let Some(c) = ctx
-                .editor
-                .buffers
-                .get(completion_ctx.buffer_handle)
-                .content()
-                .text_range(completion_ctx.word_range)
-                .next()
-                .and_then(|s| s.chars().next_back())
-            {
-                if client.signature_help_triggers().contains(c) {
+            if let Some(c) = trigger_char {
+                if !requested_signature_help
+                    && client.features.contains(ClientFeature::SignatureHelp)
+                    && client.signature_help_triggers().contains(c)
+                {
                     client.signature_help(
                         &ctx.editor,
                         &mut ctx.platform,
                         completion_ctx.buffer_handle,
                         completion_ctx.cursor_position,
                     );
-                    return false;
+                    requested_signature_help = true;
+                    continue;
                 }
 
                 should_complete = client.completion_triggers().contains(c);
             }
         }
 
-        if should_complete {
+        if should_complete && client.features.contains(ClientFeature::Completion) {
             client.completion(
                 &ctx.editor,
                 &mut ctx.platform,
@@ -544,9 +892,97 @@ fn on_completion(
                 completion_ctx.buffer_handle,
                 completion_ctx.cursor_position,
             );
-            return true;
+            requested_completion = true;
         }
     }
 
-    false
+    requested_completion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_recipe_replacement() {
+        fn commands(lsp: &LspPlugin) -> Vec<(&str, &str)> {
+            lsp.recipes
+                .iter()
+                .map(|r| (&r.glob_text[..], &r.command[..]))
+                .collect()
+        }
+
+        let mut lsp = LspPlugin::default();
+        let features = ClientFeatures::ALL;
+
+        lsp.add_recipe("**/*.rs", "old-cmd", None, None, features)
+            .unwrap();
+        lsp.add_recipe("**/*.rs", "new-cmd", None, None, features)
+            .unwrap();
+        assert_eq!(vec![("**/*.rs", "new-cmd")], commands(&lsp));
+
+        lsp.add_extra_recipe("**/*.rs", "linter", None, None, features)
+            .unwrap();
+        lsp.add_extra_recipe("**/*.rs", "linter", None, None, features)
+            .unwrap();
+        lsp.add_recipe("**/*.c", "new-cmd", None, None, features)
+            .unwrap();
+        assert_eq!(
+            vec![
+                ("**/*.rs", "new-cmd"),
+                ("**/*.rs", "linter"),
+                ("**/*.c", "new-cmd")
+            ],
+            commands(&lsp)
+        );
+
+        lsp.add_recipe("**/*.rs", "other-cmd", None, None, features)
+            .unwrap();
+        assert_eq!(
+            vec![
+                ("**/*.rs", "other-cmd"),
+                ("**/*.rs", "linter"),
+                ("**/*.c", "new-cmd")
+            ],
+            commands(&lsp)
+        );
+    }
+
+    #[test]
+    fn root_with_markers() {
+        let base = std::env::temp_dir().join("pepper-lsp-root-markers-test");
+        let _ = fs::remove_dir_all(&base);
+        let project = base.join("project");
+        let dir = project.join("sub").join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        fs::write(base.join("package.json"), "").unwrap();
+
+        let markers =
+            |markers: &[&str]| -> Vec<String> { markers.iter().map(|&m| m.into()).collect() };
+
+        assert_eq!(
+            Some(project.clone()),
+            find_root_with_markers(&dir, &markers(&["Cargo.toml"]))
+        );
+        assert_eq!(
+            Some(project.clone()),
+            find_root_with_markers(&project, &markers(&["Cargo.toml"]))
+        );
+        assert_eq!(
+            Some(project.clone()),
+            find_root_with_markers(&dir, &markers(&["package.json", "Cargo.toml"]))
+        );
+        assert_eq!(
+            Some(base.clone()),
+            find_root_with_markers(&dir, &markers(&["package.json"]))
+        );
+        assert_eq!(
+            None,
+            find_root_with_markers(&dir, &markers(&["no-such-marker-file"]))
+        );
+        assert_eq!(None, find_root_with_markers(&dir, &[]));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
 }
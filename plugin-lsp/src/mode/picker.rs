@@ -121,7 +121,7 @@ pub fn enter_code_action_mode(
                             Some((EntrySource::Custom(i), _)) => i,
                             _ => 0,
                         };
-                        client.finish_code_action(&mut ctx.editor, index);
+                        client.finish_code_action(&mut ctx.editor, &mut ctx.platform, index);
                     }
                 }
 
@@ -162,6 +162,73 @@ pub fn enter_code_action_mode(
     }
 }
 
+pub fn enter_code_lens_mode(
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    client: &mut Client,
+) {
+    fn on_client_keys(
+        ctx: &mut EditorContext,
+        _: ClientHandle,
+        _: &mut KeysIterator,
+        poll: ReadLinePoll,
+    ) -> Option<EditorFlow> {
+        match poll {
+            ReadLinePoll::Pending => Some(EditorFlow::Continue),
+            ReadLinePoll::Submitted => {
+                if let Some(handle) = ctx.editor.mode.plugin_handle {
+                    let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+                    if let Some(client) = lsp
+                        .current_client_handle
+                        .take()
+                        .and_then(|h| lsp.get_mut(h))
+                    {
+                        let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database)
+                        {
+                            Some((EntrySource::Custom(i), _)) => i,
+                            _ => 0,
+                        };
+                        client.finish_code_lens(&mut ctx.editor, &mut ctx.platform, index);
+                    }
+                }
+
+                ctx.editor.enter_mode(ModeKind::default());
+                Some(EditorFlow::Continue)
+            }
+            ReadLinePoll::Canceled => {
+                if let Some(handle) = ctx.editor.mode.plugin_handle {
+                    let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+                    if let Some(client) = lsp
+                        .current_client_handle
+                        .take()
+                        .and_then(|h| lsp.get_mut(h))
+                    {
+                        client.cancel_current_request();
+                    }
+                }
+
+                ctx.editor.enter_mode(ModeKind::default());
+                Some(EditorFlow::Continue)
+            }
+        }
+    }
+
+    ctx.editor.read_line.set_prompt("code lens:");
+    ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+    ctx.editor.picker.move_cursor(0);
+
+    if ctx.editor.picker.len() > 0 {
+        ctx.editor.mode.plugin_handle = Some(plugin_handle);
+        ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+        ctx.editor.enter_mode(ModeKind::Picker);
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        lsp.current_client_handle = Some(client.handle());
+    } else {
+        client.cancel_current_request();
+    }
+}
+
 pub fn enter_document_symbol_mode(
     ctx: &mut EditorContext,
     plugin_handle: PluginHandle,
@@ -6,11 +6,13 @@ use std::{
 };
 
 use pepper::{
-    buffer::{BufferHandle, BufferProperties},
+    buffer::{BufferHandle, BufferLintSeverity, BufferProperties},
     buffer_position::{BufferPosition, BufferRange},
     editor::Editor,
     editor_utils::MessageKind,
+    editorconfig::EditorConfigProperties,
     glob::InvalidGlobError,
+    picker::CompletionKind,
     platform::{Platform, PlatformProcessHandle, PlatformRequest},
 };
 
@@ -379,6 +381,7 @@ pub struct TextEdit {
     pub new_text: JsonString,
 }
 impl TextEdit {
+    /// Applies `edits` to the buffer as a single undo group.
     pub fn apply_edits(
         editor: &mut Editor,
         buffer_handle: BufferHandle,
@@ -386,9 +389,21 @@ impl TextEdit {
         edits: JsonArray,
         json: &Json,
     ) {
-        let buffer = editor.buffers.get_mut(buffer_handle);
+        editor.buffers.get_mut(buffer_handle).commit_edits();
+        Self::apply_edits_uncommitted(editor, buffer_handle, temp_edits, edits, json);
+        editor.buffers.get_mut(buffer_handle).commit_edits();
+    }
 
-        buffer.commit_edits();
+    /// Applies `edits` to the buffer without committing them, so they join the buffer's current
+    /// undo group (the caller is responsible for committing it).
+    pub fn apply_edits_uncommitted(
+        editor: &mut Editor,
+        buffer_handle: BufferHandle,
+        temp_edits: &mut Vec<(BufferRange, BufferRange)>,
+        edits: JsonArray,
+        json: &Json,
+    ) {
+        let buffer = editor.buffers.get_mut(buffer_handle);
         temp_edits.clear();
 
         for edit in edits.elements(json) {
@@ -418,7 +433,6 @@ impl TextEdit {
 
             temp_edits.push((delete_range, insert_range));
         }
-        buffer.commit_edits();
     }
 }
 impl<'json> FromJson<'json> for TextEdit {
@@ -596,7 +610,8 @@ impl<'json> FromJson<'json> for WorkspaceEditChange {
 
 #[derive(Default)]
 pub struct WorkspaceEdit {
-    document_changes: JsonArray,
+    changes: JsonObject,
+    document_changes: Option<JsonArray>,
 }
 impl WorkspaceEdit {
     pub fn apply(
@@ -605,114 +620,192 @@ impl WorkspaceEdit {
         temp_edits: &mut Vec<(BufferRange, BufferRange)>,
         root: &Path,
         json: &Json,
-    ) {
-        for change in self.document_changes.clone().elements(json) {
+    ) -> bool {
+        let mut edited_buffers = Vec::new();
+        let applied = self.apply_changes(editor, temp_edits, &mut edited_buffers, root, json);
+
+        for (buffer_handle, is_temp) in edited_buffers {
+            if is_temp {
+                editor
+                    .buffers
+                    .defer_remove(buffer_handle, &mut editor.events);
+            } else {
+                editor.buffers.get_mut(buffer_handle).commit_edits();
+            }
+        }
+
+        applied
+    }
+
+    fn apply_changes(
+        &self,
+        editor: &mut Editor,
+        temp_edits: &mut Vec<(BufferRange, BufferRange)>,
+        edited_buffers: &mut Vec<(BufferHandle, bool)>,
+        root: &Path,
+        json: &Json,
+    ) -> bool {
+        let document_changes = match &self.document_changes {
+            Some(document_changes) => document_changes,
+            None => {
+                for (uri, edits) in self.changes.clone().members(json) {
+                    let path = match Uri::parse(root, uri) {
+                        Ok(Uri::Path(path)) => path,
+                        Err(_) => return false,
+                    };
+                    let edits = match edits {
+                        JsonValue::Array(edits) => edits,
+                        _ => return false,
+                    };
+                    apply_document_edit(editor, temp_edits, edited_buffers, path, edits, json);
+                }
+                return true;
+            }
+        };
+
+        for change in document_changes.clone().elements(json) {
             let change = match WorkspaceEditChange::from_json(change, json) {
                 Ok(change) => change,
-                Err(_) => return,
+                Err(_) => return false,
             };
             match change {
                 WorkspaceEditChange::DocumentEdit(edit) => {
-                    let path = match Uri::parse(&root, edit.uri.as_str(json)) {
+                    let path = match Uri::parse(root, edit.uri.as_str(json)) {
                         Ok(Uri::Path(path)) => path,
-                        Err(_) => return,
+                        Err(_) => return false,
                     };
-                    let buffer_handle = editor
-                        .buffers
-                        .find_with_path(&editor.current_directory, path);
-
-                    let (is_temp, buffer_handle) = match buffer_handle {
-                        Some(handle) => (false, handle),
-                        None => {
-                            let buffer = editor.buffers.add_new();
-                            buffer.properties = BufferProperties::scratch();
-                            buffer.properties.saving_enabled = true;
-                            buffer.set_path(path);
-                            let _ = buffer
-                                .read_from_file(&mut editor.word_database, &mut editor.events);
-                            (true, buffer.handle())
-                        }
+                    apply_document_edit(editor, temp_edits, edited_buffers, path, edit.edits, json);
+                }
+                WorkspaceEditChange::CreateFile(op) => {
+                    let path = match Uri::parse(root, op.uri.as_str(json)) {
+                        Ok(Uri::Path(path)) => root.join(path),
+                        Err(_) => return false,
                     };
 
-                    TextEdit::apply_edits(editor, buffer_handle, temp_edits, edit.edits, json);
-
-                    if is_temp {
-                        let _ = editor
-                            .buffers
-                            .get_mut(buffer_handle)
-                            .write_to_file(None, &mut editor.events);
-
+                    if path.exists() && !op.overwrite {
+                        if op.ignore_if_exists {
+                            continue;
+                        }
                         editor
-                            .buffers
-                            .defer_remove(buffer_handle, &mut editor.events);
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("file {:?} already exists", path));
+                        return false;
                     }
-                }
-                WorkspaceEditChange::CreateFile(op) => {
-                    let path = match Uri::parse(&root, op.uri.as_str(json)) {
-                        Ok(Uri::Path(path)) => path,
-                        Err(_) => return,
-                    };
 
-                    let mut open_options = fs::OpenOptions::new();
-                    open_options.write(true);
-                    if op.overwrite {
-                        open_options.truncate(true).create(true);
-                    } else {
-                        open_options.create_new(true);
+                    if let Some(parent) = path.parent() {
+                        let _ = fs::create_dir_all(parent);
                     }
-                    if open_options.open(path).is_err() && !op.ignore_if_exists {
+                    let mut open_options = fs::OpenOptions::new();
+                    open_options.write(true).truncate(true).create(true);
+                    if open_options.open(&path).is_err() {
                         editor
                             .status_bar
                             .write(MessageKind::Error)
                             .fmt(format_args!("could not create file {:?}", path));
+                        return false;
                     }
                 }
                 WorkspaceEditChange::RenameFile(op) => {
-                    let old_path = match Uri::parse(&root, op.old_uri.as_str(json)) {
-                        Ok(Uri::Path(path)) => path,
-                        Err(_) => return,
+                    let old_path = match Uri::parse(root, op.old_uri.as_str(json)) {
+                        Ok(Uri::Path(path)) => root.join(path),
+                        Err(_) => return false,
                     };
-                    let new_path = match Uri::parse(&root, op.new_uri.as_str(json)) {
-                        Ok(Uri::Path(path)) => path,
-                        Err(_) => return,
+                    let new_path = match Uri::parse(root, op.new_uri.as_str(json)) {
+                        Ok(Uri::Path(path)) => root.join(path),
+                        Err(_) => return false,
                     };
 
-                    if op.overwrite || !new_path.exists() || !op.ignore_if_exists {
-                        if fs::rename(old_path, new_path).is_err() && !op.ignore_if_exists {
-                            editor
-                                .status_bar
-                                .write(MessageKind::Error)
-                                .fmt(format_args!(
-                                    "could not rename file {:?} to {:?}",
-                                    old_path, new_path
-                                ));
+                    if new_path.exists() && !op.overwrite {
+                        if op.ignore_if_exists {
+                            continue;
+                        }
+                        editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("file {:?} already exists", new_path));
+                        return false;
+                    }
+
+                    if let Some(parent) = new_path.parent() {
+                        let _ = fs::create_dir_all(parent);
+                    }
+                    if fs::rename(&old_path, &new_path).is_err() {
+                        editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!(
+                                "could not rename file {:?} to {:?}",
+                                old_path, new_path
+                            ));
+                        return false;
+                    }
+
+                    for buffer in editor.buffers.iter_mut() {
+                        let buffer_path = editor.current_directory.join(&buffer.path);
+                        if let Ok(suffix) = buffer_path.strip_prefix(&old_path) {
+                            let path = new_path.join(suffix);
+                            let path = path
+                                .strip_prefix(&editor.current_directory)
+                                .unwrap_or(&path);
+                            buffer.set_path(path);
                         }
                     }
                 }
                 WorkspaceEditChange::DeleteFile(op) => {
-                    let path = match Uri::parse(&root, op.uri.as_str(json)) {
-                        Ok(Uri::Path(path)) => path,
-                        Err(_) => return,
+                    let path = match Uri::parse(root, op.uri.as_str(json)) {
+                        Ok(Uri::Path(path)) => root.join(path),
+                        Err(_) => return false,
                     };
 
-                    if op.recursive {
-                        if fs::remove_dir_all(path).is_err() && !op.ignore_if_not_exists {
+                    if !path.exists() && op.ignore_if_not_exists {
+                        continue;
+                    }
+
+                    for buffer in editor.buffers.iter() {
+                        let buffer_path = editor.current_directory.join(&buffer.path);
+                        if buffer_path.starts_with(&path) && buffer.needs_save() {
                             editor
                                 .status_bar
                                 .write(MessageKind::Error)
-                                .fmt(format_args!("could not delete directory {:?}", path));
+                                .fmt(format_args!(
+                                    "could not delete {:?} because buffer {:?} has unsaved changes",
+                                    path, buffer.path
+                                ));
+                            return false;
+                        }
+                    }
+
+                    let result = if path.is_dir() {
+                        if op.recursive {
+                            fs::remove_dir_all(&path)
+                        } else {
+                            fs::remove_dir(&path)
                         }
                     } else {
-                        if fs::remove_file(path).is_err() && !op.ignore_if_not_exists {
+                        fs::remove_file(&path)
+                    };
+                    if result.is_err() {
+                        editor
+                            .status_bar
+                            .write(MessageKind::Error)
+                            .fmt(format_args!("could not delete {:?}", path));
+                        return false;
+                    }
+
+                    for buffer in editor.buffers.iter() {
+                        let buffer_path = editor.current_directory.join(&buffer.path);
+                        if buffer_path.starts_with(&path) {
                             editor
-                                .status_bar
-                                .write(MessageKind::Error)
-                                .fmt(format_args!("could not delete file {:?}", path));
+                                .buffers
+                                .defer_remove(buffer.handle(), &mut editor.events);
                         }
                     }
                 }
             }
         }
+
+        true
     }
 }
 impl<'json> FromJson<'json> for WorkspaceEdit {
@@ -722,16 +815,78 @@ impl<'json> FromJson<'json> for WorkspaceEdit {
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
-        let changes = value.get("documentChanges", json);
-        this.document_changes = FromJson::from_json(changes, json)?;
+        for (key, value) in value.members(json) {
+            match key {
+                "changes" => this.changes = FromJson::from_json(value, json)?,
+                "documentChanges" => {
+                    this.document_changes = Some(FromJson::from_json(value, json)?)
+                }
+                _ => (),
+            }
+        }
         Ok(this)
     }
 }
 
+fn apply_document_edit(
+    editor: &mut Editor,
+    temp_edits: &mut Vec<(BufferRange, BufferRange)>,
+    edited_buffers: &mut Vec<(BufferHandle, bool)>,
+    path: &Path,
+    edits: JsonArray,
+    json: &Json,
+) {
+    let buffer_handle = editor
+        .buffers
+        .find_with_path(&editor.current_directory, path);
+
+    let (buffer_handle, is_temp) = match buffer_handle {
+        Some(handle) => match edited_buffers.iter().find(|(h, _)| *h == handle) {
+            Some(&(_, is_temp)) => (handle, is_temp),
+            None => {
+                editor.buffers.get_mut(handle).commit_edits();
+                edited_buffers.push((handle, false));
+                (handle, false)
+            }
+        },
+        None => {
+            let buffer = editor.buffers.add_new();
+            buffer.properties = BufferProperties::scratch();
+            buffer.properties.saving_enabled = true;
+            buffer.set_path(path);
+            buffer.editorconfig = EditorConfigProperties::find(&editor.current_directory, path);
+            let _ = buffer.read_from_file(&mut editor.word_database, &mut editor.events);
+            let handle = buffer.handle();
+            edited_buffers.push((handle, true));
+            (handle, true)
+        }
+    };
+
+    TextEdit::apply_edits_uncommitted(editor, buffer_handle, temp_edits, edits, json);
+
+    if is_temp {
+        let _ = editor.buffers.get_mut(buffer_handle).write_to_file(
+            None,
+            &mut editor.word_database,
+            &mut editor.events,
+        );
+    }
+}
+
+pub fn lint_severity(severity: JsonInteger) -> BufferLintSeverity {
+    match severity {
+        2 => BufferLintSeverity::Warning,
+        3 => BufferLintSeverity::Info,
+        4 => BufferLintSeverity::Hint,
+        _ => BufferLintSeverity::Error,
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentDiagnostic {
     pub message: JsonString,
     pub range: DocumentRange,
+    pub severity: JsonInteger,
     pub data: JsonValue,
 }
 impl DocumentDiagnostic {
@@ -768,6 +923,7 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
             match key {
                 "message" => this.message = JsonString::from_json(value, json)?,
                 "range" => this.range = DocumentRange::from_json(value, json)?,
+                "severity" => this.severity = JsonInteger::from_json(value, json)?,
                 "data" => this.data = value,
                 _ => (),
             }
@@ -776,10 +932,36 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentCommand {
+    pub title: JsonString,
+    pub command: JsonString,
+    pub arguments: Option<JsonArray>,
+}
+impl<'json> FromJson<'json> for DocumentCommand {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "title" => this.title = JsonString::from_json(value, json)?,
+                "command" => this.command = JsonString::from_json(value, json)?,
+                "arguments" => this.arguments = Some(JsonArray::from_json(value, json)?),
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentCodeAction {
     pub title: JsonString,
     pub edit: WorkspaceEdit,
+    pub command: Option<DocumentCommand>,
     pub disabled: bool,
 }
 impl<'json> FromJson<'json> for DocumentCodeAction {
@@ -789,10 +971,17 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
-        for (key, value) in value.members(json) {
+        for (key, member) in value.clone().members(json) {
             match key {
-                "title" => this.title = JsonString::from_json(value, json)?,
-                "edit" => this.edit = WorkspaceEdit::from_json(value, json)?,
+                "title" => this.title = JsonString::from_json(member, json)?,
+                "edit" => this.edit = WorkspaceEdit::from_json(member, json)?,
+                "command" => {
+                    let command = match member {
+                        JsonValue::String(_) => value.clone().into(),
+                        command => command,
+                    };
+                    this.command = Some(DocumentCommand::from_json(command, json)?);
+                }
                 "disabled" => this.disabled = true,
                 _ => (),
             }
@@ -801,6 +990,54 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentCodeLens {
+    pub range: DocumentRange,
+    pub command: Option<DocumentCommand>,
+}
+impl<'json> FromJson<'json> for DocumentCodeLens {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "range" => this.range = DocumentRange::from_json(value, json)?,
+                "command" => this.command = Some(DocumentCommand::from_json(value, json)?),
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
+#[derive(Default)]
+pub struct DocumentHierarchyItem {
+    pub name: JsonString,
+    pub uri: JsonString,
+    pub selection_range: DocumentRange,
+}
+impl<'json> FromJson<'json> for DocumentHierarchyItem {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "name" => this.name = JsonString::from_json(value, json)?,
+                "uri" => this.uri = JsonString::from_json(value, json)?,
+                "selectionRange" => this.selection_range = DocumentRange::from_json(value, json)?,
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentSymbolInformation {
     pub name: JsonString,
@@ -836,7 +1073,39 @@ impl<'json> FromJson<'json> for DocumentSymbolInformation {
 
 #[derive(Default)]
 pub struct DocumentCompletionItem {
-    pub text: JsonString,
+    pub label: JsonString,
+    pub kind: u32,
+    pub detail: JsonString,
+    pub documentation: JsonValue,
+    pub insert_text: Option<JsonString>,
+    pub text_edit: Option<TextEdit>,
+    pub is_snippet: bool,
+}
+impl DocumentCompletionItem {
+    pub fn text<'json>(&self, json: &'json Json) -> &'json str {
+        match (&self.text_edit, &self.insert_text) {
+            (Some(edit), _) => edit.new_text.as_str(json),
+            (None, Some(text)) => text.as_str(json),
+            (None, None) => self.label.as_str(json),
+        }
+    }
+
+    pub fn completion_kind(&self) -> CompletionKind {
+        match self.kind {
+            2 | 4 => CompletionKind::Method,
+            3 => CompletionKind::Function,
+            5 | 10 | 20 => CompletionKind::Field,
+            6 => CompletionKind::Variable,
+            7 | 8 | 13 | 22 | 25 => CompletionKind::Type,
+            9 => CompletionKind::Module,
+            11 | 12 | 16 => CompletionKind::Value,
+            14 | 24 => CompletionKind::Keyword,
+            15 => CompletionKind::Snippet,
+            17 | 19 => CompletionKind::Path,
+            21 => CompletionKind::Constant,
+            _ => CompletionKind::Text,
+        }
+    }
 }
 impl<'json> FromJson<'json> for DocumentCompletionItem {
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
@@ -847,10 +1116,27 @@ impl<'json> FromJson<'json> for DocumentCompletionItem {
         let mut this = Self::default();
         for (key, value) in value.members(json) {
             match key {
-                "label" => this.text = JsonString::from_json(value, json)?,
-                "insertText" => {
-                    this.text = JsonString::from_json(value, json)?;
-                    break;
+                "label" => this.label = JsonString::from_json(value, json)?,
+                "kind" => this.kind = u32::from_json(value, json)?,
+                "detail" => this.detail = JsonString::from_json(value, json)?,
+                "documentation" => this.documentation = value,
+                "insertText" => this.insert_text = Some(JsonString::from_json(value, json)?),
+                "insertTextFormat" => this.is_snippet = u32::from_json(value, json)? == 2,
+                "textEdit" => {
+                    let value = match value {
+                        JsonValue::Object(value) => value,
+                        _ => return Err(JsonConvertError),
+                    };
+                    // an `InsertReplaceEdit` is read as a `TextEdit` over its insert range
+                    let mut edit = TextEdit::default();
+                    for (key, value) in value.members(json) {
+                        match key {
+                            "range" | "insert" => edit.range = FromJson::from_json(value, json)?,
+                            "newText" => edit.new_text = FromJson::from_json(value, json)?,
+                            _ => (),
+                        }
+                    }
+                    this.text_edit = Some(edit);
                 }
                 _ => (),
             }
@@ -859,6 +1145,97 @@ impl<'json> FromJson<'json> for DocumentCompletionItem {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentInlayHint {
+    pub position: DocumentPosition,
+    pub label: JsonValue,
+    pub padding_left: bool,
+    pub padding_right: bool,
+}
+impl DocumentInlayHint {
+    pub fn write_text(&self, text: &mut String, json: &Json) {
+        if self.padding_left {
+            text.push(' ');
+        }
+        match &self.label {
+            JsonValue::String(label) => text.push_str(label.as_str(json)),
+            JsonValue::Array(parts) => {
+                for part in parts.clone().elements(json) {
+                    if let JsonValue::String(value) = part.get("value", json) {
+                        text.push_str(value.as_str(json));
+                    }
+                }
+            }
+            _ => (),
+        }
+        if self.padding_right {
+            text.push(' ');
+        }
+    }
+}
+impl<'json> FromJson<'json> for DocumentInlayHint {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "position" => this.position = DocumentPosition::from_json(value, json)?,
+                "label" => this.label = value,
+                "paddingLeft" => this.padding_left = bool::from_json(value, json)?,
+                "paddingRight" => this.padding_right = bool::from_json(value, json)?,
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
+pub fn apply_semantic_tokens_edits(data: &mut Vec<u32>, edits: JsonArray, json: &Json) {
+    let mut sorted_edits = Vec::new();
+    for edit in edits.elements(json) {
+        let start = match edit.clone().get("start", json) {
+            JsonValue::Integer(start) => start as usize,
+            _ => continue,
+        };
+        let delete_count = match edit.clone().get("deleteCount", json) {
+            JsonValue::Integer(count) => count as usize,
+            _ => 0,
+        };
+        sorted_edits.push((start, delete_count, edit.get("data", json)));
+    }
+    sorted_edits.sort_by_key(|&(start, _, _)| std::cmp::Reverse(start));
+
+    for (start, delete_count, edit_data) in sorted_edits {
+        let start = start.min(data.len());
+        let end = (start + delete_count).min(data.len());
+        let edit_data = edit_data.elements(json).filter_map(|v| match v {
+            JsonValue::Integer(i) => Some(i as u32),
+            _ => None,
+        });
+        data.splice(start..end, edit_data);
+    }
+}
+
+pub fn decode_semantic_tokens(data: &[u32]) -> impl '_ + Iterator<Item = (BufferRange, u32, u32)> {
+    let mut line_index = 0;
+    let mut column_index = 0;
+    data.chunks_exact(5).map(move |token| {
+        if token[0] > 0 {
+            line_index += token[0];
+            column_index = token[1];
+        } else {
+            column_index += token[1];
+        }
+
+        let from = BufferPosition::line_col(line_index as _, column_index as _);
+        let to = BufferPosition::line_col(from.line_index, (column_index + token[2]) as _);
+        (BufferRange::between(from, to), token[3], token[4])
+    })
+}
+
 fn try_get_content_range(buf: &[u8]) -> Option<Range<usize>> {
     fn find_pattern_end(buf: &[u8], pattern: &[u8]) -> Option<usize> {
         let len = pattern.len();
@@ -986,6 +1363,12 @@ pub struct Protocol {
     next_request_id: usize,
 }
 
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Protocol {
     pub fn new() -> Self {
         Self {
@@ -1111,6 +1494,13 @@ impl PendingRequestColection {
         self.pending_requests.push(PendingRequest { id, method });
     }
 
+    pub fn methods(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.pending_requests
+            .iter()
+            .filter(|r| r.id.0 != 0)
+            .map(|r| r.method)
+    }
+
     pub fn take(&mut self, id: RequestId) -> Option<&'static str> {
         for i in 0..self.pending_requests.len() {
             let request = &self.pending_requests[i];
@@ -1127,6 +1517,8 @@ impl PendingRequestColection {
 mod tests {
     use super::*;
 
+    use pepper::testing::TestEditor;
+
     #[test]
     fn parse_uri() {
         fn assert_uri(expect: &str, raw: &str) {
@@ -1145,4 +1537,242 @@ mod tests {
             assert_uri("c:/file.rs", "file:///c:/file.rs");
         }
     }
+
+    #[test]
+    fn semantic_tokens_edits() {
+        fn apply_edits(data: &[u32], raw_edits: &str) -> Vec<u32> {
+            let mut json = Json::new();
+            let mut reader = raw_edits.as_bytes();
+            let edits = match json.read(&mut reader) {
+                Ok(JsonValue::Array(edits)) => edits,
+                _ => panic!("could not parse edits {}", raw_edits),
+            };
+            let mut data = data.to_vec();
+            apply_semantic_tokens_edits(&mut data, edits, &json);
+            data
+        }
+
+        assert_eq!(vec![0, 1, 2, 3, 4], apply_edits(&[0, 1, 2, 3, 4], "[]"));
+        assert_eq!(
+            vec![0, 9, 9, 3, 4],
+            apply_edits(
+                &[0, 1, 2, 3, 4],
+                r#"[{"start":1,"deleteCount":2,"data":[9,9]}]"#
+            )
+        );
+        assert_eq!(
+            vec![7, 0, 3, 4, 8],
+            apply_edits(
+                &[0, 1, 2, 3, 4],
+                r#"[{"start":0,"deleteCount":0,"data":[7]},{"start":1,"deleteCount":2},{"start":5,"deleteCount":0,"data":[8]}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn workspace_edit_file_operations() {
+        fn apply(editor: &mut TestEditor, root: &Path, changes: &str) -> bool {
+            let mut json = Json::new();
+            let mut reader = changes.as_bytes();
+            let changes = match json.read(&mut reader) {
+                Ok(changes) => changes,
+                Err(_) => panic!("could not parse changes {}", changes),
+            };
+            let mut edit = WorkspaceEdit::default();
+            if let JsonValue::Array(changes) = changes {
+                edit.document_changes = Some(changes);
+            }
+            let mut temp_edits = Vec::new();
+            let applied = edit.apply(&mut editor.ctx_mut().editor, &mut temp_edits, root, &json);
+            editor.ctx_mut().trigger_event_handlers();
+            applied
+        }
+        fn has_buffer(editor: &TestEditor, buffer_handle: BufferHandle) -> bool {
+            let buffers = &editor.ctx().editor.buffers;
+            buffers.iter().any(|b| b.handle() == buffer_handle)
+        }
+
+        let dir = std::env::temp_dir().join("pepper-lsp-workspace-edit-file-operations-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let uri = |name: &str| format!("file://{}", dir.join(name).display());
+
+        let mut editor = TestEditor::new(Vec::new());
+        let create = format!(r#"[{{"kind":"create","uri":"{}"}}]"#, uri("src/a.rs"));
+        assert!(apply(&mut editor, &dir, &create));
+        assert!(dir.join("src/a.rs").is_file());
+
+        let rename = format!(
+            r#"[{{"kind":"rename","oldUri":"{}","newUri":"{}"}}]"#,
+            uri("src/a.rs"),
+            uri("src/b.rs")
+        );
+        assert!(apply(&mut editor, &dir, &rename));
+        assert!(!dir.join("src/a.rs").exists());
+        assert!(dir.join("src/b.rs").is_file());
+
+        let buffer_handle = editor.open_buffer(dir.join("src/b.rs").to_str().unwrap(), "b");
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(buffer_handle);
+        buffer.properties.saving_enabled = true;
+        editor.keys("ix<esc>");
+        assert!(editor.ctx().editor.buffers.get(buffer_handle).needs_save());
+
+        let delete = format!(r#"[{{"kind":"delete","uri":"{}"}}]"#, uri("src/b.rs"));
+        assert!(!apply(&mut editor, &dir, &delete));
+        assert!(dir.join("src/b.rs").is_file());
+        assert!(has_buffer(&editor, buffer_handle));
+
+        let buffer = editor.ctx_mut().editor.buffers.get_mut(buffer_handle);
+        buffer.properties.saving_enabled = false;
+        assert!(apply(&mut editor, &dir, &delete));
+        assert!(!dir.join("src/b.rs").exists());
+        assert!(!has_buffer(&editor, buffer_handle));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn semantic_tokens_decode() {
+        fn range(from: (usize, usize), to: (usize, usize)) -> BufferRange {
+            BufferRange::between(
+                BufferPosition::line_col(from.0 as _, from.1 as _),
+                BufferPosition::line_col(to.0 as _, to.1 as _),
+            )
+        }
+
+        let mut data = vec![0, 4, 3, 1, 0, 0, 5, 2, 2, 1, 2, 1, 4, 0, 0];
+        let tokens: Vec<_> = decode_semantic_tokens(&data).collect();
+        assert_eq!(
+            vec![
+                (range((0, 4), (0, 7)), 1, 0),
+                (range((0, 9), (0, 11)), 2, 1),
+                (range((2, 1), (2, 5)), 0, 0),
+            ],
+            tokens
+        );
+
+        let mut json = Json::new();
+        let mut reader = r#"[{"start":5,"deleteCount":5,"data":[1,0,3,3,0,0,6,1,1,0]}]"#.as_bytes();
+        let edits = match json.read(&mut reader) {
+            Ok(JsonValue::Array(edits)) => edits,
+            _ => panic!("could not parse edits"),
+        };
+        apply_semantic_tokens_edits(&mut data, edits, &json);
+        let tokens: Vec<_> = decode_semantic_tokens(&data).collect();
+        assert_eq!(
+            vec![
+                (range((0, 4), (0, 7)), 1, 0),
+                (range((1, 0), (1, 3)), 3, 0),
+                (range((1, 6), (1, 7)), 1, 0),
+                (range((3, 1), (3, 5)), 0, 0),
+            ],
+            tokens
+        );
+
+        data.push(1);
+        assert_eq!(4, decode_semantic_tokens(&data).count());
+    }
+
+    #[test]
+    fn inlay_hint_text() {
+        fn hint_text(raw: &str) -> String {
+            let mut json = Json::new();
+            let mut reader = raw.as_bytes();
+            let value = json.read(&mut reader).unwrap();
+            let hint = match DocumentInlayHint::from_json(value, &json) {
+                Ok(hint) => hint,
+                Err(_) => panic!("could not parse inlay hint {}", raw),
+            };
+            let mut text = String::new();
+            hint.write_text(&mut text, &json);
+            text
+        }
+
+        assert_eq!(
+            ": i32",
+            hint_text(r#"{"position":{"line":0,"character":5},"label":": i32"}"#)
+        );
+        assert_eq!(
+            "name: ",
+            hint_text(
+                r#"{"position":{"line":0,"character":5},"label":"name:","paddingRight":true}"#
+            )
+        );
+        assert_eq!(
+            " Vec<u8>",
+            hint_text(
+                r#"{"label":[{"value":"Vec"},{"value":"<u8>"}],"paddingLeft":true,"position":{"line":0,"character":0}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn completion_item_text() {
+        fn item_text(raw: &str) -> (String, Option<u32>) {
+            let mut json = Json::new();
+            let mut reader = raw.as_bytes();
+            let value = json.read(&mut reader).unwrap();
+            let item = match DocumentCompletionItem::from_json(value, &json) {
+                Ok(item) => item,
+                Err(_) => panic!("could not parse completion item {}", raw),
+            };
+            let start = item.text_edit.as_ref().map(|e| e.range.start.character);
+            (item.text(&json).into(), start)
+        }
+
+        assert_eq!(("len".into(), None), item_text(r#"{"label":"len"}"#));
+        assert_eq!(
+            ("len()".into(), None),
+            item_text(r#"{"label":"len","insertText":"len()"}"#)
+        );
+        assert_eq!(
+            ("?.len".into(), Some(1)),
+            item_text(
+                r#"{"label":"len","insertText":"len()","textEdit":{"newText":"?.len","range":{"start":{"line":0,"character":1},"end":{"line":0,"character":2}}}}"#
+            )
+        );
+        assert_eq!(
+            ("len".into(), Some(2)),
+            item_text(
+                r#"{"label":"len","textEdit":{"newText":"len","insert":{"start":{"line":0,"character":2},"end":{"line":0,"character":2}},"replace":{"start":{"line":0,"character":2},"end":{"line":0,"character":5}}}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn code_action_command() {
+        fn command_name(raw: &str) -> Option<String> {
+            let mut json = Json::new();
+            let mut reader = raw.as_bytes();
+            let value = json.read(&mut reader).unwrap();
+            let action = match DocumentCodeAction::from_json(value, &json) {
+                Ok(action) => action,
+                Err(_) => panic!("could not parse code action {}", raw),
+            };
+            action.command.map(|c| c.command.as_str(&json).into())
+        }
+
+        assert_eq!(
+            None,
+            command_name(r#"{"title":"fix","edit":{"changes":{}}}"#)
+        );
+        assert_eq!(
+            Some("fix.all".into()),
+            command_name(r#"{"title":"fix","command":"fix.all","arguments":[1]}"#)
+        );
+        assert_eq!(
+            Some("fix.all".into()),
+            command_name(r#"{"title":"fix","command":{"title":"fix","command":"fix.all"}}"#)
+        );
+    }
+
+    #[test]
+    fn diagnostic_lint_severity() {
+        assert_eq!(BufferLintSeverity::Error, lint_severity(1));
+        assert_eq!(BufferLintSeverity::Warning, lint_severity(2));
+        assert_eq!(BufferLintSeverity::Info, lint_severity(3));
+        assert_eq!(BufferLintSeverity::Hint, lint_severity(4));
+        assert_eq!(BufferLintSeverity::Error, lint_severity(0));
+        assert_eq!(BufferLintSeverity::Error, lint_severity(5));
+    }
 }
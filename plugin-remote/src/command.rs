@@ -0,0 +1,105 @@
+use crate::{DeferredRemoteCommand, RemoteCommand, RemotePlugin};
+use pepper::{
+    command::{CommandError, CommandIO, CommandManager},
+    editor::EditorContext,
+    editor_utils::parse_process_command,
+    plugin::PluginHandle,
+};
+
+pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHandle) {
+    let mut r = |name, completions, command_fn| {
+        commands.register(Some(plugin_handle), name, completions, command_fn);
+    };
+
+    r("remote-plugin", &[], |ctx, io| {
+        let command_text = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let command = match parse_process_command(command_text) {
+            Some(command) => command,
+            None => {
+                return Err(CommandError::OtherOwned(format!(
+                    "invalid remote plugin command '{}'",
+                    command_text
+                )))
+            }
+        };
+
+        let plugin_handle = io.plugin_handle();
+        let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+        remote_plugin.start(&mut ctx.platform, plugin_handle, command_text, command);
+        Ok(())
+    });
+
+    r("remote-plugin-stop-all", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(io.plugin_handle());
+        if remote_plugin.stop_all(&mut ctx.platform) {
+            Ok(())
+        } else {
+            Err(CommandError::OtherStatic("no remote plugin running"))
+        }
+    });
+}
+
+pub fn register_remote_command(
+    commands: &mut CommandManager,
+    plugin_handle: PluginHandle,
+    remote_plugin: &mut RemotePlugin,
+    remote_index: u32,
+    name: &str,
+) -> Result<(), CommandError> {
+    if let Some(command) = remote_plugin.commands.iter_mut().find(|c| c.name == name) {
+        command.remote_index = Some(remote_index);
+        return Ok(());
+    }
+    if commands.find_command(name).is_some() {
+        return Err(CommandError::CommandAlreadyExists);
+    }
+
+    let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+    commands.register(Some(plugin_handle), name, &[], execute_remote_command);
+    remote_plugin.commands.push(RemoteCommand {
+        name,
+        remote_index: Some(remote_index),
+    });
+    Ok(())
+}
+
+fn execute_remote_command(ctx: &mut EditorContext, io: &mut CommandIO) -> Result<(), CommandError> {
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(io.plugin_handle());
+    let remote_index = remote_plugin
+        .commands
+        .iter()
+        .find(|c| c.name == io.name)
+        .and_then(|c| c.remote_index);
+    let remote_index = match remote_index {
+        Some(index) => index,
+        None => return Err(CommandError::OtherStatic("remote plugin is not running")),
+    };
+
+    let mut args = Vec::new();
+    while let Some(arg) = io.args.try_next() {
+        args.push(arg);
+    }
+    let client_handle = io.client_handle().ok();
+
+    if remote_plugin.is_reserved(remote_index) {
+        remote_plugin.deferred_commands.push(DeferredRemoteCommand {
+            name: io.name.into(),
+            args: args.iter().map(|&a| a.into()).collect(),
+            bang: io.bang,
+            client_handle,
+        });
+        return Ok(());
+    }
+
+    let remote = match remote_plugin.get_mut(remote_index) {
+        Some(remote) => remote,
+        None => return Err(CommandError::OtherStatic("remote plugin is not running")),
+    };
+    remote.json.clear();
+    remote.notify_command(&mut ctx.platform, io.name, &args, io.bang, client_handle);
+    Ok(())
+}
@@ -0,0 +1,858 @@
+use std::process::{Command, Stdio};
+
+use pepper::{
+    buffer::BufferHandle,
+    buffer_position::BufferRange,
+    client::ClientHandle,
+    editor::{Editor, EditorContext},
+    editor_utils::MessageKind,
+    events::{EditorEvent, EditorEventIter},
+    mode::ModeKind,
+    picker::{CompletionKind, CompletionSource},
+    platform::{Platform, PlatformProcessHandle, PlatformRequest, ProcessTag},
+    plugin::{CompletionContext, Plugin, PluginDefinition, PluginHandle},
+    word_database::WordKind,
+    ResourceFile,
+};
+use pepper_plugin_lsp::{
+    json::{Json, JsonArray, JsonKey, JsonObject, JsonValue},
+    protocol::{
+        DocumentPosition, DocumentRange, Protocol, ProtocolError, RequestId, ResponseError,
+        ServerEvent, ServerResponse,
+    },
+};
+
+mod command;
+mod request_handler;
+
+use request_handler::RequestError;
+
+const REMOTE_PROCESS_BUFFER_LEN: usize = 4 * 1024;
+
+pub static DEFINITION: PluginDefinition = PluginDefinition {
+    instantiate: |handle, ctx| {
+        command::register_commands(&mut ctx.editor.commands, handle);
+        Some(Plugin {
+            data: Box::new(RemotePlugin::default()),
+
+            on_editor_events,
+
+            on_process_spawned,
+            on_process_output,
+            on_process_exit,
+
+            on_completion,
+
+            ..Default::default()
+        })
+    },
+    help_pages: &[ResourceFile {
+        name: "remote_plugin_help.md",
+        content: include_str!("../rc/help.md"),
+    }],
+};
+
+pub(crate) static SUBSCRIPTION_NAMES: &[&str] = &[
+    "bufferRead",
+    "bufferWrite",
+    "bufferClose",
+    "bufferInsertText",
+    "bufferDeleteText",
+    "modeChange",
+    "clientJoin",
+    "idle",
+    "completion",
+];
+
+#[derive(Clone, Copy)]
+pub(crate) enum Subscription {
+    BufferRead,
+    BufferWrite,
+    BufferClose,
+    BufferInsertText,
+    BufferDeleteText,
+    ModeChange,
+    ClientJoin,
+    Idle,
+    Completion,
+}
+impl Subscription {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let subscription = match name {
+            "bufferRead" => Self::BufferRead,
+            "bufferWrite" => Self::BufferWrite,
+            "bufferClose" => Self::BufferClose,
+            "bufferInsertText" => Self::BufferInsertText,
+            "bufferDeleteText" => Self::BufferDeleteText,
+            "modeChange" => Self::ModeChange,
+            "clientJoin" => Self::ClientJoin,
+            "idle" => Self::Idle,
+            "completion" => Self::Completion,
+            _ => return None,
+        };
+        Some(subscription)
+    }
+
+    pub fn name(self) -> &'static str {
+        SUBSCRIPTION_NAMES[self as usize]
+    }
+}
+
+struct PendingCompletion {
+    id: RequestId,
+    client_handle: ClientHandle,
+    buffer_handle: BufferHandle,
+}
+
+pub(crate) struct Remote {
+    pub command: String,
+    pub protocol: Protocol,
+    pub json: Json,
+    subscriptions: u32,
+    pending_completion: Option<PendingCompletion>,
+    pub temp_edits: Vec<(BufferRange, BufferRange)>,
+}
+impl Remote {
+    fn new(command: String) -> Self {
+        Self {
+            command,
+            protocol: Protocol::new(),
+            json: Json::new(),
+            subscriptions: 0,
+            pending_completion: None,
+            temp_edits: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self, subscription: Subscription) {
+        self.subscriptions |= 1 << subscription as u32;
+    }
+
+    pub fn is_subscribed(&self, subscription: Subscription) -> bool {
+        self.subscriptions & (1 << subscription as u32) != 0
+    }
+
+    fn notify_event(
+        &mut self,
+        platform: &mut Platform,
+        subscription: Subscription,
+        params: JsonObject,
+    ) {
+        if self.is_subscribed(subscription) {
+            self.protocol
+                .notify(platform, &mut self.json, subscription.name(), params.into());
+        }
+    }
+
+    pub fn notify_command<S>(
+        &mut self,
+        platform: &mut Platform,
+        name: &str,
+        args: &[S],
+        bang: bool,
+        client_handle: Option<ClientHandle>,
+    ) where
+        S: AsRef<str>,
+    {
+        let json = &mut self.json;
+
+        let mut json_args = JsonArray::default();
+        for arg in args {
+            let arg = json.create_string(arg.as_ref());
+            json_args.push(arg.into(), json);
+        }
+
+        let mut params = JsonObject::default();
+        let name = json.create_string(name);
+        params.set("name".into(), name.into(), json);
+        params.set("args".into(), json_args.into(), json);
+        params.set("bang".into(), bang.into(), json);
+        let client = match client_handle {
+            Some(handle) => JsonValue::Integer(handle.0 as _),
+            None => JsonValue::Null,
+        };
+        params.set("client".into(), client, json);
+
+        self.protocol
+            .notify(platform, json, "command", params.into());
+    }
+}
+
+pub(crate) struct RemoteCommand {
+    pub name: &'static str,
+    pub remote_index: Option<u32>,
+}
+
+pub(crate) struct DeferredRemoteCommand {
+    pub name: String,
+    pub args: Vec<String>,
+    pub bang: bool,
+    pub client_handle: Option<ClientHandle>,
+}
+
+enum RemoteEntry {
+    Vacant,
+    Reserved { stopped: bool },
+    Occupied(Box<Remote>),
+}
+
+#[derive(Default)]
+pub(crate) struct RemotePlugin {
+    remotes: Vec<RemoteEntry>,
+    pub commands: Vec<RemoteCommand>,
+    pub deferred_commands: Vec<DeferredRemoteCommand>,
+}
+
+impl RemotePlugin {
+    pub fn start(
+        &mut self,
+        platform: &mut Platform,
+        plugin_handle: PluginHandle,
+        command_text: &str,
+        mut command: Command,
+    ) -> u32 {
+        let index = match self
+            .remotes
+            .iter()
+            .position(|e| matches!(e, RemoteEntry::Vacant))
+        {
+            Some(index) => index,
+            None => {
+                self.remotes.push(RemoteEntry::Vacant);
+                self.remotes.len() - 1
+            }
+        };
+
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Plugin {
+                plugin_handle,
+                id: index as _,
+            },
+            command,
+            buf_len: REMOTE_PROCESS_BUFFER_LEN,
+        });
+
+        self.remotes[index] = RemoteEntry::Occupied(Box::new(Remote::new(command_text.into())));
+        index as _
+    }
+
+    pub fn stop(&mut self, platform: &mut Platform, index: u32) -> bool {
+        let entry = match self.remotes.get_mut(index as usize) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        match entry {
+            RemoteEntry::Vacant | RemoteEntry::Reserved { stopped: true } => return false,
+            RemoteEntry::Reserved { stopped } => *stopped = true,
+            RemoteEntry::Occupied(remote) => {
+                kill_remote(platform, remote);
+                *entry = RemoteEntry::Vacant;
+            }
+        }
+        self.unregister_commands(index);
+        true
+    }
+
+    pub fn stop_all(&mut self, platform: &mut Platform) -> bool {
+        let mut any_stopped = false;
+        for i in 0..self.remotes.len() {
+            any_stopped |= self.stop(platform, i as _);
+        }
+        any_stopped
+    }
+
+    fn unregister_commands(&mut self, index: u32) {
+        for command in &mut self.commands {
+            if command.remote_index == Some(index) {
+                command.remote_index = None;
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut Remote> {
+        match self.remotes.get_mut(index as usize) {
+            Some(RemoteEntry::Occupied(remote)) => Some(remote),
+            _ => None,
+        }
+    }
+
+    pub fn is_reserved(&self, index: u32) -> bool {
+        matches!(
+            self.remotes.get(index as usize),
+            Some(RemoteEntry::Reserved { stopped: false })
+        )
+    }
+
+    fn acquire(&mut self, index: u32) -> Option<Box<Remote>> {
+        let entry = self.remotes.get_mut(index as usize)?;
+        match std::mem::replace(entry, RemoteEntry::Reserved { stopped: false }) {
+            RemoteEntry::Occupied(remote) => Some(remote),
+            previous => {
+                *entry = previous;
+                None
+            }
+        }
+    }
+
+    fn release(&mut self, platform: &mut Platform, index: u32, remote: Box<Remote>) {
+        let entry = &mut self.remotes[index as usize];
+        match entry {
+            RemoteEntry::Reserved { stopped: false } => *entry = RemoteEntry::Occupied(remote),
+            _ => {
+                kill_remote(platform, &remote);
+                *entry = RemoteEntry::Vacant;
+            }
+        }
+    }
+
+    fn remotes_mut(&mut self) -> impl Iterator<Item = &mut Remote> {
+        self.remotes.iter_mut().filter_map(|e| match e {
+            RemoteEntry::Occupied(remote) => Some(remote.as_mut()),
+            _ => None,
+        })
+    }
+}
+
+fn kill_remote(platform: &mut Platform, remote: &Remote) {
+    if let Some(handle) = remote.protocol.process_handle() {
+        platform
+            .requests
+            .enqueue(PlatformRequest::KillProcess { handle });
+    }
+}
+
+fn buffer_event_params(editor: &Editor, json: &mut Json, handle: BufferHandle) -> JsonObject {
+    let mut params = JsonObject::default();
+    params.set("buffer".into(), JsonValue::Integer(handle.0 as _), json);
+    let path = editor.buffers.get(handle).path.to_str().unwrap_or("");
+    let path = json.create_string(path);
+    params.set("path".into(), path.into(), json);
+    params
+}
+
+fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    for remote in remote_plugin.remotes_mut() {
+        if remote.subscriptions == 0 {
+            continue;
+        }
+
+        let mut events = EditorEventIter::new();
+        while let Some(event) = events.next(&ctx.editor.events) {
+            remote.json.clear();
+            let json = &mut remote.json;
+
+            match *event {
+                EditorEvent::Idle => {
+                    remote.notify_event(
+                        &mut ctx.platform,
+                        Subscription::Idle,
+                        JsonObject::default(),
+                    );
+                }
+                EditorEvent::BufferRead { handle } => {
+                    let params = buffer_event_params(&ctx.editor, json, handle);
+                    remote.notify_event(&mut ctx.platform, Subscription::BufferRead, params);
+                }
+                EditorEvent::BufferInsertText {
+                    handle,
+                    range,
+                    text,
+                } => {
+                    let mut params = JsonObject::default();
+                    params.set("buffer".into(), JsonValue::Integer(handle.0 as _), json);
+                    let range = DocumentRange::from_buffer_range(range).to_json_value(json);
+                    params.set("range".into(), range, json);
+                    let text = json.create_string(text.as_str(&ctx.editor.events));
+                    params.set("text".into(), text.into(), json);
+                    remote.notify_event(&mut ctx.platform, Subscription::BufferInsertText, params);
+                }
+                EditorEvent::BufferDeleteText { handle, range } => {
+                    let mut params = JsonObject::default();
+                    params.set("buffer".into(), JsonValue::Integer(handle.0 as _), json);
+                    let range = DocumentRange::from_buffer_range(range).to_json_value(json);
+                    params.set("range".into(), range, json);
+                    remote.notify_event(&mut ctx.platform, Subscription::BufferDeleteText, params);
+                }
+                EditorEvent::BufferWrite { handle, .. } => {
+                    let params = buffer_event_params(&ctx.editor, json, handle);
+                    remote.notify_event(&mut ctx.platform, Subscription::BufferWrite, params);
+                }
+                EditorEvent::BufferClose { handle } => {
+                    let params = buffer_event_params(&ctx.editor, json, handle);
+                    remote.notify_event(&mut ctx.platform, Subscription::BufferClose, params);
+                }
+                EditorEvent::ModeChange { kind } => {
+                    let mut params = JsonObject::default();
+                    params.set("mode".into(), kind.name().into(), json);
+                    remote.notify_event(&mut ctx.platform, Subscription::ModeChange, params);
+                }
+                EditorEvent::ClientJoin { handle } => {
+                    let mut params = JsonObject::default();
+                    params.set("client".into(), JsonValue::Integer(handle.0 as _), json);
+                    remote.notify_event(&mut ctx.platform, Subscription::ClientJoin, params);
+                }
+                EditorEvent::FixCursors { .. } => (),
+            }
+        }
+    }
+}
+
+fn on_process_spawned(
+    plugin_handle: PluginHandle,
+    ctx: &mut EditorContext,
+    remote_index: u32,
+    process_handle: PlatformProcessHandle,
+) {
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    if let Some(remote) = remote_plugin.get_mut(remote_index) {
+        remote.protocol.set_process_handle(process_handle);
+        remote.json.clear();
+
+        let mut params = JsonObject::default();
+        let current_directory = ctx.editor.current_directory.to_str().unwrap_or("");
+        let current_directory = remote.json.create_string(current_directory);
+        params.set(
+            "currentDirectory".into(),
+            current_directory.into(),
+            &mut remote.json,
+        );
+        remote.protocol.notify(
+            &mut ctx.platform,
+            &mut remote.json,
+            "initialize",
+            params.into(),
+        );
+    }
+}
+
+fn on_process_output(
+    plugin_handle: PluginHandle,
+    ctx: &mut EditorContext,
+    remote_index: u32,
+    bytes: &[u8],
+) {
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    let mut remote = match remote_plugin.acquire(remote_index) {
+        Some(remote) => remote,
+        None => return,
+    };
+    remote.json.clear();
+
+    let mut events = remote.protocol.parse_events(bytes);
+    while let Some(event) = events.next(&mut remote.protocol, &mut remote.json) {
+        match event {
+            ServerEvent::ParseError => {
+                remote.protocol.respond(
+                    &mut ctx.platform,
+                    &mut remote.json,
+                    JsonValue::Null,
+                    Err(ResponseError::parse_error()),
+                );
+            }
+            ServerEvent::Request(request) => {
+                let request_id = request.id.clone();
+                let result = request_handler::on_request(
+                    &mut remote,
+                    ctx,
+                    plugin_handle,
+                    remote_index,
+                    request,
+                );
+
+                let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+                for command in remote_plugin.deferred_commands.drain(..) {
+                    remote.notify_command(
+                        &mut ctx.platform,
+                        &command.name,
+                        &command.args,
+                        command.bang,
+                        command.client_handle,
+                    );
+                }
+
+                let result = match result {
+                    Ok(value) => Ok(value),
+                    Err(RequestError::Protocol(ProtocolError::ParseError)) => {
+                        Err(ResponseError::parse_error())
+                    }
+                    Err(RequestError::Protocol(ProtocolError::MethodNotFound)) => {
+                        Err(ResponseError::method_not_found())
+                    }
+                    Err(RequestError::Message(message)) => Err(ResponseError {
+                        code: -32603,
+                        message: JsonKey::Str(message),
+                        data: JsonValue::Null,
+                    }),
+                    Err(RequestError::Command(error)) => {
+                        let message = remote.json.fmt_string(format_args!("{}", error));
+                        Err(ResponseError {
+                            code: -32603,
+                            message: JsonKey::String(message),
+                            data: JsonValue::Null,
+                        })
+                    }
+                };
+                remote
+                    .protocol
+                    .respond(&mut ctx.platform, &mut remote.json, request_id, result);
+            }
+            ServerEvent::Notification(_) => (),
+            ServerEvent::Response(response) => {
+                on_response(&mut remote, ctx, plugin_handle, response)
+            }
+        }
+    }
+    events.finish(&mut remote.protocol);
+
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    remote_plugin.release(&mut ctx.platform, remote_index, remote);
+}
+
+fn on_response(
+    remote: &mut Remote,
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    response: ServerResponse,
+) {
+    let completion = match remote.pending_completion.take() {
+        Some(completion) if completion.id == response.id => completion,
+        completion => {
+            remote.pending_completion = completion;
+            return;
+        }
+    };
+
+    let entries = match response.result {
+        Ok(JsonValue::Array(entries)) => entries,
+        Ok(_) => return,
+        Err(error) => {
+            ctx.editor
+                .status_bar
+                .write(MessageKind::Error)
+                .fmt(format_args!(
+                    "remote plugin completion error: {}",
+                    error.message.as_str(&remote.json)
+                ));
+            return;
+        }
+    };
+
+    if ctx.editor.mode.kind() != ModeKind::Insert {
+        return;
+    }
+
+    let buffer_view_handle = match ctx
+        .clients
+        .get(completion.client_handle)
+        .buffer_view_handle()
+    {
+        Some(handle) => handle,
+        None => return,
+    };
+    let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+    if buffer_view.buffer_handle != completion.buffer_handle {
+        return;
+    }
+    let buffer = ctx.editor.buffers.get(completion.buffer_handle).content();
+
+    ctx.editor
+        .picker
+        .remove_custom_entries(CompletionSource::Plugin(plugin_handle));
+    for entry in entries.elements(&remote.json) {
+        if let JsonValue::String(entry) = entry {
+            ctx.editor.picker.add_completion_entry(
+                CompletionSource::Plugin(plugin_handle),
+                CompletionKind::Text,
+                entry.as_str(&remote.json),
+            );
+        }
+    }
+
+    let position = buffer_view.cursors.main_cursor().position;
+    let position = buffer.position_before(position);
+    let word = buffer.word_at(position);
+    let filter = match word.kind {
+        WordKind::Identifier => word.text,
+        _ => "",
+    };
+    ctx.editor
+        .picker
+        .filter_completion(ctx.editor.word_database.word_indices(), filter);
+}
+
+fn on_process_exit(plugin_handle: PluginHandle, ctx: &mut EditorContext, remote_index: u32) {
+    for buffer in ctx.editor.buffers.iter_mut() {
+        let mut lints = buffer.lints.source_mut_guard(plugin_handle, remote_index);
+        lints.clear();
+        let mut annotations = buffer.annotations.mut_guard(plugin_handle, remote_index);
+        annotations.clear();
+    }
+
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    if let Some(remote) = remote_plugin.get_mut(remote_index) {
+        ctx.editor
+            .status_bar
+            .write(MessageKind::Info)
+            .fmt(format_args!("remote plugin '{}' stopped", &remote.command));
+        remote_plugin.remotes[remote_index as usize] = RemoteEntry::Vacant;
+        remote_plugin.unregister_commands(remote_index);
+    }
+}
+
+fn on_completion(
+    plugin_handle: PluginHandle,
+    ctx: &mut EditorContext,
+    completion_ctx: &CompletionContext,
+) -> bool {
+    if !completion_ctx.completion_requested {
+        return false;
+    }
+
+    let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+    for remote in remote_plugin.remotes_mut() {
+        if !remote.is_subscribed(Subscription::Completion) {
+            continue;
+        }
+
+        remote.json.clear();
+        let json = &mut remote.json;
+        let mut params = JsonObject::default();
+        params.set(
+            "buffer".into(),
+            JsonValue::Integer(completion_ctx.buffer_handle.0 as _),
+            json,
+        );
+        let position = DocumentPosition::from_buffer_position(completion_ctx.cursor_position)
+            .to_json_value(json);
+        params.set("position".into(), position, json);
+        params.set(
+            "client".into(),
+            JsonValue::Integer(completion_ctx.client_handle.0 as _),
+            json,
+        );
+
+        let id = remote
+            .protocol
+            .request(&mut ctx.platform, json, "completion", params.into());
+        remote.pending_completion = Some(PendingCompletion {
+            id,
+            client_handle: completion_ctx.client_handle,
+            buffer_handle: completion_ctx.buffer_handle,
+        });
+        return true;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pepper::testing::TestEditor;
+
+    fn start_remote(editor: &mut TestEditor, handle: u8) -> u32 {
+        let plugin_handle = editor.plugin_handle(0);
+        let remote_plugin = editor
+            .ctx_mut()
+            .plugins
+            .get_as::<RemotePlugin>(plugin_handle);
+        let mut remote = Remote::new("test-remote".into());
+        remote
+            .protocol
+            .set_process_handle(PlatformProcessHandle(handle));
+        remote_plugin
+            .remotes
+            .push(RemoteEntry::Occupied(Box::new(remote)));
+        remote_plugin.remotes.len() as u32 - 1
+    }
+
+    fn send_requests(editor: &mut TestEditor, remote_index: u32, requests: &[&str]) -> String {
+        let mut bytes = String::new();
+        for request in requests {
+            bytes.push_str(&format!(
+                "Content-Length: {}\r\n\r\n{}",
+                request.len(),
+                request
+            ));
+        }
+
+        let plugin_handle = editor.plugin_handle(0);
+        let ctx = editor.ctx_mut();
+        on_process_output(plugin_handle, ctx, remote_index, bytes.as_bytes());
+
+        let mut output = String::new();
+        let requests: Vec<_> = ctx.platform.requests.drain().collect();
+        for request in requests {
+            match request {
+                PlatformRequest::WriteToProcess { buf, .. } => {
+                    output.push_str(&String::from_utf8_lossy(buf.as_bytes()));
+                    ctx.platform.buf_pool.release(buf);
+                }
+                PlatformRequest::WriteToClient { buf, .. } => ctx.platform.buf_pool.release(buf),
+                _ => (),
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn buffer_edit() {
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        let buffer_handle = editor.open_buffer("file.txt", "first line\nsecond line");
+        let remote_index = start_remote(&mut editor, 0);
+
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"bufferEdit","params":{{"buffer":{},"edits":[{{"range":{{"start":{{"line":0,"character":0}},"end":{{"line":0,"character":5}}}},"newText":"1st"}},{{"range":{{"start":{{"line":1,"character":0}},"end":{{"line":1,"character":6}}}},"newText":"2nd"}}]}}}}"#,
+            buffer_handle.0
+        );
+        let output = send_requests(&mut editor, remote_index, &[&request]);
+        assert!(output.contains(r#""id":1"#));
+        assert!(output.contains(r#""result":null"#));
+        assert_eq!("1st line\n2nd line", editor.buffer_text());
+
+        editor.keys("u");
+        assert_eq!("first line\nsecond line", editor.buffer_text());
+
+        editor.keys("gkghiX");
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"bufferEdit","params":{{"buffer":{},"edits":[{{"range":{{"start":{{"line":1,"character":0}},"end":{{"line":1,"character":6}}}},"newText":"2nd"}}]}}}}"#,
+            buffer_handle.0
+        );
+        send_requests(&mut editor, remote_index, &[&request]);
+        assert_eq!("Xfirst line\n2nd line", editor.buffer_text());
+
+        editor.keys("<esc>u");
+        assert_eq!("Xfirst line\nsecond line", editor.buffer_text());
+        editor.keys("u");
+        assert_eq!("first line\nsecond line", editor.buffer_text());
+
+        let output = send_requests(
+            &mut editor,
+            remote_index,
+            &[
+                r#"{"jsonrpc":"2.0","id":2,"method":"bufferEdit","params":{"buffer":99,"edits":[]}}"#,
+            ],
+        );
+        assert!(output.contains(r#""id":2"#));
+        assert!(output.contains("no such buffer"));
+    }
+
+    #[test]
+    fn buffer_lints() {
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        let buffer_handle = editor.open_buffer("file.txt", "first line\nsecond line");
+        let remote_a = start_remote(&mut editor, 0);
+        let remote_b = start_remote(&mut editor, 1);
+
+        let lints_request = |id, messages: &[&str]| {
+            let mut lints = String::new();
+            for (i, message) in messages.iter().enumerate() {
+                if i > 0 {
+                    lints.push(',');
+                }
+                lints.push_str(&format!(
+                    r#"{{"range":{{"start":{{"line":{0},"character":0}},"end":{{"line":{0},"character":1}}}},"severity":2,"message":"{1}"}}"#,
+                    i, message
+                ));
+            }
+            format!(
+                r#"{{"jsonrpc":"2.0","id":{},"method":"bufferLints","params":{{"buffer":{},"lints":[{}]}}}}"#,
+                id, buffer_handle.0, lints
+            )
+        };
+        let messages = |editor: &TestEditor| {
+            let mut messages: Vec<_> = editor
+                .ctx()
+                .editor
+                .buffers
+                .get(buffer_handle)
+                .lints
+                .all()
+                .iter()
+                .map(|l| l.message.clone())
+                .collect();
+            messages.sort();
+            messages
+        };
+
+        send_requests(&mut editor, remote_a, &[&lints_request(1, &["a0", "a1"])]);
+        send_requests(&mut editor, remote_b, &[&lints_request(1, &["b0"])]);
+        assert_eq!(vec!["a0", "a1", "b0"], messages(&editor));
+
+        send_requests(&mut editor, remote_a, &[&lints_request(2, &["a2"])]);
+        assert_eq!(vec!["a2", "b0"], messages(&editor));
+
+        let plugin_handle = editor.plugin_handle(0);
+        on_process_exit(plugin_handle, editor.ctx_mut(), remote_b);
+        assert_eq!(vec!["a2"], messages(&editor));
+    }
+
+    #[test]
+    fn eval() {
+        let mut editor = TestEditor::new(vec![DEFINITION]);
+        editor.open_buffer("file.txt", "text");
+        let remote_index = start_remote(&mut editor, 0);
+
+        let output = send_requests(
+            &mut editor,
+            remote_index,
+            &[
+                r#"{"jsonrpc":"2.0","id":1,"method":"registerCommand","params":{"name":"remote-command"}}"#,
+                r#"{"jsonrpc":"2.0","id":2,"method":"eval","params":{"command":"remote-command arg0 'arg 1'"}}"#,
+            ],
+        );
+        assert!(output.contains(r#""id":1,"result":null"#));
+        assert!(output.contains(r#""id":2,"result":null"#));
+        assert!(output.contains(r#""method":"command""#));
+        assert!(output.contains(r#""name":"remote-command""#));
+        assert!(output.contains(r#""args":["arg0","arg 1"]"#));
+
+        let output = send_requests(
+            &mut editor,
+            remote_index,
+            &[r#"{"jsonrpc":"2.0","id":3,"method":"eval","params":{"command":"no-such-command"}}"#],
+        );
+        assert!(output.contains(r#""id":3"#));
+        assert!(output.contains(r#""error""#));
+
+        send_requests(
+            &mut editor,
+            remote_index,
+            &[
+                r#"{"jsonrpc":"2.0","id":4,"method":"eval","params":{"command":"remote-plugin other-remote"}}"#,
+            ],
+        );
+        let plugin_handle = editor.plugin_handle(0);
+        let remote_plugin = editor
+            .ctx_mut()
+            .plugins
+            .get_as::<RemotePlugin>(plugin_handle);
+        assert_eq!(2, remote_plugin.remotes.len());
+        assert_eq!(
+            "test-remote",
+            remote_plugin.get_mut(remote_index).unwrap().command
+        );
+        assert_eq!("other-remote", remote_plugin.get_mut(1).unwrap().command);
+
+        send_requests(
+            &mut editor,
+            remote_index,
+            &[
+                r#"{"jsonrpc":"2.0","id":5,"method":"eval","params":{"command":"remote-plugin-stop-all"}}"#,
+            ],
+        );
+        let remote_plugin = editor
+            .ctx_mut()
+            .plugins
+            .get_as::<RemotePlugin>(plugin_handle);
+        assert!(remote_plugin.get_mut(remote_index).is_none());
+        assert!(remote_plugin.get_mut(1).is_none());
+    }
+}
@@ -0,0 +1,239 @@
+use pepper::{
+    buffer::BufferHandle,
+    client::ClientHandle,
+    command::{CommandError, CommandManager},
+    editor::EditorContext,
+    plugin::PluginHandle,
+};
+use pepper_plugin_lsp::{
+    json::{FromJson, JsonArray, JsonConvertError, JsonObject, JsonValue},
+    protocol::{self, DocumentPosition, DocumentRange, ProtocolError, ServerRequest, TextEdit},
+};
+
+use crate::{command, Remote, RemotePlugin, Subscription};
+
+pub(crate) enum RequestError {
+    Protocol(ProtocolError),
+    Command(CommandError),
+    Message(&'static str),
+}
+impl From<JsonConvertError> for RequestError {
+    fn from(_: JsonConvertError) -> Self {
+        Self::Protocol(ProtocolError::ParseError)
+    }
+}
+impl From<CommandError> for RequestError {
+    fn from(error: CommandError) -> Self {
+        Self::Command(error)
+    }
+}
+
+pub(crate) fn on_request(
+    remote: &mut Remote,
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    remote_index: u32,
+    request: ServerRequest,
+) -> Result<JsonValue, RequestError> {
+    let params = request.params;
+    match request.method.as_str(&remote.json) {
+        "subscribe" => {
+            let events = JsonArray::from_json(params.get("events", &remote.json), &remote.json)?;
+            let mut subscriptions = Vec::new();
+            for event in events.elements(&remote.json) {
+                let event = <&str>::from_json(event, &remote.json)?;
+                match Subscription::from_name(event) {
+                    Some(subscription) => subscriptions.push(subscription),
+                    None => return Err(RequestError::Message("no such event")),
+                }
+            }
+            for subscription in subscriptions {
+                remote.subscribe(subscription);
+            }
+            Ok(JsonValue::Null)
+        }
+        "registerCommand" => {
+            let name = <&str>::from_json(params.clone().get("name", &remote.json), &remote.json)?;
+            let remote_plugin = ctx.plugins.get_as::<RemotePlugin>(plugin_handle);
+            command::register_remote_command(
+                &mut ctx.editor.commands,
+                plugin_handle,
+                remote_plugin,
+                remote_index,
+                name,
+            )?;
+            Ok(JsonValue::Null)
+        }
+        "eval" => {
+            let command =
+                <&str>::from_json(params.clone().get("command", &remote.json), &remote.json)?;
+            let client_handle =
+                parse_client_handle(ctx, params.clone().get("client", &remote.json))?;
+            let client_handle = client_handle.or_else(|| ctx.clients.focused_client());
+
+            let mut command = ctx.editor.string_pool.acquire_with(command);
+            let result = CommandManager::try_eval(ctx, client_handle, &mut command);
+            ctx.editor.string_pool.release(command);
+            result?;
+            Ok(JsonValue::Null)
+        }
+        "buffers" => {
+            let mut buffers = JsonArray::default();
+            for buffer in ctx.editor.buffers.iter() {
+                let mut entry = JsonObject::default();
+                let handle = JsonValue::Integer(buffer.handle().0 as _);
+                entry.set("buffer".into(), handle, &mut remote.json);
+                let path = remote
+                    .json
+                    .create_string(buffer.path.to_str().unwrap_or(""));
+                entry.set("path".into(), path.into(), &mut remote.json);
+                buffers.push(entry.into(), &mut remote.json);
+            }
+            Ok(buffers.into())
+        }
+        "bufferText" => {
+            let buffer_handle =
+                parse_buffer_handle(ctx, params.clone().get("buffer", &remote.json))?;
+            let content = ctx.editor.buffers.get(buffer_handle).content();
+
+            let mut text = ctx.editor.string_pool.acquire();
+            for (i, line) in content.lines().iter().enumerate() {
+                if i > 0 {
+                    text.push('\n');
+                }
+                text.push_str(line.as_str());
+            }
+            let value = remote.json.create_string(&text);
+            ctx.editor.string_pool.release(text);
+            Ok(value.into())
+        }
+        "bufferEdit" => {
+            let buffer_handle =
+                parse_buffer_handle(ctx, params.clone().get("buffer", &remote.json))?;
+            let edits =
+                JsonArray::from_json(params.clone().get("edits", &remote.json), &remote.json)?;
+            TextEdit::apply_edits(
+                &mut ctx.editor,
+                buffer_handle,
+                &mut remote.temp_edits,
+                edits,
+                &remote.json,
+            );
+            Ok(JsonValue::Null)
+        }
+        "bufferLints" => {
+            let buffer_handle =
+                parse_buffer_handle(ctx, params.clone().get("buffer", &remote.json))?;
+            let lints =
+                JsonArray::from_json(params.clone().get("lints", &remote.json), &remote.json)?;
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let mut buffer_lints = buffer.lints.source_mut_guard(plugin_handle, remote_index);
+            buffer_lints.clear();
+            for lint in lints.elements(&remote.json) {
+                let lint = JsonObject::from_json(lint, &remote.json)?;
+                let range = DocumentRange::from_json(
+                    lint.clone().get("range", &remote.json),
+                    &remote.json,
+                )?;
+                let severity = match lint.clone().get("severity", &remote.json) {
+                    JsonValue::Integer(severity) => severity,
+                    _ => 1,
+                };
+                let message = <&str>::from_json(lint.get("message", &remote.json), &remote.json)?;
+                buffer_lints.add(
+                    message,
+                    range.into_buffer_range(),
+                    protocol::lint_severity(severity),
+                );
+            }
+            Ok(JsonValue::Null)
+        }
+        "bufferAnnotations" => {
+            let buffer_handle =
+                parse_buffer_handle(ctx, params.clone().get("buffer", &remote.json))?;
+            let annotations = JsonArray::from_json(
+                params.clone().get("annotations", &remote.json),
+                &remote.json,
+            )?;
+
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let mut buffer_annotations = buffer.annotations.mut_guard(plugin_handle, remote_index);
+            buffer_annotations.clear();
+            for annotation in annotations.elements(&remote.json) {
+                let annotation = JsonObject::from_json(annotation, &remote.json)?;
+                let position = DocumentPosition::from_json(
+                    annotation.clone().get("position", &remote.json),
+                    &remote.json,
+                )?;
+                let text = <&str>::from_json(annotation.get("text", &remote.json), &remote.json)?;
+                buffer_annotations.add(text, position.into_buffer_position());
+            }
+            Ok(JsonValue::Null)
+        }
+        "cursors" => {
+            let client_handle =
+                match parse_client_handle(ctx, params.clone().get("client", &remote.json))? {
+                    Some(handle) => handle,
+                    None => return Err(RequestError::Command(CommandError::NoTargetClient)),
+                };
+            let buffer_view_handle = match ctx.clients.get(client_handle).buffer_view_handle() {
+                Some(handle) => handle,
+                None => return Err(RequestError::Command(CommandError::NoBufferOpened)),
+            };
+            let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+
+            let mut cursors = JsonArray::default();
+            for cursor in &buffer_view.cursors[..] {
+                let mut entry = JsonObject::default();
+                let anchor = DocumentPosition::from_buffer_position(cursor.anchor)
+                    .to_json_value(&mut remote.json);
+                entry.set("anchor".into(), anchor, &mut remote.json);
+                let position = DocumentPosition::from_buffer_position(cursor.position)
+                    .to_json_value(&mut remote.json);
+                entry.set("position".into(), position, &mut remote.json);
+                cursors.push(entry.into(), &mut remote.json);
+            }
+
+            let mut result = JsonObject::default();
+            let buffer_handle = JsonValue::Integer(buffer_view.buffer_handle.0 as _);
+            result.set("buffer".into(), buffer_handle, &mut remote.json);
+            result.set("cursors".into(), cursors.into(), &mut remote.json);
+            Ok(result.into())
+        }
+        _ => Err(RequestError::Protocol(ProtocolError::MethodNotFound)),
+    }
+}
+
+fn parse_buffer_handle(
+    ctx: &EditorContext,
+    value: JsonValue,
+) -> Result<BufferHandle, RequestError> {
+    let index = match value {
+        JsonValue::Integer(index) if index >= 0 => index as u32,
+        _ => return Err(RequestError::Protocol(ProtocolError::ParseError)),
+    };
+    let handle = BufferHandle(index);
+    if ctx.editor.buffers.iter().any(|b| b.handle() == handle) {
+        Ok(handle)
+    } else {
+        Err(RequestError::Message("no such buffer"))
+    }
+}
+
+fn parse_client_handle(
+    ctx: &EditorContext,
+    value: JsonValue,
+) -> Result<Option<ClientHandle>, RequestError> {
+    let index = match value {
+        JsonValue::Null => return Ok(None),
+        JsonValue::Integer(index) if (0..=u8::MAX as _).contains(&index) => index as u8,
+        _ => return Err(RequestError::Protocol(ProtocolError::ParseError)),
+    };
+    let handle = ClientHandle(index);
+    if ctx.clients.iter().any(|c| c.handle() == handle) {
+        Ok(Some(handle))
+    } else {
+        Err(RequestError::Message("no such client"))
+    }
+}